@@ -0,0 +1 @@
+ALTER TABLE jobs ADD COLUMN labels TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE clusters DROP COLUMN job_id_regex;
+ALTER TABLE clusters DROP COLUMN submit_command;
+ALTER TABLE clusters DROP COLUMN header_prefix;
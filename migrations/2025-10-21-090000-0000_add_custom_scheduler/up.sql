@@ -0,0 +1,3 @@
+ALTER TABLE clusters ADD COLUMN header_prefix TEXT;
+ALTER TABLE clusters ADD COLUMN submit_command TEXT;
+ALTER TABLE clusters ADD COLUMN job_id_regex TEXT;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE configs DROP COLUMN default_postprocess;
+ALTER TABLE configs DROP COLUMN default_preprocess;
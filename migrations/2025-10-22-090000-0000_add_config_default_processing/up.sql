@@ -0,0 +1,2 @@
+ALTER TABLE configs ADD COLUMN default_preprocess TEXT;
+ALTER TABLE configs ADD COLUMN default_postprocess TEXT;
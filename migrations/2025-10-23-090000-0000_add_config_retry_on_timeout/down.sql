@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE configs DROP COLUMN retry_on_timeout;
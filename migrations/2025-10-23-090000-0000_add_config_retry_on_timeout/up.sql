@@ -0,0 +1 @@
+ALTER TABLE configs ADD COLUMN retry_on_timeout TEXT;
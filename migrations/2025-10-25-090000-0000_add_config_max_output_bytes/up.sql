@@ -0,0 +1 @@
+ALTER TABLE configs ADD COLUMN max_output_bytes BIGINT;
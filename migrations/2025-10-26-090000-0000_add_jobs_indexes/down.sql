@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX jobs_archived_idx;
+DROP INDEX jobs_config_id_idx;
+DROP INDEX jobs_status_idx;
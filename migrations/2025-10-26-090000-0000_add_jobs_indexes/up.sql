@@ -0,0 +1,3 @@
+CREATE INDEX jobs_status_idx ON jobs (status);
+CREATE INDEX jobs_config_id_idx ON jobs (config_id);
+CREATE INDEX jobs_archived_idx ON jobs (archived);
@@ -0,0 +1 @@
+ALTER TABLE clusters ADD COLUMN pre_submit_hook TEXT;
@@ -0,0 +1 @@
+ALTER TABLE configs ADD COLUMN extra_headers TEXT;
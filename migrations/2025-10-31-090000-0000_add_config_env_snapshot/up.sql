@@ -0,0 +1 @@
+ALTER TABLE configs ADD COLUMN env_snapshot TEXT;
@@ -0,0 +1 @@
+ALTER TABLE clusters ADD COLUMN submit_interval_ms INTEGER;
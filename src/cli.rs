@@ -1,24 +1,72 @@
+#[cfg(test)]
+mod tests;
 mod utils;
-use std::env;
+
+use std::{
+  collections::HashMap,
+  env,
+  path::PathBuf,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+  thread,
+  time::Duration,
+};
 
 use crate::{
-  core::{self, Sbatchman},
-  tui::launch_tui,
+  core::{
+    Sbatchman, SbatchmanError,
+    database::models::{Cluster, Config, Job, Scheduler, Status},
+    jobs::{
+      CancelLaunchSummary, DoctorReport, JobFilter, JobLog, LaunchOverrides, LogTail,
+      SubstitutionTrace, SyncSummary, VariableInfo,
+      utils::{parse_duration_to_seconds, shell_join},
+    },
+    sbatchman_configs::{DisplayTimezone, SbatchmanConfigError},
+  },
+  tui::{ColumnConfig, ColumnType, launch_tui},
 };
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use log::LevelFilter;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
   #[command(subcommand)]
   command: Option<Commands>,
+  /// Increase the log verbosity (-v for info, -vv for debug)
+  #[arg(short, long, action = ArgAction::Count, global = true)]
+  verbose: u8,
+  /// Open the database read-only without running its migrations (ex. on an exported archive).
+  /// Only the commands that do not modify the database (logs, dump-jobs, configs, explain,
+  /// validate) use it.
+  #[arg(long, global = true)]
+  no_migrate: bool,
+  /// .sbatchman directory to use instead of searching for it from the working directory.
+  /// Overrides the `SBATCHMAN_DIR` environment variable.
+  #[arg(long, global = true, value_name = "PATH")]
+  db: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-  Init {},
+  Init {
+    /// Also write an example clusters configuration and jobs file in the current directory
+    #[arg(long, visible_alias = "template")]
+    with_examples: bool,
+  },
   Configure {
     file: String,
+    /// Print the parsed clusters and configs as JSON instead of importing them
+    #[arg(long)]
+    dump_json: bool,
+    /// Fail on the `per_cluster` keys naming unknown clusters instead of warning
+    #[arg(long)]
+    strict: bool,
   },
   Update {},
   SetClusterName {
@@ -27,76 +75,1118 @@ enum Commands {
     local: bool,
   },
   Launch {
+    /// Jobs file, or `-` to read it from stdin
+    file: String,
+    /// Cluster to launch on, otherwise the one of SBATCHMAN_CLUSTER, then the configured one
+    cluster_name: Option<String>,
+    /// Use the globally configured cluster, ignoring SBATCHMAN_CLUSTER and the local one
+    #[arg(long, conflicts_with = "cluster_name")]
+    global_cluster: bool,
+    /// Only print the number of jobs that would be launched
+    #[arg(long)]
+    count: bool,
+    /// Launch even if the file generates more jobs than the maximum sweep size
+    #[arg(short, long)]
+    yes: bool,
+    /// Override a variable of the file with a single value (ex. `--var seed=42`)
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var_override)]
+    vars: Vec<(String, String)>,
+    /// Export the `KEY=VALUE` lines of this file in the job scripts, over the config `env`
+    #[arg(long)]
+    env_file: Option<String>,
+    /// Only create and launch the first N jobs of the file, the others are dropped
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+    /// Do not show the progress bar nor the final summary
+    #[arg(short, long)]
+    quiet: bool,
+    /// Create the job directories here instead of the configured output directory
+    #[arg(long)]
+    output_dir: Option<String>,
+    /// Fail on the `per_cluster` keys naming unknown clusters instead of warning
+    #[arg(long)]
+    strict: bool,
+    /// Pass this argument to sbatch/qsub for this launch only (ex. `--submit-arg=--hold`). With
+    /// `--submit-arg=--test-only`, sbatch only validates the jobs, which stay created.
+    #[arg(long = "submit-arg", value_name = "ARG", allow_hyphen_values = true)]
+    submit_args: Vec<String>,
+  },
+  /// Launch a single command with a config, without jobs file (ex. `run cpu -- ./train.sh 42`)
+  Run {
+    config_name: String,
+    /// Cluster of the config, the configured one by default
+    #[arg(long)]
+    cluster: Option<String>,
+    /// Create the job directory here instead of the configured output directory
+    #[arg(long)]
+    output_dir: Option<String>,
+    /// Command of the job, its words are quoted as needed and joined with spaces
+    #[arg(last = true, required = true)]
+    command: Vec<String>,
+  },
+  /// Launch the jobs of a file that a previous launch did not submit, skipping the other ones
+  Resume {
+    file: String,
+    cluster_name: Option<String>,
+    /// Override a variable of the file with a single value (ex. `--var seed=42`)
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var_override)]
+    vars: Vec<(String, String)>,
+    /// Do not show the progress bar nor the final summary
+    #[arg(short, long)]
+    quiet: bool,
+    /// Create the directories of the new jobs here instead of the configured output directory
+    #[arg(long)]
+    output_dir: Option<String>,
+  },
+  /// Delete the jobs with a given status (ex. failed-submission) and their directories
+  Purge {
+    #[arg(long)]
+    status: Status,
+  },
+  /// Set the status of several jobs at once, ex. jobs left running by a cluster outage
+  SetStatus {
+    /// Comma-separated job ids (ex. `1,2,3`)
+    #[arg(long, value_delimiter = ',', required = true, num_args = 1..)]
+    ids: Vec<i32>,
+    #[arg(long)]
+    status: Status,
+  },
+  /// Report the jobs whose directory is missing, the job directories with no job and the jobs
+  /// whose process is dead
+  Doctor {
+    /// Mark the dead jobs as failed and remove the orphan directories
+    #[arg(long)]
+    fix: bool,
+  },
+  /// Cancel a job that is not over yet, stopping it if it was submitted
+  Cancel {
+    #[arg(required_unless_present = "run")]
+    id: Option<i32>,
+    /// Cancel all the jobs of this launch that are not over yet instead
+    #[arg(long, value_name = "LAUNCH_ID", conflicts_with = "id")]
+    run: Option<String>,
+  },
+  /// Launch again a job, reusing its original script
+  Rerun {
+    id: i32,
+    /// Rebuild the script from the current config instead of reusing the original one
+    #[arg(long)]
+    regenerate: bool,
+  },
+  /// Rebuild the scripts of the jobs of a config that were not launched yet
+  RegenerateScripts {
+    config_name: String,
+  },
+  /// Update the status of the queued and running jobs
+  Sync {
+    /// Keep syncing until no job is queued or running
+    #[arg(short, long)]
+    watch: bool,
+    /// Seconds between two syncs in watch mode
+    #[arg(long, default_value_t = 30)]
+    interval: u64,
+    /// Query the jobs of each cluster concurrently
+    #[arg(long)]
+    parallel: bool,
+    /// Only sync the jobs submitted within this duration (ex. `12h` or `7d`). The jobs submitted
+    /// by older versions, which did not record the submit time, are always synced.
+    #[arg(long, value_name = "DURATION", value_parser = parse_since)]
+    since: Option<u64>,
+  },
+  /// Check the configs of the current cluster against this machine
+  Validate {
+    /// Warn about the modules of the configs that `module avail` does not list
+    #[arg(long)]
+    check_modules: bool,
+  },
+  /// Print all the jobs as JSON Lines (one object per job) for external analysis
+  DumpJobs {},
+  /// Serve the jobs and clusters as read-only JSON (`/jobs`, `/jobs/{id}` and `/clusters`)
+  #[cfg(feature = "server")]
+  Serve {
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+    /// Address to listen on, use 0.0.0.0 to accept remote connections
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+  },
+  /// Print a job with its config, cluster, script and log as one JSON document
+  ExportJob {
+    id: i32,
+  },
+  /// Print the events of the log of a job
+  Logs {
+    id: i32,
+    /// Keep printing the events as they are appended, until Ctrl-C
+    #[arg(short, long)]
+    follow: bool,
+  },
+  /// Show the substitution steps of the command of a job entry
+  Explain {
     file: String,
     cluster_name: Option<String>,
+    /// Name of the job entry, the first one by default
+    #[arg(long)]
+    job: Option<String>,
+    /// Fix the value of a variable (ex. `--var seed=42`), the others take their first value
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var_override)]
+    vars: Vec<(String, String)>,
+  },
+  /// Print the completion script of a shell (ex. `sbatchman completions bash > ~/.sbatchman.bash`)
+  Completions {
+    shell: Shell,
+  },
+  /// List the jobs with the columns of the TUI
+  List {
+    #[arg(long, visible_alias = "output-format", value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+    /// Only list the jobs of this launch (its id is printed by `launch`)
+    #[arg(long, value_name = "LAUNCH_ID")]
+    run: Option<String>,
+  },
+  /// Count the jobs of each status
+  Stats {
+    /// Only count the jobs of this cluster
+    #[arg(long)]
+    cluster: Option<String>,
+  },
+  /// List the configs of the current cluster
+  Configs {
+    /// Print the config names as a JSON array
+    #[arg(long)]
+    json: bool,
+  },
+  /// Print the flags and env of a config after merging the cluster defaults
+  ShowConfig {
+    name: String,
+    /// Cluster of the config, the current one by default
+    #[arg(long)]
+    cluster: Option<String>,
+    /// Also print the scheduler directives and exports of the job scripts
+    #[arg(long)]
+    script: bool,
+  },
+  /// Show the variables of a jobs file with their kind and the variables they depend on
+  Vars {
+    file: String,
+    /// Print the variables as JSON instead of a tree
+    #[arg(long)]
+    json: bool,
   },
   TUI {},
-  Import {},
+  /// Import an archive created by `export` (tar.gz, zip or directory)
+  Import {
+    archive: String,
+    /// List the clusters, configs and jobs that would be imported, without writing anything
+    #[arg(long)]
+    dry_run: bool,
+  },
   Export {
+    /// tar.gz (default), zip, or dir for an uncompressed copy
     format: Option<String>,
     compressed_name: Option<String>,
   },
 }
 
-pub fn main() {
+/// Output of the `list` command
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum OutputFormat {
+  /// Aligned columns
+  Table,
+  /// Comma-separated values with a header line, for spreadsheets
+  Csv,
+  /// Array of objects keyed by column name
+  Json,
+}
+
+/// Parse a `--var NAME=VALUE` argument
+fn parse_var_override(arg: &str) -> Result<(String, String)> {
+  let (name, value) = arg
+    .split_once('=')
+    .ok_or_else(|| anyhow!("Invalid variable override \"{}\", expected NAME=VALUE", arg))?;
+  Ok((name.to_string(), value.to_string()))
+}
+
+/// Parse a `--since` duration (ex. `12h` or `7d`) into seconds
+fn parse_since(arg: &str) -> Result<u64> {
+  parse_duration_to_seconds(arg).map_err(|e| anyhow!(e.to_string()))
+}
+
+/// Turn a SbatchmanError into a message suited for the command line
+fn to_cli_error(error: SbatchmanError) -> anyhow::Error {
+  match error {
+    SbatchmanError::NoClusterSet => anyhow!(
+      "No cluster set. Run `sbatchman set-cluster-name <NAME>` first, set SBATCHMAN_CLUSTER or pass the cluster name."
+    ),
+    SbatchmanError::ConfigError(SbatchmanConfigError::SbatchmanDirNotFound) => {
+      anyhow!("Not inside a SbatchMan project. Run `sbatchman init` first.")
+    }
+    error => anyhow!(error),
+  }
+}
+
+/// Log level for the number of `-v` flags. `RUST_LOG` still takes precedence when set.
+fn log_level(verbose: u8) -> LevelFilter {
+  match verbose {
+    0 => LevelFilter::Warn,
+    1 => LevelFilter::Info,
+    _ => LevelFilter::Debug,
+  }
+}
+
+fn open_sbatchman(cli: &Cli) -> Result<Sbatchman> {
+  Sbatchman::open(cli.db.as_deref(), false)
+    .map_err(to_cli_error)
+    .context("Failed to initialize Sbatchman")
+}
+
+/// Open Sbatchman for a command that only queries the database, read-only with `no_migrate`
+fn open_sbatchman_for_queries(cli: &Cli) -> Result<Sbatchman> {
+  if !cli.no_migrate {
+    return open_sbatchman(cli);
+  }
+  Sbatchman::open(cli.db.as_deref(), true)
+    .map_err(to_cli_error)
+    .context("Failed to open Sbatchman read-only")
+}
+
+/// Cluster requested on the command line. With `global`, the globally configured cluster is
+/// passed explicitly so that the local configuration is bypassed.
+fn select_cluster(
+  sbatchman: &Sbatchman,
+  cluster: &Option<String>,
+  global: bool,
+) -> Result<Option<String>> {
+  if !global || cluster.is_some() {
+    return Ok(cluster.clone());
+  }
+  sbatchman
+    .get_cluster_name_global()
+    .map(Some)
+    .ok_or_else(|| to_cli_error(SbatchmanError::NoClusterSet))
+}
+
+fn launch_jobs(
+  sbatchman: &mut Sbatchman,
+  file: &str,
+  cluster: &Option<String>,
+  overrides: &LaunchOverrides,
+  output_dir: &Option<String>,
+  yes: bool,
+  quiet: bool,
+) -> Result<()> {
+  let summary = sbatchman
+    .launch_jobs_from_file(file, cluster, overrides, output_dir, yes, quiet)
+    .map_err(to_cli_error)
+    .context("Failed to launch jobs from file")?;
+  if !quiet {
+    println!(
+      "✅ Launched {} job(s), {} in the virtual queue, {} failed",
+      summary.launched, summary.virtual_queued, summary.failed
+    );
+    if let Some(launch_id) = &summary.launch_id {
+      println!(
+        "Launch id: {} (see `sbatchman list --run {}`)",
+        launch_id, launch_id
+      );
+    }
+  }
+  Ok(())
+}
+
+/// Write the completion script of `shell` for all the commands and flags to `out`
+fn generate_completions(shell: Shell, out: &mut dyn std::io::Write) {
+  clap_complete::generate(shell, &mut Cli::command(), "sbatchman", out);
+}
+
+/// Names of the configs of the current cluster, one per line or as a JSON array
+fn format_configs(sbatchman: &mut Sbatchman, json: bool) -> Result<String> {
+  let (_, configs) = sbatchman
+    .get_this_cluster_configs()
+    .map_err(to_cli_error)
+    .context("Failed to get the configs of the current cluster")?;
+  let names: Vec<&str> = configs
+    .iter()
+    .map(|config| config.config_name.as_str())
+    .collect();
+  if json {
+    return Ok(format!(
+      "{}\n",
+      serde_json::to_string(&names).context("Failed to serialize config names")?
+    ));
+  }
+  Ok(names.iter().map(|name| format!("{}\n", name)).collect())
+}
+
+/// Merged flags and env of a config, one `key=value` per line. With `script`, followed by the
+/// header block the job scripts get from them.
+fn format_config(cluster: &Cluster, config: &Config, script: bool) -> String {
+  let mut out = format!(
+    "Config '{}' of cluster '{}'\nFlags:\n",
+    config.config_name, cluster.cluster_name
+  );
+  for (key, value) in config.flags.as_object().into_iter().flatten() {
+    match value {
+      serde_json::Value::String(s) => out.push_str(&format!("  {}={}\n", key, s)),
+      other => out.push_str(&format!("  {}={}\n", key, other)),
+    }
+  }
+  out.push_str("Env:\n");
+  for (key, value) in config.env.as_object().into_iter().flatten() {
+    // Values are exported quoted, as in the scripts
+    out.push_str(&format!("  {}={}\n", key, value));
+  }
+  if script {
+    out.push_str("Script header:\n");
+    out.push_str(&Sbatchman::config_script_header(cluster, config));
+  }
+  out
+}
+
+/// Number of jobs of each status that has some, followed by the total
+fn format_status_counts(counts: &HashMap<Status, usize>) -> String {
+  let mut out = String::new();
+  for status in Status::all_variants() {
+    if let Some(count) = counts.get(&status) {
+      out.push_str(&format!("{:?}: {}\n", status, count));
+    }
+  }
+  out.push_str(&format!("Total: {}\n", counts.values().sum::<usize>()));
+  out
+}
+
+/// Quote a CSV field if it contains a comma, a quote or a newline (RFC 4180)
+fn csv_field(field: &str) -> String {
+  if field.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    field.to_string()
+  }
+}
+
+/// The `columns` of `jobs` as a table, CSV or JSON, one job per row
+fn format_jobs(
+  jobs: &[Job],
+  columns: &[ColumnType],
+  format: OutputFormat,
+  timezone: DisplayTimezone,
+) -> Result<String> {
+  let rows: Vec<Vec<String>> = jobs
+    .iter()
+    .map(|job| columns.iter().map(|col| col.value(job, timezone)).collect())
+    .collect();
+  let header: Vec<String> = columns.iter().map(|col| col.name().to_string()).collect();
+  let lines = std::iter::once(&header).chain(&rows);
+  match format {
+    OutputFormat::Table => {
+      let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+          lines
+            .clone()
+            .map(|cells| cells[i].chars().count())
+            .max()
+            .unwrap_or_default()
+        })
+        .collect();
+      Ok(
+        lines
+          .map(|cells| {
+            let cells: Vec<String> = cells
+              .iter()
+              .zip(&widths)
+              .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+              .collect();
+            format!("{}\n", cells.join("  ").trim_end())
+          })
+          .collect(),
+      )
+    }
+    OutputFormat::Csv => Ok(
+      lines
+        .map(|cells| {
+          let cells: Vec<String> = cells.iter().map(|cell| csv_field(cell)).collect();
+          format!("{}\n", cells.join(","))
+        })
+        .collect(),
+    ),
+    OutputFormat::Json => {
+      let objects: Vec<serde_json::Map<String, serde_json::Value>> = rows
+        .into_iter()
+        .map(|row| {
+          header
+            .iter()
+            .cloned()
+            .zip(row.into_iter().map(serde_json::Value::from))
+            .collect()
+        })
+        .collect();
+      Ok(format!(
+        "{}\n",
+        serde_json::to_string_pretty(&objects).context("Failed to serialize jobs")?
+      ))
+    }
+  }
+}
+
+/// One-line description of a sync
+fn format_sync_summary(summary: &SyncSummary) -> String {
+  format!(
+    "{} job(s) updated, {} resubmitted, {} queued, {} running",
+    summary.updated, summary.resubmitted, summary.queued, summary.running
+  )
+}
+
+/// The jobs canceled by `cancel --run`, followed by the ones that could not be canceled and why
+fn format_cancel_launch_summary(summary: &CancelLaunchSummary, launch_id: &str) -> String {
+  let mut out = format!(
+    "✅ {} job(s) of launch '{}' canceled\n",
+    summary.canceled.len(),
+    launch_id
+  );
+  for (id, error) in &summary.failed {
+    out.push_str(&format!(
+      "❌ Job #{} could not be canceled: {}\n",
+      id, error
+    ));
+  }
+  out
+}
+
+/// The inconsistencies found by `doctor`, one per line, and what `--fix` did about them
+fn format_doctor_report(report: &DoctorReport, fix: bool) -> String {
+  if report.is_healthy() {
+    return "✅ The jobs match their directories\n".to_string();
+  }
+  let mut out = String::new();
+  for job in &report.missing_directories {
+    out.push_str(&format!(
+      "❌ Job #{} \"{}\": missing directory {}\n",
+      job.id, job.job_name, job.directory
+    ));
+  }
+  let orphan_action = if fix { " (removed)" } else { "" };
+  for directory in &report.orphan_directories {
+    out.push_str(&format!(
+      "❌ Orphan directory {}{}\n",
+      directory.display(),
+      orphan_action
+    ));
+  }
+  let dead_action = if fix { " (marked failed)" } else { "" };
+  for job in &report.dead_jobs {
+    out.push_str(&format!(
+      "❌ Job #{} \"{}\": {:?} but its process is dead{}\n",
+      job.id, job.job_name, job.status, dead_action
+    ));
+  }
+  if !fix && (!report.orphan_directories.is_empty() || !report.dead_jobs.is_empty()) {
+    out.push_str(
+      "ℹ️  Run with --fix to mark the dead jobs failed and remove the orphan directories\n",
+    );
+  }
+  out
+}
+
+/// The stages of a substitution, one per line
+fn format_substitution_trace(trace: &SubstitutionTrace) -> String {
+  [
+    ("Template", trace.template.as_str()),
+    ("Variables", trace.after_simple.as_str()),
+    ("Maps", trace.after_maps.as_str()),
+    ("Python", trace.after_python.as_str()),
+    ("Final command", trace.final_command()),
+  ]
+  .iter()
+  .map(|(stage, value)| format!("{:<15}{}\n", format!("{}:", stage), value))
+  .collect()
+}
+
+/// Indented tree of variables, each followed by the variables it depends on
+fn format_variables(variables: &[VariableInfo]) -> String {
+  let mut tree = String::new();
+  for variable in variables {
+    tree.push_str(&format!("{} ({})\n", variable.name, variable.kind));
+    for dependency in &variable.dependencies {
+      tree.push_str(&format!("  └─ {}\n", dependency));
+    }
+  }
+  tree
+}
+
+/// Call `sync` every `interval` until no job is queued or running, or until `interrupted` is set.
+/// `report` is called with the summary of each sync. Returns the last summary.
+fn watch_jobs(
+  mut sync: impl FnMut() -> Result<SyncSummary>,
+  mut report: impl FnMut(&SyncSummary),
+  interval: Duration,
+  interrupted: &AtomicBool,
+) -> Result<SyncSummary> {
+  loop {
+    let summary = sync()?;
+    report(&summary);
+    if summary.is_done() {
+      return Ok(summary);
+    }
+    // Sleep in small steps to exit promptly on Ctrl-C
+    let step = Duration::from_millis(100);
+    let mut slept = Duration::ZERO;
+    while slept < interval {
+      if interrupted.load(Ordering::SeqCst) {
+        return Ok(summary);
+      }
+      thread::sleep(step.min(interval - slept));
+      slept += step;
+    }
+    if interrupted.load(Ordering::SeqCst) {
+      return Ok(summary);
+    }
+  }
+}
+
+/// One line per event of a job log: timestamp (UTC), event type and its main fields
+fn format_log_entry(timestamp: &DateTime<Utc>, log: &JobLog) -> String {
+  let optional = |value: &Option<u64>| value.map_or("-".to_string(), |v| v.to_string());
+  let (kind, details) = match log {
+    JobLog::Metadata(job) => (
+      "Metadata",
+      format!("job #{} \"{}\": {}", job.id, job.job_name, job.command),
+    ),
+    JobLog::StatusUpdate(status) => ("StatusUpdate", format!("{:?}", status)),
+    JobLog::BashVariable(variable) => ("BashVariable", variable.clone()),
+    JobLog::Variable(name, value) => ("Variable", format!("{}={}", name, value)),
+    JobLog::ResourceUsage {
+      max_rss_kb,
+      cpu_seconds,
+      elapsed_seconds,
+    } => (
+      "ResourceUsage",
+      format!(
+        "max_rss_kb={} cpu_seconds={} elapsed_seconds={}",
+        optional(max_rss_kb),
+        optional(cpu_seconds),
+        optional(elapsed_seconds)
+      ),
+    ),
+    JobLog::PreSubmitHook { exit_code, output } => (
+      "PreSubmitHook",
+      format!(
+        "exit_code={} {}",
+        exit_code.map_or("-".to_string(), |code| code.to_string()),
+        output.trim_end()
+      ),
+    ),
+    JobLog::Action(action) => ("Action", action.clone()),
+    JobLog::Environment(variables) => {
+      let mut variables: Vec<String> = variables
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+      variables.sort();
+      ("Environment", variables.join(" "))
+    }
+  };
+  format!(
+    "{}  {:<13}  {}",
+    timestamp.format("%Y-%m-%d %H:%M:%S%.3f"),
+    kind,
+    details
+  )
+}
+
+/// Print the new events of `tail` every `interval` until `interrupted` is set
+fn follow_log(
+  tail: &mut LogTail,
+  mut report: impl FnMut(&str),
+  interval: Duration,
+  interrupted: &AtomicBool,
+) -> Result<()> {
+  while !interrupted.load(Ordering::SeqCst) {
+    for (timestamp, log) in tail
+      .read_new_entries()
+      .context("Failed to read the job log")?
+    {
+      report(&format_log_entry(&timestamp, &log));
+    }
+    thread::sleep(interval);
+  }
+  Ok(())
+}
+
+pub fn main() -> Result<()> {
   let cli = Cli::parse();
+  // Configure the logger before Sbatchman::new, which would otherwise initialize it
+  let _ = env_logger::Builder::new()
+    .filter_level(log_level(cli.verbose))
+    .parse_default_env()
+    .try_init();
 
-  // if let Some(Commands::Init {}) = &cli.command {
-  // } else {
   match &cli.command {
-    Some(Commands::Init {}) => {
-      let path = env::current_dir().expect("Failed to get current directory");
-      Sbatchman::init(&path).expect("Failed to initialize sbatchman directory");
+    Some(Commands::Init { with_examples }) => {
+      let path = env::current_dir().context("Failed to get current directory")?;
+      let examples = Sbatchman::init(&path, *with_examples)
+        .map_err(to_cli_error)
+        .context("Failed to initialize sbatchman directory")?;
       println!("✅ Sbatchman initialized successfully!");
+      for example in &examples {
+        println!("📄 Wrote {}", example.display());
+      }
+      if !examples.is_empty() {
+        println!(
+          "💡 Try them with `sbatchman configure clusters.yaml`, `sbatchman set-cluster-name local` and `sbatchman launch jobs.yaml`"
+        );
+      }
+      if let Some(scheduler) = Scheduler::detect() {
+        println!(
+          "💡 Detected scheduler: use `scheduler: {:?}` for this machine in your clusters file",
+          scheduler
+        );
+      }
     }
 
     Some(Commands::SetClusterName { name, local }) => {
-      let mut sbatchman = core::Sbatchman::new().expect("Failed to initialize Sbatchman");
+      let mut sbatchman = open_sbatchman(&cli)?;
       sbatchman
         .set_cluster_name(name, *local)
-        .expect("Failed to set cluster name in sbatchman configuration");
+        .map_err(to_cli_error)
+        .context("Failed to set cluster name in sbatchman configuration")?;
       let scope = if *local { "locally" } else { "globally" };
       println!("✅ Cluster name {} set to '{}' successfully!", scope, name);
     }
 
-    Some(Commands::Configure { file }) => {
-      let mut sbatchman = core::Sbatchman::new().expect("Failed to initialize Sbatchman");
+    Some(Commands::Configure {
+      file,
+      dump_json: true,
+      ..
+    }) => {
+      let json = Sbatchman::clusters_configs_to_json(file)
+        .map_err(to_cli_error)
+        .context("Failed to parse clusters and configs from file")?;
+      println!(
+        "{}",
+        serde_json::to_string_pretty(&json).context("Failed to serialize clusters and configs")?
+      );
+    }
+
+    Some(Commands::Configure {
+      file,
+      dump_json: false,
+      strict,
+    }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
       sbatchman
-        .import_clusters_configs_from_file(file)
-        .expect("Failed to import clusters and configs from file");
+        .import_clusters_configs_from_file(file, *strict)
+        .map_err(to_cli_error)
+        .context("Failed to import clusters and configs from file")?;
     }
 
     Some(Commands::Launch {
       file,
       cluster_name: cluster,
+      global_cluster,
+      count: true,
+      vars,
+      limit,
+      ..
     }) => {
-      let mut sbatchman = core::Sbatchman::new().expect("Failed to initialize Sbatchman");
+      let mut sbatchman = open_sbatchman(&cli)?;
+      let cluster = select_cluster(&sbatchman, cluster, *global_cluster)?;
+      let count = sbatchman
+        .count_jobs_from_file(file, &cluster, &vars.iter().cloned().collect())
+        .map_err(to_cli_error)
+        .context("Failed to count jobs from file")?;
+      let count = limit.map_or(count, |limit| count.min(limit));
+      println!("{} job(s) would be launched from '{}'", count, file);
+    }
+
+    Some(Commands::Launch {
+      file,
+      cluster_name: cluster,
+      global_cluster,
+      count: false,
+      yes,
+      vars,
+      env_file,
+      limit,
+      quiet,
+      output_dir,
+      strict,
+      submit_args,
+    }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
+      let cluster = select_cluster(&sbatchman, cluster, *global_cluster)?;
+      let overrides = LaunchOverrides {
+        vars: vars.iter().cloned().collect(),
+        env: match env_file {
+          Some(env_file) => Sbatchman::parse_env_file(env_file)
+            .map_err(to_cli_error)
+            .context("Failed to read the env file")?,
+          None => HashMap::new(),
+        },
+        limit: *limit,
+        strict: *strict,
+        submit_args: submit_args.clone(),
+      };
+      launch_jobs(
+        &mut sbatchman,
+        file,
+        &cluster,
+        &overrides,
+        output_dir,
+        *yes,
+        *quiet,
+      )?;
+    }
+
+    Some(Commands::Run {
+      config_name,
+      cluster,
+      output_dir,
+      command,
+    }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
+      let summary = sbatchman
+        .run_command(config_name, &shell_join(command), cluster, output_dir)
+        .map_err(to_cli_error)
+        .context("Failed to run the command")?;
+      let id = summary.job_ids[0];
+      if summary.failed > 0 {
+        return Err(anyhow!("Job #{} failed submission, see its log", id));
+      }
+      if summary.virtual_queued > 0 {
+        println!("✅ Job #{} added to the virtual queue", id);
+      } else {
+        println!("✅ Job #{} launched", id);
+      }
+    }
+
+    Some(Commands::Resume {
+      file,
+      cluster_name: cluster,
+      vars,
+      quiet,
+      output_dir,
+    }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
+      let summary = sbatchman
+        .resume_jobs_from_file(
+          file,
+          cluster,
+          &vars.iter().cloned().collect(),
+          output_dir,
+          *quiet,
+        )
+        .map_err(to_cli_error)
+        .context("Failed to resume jobs from file")?;
+      if !quiet {
+        println!(
+          "✅ Launched {} job(s), {} in the virtual queue, {} failed, {} skipped",
+          summary.launch.launched,
+          summary.launch.virtual_queued,
+          summary.launch.failed,
+          summary.skipped
+        );
+      }
+    }
+
+    Some(Commands::Purge { status }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
+      let purged = sbatchman
+        .purge_jobs(status)
+        .map_err(to_cli_error)
+        .context("Failed to purge jobs")?;
+      println!("✅ Purged {} {:?} job(s)", purged, status);
+    }
+
+    Some(Commands::SetStatus { ids, status }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
+      let updated = sbatchman
+        .set_status_for_ids(ids, status)
+        .map_err(to_cli_error)
+        .context("Failed to set the status of the jobs")?;
+      println!("✅ Set {} job(s) to {:?}", updated, status);
+    }
+
+    Some(Commands::Doctor { fix }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
+      let report = sbatchman
+        .check_jobs(*fix)
+        .map_err(to_cli_error)
+        .context("Failed to check the jobs")?;
+      print!("{}", format_doctor_report(&report, *fix));
+    }
+
+    Some(Commands::Cancel { id, run }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
+      if let Some(launch_id) = run {
+        let summary = sbatchman
+          .cancel_launch(launch_id)
+          .map_err(to_cli_error)
+          .context(format!("Failed to cancel launch '{}'", launch_id))?;
+        print!("{}", format_cancel_launch_summary(&summary, launch_id));
+        if !summary.failed.is_empty() {
+          return Err(anyhow!(
+            "{} job(s) of launch '{}' could not be canceled",
+            summary.failed.len(),
+            launch_id
+          ));
+        }
+      } else if let Some(id) = id {
+        sbatchman
+          .cancel_job(*id)
+          .map_err(to_cli_error)
+          .context(format!("Failed to cancel job #{}", id))?;
+        println!("✅ Job #{} canceled", id);
+      }
+    }
+
+    Some(Commands::Rerun { id, regenerate }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
+      let job = sbatchman
+        .rerun_job(*id, *regenerate)
+        .map_err(to_cli_error)
+        .context("Failed to re-run job")?;
+      println!("✅ Job #{} re-run as job #{}", id, job.id);
+    }
+
+    Some(Commands::RegenerateScripts { config_name }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
+      let count = sbatchman
+        .regenerate_scripts(config_name)
+        .map_err(to_cli_error)
+        .context(format!(
+          "Failed to regenerate the scripts of config '{}'",
+          config_name
+        ))?;
+      println!("✅ Regenerated {} script(s)", count);
+    }
+
+    Some(Commands::Sync {
+      watch,
+      interval,
+      parallel,
+      since,
+    }) => {
+      let mut sbatchman = open_sbatchman(&cli)?;
+      let mut sync = || {
+        sbatchman
+          .sync_job_statuses(*parallel, *since)
+          .map_err(to_cli_error)
+          .context("Failed to sync job statuses")
+      };
+      if !*watch {
+        println!("✅ {}", format_sync_summary(&sync()?));
+        return Ok(());
+      }
+      let interrupted = Arc::new(AtomicBool::new(false));
+      let handler_flag = interrupted.clone();
+      ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))
+        .context("Failed to set the Ctrl-C handler")?;
+      let summary = watch_jobs(
+        sync,
+        |summary| println!("{}", format_sync_summary(summary)),
+        Duration::from_secs(*interval),
+        &interrupted,
+      )?;
+      if summary.is_done() {
+        println!("✅ No job left queued or running");
+      }
+    }
+
+    Some(Commands::DumpJobs {}) => {
+      let mut sbatchman = open_sbatchman_for_queries(&cli)?;
       sbatchman
-        .launch_jobs_from_file(file, cluster)
-        .expect("Failed to launch jobs from file");
+        .dump_jobs_jsonl(&mut std::io::stdout().lock())
+        .map_err(to_cli_error)
+        .context("Failed to dump the jobs")?;
+    }
+
+    #[cfg(feature = "server")]
+    Some(Commands::Serve { port, host }) => {
+      let mut sbatchman = open_sbatchman_for_queries(&cli)?;
+      println!("🌐 Serving on http://{}:{}", host, port);
+      sbatchman
+        .serve(host, *port)
+        .map_err(to_cli_error)
+        .context("Failed to serve the jobs")?;
+    }
+
+    Some(Commands::ExportJob { id }) => {
+      let mut sbatchman = open_sbatchman_for_queries(&cli)?;
+      let document = sbatchman
+        .export_job(*id)
+        .map_err(to_cli_error)
+        .context(format!("Failed to export job #{}", id))?;
+      println!("{}", serde_json::to_string_pretty(&document)?);
+    }
+
+    Some(Commands::Logs { id, follow }) => {
+      let mut sbatchman = open_sbatchman_for_queries(&cli)?;
+      let job = sbatchman
+        .get_job(*id)
+        .map_err(to_cli_error)
+        .context(format!("Failed to get job #{}", id))?;
+      let mut tail = LogTail::new(job.get_log_path());
+      if !*follow {
+        for (timestamp, log) in tail
+          .read_new_entries()
+          .context("Failed to read the job log")?
+        {
+          println!("{}", format_log_entry(&timestamp, &log));
+        }
+        return Ok(());
+      }
+      let interrupted = Arc::new(AtomicBool::new(false));
+      let handler_flag = interrupted.clone();
+      ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))
+        .context("Failed to set the Ctrl-C handler")?;
+      follow_log(
+        &mut tail,
+        |line| println!("{}", line),
+        Duration::from_millis(500),
+        &interrupted,
+      )?;
+    }
+
+    Some(Commands::Explain {
+      file,
+      cluster_name: cluster,
+      job,
+      vars,
+    }) => {
+      let mut sbatchman = open_sbatchman_for_queries(&cli)?;
+      let trace = sbatchman
+        .explain_job(
+          file,
+          cluster,
+          job.as_deref(),
+          &vars.iter().cloned().collect(),
+        )
+        .map_err(to_cli_error)
+        .context("Failed to explain job")?;
+      print!("{}", format_substitution_trace(&trace));
+    }
+
+    Some(Commands::Completions { shell }) => {
+      generate_completions(*shell, &mut std::io::stdout());
+    }
+
+    Some(Commands::List { format, run }) => {
+      let mut sbatchman = open_sbatchman_for_queries(&cli)?;
+      let filter = run.as_ref().map(|launch_id| JobFilter {
+        launch_id: Some(launch_id.clone()),
+        ..Default::default()
+      });
+      let jobs = sbatchman
+        .get_jobs(filter)
+        .map_err(to_cli_error)
+        .context("Failed to get the jobs")?;
+      print!(
+        "{}",
+        format_jobs(
+          &jobs,
+          &ColumnConfig::default().columns,
+          *format,
+          sbatchman.get_display_timezone()
+        )?
+      );
+    }
+
+    Some(Commands::Stats { cluster }) => {
+      let mut sbatchman = open_sbatchman_for_queries(&cli)?;
+      let counts = sbatchman
+        .count_jobs_by_status(cluster.as_deref())
+        .map_err(to_cli_error)
+        .context("Failed to count the jobs")?;
+      print!("{}", format_status_counts(&counts));
+    }
+
+    Some(Commands::Configs { json }) => {
+      let mut sbatchman = open_sbatchman_for_queries(&cli)?;
+      print!("{}", format_configs(&mut sbatchman, *json)?);
+    }
+
+    Some(Commands::ShowConfig {
+      name,
+      cluster,
+      script,
+    }) => {
+      let mut sbatchman = open_sbatchman_for_queries(&cli)?;
+      let (cluster, config) = sbatchman
+        .get_config(name, cluster)
+        .map_err(to_cli_error)
+        .context(format!("Failed to get config '{}'", name))?;
+      print!("{}", format_config(&cluster, &config, *script));
+    }
+
+    Some(Commands::Vars { file, json }) => {
+      let variables = Sbatchman::describe_variables(file)
+        .map_err(to_cli_error)
+        .context("Failed to read the variables of the file")?;
+      if *json {
+        println!(
+          "{}",
+          serde_json::to_string_pretty(&variables).context("Failed to serialize variables")?
+        );
+      } else {
+        print!("{}", format_variables(&variables));
+      }
+    }
+
+    Some(Commands::Validate { check_modules }) => {
+      let mut sbatchman = open_sbatchman_for_queries(&cli)?;
+      let (cluster, configs) = sbatchman
+        .get_this_cluster_configs()
+        .map_err(to_cli_error)
+        .context("Failed to get the configs of the current cluster")?;
+      println!(
+        "Cluster '{}' has {} config(s)",
+        cluster.cluster_name,
+        configs.len()
+      );
+      if *check_modules {
+        let missing = sbatchman
+          .find_missing_modules()
+          .map_err(to_cli_error)
+          .context("Failed to check the modules")?;
+        match missing {
+          None => println!("⚠️ The `module` command is not available, modules were not checked"),
+          Some(missing) if missing.is_empty() => println!("✅ All the modules are available"),
+          Some(missing) => {
+            for (config, module) in missing {
+              println!("⚠️ Module '{}' of config '{}' not found", module, config);
+            }
+          }
+        }
+      }
     }
 
     Some(Commands::TUI {}) => {
-      let mut sbatchman = core::Sbatchman::new().expect("Failed to initialize Sbatchman");
-      launch_tui(&mut sbatchman).expect("Failed to launch TUI")
-    },
+      let mut sbatchman = open_sbatchman(&cli)?;
+      launch_tui(&mut sbatchman).context("Failed to launch TUI")?;
+    }
 
     Some(Commands::Export {
       format,
       compressed_name,
     }) => {
-      crate::import_export::export::export(format.as_deref(), compressed_name.as_deref());
+      crate::import_export::export::export(
+        format.as_deref(),
+        compressed_name.as_deref(),
+        cli.db.as_deref(),
+      );
     }
 
-    Some(Commands::Import {}) => {
-      crate::import_export::import::import();
+    Some(Commands::Import { archive, dry_run }) => {
+      crate::import_export::import::import(archive, *dry_run, cli.db.as_deref())
+        .map_err(to_cli_error)
+        .context("Failed to import the archive")?;
     }
 
     Some(Commands::Update {}) => {
-      utils::update().expect("Failed to update sbatchman");
+      utils::update().context("Failed to update sbatchman")?;
     }
 
     None => {}
   }
+  Ok(())
 }
-// }
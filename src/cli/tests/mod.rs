@@ -0,0 +1,379 @@
+use std::{
+  collections::HashMap,
+  sync::atomic::{AtomicBool, Ordering},
+  time::Duration,
+};
+
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use log::LevelFilter;
+use serde_json::json;
+
+use crate::{
+  cli::{
+    Cli, Commands, OutputFormat, format_cancel_launch_summary, format_config, format_configs,
+    format_jobs, format_log_entry, format_status_counts, format_variables, generate_completions,
+    launch_jobs, log_level, select_cluster, watch_jobs,
+  },
+  core::{
+    database::models::{Job, Status},
+    jobs::{CancelLaunchSummary, JobError, LaunchOverrides, LogTail, SyncSummary, VariableInfo},
+    sbatchman_configs::{DisplayTimezone, tests::init_sbatchman_for_tests},
+    tests::{lock_cluster_env, sbatchman_for_tests, set_local_cluster_for_tests},
+  },
+  tui::ColumnType,
+};
+
+#[test]
+fn test_launch_without_cluster_set() {
+  let _env = lock_cluster_env();
+  let dir = init_sbatchman_for_tests();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+
+  let error = launch_jobs(
+    &mut sbatchman,
+    "src/core/jobs/tests/files/single.yaml",
+    &None,
+    &LaunchOverrides::default(),
+    &None,
+    false,
+    true,
+  )
+  .unwrap_err();
+
+  assert_eq!(
+    format!("{:#}", error),
+    "Failed to launch jobs from file: No cluster set. Run `sbatchman set-cluster-name <NAME>` first, set SBATCHMAN_CLUSTER or pass the cluster name."
+  );
+}
+
+#[test]
+fn test_verbose_flag_sets_log_level() {
+  for (args, level) in [
+    (vec!["sbatchman", "init"], LevelFilter::Warn),
+    (vec!["sbatchman", "-v", "init"], LevelFilter::Info),
+    (vec!["sbatchman", "init", "-vv"], LevelFilter::Debug),
+    (vec!["sbatchman", "-vvv", "init"], LevelFilter::Debug),
+  ] {
+    let cli = Cli::try_parse_from(args).unwrap();
+    assert_eq!(log_level(cli.verbose), level);
+  }
+}
+
+#[test]
+fn test_launch_var_overrides() {
+  let cli = Cli::try_parse_from([
+    "sbatchman",
+    "launch",
+    "jobs.yaml",
+    "--var",
+    "seed=42",
+    "--var",
+    "expr=a=b",
+  ])
+  .unwrap();
+  let Some(Commands::Launch { vars, .. }) = cli.command else {
+    panic!("Expected the launch command");
+  };
+  assert_eq!(
+    vars,
+    vec![
+      ("seed".to_string(), "42".to_string()),
+      ("expr".to_string(), "a=b".to_string())
+    ]
+  );
+
+  assert!(Cli::try_parse_from(["sbatchman", "launch", "jobs.yaml", "--var", "seed"]).is_err());
+}
+
+#[test]
+fn test_launch_submit_args() {
+  let cli = Cli::try_parse_from([
+    "sbatchman",
+    "launch",
+    "jobs.yaml",
+    "--submit-arg",
+    "--hold",
+    "--submit-arg=--test-only",
+  ])
+  .unwrap();
+  let Some(Commands::Launch { submit_args, .. }) = cli.command else {
+    panic!("Expected the launch command");
+  };
+  assert_eq!(submit_args, vec!["--hold", "--test-only"]);
+}
+
+/// Summary of a sync leaving `active` jobs running
+fn active_summary(active: usize) -> SyncSummary {
+  SyncSummary {
+    updated: 1,
+    running: active,
+    ..Default::default()
+  }
+}
+
+#[test]
+fn test_watch_stops_when_no_active_jobs() {
+  let mut remaining = vec![active_summary(0), active_summary(1), active_summary(2)];
+  let mut reports = vec![];
+
+  let summary = watch_jobs(
+    || Ok(remaining.pop().unwrap()),
+    |summary| reports.push(summary.running),
+    Duration::ZERO,
+    &AtomicBool::new(false),
+  )
+  .unwrap();
+
+  assert!(summary.is_done());
+  assert_eq!(reports, vec![2, 1, 0]);
+}
+
+#[test]
+fn test_watch_stops_when_interrupted() {
+  let interrupted = AtomicBool::new(false);
+  let mut syncs = 0;
+
+  let summary = watch_jobs(
+    || {
+      syncs += 1;
+      interrupted.store(true, Ordering::SeqCst);
+      Ok(active_summary(3))
+    },
+    |_| {},
+    Duration::from_secs(30),
+    &interrupted,
+  )
+  .unwrap();
+
+  assert!(!summary.is_done());
+  assert_eq!(syncs, 1);
+}
+
+#[test]
+fn test_select_global_cluster() {
+  let dir = init_sbatchman_for_tests();
+  let sbatchman = sbatchman_for_tests(&dir);
+
+  let cli = Cli::try_parse_from(["sbatchman", "launch", "jobs.yaml", "--global-cluster"]).unwrap();
+  let Some(Commands::Launch { global_cluster, .. }) = cli.command else {
+    panic!("expected the launch command");
+  };
+  assert!(global_cluster);
+
+  let cluster = Some("explicit".to_string());
+  assert_eq!(
+    select_cluster(&sbatchman, &cluster, false).unwrap(),
+    cluster
+  );
+  assert_eq!(select_cluster(&sbatchman, &None, false).unwrap(), None);
+  let error = select_cluster(&sbatchman, &None, true).unwrap_err();
+  assert!(error.to_string().starts_with("No cluster set."));
+
+  assert!(
+    Cli::try_parse_from([
+      "sbatchman",
+      "launch",
+      "jobs.yaml",
+      "cluster",
+      "--global-cluster"
+    ])
+    .is_err()
+  );
+}
+
+#[test]
+fn test_format_variables() {
+  let variables = vec![
+    VariableInfo {
+      name: "size".to_string(),
+      kind: "List",
+      dependencies: vec![],
+    },
+    VariableInfo {
+      name: "output".to_string(),
+      kind: "Scalar",
+      dependencies: vec!["size".to_string()],
+    },
+  ];
+  assert_eq!(
+    format_variables(&variables),
+    "size (List)\noutput (Scalar)\n  └─ size\n"
+  );
+}
+
+#[test]
+fn test_format_log_entries() {
+  let mut tail = LogTail::new("src/cli/tests/files/log.jsonb".into());
+  let lines: Vec<String> = tail
+    .read_new_entries()
+    .unwrap()
+    .iter()
+    .map(|(timestamp, log)| format_log_entry(timestamp, log))
+    .collect();
+  assert_eq!(
+    lines,
+    vec![
+      "2025-10-20 08:00:00.000  Metadata       job #7 \"train\": python train.py",
+      "2025-10-20 08:00:01.250  StatusUpdate   Running",
+      "2025-10-20 08:00:01.300  Variable       PID=4242",
+      "2025-10-20 08:01:01.000  ResourceUsage  max_rss_kb=1024 cpu_seconds=- elapsed_seconds=60",
+      "2025-10-20 08:01:01.500  StatusUpdate   Completed",
+    ]
+  );
+  assert!(tail.read_new_entries().unwrap().is_empty());
+}
+
+#[test]
+fn test_format_configs() {
+  let _env = lock_cluster_env();
+  let dir = init_sbatchman_for_tests();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  sbatchman
+    .import_clusters_configs_from_file("src/core/parsers/tests/files/description.yaml", false)
+    .unwrap();
+
+  let error = format_configs(&mut sbatchman, false).unwrap_err();
+  assert!(format!("{:#}", error).contains("Run `sbatchman set-cluster-name <NAME>` first"));
+
+  set_local_cluster_for_tests(&mut sbatchman, "clusterA");
+  assert_eq!(
+    format_configs(&mut sbatchman, false).unwrap(),
+    "documented\nundocumented\n"
+  );
+  assert_eq!(
+    format_configs(&mut sbatchman, true).unwrap(),
+    "[\"documented\",\"undocumented\"]\n"
+  );
+}
+
+#[test]
+fn test_format_config_merges_cluster_defaults() {
+  let _env = lock_cluster_env();
+  let dir = init_sbatchman_for_tests();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  sbatchman
+    .import_clusters_configs_from_file("src/core/parsers/tests/files/merged_params.yaml", false)
+    .unwrap();
+  set_local_cluster_for_tests(&mut sbatchman, "clusterA");
+
+  let (cluster, config) = sbatchman.get_config("inherits", &None).unwrap();
+  assert_eq!(
+    format_config(&cluster, &config, false),
+    "Config 'inherits' of cluster 'clusterA'\n\
+     Flags:\n  mem=4G\n  time=01:00:00\n\
+     Env:\n  OMP_NUM_THREADS=\"4\"\n"
+  );
+
+  let (cluster, config) = sbatchman
+    .get_config("bigger", &Some("clusterA".to_string()))
+    .unwrap();
+  let output = format_config(&cluster, &config, true);
+  assert!(output.contains("  mem=16G\n  time=01:00:00\n"));
+  assert!(output.ends_with(
+    "Script header:\n\
+     #SBATCH --mem=16G\n\
+     #SBATCH --time=01:00:00\n\
+     # Environment variables\n\
+     export OMP_NUM_THREADS=\"4\"\n\n"
+  ));
+
+  assert!(sbatchman.get_config("missing", &None).is_err());
+}
+
+fn list_job(id: i32, job_name: &str) -> Job {
+  Job {
+    id,
+    job_name: job_name.to_string(),
+    config_id: 1,
+    submit_time: Some(1700000000),
+    directory: String::new(),
+    command: "echo".to_string(),
+    status: Status::Completed,
+    variables: json!({}),
+    labels: json!({}),
+    ..Default::default()
+  }
+}
+
+#[test]
+fn test_format_jobs() {
+  let jobs = vec![list_job(1, "sweep, large"), list_job(2, "say \"hi\"")];
+  let columns = [ColumnType::Id, ColumnType::JobName, ColumnType::SubmitTime];
+
+  let csv = format_jobs(&jobs, &columns, OutputFormat::Csv, DisplayTimezone::Utc).unwrap();
+  assert_eq!(
+    csv,
+    "ID,Job Name,Submit Time\n\
+     1,\"sweep, large\",2023-11-14 22:13:20 UTC\n\
+     2,\"say \"\"hi\"\"\",2023-11-14 22:13:20 UTC\n"
+  );
+
+  let table = format_jobs(
+    &jobs,
+    &columns[..2],
+    OutputFormat::Table,
+    DisplayTimezone::Utc,
+  )
+  .unwrap();
+  assert_eq!(table, "ID  Job Name\n1   sweep, large\n2   say \"hi\"\n");
+
+  let json = format_jobs(
+    &jobs,
+    &columns[..2],
+    OutputFormat::Json,
+    DisplayTimezone::Utc,
+  )
+  .unwrap();
+  assert_eq!(
+    serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+    json!([
+      {"ID": "1", "Job Name": "sweep, large"},
+      {"ID": "2", "Job Name": "say \"hi\""},
+    ])
+  );
+}
+
+#[test]
+fn test_generate_bash_completions() {
+  let mut out = Vec::new();
+  generate_completions(Shell::Bash, &mut out);
+
+  let script = String::from_utf8(out).unwrap();
+  assert!(script.contains("_sbatchman()"));
+  for subcommand in ["launch", "resume", "logs", "configs", "completions"] {
+    assert!(script.contains(subcommand), "{}", subcommand);
+  }
+}
+
+#[test]
+fn test_help_lists_subcommands() {
+  let help = Cli::command().render_help().to_string();
+  for subcommand in ["configure", "launch", "sync", "list", "show-config", "tui"] {
+    assert!(help.contains(subcommand), "{}", subcommand);
+  }
+  assert!(!help.contains("posts"));
+}
+
+#[test]
+fn test_format_status_counts() {
+  let counts = HashMap::from([(Status::Failed, 1), (Status::Completed, 3)]);
+  assert_eq!(
+    format_status_counts(&counts),
+    "Completed: 3\nFailed: 1\nTotal: 4\n"
+  );
+  assert_eq!(format_status_counts(&HashMap::new()), "Total: 0\n");
+}
+
+#[test]
+fn test_format_cancel_launch_summary() {
+  let summary = CancelLaunchSummary {
+    canceled: vec![list_job(1, "first")],
+    failed: vec![(2, JobError::NotCancelable(2, Status::Completed))],
+  };
+  assert_eq!(
+    format_cancel_launch_summary(&summary, "run-1"),
+    "✅ 1 job(s) of launch 'run-1' canceled\n\
+     ❌ Job #2 could not be canceled: Cannot cancel job #2: it is already Completed\n"
+  );
+}
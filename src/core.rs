@@ -1,12 +1,32 @@
 mod cluster_configs;
 pub mod database;
 pub mod jobs;
+mod modules;
 mod parsers;
 pub mod sbatchman_configs;
+#[cfg(feature = "server")]
+pub mod server;
 
-use std::{collections::HashMap, path::{Path, PathBuf}};
+#[cfg(test)]
+pub(crate) mod tests;
 
-use crate::core::{database::{Database, models::{Cluster, Config, Job}}, jobs::JobFilter};
+use std::{
+  collections::HashMap,
+  io::Write,
+  path::{Path, PathBuf},
+};
+
+use crate::core::{
+  cluster_configs::ClusterConfig,
+  database::{
+    Database,
+    models::{Cluster, Config, Job, Status},
+  },
+  jobs::{
+    CancelLaunchSummary, DoctorReport, JobFilter, LaunchOverrides, LaunchSummary, ResumeSummary,
+    SubstitutionTrace, SyncSummary, VariableInfo,
+  },
+};
 
 pub struct Sbatchman {
   db: Database,
@@ -27,16 +47,43 @@ pub enum SbatchmanError {
     "No cluster set. Please set a cluster before launching jobs using `set-cluster` command."
   )]
   NoClusterSet,
+  #[error("Unknown cluster \"{0}\", {available}", available = describe_available_clusters(.1))]
+  UnknownCluster(String, Vec<String>),
   #[error("Job Error: {0}")]
   JobError(#[from] jobs::JobError),
+  #[error("IO Error: {0}")]
+  IoError(#[from] std::io::Error),
+  #[error("Import Error: {0}")]
+  ImportError(#[from] crate::import_export::import::ImportError),
+}
+
+/// End of the `UnknownCluster` message, pointing to `configure` if no cluster exists yet
+fn describe_available_clusters(clusters: &[String]) -> String {
+  if clusters.is_empty() {
+    "no clusters are configured yet, add them with `sbatchman configure`".to_string()
+  } else {
+    format!("available clusters: {}", clusters.join(", "))
+  }
 }
 
 impl Sbatchman {
   pub fn new() -> Result<Self, SbatchmanError> {
+    Self::open(None, false)
+  }
+
+  /// Open the .sbatchman directory `dir` (ex. `--db`), or the one selected by `SBATCHMAN_DIR` or
+  /// found from the working directory if `None`, see `sbatchman_configs::resolve_sbatchman_dir`.
+  /// With `readonly`, the database is opened read-only without running its migrations, see
+  /// `Database::open_readonly`.
+  pub fn open(dir: Option<&Path>, readonly: bool) -> Result<Self, SbatchmanError> {
     let _ = env_logger::try_init();
 
-    let path = sbatchman_configs::get_sbatchman_dir()?;
-    let db = Database::new(&path)?;
+    let path = sbatchman_configs::resolve_sbatchman_dir(dir)?;
+    let db = if readonly {
+      Database::open_readonly(&path)?
+    } else {
+      Database::new(&path)?
+    };
     let config_global = sbatchman_configs::get_sbatchman_config_global()?;
     let config_local = sbatchman_configs::get_sbatchman_config_local(&path)?;
     Ok(Sbatchman {
@@ -47,26 +94,35 @@ impl Sbatchman {
     })
   }
 
-  pub fn init(path: &PathBuf) -> Result<(), SbatchmanError> {
+  /// Initialize the .sbatchman directory in `path`. With `with_examples`, an example clusters
+  /// configuration and jobs file are written into `path` too, their paths are returned.
+  pub fn init(path: &PathBuf, with_examples: bool) -> Result<Vec<PathBuf>, SbatchmanError> {
     sbatchman_configs::init_sbatchman_dir(path)?;
     sbatchman_configs::init_sbatchman_config_global()?;
-    Ok(())
+    if !with_examples {
+      return Ok(vec![]);
+    }
+    Ok(sbatchman_configs::write_example_files(path)?)
   }
 
   pub fn set_cluster_name(&mut self, name: &str, local: bool) -> Result<(), SbatchmanError> {
+    self.ensure_cluster_exists(name)?;
     if local {
-      self.config_global.cluster_name = Some(name.to_string());
-      sbatchman_configs::set_sbatchman_config_global(&mut self.config_global)?;
+      self.config_local.cluster_name = Some(name.to_string());
+      sbatchman_configs::set_sbatchman_config_local(&self.path, &self.config_local)?;
     } else {
       self.config_global.cluster_name = Some(name.to_string());
-      sbatchman_configs::set_sbatchman_config_local(&self.path, &mut self.config_global)?;
+      sbatchman_configs::set_sbatchman_config_global(&self.config_global)?;
     }
     Ok(())
   }
 
+  /// Cluster used when none is passed explicitly.
+  /// `SBATCHMAN_CLUSTER` takes precedence over the local configuration, which takes precedence
+  /// over the global one; use `get_cluster_name_global` to bypass both.
   pub fn get_cluster_name(&self) -> Option<String> {
-    self
-      .get_cluster_name_local()
+    sbatchman_configs::get_cluster_name_env()
+      .or_else(|| self.get_cluster_name_local())
       .or_else(|| self.get_cluster_name_global())
   }
 
@@ -78,8 +134,18 @@ impl Sbatchman {
     self.config_local.cluster_name.clone()
   }
 
-  pub fn import_clusters_configs_from_file(&mut self, path: &str) -> Result<(), SbatchmanError> {
+  /// Import the clusters and configs of a file. The `per_cluster` keys naming unknown clusters
+  /// are reported as warnings, or as errors if `strict`.
+  pub fn import_clusters_configs_from_file(
+    &mut self,
+    path: &str,
+    strict: bool,
+  ) -> Result<(), SbatchmanError> {
     let mut clusters_configs = parsers::parse_clusters_configs_from_file(&Path::new(path))?;
+    // The `per_cluster` keys may name the clusters of the file or the already imported ones
+    let unknown =
+      parsers::unknown_cluster_keys_in_file(Path::new(path), &self.db.get_cluster_names()?)?;
+    parsers::variables::check_cluster_keys(&unknown, strict)?;
     for cluster_config in &mut clusters_configs {
       self.db.create_cluster_with_configs(cluster_config)?;
     }
@@ -87,34 +153,374 @@ impl Sbatchman {
     Ok(())
   }
 
-  pub fn launch_jobs_from_file(
+  /// Parse a clusters configuration file and return the result as JSON, without touching the database
+  pub fn clusters_configs_to_json(path: &str) -> Result<serde_json::Value, SbatchmanError> {
+    let clusters_configs = parsers::parse_clusters_configs_from_file(Path::new(path))?;
+    Ok(serde_json::json!(clusters_configs))
+  }
+
+  /// Describe the variables of a jobs file and their dependencies, without touching the database
+  pub fn describe_variables(path: &str) -> Result<Vec<VariableInfo>, SbatchmanError> {
+    Ok(jobs::describe_variables(Path::new(path))?)
+  }
+
+  /// Cluster to launch jobs on: the explicitly requested one, otherwise `get_cluster_name`
+  /// (`SBATCHMAN_CLUSTER`, then the local and the global configurations)
+  fn resolve_cluster_name(
     &mut self,
-    path: &str,
     cluster_name: &Option<String>,
-  ) -> Result<(), SbatchmanError> {
-    let cluster_name = match &cluster_name {
-      Some(name) => name,
+  ) -> Result<String, SbatchmanError> {
+    let name = match cluster_name {
+      Some(name) => name.clone(),
       None => self
-        .config_global
-        .cluster_name
-        .as_ref()
+        .get_cluster_name()
         .ok_or(SbatchmanError::NoClusterSet)?,
     };
-    Ok(jobs::launch_jobs_from_file(
-      &PathBuf::from(path),
+    self.ensure_cluster_exists(&name)?;
+    Ok(name)
+  }
+
+  /// Fail with the list of available clusters if `name` is not a known cluster
+  fn ensure_cluster_exists(&mut self, name: &str) -> Result<(), SbatchmanError> {
+    if self.db.cluster_name_exists(name)? {
+      return Ok(());
+    }
+    Err(SbatchmanError::UnknownCluster(
+      name.to_string(),
+      self.db.get_cluster_names()?,
+    ))
+  }
+
+  /// Maximum number of jobs a launch can generate. The local configuration wins over the global one.
+  pub fn get_max_sweep_size(&self) -> usize {
+    self
+      .config_local
+      .max_sweep_size
+      .or(self.config_global.max_sweep_size)
+      .unwrap_or(sbatchman_configs::DEFAULT_MAX_SWEEP_SIZE)
+  }
+
+  /// Timezone the timestamps are displayed in. The local configuration wins over the global one.
+  pub fn get_display_timezone(&self) -> sbatchman_configs::DisplayTimezone {
+    self
+      .config_local
+      .timezone
+      .or(self.config_global.timezone)
+      .unwrap_or_default()
+  }
+
+  /// Directory where the `jobs/<id>` directories are created. The `output_dir` override wins over
+  /// the local configuration, which wins over the global one. Defaults to the `.sbatchman` directory.
+  pub fn get_output_dir(&self, output_dir: &Option<String>) -> Result<PathBuf, SbatchmanError> {
+    if let Some(output_dir) = output_dir {
+      return Ok(
+        std::path::absolute(output_dir)
+          .map_err(sbatchman_configs::SbatchmanConfigError::FilesystemError)?,
+      );
+    }
+    let configured = self
+      .config_local
+      .output_dir
+      .as_ref()
+      .or(self.config_global.output_dir.as_ref());
+    Ok(match configured {
+      // Relative to the project root, the directory containing `.sbatchman`
+      Some(dir) => self.path.parent().unwrap_or(&self.path).join(dir),
+      None => self.path.clone(),
+    })
+  }
+
+  /// Launch the jobs defined in a file. Unless `yes` is set, launches generating more than
+  /// `get_max_sweep_size()` jobs are refused. `overrides` replace the variables of the file and the
+  /// environment of the configs.
+  /// With `quiet` no progress bar is shown. The job directories are created in `get_output_dir()`.
+  /// The outcome is recorded in `jobs::LAUNCH_SUMMARY_FILE`.
+  pub fn launch_jobs_from_file(
+    &mut self,
+    path: &str,
+    cluster_name: &Option<String>,
+    overrides: &LaunchOverrides,
+    output_dir: &Option<String>,
+    yes: bool,
+    quiet: bool,
+  ) -> Result<LaunchSummary, SbatchmanError> {
+    let cluster_name = self.resolve_cluster_name(cluster_name)?;
+    let max_sweep_size = (!yes).then(|| self.get_max_sweep_size());
+    let output_dir = self.get_output_dir(output_dir)?;
+    let summary = jobs::launch_jobs_from_file(
+      Path::new(path),
+      &output_dir,
+      &mut self.db,
+      &cluster_name,
+      overrides,
+      max_sweep_size,
+      quiet,
+    )?;
+    jobs::write_launch_summary(&self.path, Path::new(path), &summary)?;
+    Ok(summary)
+  }
+
+  /// Launch a single job running `command` with a config of the cluster, see `jobs::run_command`.
+  /// The job directory is created in `get_output_dir()`.
+  pub fn run_command(
+    &mut self,
+    config_name: &str,
+    command: &str,
+    cluster_name: &Option<String>,
+    output_dir: &Option<String>,
+  ) -> Result<LaunchSummary, SbatchmanError> {
+    let cluster_name = self.resolve_cluster_name(cluster_name)?;
+    let output_dir = self.get_output_dir(output_dir)?;
+    Ok(jobs::run_command(
+      command,
+      &output_dir,
+      &mut self.db,
+      &cluster_name,
+      config_name,
+    )?)
+  }
+
+  /// Parse an env file of `KEY=VALUE` lines, see `LaunchOverrides::env`
+  pub fn parse_env_file(path: &str) -> Result<HashMap<String, String>, SbatchmanError> {
+    Ok(parsers::parse_env_file(Path::new(path))?)
+  }
+
+  /// Launch the jobs of a file that a previous launch did not submit, see
+  /// `jobs::resume_jobs_from_file`
+  pub fn resume_jobs_from_file(
+    &mut self,
+    path: &str,
+    cluster_name: &Option<String>,
+    var_overrides: &HashMap<String, String>,
+    output_dir: &Option<String>,
+    quiet: bool,
+  ) -> Result<ResumeSummary, SbatchmanError> {
+    let cluster_name = self.resolve_cluster_name(cluster_name)?;
+    let output_dir = self.get_output_dir(output_dir)?;
+    Ok(jobs::resume_jobs_from_file(
+      Path::new(path),
+      &output_dir,
       &mut self.db,
-      cluster_name,
+      &cluster_name,
+      var_overrides,
+      quiet,
     )?)
   }
 
+  /// Count the jobs a file would generate, including the expansion of list and map variables
+  pub fn count_jobs_from_file(
+    &mut self,
+    path: &str,
+    cluster_name: &Option<String>,
+    var_overrides: &HashMap<String, String>,
+  ) -> Result<usize, SbatchmanError> {
+    let cluster_name = self.resolve_cluster_name(cluster_name)?;
+    Ok(jobs::count_jobs_from_file(
+      Path::new(path),
+      &mut self.db,
+      &cluster_name,
+      var_overrides,
+    )?)
+  }
+
+  /// Show how the command of a job entry of a file is substituted, see `SubstitutionTrace`
+  pub fn explain_job(
+    &mut self,
+    path: &str,
+    cluster_name: &Option<String>,
+    job_name: Option<&str>,
+    var_overrides: &HashMap<String, String>,
+  ) -> Result<SubstitutionTrace, SbatchmanError> {
+    let cluster_name = self.resolve_cluster_name(cluster_name)?;
+    Ok(jobs::explain_job_from_file(
+      Path::new(path),
+      &mut self.db,
+      &cluster_name,
+      job_name,
+      var_overrides,
+    )?)
+  }
+
+  /// Delete the jobs with the given status and their directories. Running and queued jobs cannot be purged.
+  pub fn purge_jobs(&mut self, status: &Status) -> Result<usize, SbatchmanError> {
+    Ok(jobs::purge_jobs(&mut self.db, status)?)
+  }
+
+  /// Delete the jobs of `ids` and their directories, see `jobs::delete_jobs`
+  pub fn delete_jobs(&mut self, ids: &[i32]) -> Result<usize, SbatchmanError> {
+    Ok(jobs::delete_jobs(&mut self.db, ids)?)
+  }
+
+  /// Set the status of several jobs at once (ex. jobs left running by an outage), see
+  /// `jobs::set_status_for_ids`
+  pub fn set_status_for_ids(
+    &mut self,
+    ids: &[i32],
+    status: &Status,
+  ) -> Result<usize, SbatchmanError> {
+    Ok(jobs::set_status_for_ids(&mut self.db, ids, status)?)
+  }
+
+  /// Cancel a job that is not over yet, see `jobs::cancel_job`
+  pub fn cancel_job(&mut self, id: i32) -> Result<Job, SbatchmanError> {
+    Ok(jobs::cancel_job(&mut self.db, id)?)
+  }
+
+  /// Cancel the jobs of a launch that are not over yet, see `jobs::cancel_launch`
+  pub fn cancel_launch(&mut self, launch_id: &str) -> Result<CancelLaunchSummary, SbatchmanError> {
+    Ok(jobs::cancel_launch(&mut self.db, launch_id)?)
+  }
+
+  /// Check that the jobs of the database match the job directories of the output directory,
+  /// see `jobs::check_jobs`
+  pub fn check_jobs(&mut self, fix: bool) -> Result<DoctorReport, SbatchmanError> {
+    let output_dir = self.get_output_dir(&None)?;
+    Ok(jobs::check_jobs(&mut self.db, &output_dir, fix)?)
+  }
+
+  /// Launch again a job as a new job. Without `regenerate` the original script is reused as-is.
+  pub fn rerun_job(&mut self, id: i32, regenerate: bool) -> Result<Job, SbatchmanError> {
+    let output_dir = self.get_output_dir(&None)?;
+    Ok(jobs::rerun_job(&mut self.db, &output_dir, id, regenerate)?)
+  }
+
+  /// Rebuild the scripts of the jobs of a config of the current cluster that were not launched
+  /// yet, after the config was changed. Returns the number of regenerated scripts.
+  pub fn regenerate_scripts(&mut self, config_name: &str) -> Result<usize, SbatchmanError> {
+    let (cluster, configs) = self.get_this_cluster_configs()?;
+    let config = configs
+      .into_iter()
+      .find(|config| config.config_name == config_name)
+      .ok_or_else(|| jobs::JobError::ConfigNotFound(config_name.to_string()))?;
+    Ok(jobs::regenerate_scripts(&mut self.db, &cluster, &config)?)
+  }
+
+  /// A config with its cluster: `cluster_name` if given, otherwise the current cluster. Its
+  /// flags and env are the ones merged with the cluster defaults when it was configured.
+  pub fn get_config(
+    &mut self,
+    config_name: &str,
+    cluster_name: &Option<String>,
+  ) -> Result<(Cluster, Config), SbatchmanError> {
+    let cluster_name = self.resolve_cluster_name(cluster_name)?;
+    let cluster = self.db.get_cluster_by_name(&cluster_name)?;
+    let config = self
+      .db
+      .get_configs_by_cluster(&cluster)?
+      .remove(config_name)
+      .ok_or_else(|| jobs::JobError::ConfigNotFound(config_name.to_string()))?;
+    Ok((cluster, config))
+  }
+
+  /// Scheduler directives and environment exports the job scripts of a config start with
+  pub fn config_script_header(cluster: &Cluster, config: &Config) -> String {
+    let cluster_config = ClusterConfig::new(cluster, config);
+    let mut header = String::new();
+    cluster_config.add_scheduler_directives(&mut header);
+    cluster_config.add_environment_variables(&mut header);
+    header
+  }
+
+  /// Update the status of the queued and running jobs from their logs, resubmitting the jobs
+  /// that hit their time limit if their config allows it. With `parallel`, the jobs of each
+  /// cluster are queried concurrently. With `since` (seconds), only the jobs submitted in this
+  /// window are synced.
+  pub fn sync_job_statuses(
+    &mut self,
+    parallel: bool,
+    since: Option<u64>,
+  ) -> Result<SyncSummary, SbatchmanError> {
+    let output_dir = self.get_output_dir(&None)?;
+    // Submit times are i32 timestamps: a window starting before them includes all the jobs
+    let since = since.map(|seconds| {
+      let start = chrono::Utc::now()
+        .timestamp()
+        .saturating_sub_unsigned(seconds);
+      i32::try_from(start).unwrap_or(i32::MIN)
+    });
+    if parallel {
+      return Ok(jobs::sync_job_statuses_parallel(
+        &mut self.db,
+        &output_dir,
+        since,
+      )?);
+    }
+    Ok(jobs::sync_job_statuses(&mut self.db, &output_dir, since)?)
+  }
+
+  pub fn get_job(&mut self, id: i32) -> Result<Job, SbatchmanError> {
+    Ok(self.db.get_job_by_id(id)?)
+  }
+
   pub fn get_jobs(&mut self, filter: Option<JobFilter>) -> Result<Vec<Job>, SbatchmanError> {
     self.db.get_jobs(filter).map_err(|e| SbatchmanError::StorageError(e))
   }
 
-  pub fn get_this_cluster_configs(&mut self) -> Result<(Cluster, HashMap<String, Config>), SbatchmanError> {
+  /// Number of jobs of each status, of a cluster or of all the clusters, see
+  /// `Database::count_jobs_by_status`
+  pub fn count_jobs_by_status(
+    &mut self,
+    cluster_name: Option<&str>,
+  ) -> Result<HashMap<Status, usize>, SbatchmanError> {
+    Ok(self.db.count_jobs_by_status(cluster_name)?)
+  }
+
+  /// Write all the jobs to `writer` as JSON Lines, one object per job with all its columns plus
+  /// `config_name` and `cluster_name`. Returns the number of jobs written.
+  pub fn dump_jobs_jsonl<W: Write>(&mut self, writer: &mut W) -> Result<usize, SbatchmanError> {
+    let jobs = self.db.get_jobs_with_names()?;
+    for (job, config_name, cluster_name) in &jobs {
+      let mut value = serde_json::to_value(job).map_err(std::io::Error::from)?;
+      value["config_name"] = serde_json::Value::from(config_name.as_str());
+      value["cluster_name"] = serde_json::Value::from(cluster_name.as_str());
+      writeln!(writer, "{}", value)?;
+    }
+    Ok(jobs.len())
+  }
+
+  /// Bundle a job with its config, cluster, script and parsed log entries into one JSON document,
+  /// ex. to attach it to a bug report. A missing script is exported as `null`.
+  pub fn export_job(&mut self, id: i32) -> Result<serde_json::Value, SbatchmanError> {
+    let job = self.db.get_job_by_id(id)?;
+    let config = self.db.get_config_by_id(job.config_id)?;
+    let cluster = self.db.get_cluster_by_id(config.cluster_id)?;
+    // Same layout as the lines of the log file
+    let mut logs = Vec::new();
+    for (timestamp, log) in job.parse_log_typed().unwrap_or_default() {
+      let mut entry = serde_json::to_value(&log).map_err(std::io::Error::from)?;
+      entry["timestamp"] = serde_json::json!(timestamp);
+      logs.push(entry);
+    }
+    Ok(serde_json::json!({
+      "job": job,
+      "config": config,
+      "cluster": cluster,
+      "script": job.get_script().ok(),
+      "logs": logs,
+    }))
+  }
+
+  /// Serve the jobs and clusters as read-only JSON on `host:port`, see `server::handle_request`
+  #[cfg(feature = "server")]
+  pub fn serve(&mut self, host: &str, port: u16) -> Result<(), SbatchmanError> {
+    Ok(server::serve(&mut self.db, host, port)?)
+  }
+
+  /// Modules of the configs of the current cluster that are not available on this machine, as
+  /// `(config name, module)` pairs. Returns `None` if the `module` command is not available.
+  pub fn find_missing_modules(&mut self) -> Result<Option<Vec<(String, String)>>, SbatchmanError> {
+    let (_, configs) = self.get_this_cluster_configs()?;
+    Ok(
+      modules::get_available_modules()
+        .map(|available| modules::find_missing_modules(&configs, &available)),
+    )
+  }
+
+  /// Get the current cluster and its configs, ordered by name
+  pub fn get_this_cluster_configs(&mut self) -> Result<(Cluster, Vec<Config>), SbatchmanError> {
     if let Some(cluster_name) = self.get_cluster_name() {
       let cluster = self.db.get_cluster_by_name(&cluster_name).map_err(|e| SbatchmanError::StorageError(e))?;
-      let configs = self.db.get_configs_by_cluster(&cluster).map_err(|e| SbatchmanError::StorageError(e))?;
+      let configs = self.db.get_configs_by_cluster_sorted(&cluster).map_err(SbatchmanError::StorageError)?;
       return Ok((cluster, configs));
     }
     Err(SbatchmanError::NoClusterSet)
@@ -1,3 +1,4 @@
+use serde_json::Value;
 use std::path::Path;
 use thiserror::Error;
 
@@ -5,8 +6,9 @@ use crate::core::{
   SbatchmanError,
   database::{
     StorageError,
-    models::{Cluster, Config},
+    models::{Cluster, Config, Scheduler},
   },
+  jobs::utils::{get_timestamp_string, parse_time_to_seconds},
 };
 
 #[derive(Error, Debug)]
@@ -21,6 +23,9 @@ pub enum ClusterConfigError {
   SbatchManProjectNotFound(#[from] std::io::Error),
 }
 
+/// Placeholder for the job id in the script header, replaced when the script of a job is created
+pub const JOB_ID_PLACEHOLDER: &str = "__JOB_ID__";
+
 pub struct ClusterConfig<'a> {
   pub cluster: &'a Cluster,
   pub config: &'a Config,
@@ -57,19 +62,52 @@ impl<'a> ClusterConfig<'a> {
   //   Ok(config_dir)
   // }
 
-  /// Generate bash script header with job metadata
-  /// This is used by all schedulers to create consistent script headers
+  /// Shebang of the job scripts: the `shell` of the config, looked up in the `PATH` when it is
+  /// not a path, or bash
+  pub fn shebang(&self) -> String {
+    match self.config.shell.as_deref() {
+      None => "#!/bin/bash".to_string(),
+      Some(shell) if shell.starts_with('/') => format!("#!{}", shell),
+      Some(shell) => format!("#!/usr/bin/env {}", shell),
+    }
+  }
+
+  /// Generate script header with job metadata
+  /// This is used by all schedulers to create consistent script headers. It contains:
+  /// - the shebang, see `shebang`
+  /// - a metadata block (job id, cluster, config, scheduler and generation timestamp).
+  ///   The job id is written as `JOB_ID_PLACEHOLDER`, schedulers replace it once the job id is known
+  /// - the scheduler directives generated from the config flags
+  /// - the `cd` into the working directory
   pub fn generate_script_header(&self, launch_base_path: &Path) -> String {
+    self.generate_job_script_header(launch_base_path, &[])
+  }
+
+  /// Same as `generate_script_header`, with the `job_directives` of a single job (ex. its name
+  /// and output paths) after the directives of the config
+  pub fn generate_job_script_header(
+    &self,
+    launch_base_path: &Path,
+    job_directives: &[String],
+  ) -> String {
     let mut script = String::new();
-    script.push_str("#!/bin/bash\n");
+    script.push_str(&self.shebang());
+    script.push('\n');
     script.push_str("# ======================================================================\n");
     script.push_str("# This file was automatically generated by SbatchMan.\n");
     script.push_str("# Do not edit this file directly (unless you know what you are doing).\n");
     script.push_str("# \n# --- Metadata ---\n");
+    script.push_str(&format!("# JobId: {}\n", JOB_ID_PLACEHOLDER));
+    script.push_str(&format!("# Cluster: {:?}\n", self.cluster.cluster_name));
     script.push_str(&format!("# ConfigName: {:?}\n", self.config.config_name));
     script.push_str(&format!("# Scheduler: {:?}\n", self.cluster.scheduler));
+    script.push_str(&format!("# GeneratedAt: {}\n", get_timestamp_string()));
     script.push_str("# ======================================================================\n");
-    // TODO add scheduler fields
+    self.add_scheduler_directives(&mut script);
+    for directive in job_directives {
+      script.push_str(directive);
+      script.push('\n');
+    }
     script.push_str(
       format!(
         "\n# Set Working Directory\ncd \"{}\"\n",
@@ -80,6 +118,35 @@ impl<'a> ClusterConfig<'a> {
     script
   }
 
+  /// Add scheduler directives (e.g. `#SBATCH --time=01:00:00`) generated from the config flags,
+  /// followed by the `extra_headers` of the config as they are
+  pub fn add_scheduler_directives(&self, script: &mut String) {
+    if let Some(flags) = self.config.flags.as_object() {
+      for (key, value) in flags {
+        if let Some(directive) = format_directive(self.cluster, key, value) {
+          script.push_str(&directive);
+          script.push('\n');
+        }
+      }
+    }
+    // Raw headers not covered by the known params, ex. `#SBATCH --constraint=gpu`
+    for header in self.config.extra_header_lines() {
+      script.push_str(&header);
+      script.push('\n');
+    }
+  }
+
+  /// Time limit (in seconds) the job command is wrapped with (`timeout`), from the `time` flag.
+  /// Only the local scheduler needs it: Slurm, PBS and custom schedulers enforce the time limit
+  /// natively through the directives, so the wrapper would only duplicate it.
+  pub fn script_time_limit(&self) -> Option<u64> {
+    if self.cluster.scheduler != Scheduler::Local {
+      return None;
+    }
+    let time = self.config.flags.get("time")?.as_str()?;
+    parse_time_to_seconds(time).ok()
+  }
+
   /// Add environment variables from config to script
   /// This is used by all schedulers to set up the job environment
   pub fn add_environment_variables(&self, script: &mut String) {
@@ -126,3 +193,45 @@ impl<'a> ClusterConfig<'a> {
   //   Ok(script_template_path)
   // }
 }
+
+/// Format a single config flag as a scheduler directive.
+/// Boolean flags are emitted without a value when true and skipped when false.
+fn format_directive(cluster: &Cluster, key: &str, value: &Value) -> Option<String> {
+  let value = match value {
+    Value::Bool(false) => return None,
+    Value::Bool(true) => None,
+    Value::String(s) => Some(s.clone()),
+    other => Some(other.to_string()),
+  };
+  match cluster.scheduler {
+    // Local jobs have no scheduler, flags such as `time` are handled by the script itself
+    Scheduler::Local => None,
+    Scheduler::Slurm => {
+      // Modules are loaded by the script, they are not an sbatch option
+      if key == "modules" {
+        return None;
+      }
+      let option = key.replace('_', "-");
+      Some(match value {
+        Some(v) => format!("#SBATCH --{}={}", option, v),
+        None => format!("#SBATCH --{}", option),
+      })
+    }
+    Scheduler::Pbs => {
+      let value = value?;
+      Some(match key {
+        "queue" => format!("#PBS -q {}", value),
+        "cpus" => format!("#PBS -l ncpus={}", value),
+        _ => format!("#PBS -l {}={}", key, value),
+      })
+    }
+    // Keys are used as-is, ex. `W: "02:00"` becomes `#BSUB -W 02:00`
+    Scheduler::Custom => {
+      let prefix = cluster.header_prefix.as_deref().unwrap_or("#");
+      Some(match value {
+        Some(v) => format!("{} -{} {}", prefix, key, v),
+        None => format!("{} -{}", prefix, key),
+      })
+    }
+  }
+}
@@ -4,11 +4,23 @@ pub mod schema;
 #[cfg(test)]
 mod tests;
 
-use diesel::prelude::*;
+use diesel::{
+  connection::SimpleConnection,
+  dsl::sql,
+  migration::MigrationSource,
+  prelude::*,
+  sql_types::{Bool, Text},
+  sqlite::Sqlite,
+};
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
 use log::debug;
-use std::{collections::HashMap, io, path::Path};
+use std::{
+  collections::{HashMap, HashSet},
+  io,
+  path::Path,
+};
 use thiserror::Error;
+use uuid::Uuid;
 
 use crate::core::{
   database::models::{Job, NewClusterConfig, Status},
@@ -17,7 +29,7 @@ use crate::core::{
 
 use super::database::{
   models::{Cluster, Config, NewCluster, NewConfig},
-  schema::clusters,
+  schema::{clusters, configs},
 };
 
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
@@ -34,8 +46,75 @@ pub enum StorageError {
   OperationError(String),
   #[error("Database query error: {0}")]
   QueryError(String),
+  #[error("No job with id {0}")]
+  JobNotFound(i32),
+  #[error(
+    "The database was migrated by a newer version of SbatchMan (schema version {0}), please update sbatchman"
+  )]
+  NewerSchema(String),
+  #[error(
+    "The database was created by an older version of SbatchMan and has pending migrations, run a command without `--no-migrate` first to migrate it"
+  )]
+  PendingMigrations,
+  #[error(
+    "The database {0} is corrupt ({1}). Move it away and run `sbatchman init` to create a new one, or restore it from an archive with `sbatchman import`"
+  )]
+  CorruptDatabase(String, String),
+}
+
+/// Refuse a database migrated by a newer SbatchMan: its schema may not be the one this binary
+/// expects, and running the pending migrations over it could corrupt it
+fn check_schema_version(conn: &mut SqliteConnection) -> Result<(), StorageError> {
+  let known: HashSet<String> = MigrationSource::<Sqlite>::migrations(&MIGRATIONS)?
+    .iter()
+    .map(|migration| migration.name().version().to_string())
+    .collect();
+  let applied = conn.applied_migrations()?;
+  match applied
+    .iter()
+    .find(|version| !known.contains(&version.to_string()))
+  {
+    Some(version) => Err(StorageError::NewerSchema(version.to_string())),
+    None => Ok(()),
+  }
+}
+
+/// Whether `variables` use the tagged encoding of the jobs created before the variables were
+/// stored as typed values (ex. `{"N": {"Scalar": {"Int": 42}}}`)
+fn is_legacy_variables(variables: &serde_json::Value) -> bool {
+  const TAGS: [&str; 4] = ["Scalar", "List", "StandardMap", "ClusterMap"];
+  variables.as_object().is_some_and(|vars| {
+    !vars.is_empty()
+      && vars.values().all(|var| {
+        var.as_object().is_some_and(|tagged| {
+          tagged.len() == 1 && tagged.keys().all(|tag| TAGS.contains(&tag.as_str()))
+        })
+      })
+  })
 }
 
+/// Read the schema of the database, failing with `CorruptDatabase` if the file is not a valid
+/// SQLite database (ex. overwritten or truncated)
+fn check_not_corrupt(conn: &mut SqliteConnection, path: &Path) -> Result<(), StorageError> {
+  conn
+    .batch_execute("SELECT count(*) FROM sqlite_master;")
+    .map_err(|e| match e {
+      diesel::result::Error::DatabaseError(_, ref info)
+        if info.message().contains("not a database") || info.message().contains("malformed") =>
+      {
+        StorageError::CorruptDatabase(path.display().to_string(), info.message().to_string())
+      }
+      e => StorageError::OperationError(e.to_string()),
+    })
+}
+
+/// How long a connection waits for a lock held by another connection before failing
+const BUSY_TIMEOUT_MS: u32 = 5000;
+
+/// Connection to the SQLite database of a SbatchMan project.
+/// Several processes can use the database at once (ex. the TUI while a launch runs): the database
+/// is in WAL mode, so readers are not blocked by a writer, and writers wait up to
+/// `BUSY_TIMEOUT_MS` for each other instead of failing with "database is locked".
 pub struct Database {
   conn: SqliteConnection,
 }
@@ -46,6 +125,14 @@ impl Database {
     let database_url = path.to_str().unwrap();
     let mut conn =
       SqliteConnection::establish(&database_url).map_err(StorageError::ConnectionError)?;
+    check_not_corrupt(&mut conn, &path)?;
+    conn
+      .batch_execute(&format!(
+        "PRAGMA journal_mode = WAL; PRAGMA busy_timeout = {};",
+        BUSY_TIMEOUT_MS
+      ))
+      .map_err(|e| StorageError::OperationError(e.to_string()))?;
+    check_schema_version(&mut conn)?;
     let _ = conn
       .run_pending_migrations(MIGRATIONS)
       .map_err(StorageError::MigrationError)?;
@@ -54,6 +141,27 @@ impl Database {
     Ok(Database { conn })
   }
 
+  /// Open the database read-only, without running the migrations, for the commands that only
+  /// query it (ex. on an exported archive without write access). Its schema must be up to date:
+  /// a database with pending migrations is refused with `PendingMigrations`.
+  pub fn open_readonly(path: &Path) -> Result<Self, StorageError> {
+    let database_url = format!("file:{}?mode=ro", path.join("sbatchman.db").display());
+    let mut conn =
+      SqliteConnection::establish(&database_url).map_err(StorageError::ConnectionError)?;
+    check_not_corrupt(&mut conn, &path.join("sbatchman.db"))?;
+    conn
+      .batch_execute(&format!("PRAGMA busy_timeout = {};", BUSY_TIMEOUT_MS))
+      .map_err(|e| StorageError::OperationError(e.to_string()))?;
+    // The migrations cannot run on a read-only connection
+    check_schema_version(&mut conn)?;
+    if conn.has_pending_migration(MIGRATIONS)? {
+      return Err(StorageError::PendingMigrations);
+    }
+
+    debug!("Connected to database at {} (read-only).", database_url);
+    Ok(Database { conn })
+  }
+
   pub fn create_cluster(&mut self, new_cluster: &NewCluster) -> Result<Cluster, StorageError> {
     let cluster = diesel::insert_into(clusters::table)
       .values(new_cluster)
@@ -90,20 +198,57 @@ impl Database {
     Ok(())
   }
 
+  /// Create a job with a new random `run_id`
   pub fn create_job(
     &mut self,
     new_job: &models::NewJob,
   ) -> Result<super::database::models::Job, StorageError> {
     use self::schema::jobs;
 
+    let run_id = Uuid::new_v4().to_string();
     let job = diesel::insert_into(jobs::table)
-      .values(new_job)
+      .values((new_job, jobs::run_id.eq(run_id)))
       .returning(super::database::models::Job::as_returning())
       .get_result(&mut self.conn)
       .map_err(|e| StorageError::OperationError(e.to_string()))?;
     Ok(job)
   }
 
+  /// Copy a job of another database under `config_id`, keeping its state and `run_id`.
+  /// Jobs without `run_id` get a new one.
+  pub fn import_job(&mut self, job: &Job, config_id: i32) -> Result<Job, StorageError> {
+    use self::schema::jobs;
+
+    let new_job = models::NewJob {
+      job_name: &job.job_name,
+      config_id,
+      directory: &job.directory,
+      command: &job.command,
+      status: &job.status,
+      preprocess: job.preprocess.as_deref(),
+      postprocess: job.postprocess.as_deref(),
+      variables: &job.variables,
+      labels: &job.labels,
+      launch_id: job.launch_id.as_deref(),
+    };
+    let run_id = job
+      .run_id
+      .clone()
+      .unwrap_or_else(|| Uuid::new_v4().to_string());
+    diesel::insert_into(jobs::table)
+      .values((
+        new_job,
+        jobs::run_id.eq(run_id),
+        jobs::job_id.eq(&job.job_id),
+        jobs::submit_time.eq(job.submit_time),
+        jobs::end_time.eq(job.end_time),
+        jobs::archived.eq(job.archived),
+      ))
+      .returning(Job::as_returning())
+      .get_result(&mut self.conn)
+      .map_err(|e| StorageError::OperationError(e.to_string()))
+  }
+
   pub fn update_job_path(&mut self, id: i32, directory: &str) -> Result<(), StorageError> {
     use self::schema::jobs::dsl as jobs_dsl;
 
@@ -114,6 +259,27 @@ impl Database {
     Ok(())
   }
 
+  pub fn update_job_name(&mut self, id: i32, name: &str) -> Result<(), StorageError> {
+    use self::schema::jobs::dsl as jobs_dsl;
+
+    diesel::update(jobs_dsl::jobs.filter(jobs_dsl::id.eq(id)))
+      .set(jobs_dsl::job_name.eq(name))
+      .execute(&mut self.conn)
+      .map_err(|e| StorageError::OperationError(e.to_string()))?;
+    Ok(())
+  }
+
+  /// Set the submit time (Unix seconds) of a job
+  pub fn update_job_submit_time(&mut self, id: i32, time: i32) -> Result<(), StorageError> {
+    use self::schema::jobs::dsl as jobs_dsl;
+
+    diesel::update(jobs_dsl::jobs.filter(jobs_dsl::id.eq(id)))
+      .set(jobs_dsl::submit_time.eq(time))
+      .execute(&mut self.conn)
+      .map_err(|e| StorageError::OperationError(e.to_string()))?;
+    Ok(())
+  }
+
   pub fn update_job_status(&mut self, id: i32, new_status: &Status) -> Result<(), StorageError> {
     use self::schema::jobs::dsl as jobs_dsl;
 
@@ -124,20 +290,243 @@ impl Database {
     Ok(())
   }
 
+  /// Run `f` in a transaction: everything it wrote is rolled back if it fails
+  pub fn transaction<T, E: From<StorageError>>(
+    &mut self,
+    f: impl FnOnce(&mut Self) -> Result<T, E>,
+  ) -> Result<T, E> {
+    use diesel::connection::TransactionManager;
+    type Manager = <SqliteConnection as Connection>::TransactionManager;
+
+    let storage_error = |e: diesel::result::Error| StorageError::OperationError(e.to_string());
+    Manager::begin_transaction(&mut self.conn).map_err(storage_error)?;
+    match f(self) {
+      Ok(value) => {
+        Manager::commit_transaction(&mut self.conn).map_err(storage_error)?;
+        Ok(value)
+      }
+      Err(e) => {
+        Manager::rollback_transaction(&mut self.conn).map_err(storage_error)?;
+        Err(e)
+      }
+    }
+  }
+
+  /// Set the status of the jobs of `ids` in one transaction: if one of them does not exist, none
+  /// is updated. Returns the number of updated jobs, an empty `ids` updates nothing.
+  pub fn set_status_for_ids(
+    &mut self,
+    ids: &[i32],
+    status: &Status,
+  ) -> Result<usize, StorageError> {
+    use self::schema::jobs::dsl as jobs_dsl;
+
+    if ids.is_empty() {
+      return Ok(0);
+    }
+    let mut unique_ids = ids.to_vec();
+    unique_ids.sort_unstable();
+    unique_ids.dedup();
+    let mut missing = Vec::new();
+    self
+      .conn
+      .transaction(|conn| {
+        let existing: HashSet<i32> = jobs_dsl::jobs
+          .filter(jobs_dsl::id.eq_any(&unique_ids))
+          .select(jobs_dsl::id)
+          .load::<i32>(conn)?
+          .into_iter()
+          .collect();
+        missing = unique_ids
+          .iter()
+          .copied()
+          .filter(|id| !existing.contains(id))
+          .collect();
+        if !missing.is_empty() {
+          return Err(diesel::result::Error::RollbackTransaction);
+        }
+        diesel::update(jobs_dsl::jobs.filter(jobs_dsl::id.eq_any(&unique_ids)))
+          .set(jobs_dsl::status.eq(status))
+          .execute(conn)
+      })
+      .map_err(|e| match e {
+        diesel::result::Error::RollbackTransaction => {
+          StorageError::QueryError(format!("No job with id {:?}", missing))
+        }
+        e => StorageError::OperationError(e.to_string()),
+      })
+  }
+
+  /// Delete all the jobs with the given status, returning the deleted jobs
+  pub fn delete_jobs_by_status(&mut self, status: &Status) -> Result<Vec<Job>, StorageError> {
+    use self::schema::jobs::dsl as jobs_dsl;
+
+    diesel::delete(jobs_dsl::jobs.filter(jobs_dsl::status.eq(status)))
+      .returning(Job::as_returning())
+      .get_results(&mut self.conn)
+      .map_err(|e| StorageError::OperationError(e.to_string()))
+  }
+
+  /// Delete the jobs of `ids`, returning the deleted jobs. The missing ids are ignored.
+  pub fn delete_jobs_by_ids(&mut self, ids: &[i32]) -> Result<Vec<Job>, StorageError> {
+    use self::schema::jobs::dsl as jobs_dsl;
+
+    diesel::delete(jobs_dsl::jobs.filter(jobs_dsl::id.eq_any(ids)))
+      .returning(Job::as_returning())
+      .get_results(&mut self.conn)
+      .map_err(|e| StorageError::OperationError(e.to_string()))
+  }
+
   pub fn get_jobs(&mut self, filter: Option<JobFilter>) -> Result<Vec<Job>, StorageError> {
     use self::schema::jobs::dsl as jobs_dsl;
 
-    Ok(if let Some(f) = filter {
-      jobs_dsl::jobs
-        .filter(jobs_dsl::status.eq_any(&f.statuses))
-        .filter(jobs_dsl::config_id.eq_any(&f.config_ids))
-        .load::<Job>(&mut self.conn)
-        .map_err(|e| StorageError::QueryError(e.to_string()))?
-    } else {
-      jobs_dsl::jobs
-        .load::<Job>(&mut self.conn)
-        .map_err(|e| StorageError::QueryError(e.to_string()))?
-    })
+    let mut query = jobs_dsl::jobs.into_boxed();
+    if let Some(f) = filter {
+      if !f.statuses.is_empty() {
+        query = query.filter(jobs_dsl::status.eq_any(f.statuses));
+      }
+      if !f.config_ids.is_empty() {
+        query = query.filter(jobs_dsl::config_id.eq_any(f.config_ids));
+      }
+      if let Some(since) = f.submitted_since {
+        query = query.filter(
+          jobs_dsl::submit_time
+            .ge(since)
+            .or(jobs_dsl::submit_time.is_null()),
+        );
+      }
+      if let Some(launch_id) = f.launch_id {
+        query = query.filter(jobs_dsl::launch_id.eq(launch_id));
+      }
+      // Labels are stored as a JSON object of strings
+      for (key, value) in f.labels {
+        let json_path = format!("$.\"{}\"", key.replace('"', "\\\""));
+        query = query.filter(
+          sql::<Bool>("json_extract(labels, ")
+            .bind::<Text, _>(json_path)
+            .sql(") = ")
+            .bind::<Text, _>(value),
+        );
+      }
+    }
+    query
+      .load::<Job>(&mut self.conn)
+      .map_err(|e| StorageError::QueryError(e.to_string()))
+  }
+
+  /// Find the latest job of a config with the given command and variables. The jobs stored with
+  /// the legacy encoding of the variables are matched on the command only.
+  pub fn find_job(
+    &mut self,
+    config_id: i32,
+    command: &str,
+    variables: &serde_json::Value,
+  ) -> Result<Option<Job>, StorageError> {
+    use self::schema::jobs::dsl as jobs_dsl;
+
+    jobs_dsl::jobs
+      .filter(jobs_dsl::config_id.eq(config_id))
+      .filter(jobs_dsl::command.eq(command))
+      .order(jobs_dsl::id.desc())
+      .select(Job::as_select())
+      .load(&mut self.conn)
+      .map(|jobs: Vec<Job>| {
+        jobs
+          .into_iter()
+          .find(|job| job.variables == *variables || is_legacy_variables(&job.variables))
+      })
+      .map_err(|e| StorageError::QueryError(e.to_string()))
+  }
+
+  pub fn get_job_by_id(&mut self, id: i32) -> Result<Job, StorageError> {
+    use self::schema::jobs::dsl as jobs_dsl;
+
+    jobs_dsl::jobs
+      .filter(jobs_dsl::id.eq(id))
+      .first::<Job>(&mut self.conn)
+      .map_err(|e| match e {
+        diesel::result::Error::NotFound => StorageError::JobNotFound(id),
+        e => StorageError::QueryError(e.to_string()),
+      })
+  }
+
+  pub fn get_config_by_id(&mut self, config_id: i32) -> Result<Config, StorageError> {
+    use self::schema::configs::dsl::*;
+
+    configs
+      .filter(id.eq(config_id))
+      .select(Config::as_select())
+      .first(&mut self.conn)
+      .map_err(|e| StorageError::QueryError(e.to_string()))
+  }
+
+  /// Retrieve a config together with its cluster.
+  /// If several clusters have a config with this name, the first one created is returned.
+  pub fn get_config_with_cluster(&mut self, name: &str) -> Result<(Config, Cluster), StorageError> {
+    use self::schema::configs::dsl::*;
+
+    configs
+      .inner_join(clusters::table)
+      .filter(config_name.eq(name))
+      .order(id.asc())
+      .select((Config::as_select(), Cluster::as_select()))
+      .first(&mut self.conn)
+      .map_err(|e| StorageError::QueryError(e.to_string()))
+  }
+
+  /// Retrieve all the jobs with the names of their config and cluster, ordered by id
+  pub fn get_jobs_with_names(&mut self) -> Result<Vec<(Job, String, String)>, StorageError> {
+    use self::schema::jobs::dsl as jobs_dsl;
+
+    jobs_dsl::jobs
+      .inner_join(configs::table.inner_join(clusters::table))
+      .order(jobs_dsl::id.asc())
+      .select((
+        Job::as_select(),
+        configs::config_name,
+        clusters::cluster_name,
+      ))
+      .load(&mut self.conn)
+      .map_err(|e| StorageError::QueryError(e.to_string()))
+  }
+
+  /// Number of jobs of each status, counting the jobs of the cluster `cluster_name` or of all
+  /// the clusters. The statuses without jobs are missing.
+  pub fn count_jobs_by_status(
+    &mut self,
+    cluster_name: Option<&str>,
+  ) -> Result<HashMap<Status, usize>, StorageError> {
+    use self::schema::jobs::dsl as jobs_dsl;
+
+    let mut query = jobs_dsl::jobs
+      .inner_join(configs::table.inner_join(clusters::table))
+      .group_by(jobs_dsl::status)
+      .select((jobs_dsl::status, diesel::dsl::count_star()))
+      .into_boxed();
+    if let Some(name) = cluster_name {
+      query = query.filter(clusters::cluster_name.eq(name));
+    }
+    let counts: Vec<(Status, i64)> = query
+      .load(&mut self.conn)
+      .map_err(|e| StorageError::QueryError(e.to_string()))?;
+    Ok(
+      counts
+        .into_iter()
+        .map(|(status, count)| (status, count as usize))
+        .collect(),
+    )
+  }
+
+  /// Retrieve the jobs of a config, ordered by id
+  pub fn get_jobs_by_config(&mut self, config_id: i32) -> Result<Vec<Job>, StorageError> {
+    use self::schema::jobs::dsl as jobs_dsl;
+
+    let config = self.get_config_by_id(config_id)?;
+    Job::belonging_to(&config)
+      .select(Job::as_select())
+      .order(jobs_dsl::id.asc())
+      .load(&mut self.conn)
+      .map_err(|e| StorageError::QueryError(e.to_string()))
   }
 
   pub fn get_cluster_by_name(&mut self, name: &str) -> Result<Cluster, StorageError> {
@@ -150,6 +539,26 @@ impl Database {
     Ok(cluster)
   }
 
+  /// Whether a cluster with the given name exists
+  pub fn cluster_name_exists(&mut self, name: &str) -> Result<bool, StorageError> {
+    use self::schema::clusters::dsl::*;
+
+    diesel::select(diesel::dsl::exists(clusters.filter(cluster_name.eq(name))))
+      .get_result(&mut self.conn)
+      .map_err(|e| StorageError::QueryError(e.to_string()))
+  }
+
+  /// Names of all the clusters, sorted alphabetically
+  pub fn get_cluster_names(&mut self) -> Result<Vec<String>, StorageError> {
+    use self::schema::clusters::dsl::*;
+
+    clusters
+      .select(cluster_name)
+      .order(cluster_name.asc())
+      .load(&mut self.conn)
+      .map_err(|e| StorageError::QueryError(e.to_string()))
+  }
+
   pub fn get_cluster_by_id(&mut self, cluster_id: i32) -> Result<Cluster, StorageError> {
     use self::schema::clusters::dsl::*;
 
@@ -176,4 +585,18 @@ impl Database {
     }
     Ok(configs_map)
   }
+
+  /// Retrieve all configs for a given cluster, ordered by name
+  pub fn get_configs_by_cluster_sorted(
+    &mut self,
+    cluster: &Cluster,
+  ) -> Result<Vec<Config>, StorageError> {
+    use self::schema::configs::dsl::*;
+
+    Config::belonging_to(&cluster)
+      .select(Config::as_select())
+      .order(config_name.asc())
+      .load(&mut self.conn)
+      .map_err(|e| StorageError::OperationError(e.to_string()))
+  }
 }
@@ -8,15 +8,39 @@ use diesel::{
   sql_types::Integer,
 };
 use serde::{Deserialize, Serialize};
+use std::{env, ffi::OsStr};
 use strum::EnumString;
 
 #[repr(i32)]
-#[derive(FromSqlRow, Debug, AsExpression, EnumString, PartialEq, Clone)]
+#[derive(FromSqlRow, Debug, AsExpression, EnumString, PartialEq, Clone, Serialize, Default)]
 #[diesel(sql_type = Integer)]
 pub enum Scheduler {
+  #[default]
   Local,
   Slurm,
   Pbs,
+  /// A scheduler driven by the `header_prefix` and `submit_command` of the cluster (ex. LSF)
+  Custom,
+}
+
+impl Scheduler {
+  /// Guess the scheduler of this machine from the submission commands in `PATH`: `sbatch` for
+  /// Slurm, `qsub` for PBS, otherwise `Local`. Returns `None` if `PATH` is not set.
+  pub fn detect() -> Option<Scheduler> {
+    env::var_os("PATH").map(|path| Self::detect_in_path(&path))
+  }
+
+  /// Like `detect`, looking for the submission commands in the directories of `path`
+  pub(crate) fn detect_in_path(path: &OsStr) -> Scheduler {
+    let has_command = |name: &str| env::split_paths(path).any(|dir| dir.join(name).is_file());
+    if has_command("sbatch") {
+      Scheduler::Slurm
+    } else if has_command("qsub") {
+      Scheduler::Pbs
+    } else {
+      Scheduler::Local
+    }
+  }
 }
 
 impl<DB> FromSql<Integer, DB> for Scheduler
@@ -29,6 +53,7 @@ where
       0 => Ok(Scheduler::Local),
       1 => Ok(Scheduler::Slurm),
       2 => Ok(Scheduler::Pbs),
+      3 => Ok(Scheduler::Custom),
       x => Err(format!("Unrecognized variant {}", x).into()),
     }
   }
@@ -44,11 +69,12 @@ where
       Scheduler::Local => 0.to_sql(out),
       Scheduler::Slurm => 1.to_sql(out),
       Scheduler::Pbs => 2.to_sql(out),
+      Scheduler::Custom => 3.to_sql(out),
     }
   }
 }
 
-#[derive(Queryable, Selectable, Identifiable)]
+#[derive(Queryable, Selectable, Identifiable, Serialize, Default)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 #[diesel(table_name = clusters)]
 pub struct Cluster {
@@ -56,18 +82,40 @@ pub struct Cluster {
   pub cluster_name: String,
   pub scheduler: Scheduler,
   pub max_jobs: Option<i32>,
+  /// Prefix of the script directives of a custom scheduler (ex. `#BSUB`)
+  pub header_prefix: Option<String>,
+  /// Submit command of a custom scheduler, `{script}` is replaced with the quoted script path
+  pub submit_command: Option<String>,
+  /// Regex extracting the job id from the output of the submit command of a custom scheduler
+  pub job_id_regex: Option<String>,
+  /// Shell command run before each submission, which is aborted if the command fails
+  pub pre_submit_hook: Option<String>,
+  /// Pause between two successive submissions of a launch, in milliseconds
+  pub submit_interval_ms: Option<i32>,
 }
 
-#[derive(Insertable)]
+#[derive(Insertable, Serialize, Default)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 #[diesel(table_name = clusters)]
 pub struct NewCluster {
   pub cluster_name: String,
   pub scheduler: Scheduler,
   pub max_jobs: Option<i32>,
+  /// Prefix of the script directives of a custom scheduler (ex. `#BSUB`)
+  pub header_prefix: Option<String>,
+  /// Submit command of a custom scheduler, `{script}` is replaced with the quoted script path
+  pub submit_command: Option<String>,
+  /// Regex extracting the job id from the output of the submit command of a custom scheduler
+  pub job_id_regex: Option<String>,
+  /// Shell command run before each submission, which is aborted if the command fails
+  pub pre_submit_hook: Option<String>,
+  /// Pause between two successive submissions of a launch, in milliseconds
+  pub submit_interval_ms: Option<i32>,
 }
 
-#[derive(Queryable, Selectable, Associations, Debug, PartialEq, Identifiable)]
+#[derive(
+  Queryable, Selectable, Associations, Debug, PartialEq, Identifiable, Clone, Serialize, Default,
+)]
 #[diesel(belongs_to(Cluster))]
 #[diesel(table_name = configs)]
 pub struct Config {
@@ -76,27 +124,101 @@ pub struct Config {
   pub cluster_id: i32,
   pub flags: serde_json::Value,
   pub env: serde_json::Value,
+  /// Commands run before the preprocessing of every job (cluster defaults first)
+  pub default_preprocess: Option<String>,
+  /// Commands run after the postprocessing of every job (cluster defaults first)
+  pub default_postprocess: Option<String>,
+  /// Resubmission policy of the jobs that hit their time limit, see `RetryPolicy`
+  pub retry_on_timeout: Option<serde_json::Value>,
+  /// Maximum size of the stdout and stderr logs of a job, see `CappedWriter`
+  pub max_output_bytes: Option<i64>,
+  /// Shell of the job scripts (ex. `/bin/zsh` or `zsh`), bash by default
+  pub shell: Option<String>,
+  /// Free-text note about the config, shown in the TUI
+  pub description: Option<String>,
+  /// Raw header lines (JSON array of strings) emitted verbatim after the generated directives
+  pub extra_headers: Option<serde_json::Value>,
+  /// Prefixes (JSON array of strings) of the submit-time environment variables logged at launch
+  pub env_snapshot: Option<serde_json::Value>,
 }
 
-#[derive(Insertable)]
+#[derive(Insertable, Serialize, Default)]
 #[diesel(table_name = configs)]
 pub struct NewConfig {
   pub config_name: String,
   pub cluster_id: i32,
   pub flags: serde_json::Value,
   pub env: serde_json::Value,
+  /// Commands run before the preprocessing of every job (cluster defaults first)
+  pub default_preprocess: Option<String>,
+  /// Commands run after the postprocessing of every job (cluster defaults first)
+  pub default_postprocess: Option<String>,
+  /// Resubmission policy of the jobs that hit their time limit, see `RetryPolicy`
+  pub retry_on_timeout: Option<serde_json::Value>,
+  /// Maximum size of the stdout and stderr logs of a job, see `CappedWriter`
+  pub max_output_bytes: Option<i64>,
+  /// Shell of the job scripts (ex. `/bin/zsh` or `zsh`), bash by default
+  pub shell: Option<String>,
+  /// Free-text note about the config, shown in the TUI
+  pub description: Option<String>,
+  /// Raw header lines (JSON array of strings) emitted verbatim after the generated directives
+  pub extra_headers: Option<serde_json::Value>,
+  /// Prefixes (JSON array of strings) of the submit-time environment variables logged at launch
+  pub env_snapshot: Option<serde_json::Value>,
+}
+
+/// Resubmission of the jobs that hit their time limit, with the time limit scaled at each retry
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RetryPolicy {
+  pub max_retries: u32,
+  pub time_multiplier: f64,
+}
+
+impl Config {
+  pub fn retry_policy(&self) -> Option<RetryPolicy> {
+    serde_json::from_value(self.retry_on_timeout.clone()?).ok()
+  }
+
+  /// Raw header lines of the job scripts, empty if none is set
+  pub fn extra_header_lines(&self) -> Vec<String> {
+    self
+      .extra_headers
+      .clone()
+      .and_then(|headers| serde_json::from_value(headers).ok())
+      .unwrap_or_default()
+  }
+
+  /// Prefixes of the environment variables logged at launch, `None` if nothing is logged
+  pub fn env_snapshot_prefixes(&self) -> Option<Vec<String>> {
+    serde_json::from_value(self.env_snapshot.clone()?).ok()
+  }
 }
 
+#[derive(Serialize)]
 pub struct NewClusterConfig {
   pub cluster: NewCluster,
   pub configs: Vec<NewConfig>,
 }
 
 #[repr(i32)]
-#[derive(FromSqlRow, Debug, AsExpression, EnumString, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(
+  FromSqlRow,
+  Debug,
+  AsExpression,
+  EnumString,
+  PartialEq,
+  Eq,
+  Hash,
+  Serialize,
+  Deserialize,
+  Clone,
+  Default,
+)]
 #[diesel(sql_type = Integer)]
+#[strum(serialize_all = "kebab-case")]
 pub enum Status {
-  Created,          // Job created but not yet submitted
+  #[default]
+  Created, // Job created but not yet submitted
   VirtualQueue,     // Job in virtual queue waiting for submission
   Queued,           // Job submitted and waiting in scheduler queue
   Running,          // Job is currently running
@@ -104,6 +226,21 @@ pub enum Status {
   Failed,           // Job failed
   Timeout,          // Job timed-out
   FailedSubmission, // Job submission failed
+  Canceled,         // Job canceled by the user
+}
+
+impl Status {
+  /// Whether the job is over: it will not change status anymore, unless resubmitted
+  pub fn is_terminal(&self) -> bool {
+    matches!(
+      self,
+      Status::Completed
+        | Status::Failed
+        | Status::Timeout
+        | Status::FailedSubmission
+        | Status::Canceled
+    )
+  }
 }
 
 impl<DB> FromSql<Integer, DB> for Status
@@ -119,6 +256,9 @@ where
       3 => Ok(Status::Running),
       4 => Ok(Status::Completed),
       5 => Ok(Status::Failed),
+      6 => Ok(Status::Timeout),
+      7 => Ok(Status::FailedSubmission),
+      8 => Ok(Status::Canceled),
       x => Err(format!("Unrecognized variant {}", x).into()),
     }
   }
@@ -139,11 +279,23 @@ where
       Status::Failed => 5.to_sql(out),
       Status::Timeout => 6.to_sql(out),
       Status::FailedSubmission => 7.to_sql(out),
+      Status::Canceled => 8.to_sql(out),
     }
   }
 }
 
-#[derive(Queryable, Selectable, Associations, Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(
+  Queryable,
+  Selectable,
+  Associations,
+  Debug,
+  PartialEq,
+  Identifiable,
+  Serialize,
+  Deserialize,
+  Clone,
+  Default,
+)]
 #[diesel(belongs_to(Config))]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 #[diesel(table_name = jobs)]
@@ -162,6 +314,12 @@ pub struct Job {
   // pub exit_code: Option<i32>,
   pub archived: Option<i32>,
   pub variables: serde_json::Value,
+  pub labels: serde_json::Value,
+  /// Unique id of the job across databases, generated when the job is created. `None` for the
+  /// jobs created before it was introduced.
+  pub run_id: Option<String>,
+  /// Id shared by the jobs created by the same launch, `None` for the jobs created otherwise
+  pub launch_id: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -175,4 +333,23 @@ pub struct NewJob<'a> {
   pub preprocess: Option<&'a str>,
   pub postprocess: Option<&'a str>,
   pub variables: &'a serde_json::Value,
+  pub labels: &'a serde_json::Value,
+  pub launch_id: Option<&'a str>,
+}
+
+impl Default for NewJob<'_> {
+  fn default() -> Self {
+    NewJob {
+      job_name: "",
+      config_id: 0,
+      directory: "",
+      command: "",
+      status: &Status::Created,
+      preprocess: None,
+      postprocess: None,
+      variables: &serde_json::Value::Null,
+      labels: &serde_json::Value::Null,
+      launch_id: None,
+    }
+  }
 }
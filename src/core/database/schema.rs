@@ -6,6 +6,11 @@ diesel::table! {
         cluster_name -> Text,
         scheduler -> Integer,
         max_jobs -> Nullable<Integer>,
+        header_prefix -> Nullable<Text>,
+        submit_command -> Nullable<Text>,
+        job_id_regex -> Nullable<Text>,
+        pre_submit_hook -> Nullable<Text>,
+        submit_interval_ms -> Nullable<Integer>,
     }
 }
 
@@ -16,6 +21,14 @@ diesel::table! {
         cluster_id -> Integer,
         flags -> Json,
         env -> Json,
+        default_preprocess -> Nullable<Text>,
+        default_postprocess -> Nullable<Text>,
+        retry_on_timeout -> Nullable<Json>,
+        max_output_bytes -> Nullable<BigInt>,
+        shell -> Nullable<Text>,
+        description -> Nullable<Text>,
+        extra_headers -> Nullable<Json>,
+        env_snapshot -> Nullable<Json>,
     }
 }
 
@@ -34,6 +47,9 @@ diesel::table! {
         postprocess -> Nullable<Text>,
         archived -> Nullable<Integer>,
         variables -> Json,
+        labels -> Json,
+        run_id -> Nullable<Text>,
+        launch_id -> Nullable<Text>,
     }
 }
 
@@ -11,6 +11,7 @@ fn get_set_config() {
     cluster_name: "test_cluster".to_string(),
     scheduler: Scheduler::Local,
     max_jobs: Some(10),
+    ..Default::default()
   };
   let cluster = db.create_cluster(&new_cluster).unwrap();
 
@@ -22,6 +23,7 @@ fn get_set_config() {
     cluster_id: cluster.id,
     flags: flags,
     env: env,
+    ..Default::default()
   };
   db.create_cluster_config(&new_config).unwrap();
   let configs = db.get_configs_by_cluster(&cluster).unwrap();
@@ -37,8 +39,523 @@ fn create_cluster_same_name() {
     cluster_name: "duplicate_cluster".to_string(),
     scheduler: Scheduler::Local,
     max_jobs: Some(10),
+    ..Default::default()
   };
   let _cluster1 = db.create_cluster(&new_cluster).unwrap();
   let result = db.create_cluster(&new_cluster);
   assert!(result.is_err());
 }
+
+#[test]
+fn get_configs_by_cluster_sorted() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  let cluster = db
+    .create_cluster(&NewCluster {
+      cluster_name: "test_cluster".to_string(),
+      scheduler: Scheduler::Local,
+      ..Default::default()
+    })
+    .unwrap();
+
+  for name in ["medium", "big", "small"] {
+    db.create_cluster_config(&NewConfig {
+      config_name: name.to_string(),
+      cluster_id: cluster.id,
+      flags: serde_json::json!({}),
+      env: serde_json::json!({}),
+      ..Default::default()
+    })
+    .unwrap();
+  }
+
+  let names: Vec<String> = db
+    .get_configs_by_cluster_sorted(&cluster)
+    .unwrap()
+    .into_iter()
+    .map(|config| config.config_name)
+    .collect();
+  assert_eq!(names, vec!["big", "medium", "small"]);
+}
+
+#[test]
+fn get_jobs_by_config() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  let cluster = db
+    .create_cluster(&NewCluster {
+      cluster_name: "test_cluster".to_string(),
+      scheduler: Scheduler::Local,
+      ..Default::default()
+    })
+    .unwrap();
+  let mut config_ids = vec![];
+  for name in ["cpu", "gpu"] {
+    let config = db
+      .create_cluster_config(&NewConfig {
+        config_name: name.to_string(),
+        cluster_id: cluster.id,
+        flags: serde_json::json!({}),
+        env: serde_json::json!({}),
+        ..Default::default()
+      })
+      .unwrap();
+    config_ids.push(config.id);
+  }
+
+  // Jobs of the two configs are interleaved
+  let mut gpu_job_ids = vec![];
+  for i in 0..4 {
+    let config_id = config_ids[i % 2];
+    let job = db
+      .create_job(&NewJob {
+        job_name: &format!("job_{}", i),
+        config_id,
+        directory: "",
+        command: "echo",
+        status: &Status::Created,
+        variables: &serde_json::json!({}),
+        labels: &serde_json::json!({}),
+        ..Default::default()
+      })
+      .unwrap();
+    if config_id == config_ids[1] {
+      gpu_job_ids.push(job.id);
+    }
+  }
+
+  let jobs = db.get_jobs_by_config(config_ids[1]).unwrap();
+  let ids: Vec<i32> = jobs.iter().map(|job| job.id).collect();
+  assert_eq!(ids, gpu_job_ids);
+  assert!(jobs.iter().all(|job| job.config_id == config_ids[1]));
+
+  assert!(db.get_jobs_by_config(42).is_err());
+}
+
+#[test]
+fn cluster_name_exists() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  for name in ["zeta", "alpha"] {
+    db.create_cluster(&NewCluster {
+      cluster_name: name.to_string(),
+      scheduler: Scheduler::Local,
+      ..Default::default()
+    })
+    .unwrap();
+  }
+
+  assert!(db.cluster_name_exists("alpha").unwrap());
+  assert!(!db.cluster_name_exists("beta").unwrap());
+  assert_eq!(db.get_cluster_names().unwrap(), vec!["alpha", "zeta"]);
+}
+
+#[test]
+fn get_config_with_cluster() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  let cluster = db
+    .create_cluster(&NewCluster {
+      cluster_name: "slurm_cluster".to_string(),
+      scheduler: Scheduler::Slurm,
+      ..Default::default()
+    })
+    .unwrap();
+  let config = db
+    .create_cluster_config(&NewConfig {
+      config_name: "gpu".to_string(),
+      cluster_id: cluster.id,
+      flags: serde_json::json!({"gpus": 1}),
+      env: serde_json::json!({}),
+      ..Default::default()
+    })
+    .unwrap();
+
+  let (joined_config, joined_cluster) = db.get_config_with_cluster("gpu").unwrap();
+  assert_eq!(joined_config, config);
+  assert_eq!(joined_cluster.id, cluster.id);
+  assert_eq!(joined_cluster.scheduler, Scheduler::Slurm);
+
+  assert!(db.get_config_with_cluster("missing").is_err());
+}
+
+#[test]
+fn read_during_write() {
+  use diesel::connection::SimpleConnection;
+
+  let dir = init_sbatchman_for_tests();
+  let mut writer = Database::new(dir.path()).unwrap();
+  let mut reader = Database::new(dir.path()).unwrap();
+
+  // Hold the write lock with an uncommitted insert
+  writer
+    .conn
+    .batch_execute(
+      "BEGIN IMMEDIATE; INSERT INTO clusters (cluster_name, scheduler) VALUES ('pending', 0);",
+    )
+    .unwrap();
+
+  assert!(dir.path().join("sbatchman.db-wal").exists());
+  // The reader does not see the pending cluster, but it is not blocked either
+  assert!(reader.get_cluster_names().unwrap().is_empty());
+
+  writer.conn.batch_execute("COMMIT;").unwrap();
+  assert_eq!(reader.get_cluster_names().unwrap(), vec!["pending"]);
+}
+
+#[test]
+fn job_filters_use_indexes() {
+  #[derive(QueryableByName)]
+  struct QueryPlanStep {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    detail: String,
+  }
+
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  for (column, index) in [
+    ("status", "jobs_status_idx"),
+    ("config_id", "jobs_config_id_idx"),
+    ("archived", "jobs_archived_idx"),
+  ] {
+    let plan = diesel::sql_query(format!(
+      "EXPLAIN QUERY PLAN SELECT * FROM jobs WHERE {} = 1",
+      column
+    ))
+    .load::<QueryPlanStep>(&mut db.conn)
+    .unwrap();
+    assert!(
+      plan.iter().any(|step| step.detail.contains(index)),
+      "query on {} does not use {}",
+      column,
+      index
+    );
+  }
+}
+
+#[test]
+fn status_round_trip() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  let cluster = db
+    .create_cluster(&NewCluster {
+      cluster_name: "test_cluster".to_string(),
+      scheduler: Scheduler::Local,
+      ..Default::default()
+    })
+    .unwrap();
+  let config = db
+    .create_cluster_config(&NewConfig {
+      config_name: "test_config".to_string(),
+      cluster_id: cluster.id,
+      flags: serde_json::json!({}),
+      env: serde_json::json!({}),
+      ..Default::default()
+    })
+    .unwrap();
+
+  // The stored integers must never be renumbered, existing databases rely on them
+  let cases = [
+    (Status::Created, 0),
+    (Status::VirtualQueue, 1),
+    (Status::Queued, 2),
+    (Status::Running, 3),
+    (Status::Completed, 4),
+    (Status::Failed, 5),
+    (Status::Timeout, 6),
+    (Status::FailedSubmission, 7),
+    (Status::Canceled, 8),
+  ];
+  for (status, value) in cases {
+    let job = db
+      .create_job(&NewJob {
+        job_name: "job",
+        config_id: config.id,
+        directory: "",
+        command: "echo",
+        status: &status,
+        variables: &serde_json::json!({}),
+        labels: &serde_json::json!({}),
+        ..Default::default()
+      })
+      .unwrap();
+    assert_eq!(db.get_job_by_id(job.id).unwrap().status, status);
+    let stored: i32 = schema::jobs::table
+      .filter(schema::jobs::id.eq(job.id))
+      .select(diesel::dsl::sql::<diesel::sql_types::Integer>("status"))
+      .first(&mut db.conn)
+      .unwrap();
+    assert_eq!(stored, value, "{:?}", status);
+  }
+}
+
+#[test]
+fn set_status_for_ids() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  let cluster = db
+    .create_cluster(&NewCluster {
+      cluster_name: "test_cluster".to_string(),
+      scheduler: Scheduler::Local,
+      ..Default::default()
+    })
+    .unwrap();
+  let config = db
+    .create_cluster_config(&NewConfig {
+      config_name: "test_config".to_string(),
+      cluster_id: cluster.id,
+      flags: serde_json::json!({}),
+      env: serde_json::json!({}),
+      ..Default::default()
+    })
+    .unwrap();
+  let ids: Vec<i32> = (0..4)
+    .map(|_| {
+      db.create_job(&NewJob {
+        job_name: "job",
+        config_id: config.id,
+        directory: "",
+        command: "echo",
+        status: &Status::Running,
+        variables: &serde_json::json!({}),
+        labels: &serde_json::json!({}),
+        ..Default::default()
+      })
+      .unwrap()
+      .id
+    })
+    .collect();
+
+  let updated = db
+    .set_status_for_ids(&[ids[0], ids[2], ids[3], ids[0]], &Status::Failed)
+    .unwrap();
+  assert_eq!(updated, 3);
+  let statuses: Vec<Status> = ids
+    .iter()
+    .map(|id| db.get_job_by_id(*id).unwrap().status)
+    .collect();
+  assert_eq!(
+    statuses,
+    vec![
+      Status::Failed,
+      Status::Running,
+      Status::Failed,
+      Status::Failed
+    ]
+  );
+
+  // An unknown id rolls back the whole update
+  let result = db.set_status_for_ids(&[ids[1], 999], &Status::Completed);
+  assert!(matches!(result, Err(StorageError::QueryError(message)) if message.contains("999")));
+  assert_eq!(db.get_job_by_id(ids[1]).unwrap().status, Status::Running);
+
+  assert_eq!(db.set_status_for_ids(&[], &Status::Completed).unwrap(), 0);
+}
+
+#[test]
+fn count_jobs_by_status() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  let mut config_ids = vec![];
+  for cluster_name in ["cluster_a", "cluster_b"] {
+    let cluster = db
+      .create_cluster(&NewCluster {
+        cluster_name: cluster_name.to_string(),
+        scheduler: Scheduler::Local,
+        ..Default::default()
+      })
+      .unwrap();
+    let config = db
+      .create_cluster_config(&NewConfig {
+        config_name: "test_config".to_string(),
+        cluster_id: cluster.id,
+        flags: serde_json::json!({}),
+        env: serde_json::json!({}),
+        ..Default::default()
+      })
+      .unwrap();
+    config_ids.push(config.id);
+  }
+  let jobs = [
+    (config_ids[0], Status::Completed),
+    (config_ids[0], Status::Completed),
+    (config_ids[0], Status::Failed),
+    (config_ids[0], Status::Running),
+    (config_ids[1], Status::Completed),
+    (config_ids[1], Status::Queued),
+  ];
+  for (config_id, status) in &jobs {
+    db.create_job(&NewJob {
+      job_name: "job",
+      config_id: *config_id,
+      directory: "",
+      command: "echo",
+      status,
+      variables: &serde_json::json!({}),
+      labels: &serde_json::json!({}),
+      ..Default::default()
+    })
+    .unwrap();
+  }
+
+  assert_eq!(
+    db.count_jobs_by_status(None).unwrap(),
+    HashMap::from([
+      (Status::Completed, 3),
+      (Status::Failed, 1),
+      (Status::Running, 1),
+      (Status::Queued, 1),
+    ])
+  );
+  assert_eq!(
+    db.count_jobs_by_status(Some("cluster_a")).unwrap(),
+    HashMap::from([
+      (Status::Completed, 2),
+      (Status::Failed, 1),
+      (Status::Running, 1),
+    ])
+  );
+  assert!(
+    db.count_jobs_by_status(Some("missing_cluster"))
+      .unwrap()
+      .is_empty()
+  );
+}
+
+#[test]
+fn status_is_terminal() {
+  let cases = [
+    (Status::Created, false),
+    (Status::VirtualQueue, false),
+    (Status::Queued, false),
+    (Status::Running, false),
+    (Status::Completed, true),
+    (Status::Failed, true),
+    (Status::Timeout, true),
+    (Status::FailedSubmission, true),
+    (Status::Canceled, true),
+  ];
+  for (status, terminal) in cases {
+    assert_eq!(status.is_terminal(), terminal, "{:?}", status);
+  }
+}
+
+#[test]
+fn newer_schema_is_refused() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  // A migration applied by a future version of SbatchMan
+  diesel::sql_query("INSERT INTO __diesel_schema_migrations (version) VALUES ('99991231000000')")
+    .execute(&mut db.conn)
+    .unwrap();
+  drop(db);
+
+  let result = Database::new(dir.path());
+  assert!(matches!(result, Err(StorageError::NewerSchema(version)) if version == "99991231000000"));
+}
+
+#[test]
+fn corrupt_database_is_reported() {
+  let dir = tempfile::tempdir().unwrap();
+  let path = dir.path().join("sbatchman.db");
+  std::fs::write(
+    &path,
+    "this is not a SQLite database, just some garbage bytes".repeat(100),
+  )
+  .unwrap();
+
+  for result in [
+    Database::new(dir.path()),
+    Database::open_readonly(dir.path()),
+  ] {
+    assert!(matches!(
+      result,
+      Err(StorageError::CorruptDatabase(db_path, _)) if db_path == path.display().to_string()
+    ));
+  }
+}
+
+#[test]
+fn open_readonly_queries_without_writing() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  let cluster = db
+    .create_cluster(&NewCluster {
+      cluster_name: "test_cluster".to_string(),
+      scheduler: Scheduler::Local,
+      ..Default::default()
+    })
+    .unwrap();
+  let config = db
+    .create_cluster_config(&NewConfig {
+      config_name: "test_config".to_string(),
+      cluster_id: cluster.id,
+      flags: serde_json::json!({}),
+      env: serde_json::json!({}),
+      ..Default::default()
+    })
+    .unwrap();
+  let job = db
+    .create_job(&NewJob {
+      job_name: "job",
+      config_id: config.id,
+      directory: "",
+      command: "echo hello",
+      status: &Status::Created,
+      variables: &serde_json::json!({}),
+      labels: &serde_json::json!({}),
+      ..Default::default()
+    })
+    .unwrap();
+  drop(db);
+
+  let mut readonly = Database::open_readonly(dir.path()).unwrap();
+  assert_eq!(readonly.get_jobs(None).unwrap(), vec![job]);
+  assert!(readonly.update_job_status(1, &Status::Running).is_err());
+}
+
+#[test]
+fn open_readonly_refuses_pending_migrations() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  // A database last opened by an older SbatchMan, without the latest migration
+  diesel::sql_query(
+    "DELETE FROM __diesel_schema_migrations WHERE version = (SELECT max(version) FROM \
+     __diesel_schema_migrations)",
+  )
+  .execute(&mut db.conn)
+  .unwrap();
+  drop(db);
+
+  let result = Database::open_readonly(dir.path());
+  assert!(matches!(result, Err(StorageError::PendingMigrations)));
+}
+
+#[test]
+fn scheduler_detect_in_path() {
+  let slurm_bin = tempfile::TempDir::new().unwrap();
+  let pbs_bin = tempfile::TempDir::new().unwrap();
+  let empty_bin = tempfile::TempDir::new().unwrap();
+  std::fs::write(slurm_bin.path().join("sbatch"), "").unwrap();
+  std::fs::write(pbs_bin.path().join("qsub"), "").unwrap();
+  let path =
+    |dirs: &[&tempfile::TempDir]| std::env::join_paths(dirs.iter().map(|dir| dir.path())).unwrap();
+
+  assert_eq!(
+    Scheduler::detect_in_path(&path(&[&empty_bin, &slurm_bin])),
+    Scheduler::Slurm
+  );
+  assert_eq!(
+    Scheduler::detect_in_path(&path(&[&pbs_bin])),
+    Scheduler::Pbs
+  );
+  // Slurm wins when both are available
+  assert_eq!(
+    Scheduler::detect_in_path(&path(&[&pbs_bin, &slurm_bin])),
+    Scheduler::Slurm
+  );
+  assert_eq!(
+    Scheduler::detect_in_path(&path(&[&empty_bin])),
+    Scheduler::Local
+  );
+}
@@ -1,36 +1,54 @@
+mod custom;
 mod local;
 mod pbs;
 mod slurm;
-mod utils;
-mod variable_substitutions;
+pub(crate) mod utils;
+pub(crate) mod variable_substitutions;
 mod r#virtual;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
 use std::{
   fs,
   path::{Path, PathBuf},
 };
 
 #[cfg(test)]
-mod tests;
+pub(crate) mod tests;
 
+pub use variable_substitutions::SubstitutionTrace;
+
+use chrono::{DateTime, Utc};
+use hashlink::LinkedHashMap;
+use indicatif::{ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use thiserror::Error;
 
-use crate::core::jobs::utils::{escape_for_printf, get_timestamp_string};
+use crate::core::jobs::utils::{
+  escape_for_printf, format_seconds_as_time, get_timestamp_string, make_script_executable,
+  new_launch_id, parse_time_to_seconds, parse_timestamp,
+};
 use crate::core::jobs::variable_substitutions::{
-  CartesianGenerator, DependencyGraph, VariableResolver, substitute_and_evaluate,
+  CartesianGenerator, DependencyGraph, VariableResolver, check_map_keys, job_variables_json,
+  substitute_and_evaluate, substitute_list_indices, trace_substitution,
 };
-use crate::core::parsers::variables::{CompleteVar, Variable};
+use crate::core::parsers::variables::{CompleteVar, Scalar, Variable};
 use crate::core::{
-  cluster_configs::ClusterConfig,
+  cluster_configs::{ClusterConfig, JOB_ID_PLACEHOLDER},
   database::{
     Database,
     models::{Cluster, Config, Job, NewJob, Status},
   },
   jobs::utils::{map_err_adding_description, serialize_log_entry},
-  parsers::ParsedJob,
+  parsers::{
+    ParsedJob, ParserError, get_include_variables, parse_jobs_from_file, parse_jobs_from_reader,
+    variables::{check_cluster_keys, unknown_cluster_keys},
+  },
 };
 
 trait SchedulerTrait {
@@ -40,9 +58,55 @@ trait SchedulerTrait {
     cluster_config: &ClusterConfig,
   ) -> Result<String, JobError>;
   fn launch_job(&self, job: &mut Job, cluster_config: &ClusterConfig) -> Result<(), JobError>;
+  /// Submit the script already written in the job directory
+  fn submit_job_script(&self, job: &mut Job) -> Result<(), JobError>;
   fn get_number_of_enqueued_jobs(&self) -> Result<usize, JobError> {
     Ok(0)
   }
+  /// Resource usage of a finished job, if the scheduler accounts for it
+  fn get_resource_usage(&self, _job: &Job) -> Result<Option<JobLog>, JobError> {
+    Ok(None)
+  }
+  /// Resource usage of several finished jobs, by job id. Schedulers whose accounting accepts
+  /// several jobs (ex. `sacct -j id1,id2`) query them at once.
+  fn get_resource_usages(&self, jobs: &[&Job]) -> Result<HashMap<i32, JobLog>, JobError> {
+    let mut usages = HashMap::new();
+    for job in jobs {
+      if let Some(usage) = self.get_resource_usage(job)? {
+        usages.insert(job.id, usage);
+      }
+    }
+    Ok(usages)
+  }
+  /// Stop a submitted job, if the scheduler supports it
+  fn cancel_job(&self, _job: &Job) -> Result<(), JobError> {
+    Ok(())
+  }
+}
+
+/// Runs the submit commands of the schedulers (ex. `sbatch`), replaced in the tests to record them
+pub trait CommandRunner {
+  /// Run `program` with `args` in `dir` and return its stdout, or its stderr if it fails
+  fn run(&self, program: &str, args: &[String], dir: &Path) -> Result<String, JobError>;
+}
+
+/// Runs the commands as child processes
+pub struct SystemCommandRunner;
+
+impl CommandRunner for SystemCommandRunner {
+  fn run(&self, program: &str, args: &[String], dir: &Path) -> Result<String, JobError> {
+    let output = Command::new(program)
+      .args(args)
+      .current_dir(dir)
+      .output()
+      .map_err(|e| JobError::SpawnError(format!("Failed to run \"{}\": {}", program, e)))?;
+    if !output.status.success() {
+      return Err(JobError::ExecutionFailed(
+        String::from_utf8_lossy(&output.stderr).to_string(),
+      ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+  }
 }
 
 use crate::core::database::models::Scheduler as DbScheduler;
@@ -59,6 +123,14 @@ pub enum JobError {
   DatabaseError(#[from] crate::core::database::StorageError),
   #[error("Config '{0}' not found for cluster")]
   ConfigNotFound(String),
+  #[error(
+    "The jobs file generates {0} jobs, more than the maximum sweep size of {1}. Use `--yes` to launch them anyway."
+  )]
+  SweepTooLarge(usize, usize),
+  #[error("Cannot purge {0:?} jobs: they are still handled by the scheduler")]
+  PurgeActiveStatus(Status),
+  #[error("Cannot cancel job #{0}: it is already {1:?}")]
+  NotCancelable(i32, Status),
   #[error("IO Error: {0}")]
   IoError(#[from] std::io::Error),
   #[error("Invalid Time Format: {0}")]
@@ -69,23 +141,157 @@ pub enum JobError {
   WaitError(String),
   #[error("Job Execution: {0}")]
   ExecutionFailed(String),
+  #[error("Invalid log entry: {0}")]
+  InvalidLogEntry(String),
+  #[error("Circular dependency between variables: {0}")]
+  CircularVariables(String),
+  #[error("Map variable \"{0}\" has no key \"{1}\"")]
+  MissingMapKey(String, String),
+  #[error("Index {1} is out of range for list variable \"{0}\" of {2} element(s)")]
+  ListIndexOutOfRange(String, String, usize),
+  #[error("No job named '{0}' in the jobs file")]
+  JobEntryNotFound(String),
+  #[error("Pre-submit hook failed: {0}")]
+  PreSubmitHookFailed(String),
+  #[error("No job was created by launch '{0}'")]
+  LaunchNotFound(String),
+  #[error("Cannot write {0:?} in a scheduler directive: it contains a quote or a line break")]
+  InvalidDirectiveValue(String),
   #[error("Generic Error: {0}")]
   Other(String),
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 #[serde(tag = "type", content = "data")]
 pub enum JobLog {
-  Metadata(Job),
+  Metadata(Box<Job>),
   StatusUpdate(Status),
   BashVariable(String), // The string must contain the bash variable name in the format "${VAR}"
   Variable(String, String),
+  ResourceUsage {
+    max_rss_kb: Option<u64>,
+    cpu_seconds: Option<u64>,
+    elapsed_seconds: Option<u64>,
+  },
+  /// Exit code and output (stdout then stderr) of the `pre_submit_hook` of the cluster
+  PreSubmitHook {
+    exit_code: Option<i32>,
+    output: String,
+  },
+  /// Operation run on the job through the API (ex. `cancel`), logged before the database is updated
+  Action(String),
+  /// Submit-time environment variables matching the `env_snapshot` prefixes of the config
+  Environment(HashMap<String, String>),
+}
+
+/// Variables of the current environment whose name starts with one of `prefixes`. An empty
+/// prefix matches every variable.
+fn environment_snapshot(prefixes: &[String]) -> HashMap<String, String> {
+  std::env::vars_os()
+    .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+    .filter(|(name, _)| {
+      prefixes
+        .iter()
+        .any(|prefix| name.starts_with(prefix.as_str()))
+    })
+    .collect()
+}
+
+/// Parse an entry of a job log into a typed entry paired with its timestamp
+fn parse_log_entry(entry: &Value) -> Result<(DateTime<Utc>, JobLog), JobError> {
+  let invalid = || JobError::InvalidLogEntry(entry.to_string());
+  let timestamp = entry["timestamp"]
+    .as_str()
+    .and_then(|timestamp| parse_timestamp(timestamp).ok())
+    .ok_or_else(invalid)?;
+  let log = match (&entry["type"], &entry["data"]) {
+    (Value::String(t), Value::Object(data)) if t == "BashVariable" && data.len() == 1 => {
+      let (name, value) = data.iter().next().unwrap();
+      let value = value
+        .as_str()
+        .map_or_else(|| value.to_string(), str::to_string);
+      JobLog::Variable(name.clone(), value)
+    }
+    _ => serde_json::from_value(entry.clone()).map_err(|_| invalid())?,
+  };
+  Ok((timestamp, log))
+}
+
+/// Reads the entries appended to a job log since the previous read, like `tail -f`
+pub struct LogTail {
+  path: PathBuf,
+  offset: u64,
+  inode: Option<u64>,
+  /// Bytes of a line not completely written yet
+  partial: Vec<u8>,
 }
 
+impl LogTail {
+  /// Tail of the log at `path`, see `Job::get_log_path`
+  pub fn new(path: PathBuf) -> Self {
+    LogTail {
+      path,
+      offset: 0,
+      inode: None,
+      partial: Vec::new(),
+    }
+  }
+
+  /// Typed entries appended since the previous call, the whole log on the first call.
+  /// A log that was truncated or replaced (ex. rotated) is read again from the start, a missing
+  /// log has no entries yet. Invalid lines are skipped with a warning.
+  pub fn read_new_entries(&mut self) -> Result<Vec<(DateTime<Utc>, JobLog)>, JobError> {
+    use std::io::{ErrorKind, Read, Seek, SeekFrom};
+    use std::os::unix::fs::MetadataExt;
+
+    let mut file = match fs::File::open(&self.path) {
+      Ok(file) => file,
+      Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+      Err(e) => return Err(e.into()),
+    };
+    let metadata = file.metadata()?;
+    if self.inode != Some(metadata.ino()) || metadata.len() < self.offset {
+      self.inode = Some(metadata.ino());
+      self.offset = 0;
+      self.partial.clear();
+    }
+    file.seek(SeekFrom::Start(self.offset))?;
+    self.offset += file.read_to_end(&mut self.partial)? as u64;
+
+    let Some(end) = self.partial.iter().rposition(|b| *b == b'\n') else {
+      return Ok(vec![]);
+    };
+    let lines: Vec<u8> = self.partial.drain(..=end).collect();
+    let entries = String::from_utf8_lossy(&lines)
+      .lines()
+      .filter(|line| !line.is_empty())
+      .filter_map(|line| {
+        let entry = serde_json::from_str::<Value>(line)
+          .map_err(|_| JobError::InvalidLogEntry(line.to_string()))
+          .and_then(|entry| parse_log_entry(&entry));
+        entry
+          .inspect_err(|e| log::warn!("Skipped a line of {}: {}", self.path.display(), e))
+          .ok()
+      })
+      .collect();
+    Ok(entries)
+  }
+}
+
+/// Filter on the jobs. Empty fields do not restrict the result.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct JobFilter {
   pub statuses: Vec<Status>,
   pub config_ids: Vec<i32>,
+  /// Label key/value pairs the job must all have
+  pub labels: Vec<(String, String)>,
+  /// Only the jobs submitted at or after this Unix time. The jobs without a submit time (not
+  /// submitted yet, or submitted before it was recorded) are kept, as their age is unknown.
+  #[serde(default)]
+  pub submitted_since: Option<i32>,
+  /// Only the jobs created by this launch, see `LaunchSummary::launch_id`
+  #[serde(default)]
+  pub launch_id: Option<String>,
 }
 
 impl Default for JobFilter {
@@ -93,29 +299,99 @@ impl Default for JobFilter {
     Self {
       statuses: vec![],
       config_ids: vec![],
+      labels: vec![],
+      submitted_since: None,
+      launch_id: None,
     }
   }
 }
 
+impl JobFilter {
+  /// Check whether the job has all the labels of the filter
+  pub fn matches_labels(&self, job: &Job) -> bool {
+    self
+      .labels
+      .iter()
+      .all(|(key, value)| job.labels.get(key).and_then(Value::as_str) == Some(value.as_str()))
+  }
+}
+
+/// Join the default and the job-specific processing commands, skipping the empty ones
+fn join_processing(default: &Option<String>, job: &Option<String>) -> Option<String> {
+  let steps: Vec<&str> = [default, job]
+    .into_iter()
+    .flatten()
+    .map(String::as_str)
+    .filter(|step| !step.is_empty())
+    .collect();
+  (!steps.is_empty()).then(|| steps.join("\n"))
+}
+
 impl Job {
+  /// Script of the job, the same for all the schedulers: the header with the directives of the
+  /// config followed by `job_directives` (ex. the job name and output paths), the environment,
+  /// then the job commands between the status updates.
+  pub fn generate_script(
+    &self,
+    cluster_config: &ClusterConfig,
+    launch_base_path: &Path,
+    job_directives: &[String],
+  ) -> String {
+    let mut script = cluster_config
+      .generate_job_script_header(launch_base_path, job_directives)
+      .replace(JOB_ID_PLACEHOLDER, &self.id.to_string());
+
+    cluster_config.add_environment_variables(&mut script);
+
+    script.push_str("\n# Status update");
+    self.add_log_command(&mut script, JobLog::StatusUpdate(Status::Running), None);
+
+    self.add_job_commands(
+      &mut script,
+      cluster_config.config,
+      cluster_config.script_time_limit(),
+    );
+
+    script.push_str("\n# Export EXIT CODE");
+    self.add_log_command(
+      &mut script,
+      JobLog::BashVariable("SBM_EXIT_CODE".to_string()),
+      None,
+    );
+
+    script.push_str("\nexit \"${SBM_EXIT_CODE}\"");
+    script
+  }
+
   /// Add preprocessing, main command, and postprocessing to script
-  /// This is used by all schedulers to construct the job execution flow
-  pub fn add_job_commands(&self, script: &mut String, time_limit: Option<u64>) {
+  /// This is used by all schedulers to construct the job execution flow.
+  /// The default pre/postprocessing of the config run before the ones of the job.
+  /// With `time_limit`, the main command is wrapped with `timeout`: schedulers pass
+  /// `ClusterConfig::script_time_limit`, which is set only for the local scheduler.
+  /// The main command may span several lines (one command per line), `SBM_EXIT_CODE` is the exit
+  /// code of the last one.
+  pub fn add_job_commands(&self, script: &mut String, config: &Config, time_limit: Option<u64>) {
     // Add preprocessing if present
-    if let Some(preprocess) = &self.preprocess {
-      if !preprocess.is_empty() {
-        script.push_str("\n# Preprocessing\n");
-        script.push_str(preprocess);
-        script.push_str("\n\n");
-      }
+    let preprocess = join_processing(&config.default_preprocess, &self.preprocess);
+    if let Some(preprocess) = preprocess {
+      script.push_str("\n# Preprocessing\n");
+      script.push_str(&preprocess);
+      script.push_str("\n\n");
     }
 
     // Add the main command
     script.push_str("\n# Main command\n");
-    if let Some(time_s) = time_limit {
-      script.push_str(format!("timeout {} ", time_s).as_str());
+    match time_limit {
+      // `timeout` runs a single program, a block of commands goes through a shell
+      Some(time_s) if self.command.contains('\n') => script.push_str(&format!(
+        "timeout {} {} -c '{}'",
+        time_s,
+        config.shell.as_deref().unwrap_or("bash"),
+        self.command.replace('\'', "'\\''")
+      )),
+      Some(time_s) => script.push_str(&format!("timeout {} {}", time_s, self.command)),
+      None => script.push_str(&self.command),
     }
-    script.push_str(&self.command);
     script.push_str("\n\nSBM_EXIT_CODE=$?\n");
 
     script.push_str("\n# Status update\n");
@@ -128,12 +404,11 @@ impl Job {
     script.push_str("fi\n");
 
     // Add postprocessing if present
-    if let Some(postprocess) = &self.postprocess {
-      if !postprocess.is_empty() {
-        script.push_str("\n# Postprocessing\n");
-        script.push_str(postprocess);
-        script.push_str("\n");
-      }
+    let postprocess = join_processing(&config.default_postprocess, &self.postprocess);
+    if let Some(postprocess) = postprocess {
+      script.push_str("\n# Postprocessing\n");
+      script.push_str(&postprocess);
+      script.push('\n');
     }
   }
 
@@ -150,6 +425,26 @@ impl Job {
   pub fn get_script(&self) -> std::io::Result<String> {
     fs::read_to_string(self.get_script_path())
   }
+  /// Write the job script and make it executable
+  pub fn write_script(&self, script: &str) -> Result<(), JobError> {
+    let script_path = self.get_script_path();
+    {
+      // FIXME this seems to be an issue sometimes SpawnError("Failed to spawn process: Text file busy (os error 26)")
+      let mut file = fs::File::create(&script_path)
+        .map_err(|e| map_err_adding_description(e, "Failed to create script file: {}"))?;
+
+      file
+        .write_all(script.as_bytes())
+        .map_err(|e| map_err_adding_description(e, "Failed to write script: {}"))?;
+
+      // Explicitly flush and close the file
+      file
+        .flush()
+        .map_err(|e| map_err_adding_description(e, "Failed to flush script: {}"))?;
+    } // File is dropped and closed here
+
+    make_script_executable(&script_path)
+  }
 
   pub fn get_stdout_path(&self) -> PathBuf {
     Path::new(&self.directory).join("stdout.log")
@@ -173,12 +468,46 @@ impl Job {
     Ok(())
   }
 
+  /// Get the last resource usage recorded in the job log
+  pub fn get_resource_usage(&self) -> Option<JobLog> {
+    self
+      .read_log_entries()
+      .ok()?
+      .into_iter()
+      .rev()
+      .filter(|entry| entry["type"] == "ResourceUsage")
+      .find_map(|entry| serde_json::from_value(entry).ok())
+  }
+
+  /// Get the last status update written in the job log
+  pub fn get_logged_status(&self) -> Option<Status> {
+    self
+      .read_log_entries()
+      .ok()?
+      .into_iter()
+      .rev()
+      .filter(|entry| entry["type"] == "StatusUpdate")
+      .find_map(|entry| serde_json::from_value(entry["data"].clone()).ok())
+  }
+
+  /// Parse the job log into typed entries paired with their timestamp.
+  /// The bash variables are logged with their value, so they are returned as `JobLog::Variable`.
+  pub fn parse_log_typed(&self) -> Result<Vec<(DateTime<Utc>, JobLog)>, JobError> {
+    self
+      .read_log_entries()?
+      .iter()
+      .map(parse_log_entry)
+      .collect()
+  }
+
+  /// Read the entries of the job log. Lines that cannot be parsed (ex. an entry still being
+  /// written by the job) are skipped.
   fn read_log_entries(&self) -> Result<Vec<serde_json::Value>, std::io::Error> {
     let content = self.get_log()?;
     let entries: Vec<serde_json::Value> = content
       .lines()
       .filter(|line| !line.is_empty())
-      .map(|line| serde_json::from_str(line).unwrap())
+      .filter_map(|line| serde_json::from_str(line).ok())
       .collect();
     Ok(entries)
   }
@@ -211,6 +540,32 @@ impl Job {
     Ok(())
   }
 
+  /// Record that the scheduler accepted the job with id `job_id`: the job is now `Queued`
+  pub fn record_submission(&mut self, job_id: String) -> Result<(), JobError> {
+    self.write_log_entry(
+      JobLog::Variable(String::from("JOB_ID"), job_id.clone()),
+      None,
+    )?;
+    self.write_log_entry(JobLog::StatusUpdate(Status::Queued), None)?;
+    self.job_id = Some(job_id);
+    self.status = Status::Queued;
+    Ok(())
+  }
+
+  /// Write the `Metadata` entry of a launch, followed by the `Environment` snapshot if the config
+  /// sets `env_snapshot`. The snapshot also records the flags and env of the config, as resolved
+  /// through `extends` and the cluster defaults.
+  pub fn write_launch_log_entries(&self, config: &Config) -> Result<(), JobError> {
+    self.write_log_entry(JobLog::Metadata(Box::new(self.clone())), None)?;
+    if let Some(prefixes) = config.env_snapshot_prefixes() {
+      self.write_log_entry(
+        JobLog::Environment(environment_snapshot(&prefixes)),
+        Some(json!({"config": {"flags": config.flags, "env": config.env}})),
+      )?;
+    }
+    Ok(())
+  }
+
   /// Creates a bash command to add a log entry to the job log file
   /// This logs complete job metadata with timestamps for database reconstruction
   pub fn add_log_command(&self, script: &mut String, log: JobLog, additional_data: Option<Value>) {
@@ -246,28 +601,86 @@ impl Job {
     // Build the printf command
     // The timestamp must be outside quotes to be evaluated
     let printf_cmd = format!(
-      "printf '%s\"%s\"%s\\n' '{}' \"$(date +\"%Y-%m-%d %H:%M:%S.%3N\")\" '{}'",
+      "printf '%s\"%s\"%s\\n' '{}' \"$(date -u +\"%Y-%m-%dT%H:%M:%S.%3NZ\")\" '{}'",
       before_escaped, after_escaped
     );
 
     script.push_str(&format!("\n{} >> {}\n", printf_cmd, abs_path.display()));
   }
 
+  /// Trace the substitution of `command` for the first combination of the values of its variables
+  pub fn explain(
+    cluster_config: &ClusterConfig,
+    variables: &[Variable],
+    command: &str,
+    python_header: &Option<String>,
+  ) -> Result<SubstitutionTrace, JobError> {
+    let var_map: HashMap<String, &CompleteVar> = variables
+      .iter()
+      .map(|v| (v.name.clone(), &v.contents))
+      .collect();
+    let indexed = substitute_list_indices(command, &var_map)?;
+    let dep_graph = DependencyGraph::build(&indexed, &None, &None, &var_map)?;
+    let resolved_vars = VariableResolver::resolve_for_cluster(cluster_config, &var_map, &dep_graph);
+    let combo = CartesianGenerator::generate(&resolved_vars, &dep_graph, &indexed, &None, &None)
+      .into_iter()
+      .next()
+      .unwrap_or_default();
+    let mut trace = trace_substitution(&indexed, &combo, &var_map, &dep_graph, python_header);
+    // The list elements picked by index are shown as part of the simple substitution
+    trace.template = command.to_string();
+    Ok(trace)
+  }
+
+  /// Generate one job for each combination of the values of the variables used by the commands.
+  /// Combinations resolving to the same command, preprocess and postprocess (ex. duplicated list
+  /// values) generate a single job.
   pub fn generate_from(
     cluster_config: &ClusterConfig,
-    variables: &Vec<Variable>,
+    variables: &[Variable],
+    command: String,
+    preprocess: Option<String>,
+    postprocess: Option<String>,
+    python_header: Option<String>,
+  ) -> Result<Vec<Self>, JobError> {
+    Self::generate_named_from(
+      cluster_config,
+      variables,
+      command,
+      preprocess,
+      postprocess,
+      python_header,
+      None,
+    )
+  }
+
+  /// Same as `generate_from`, naming each job after `name_template` substituted with the values
+  /// of its combination. Variables with a single value can be used even if the commands do not.
+  pub fn generate_named_from(
+    cluster_config: &ClusterConfig,
+    variables: &[Variable],
     command: String,
     preprocess: Option<String>,
     postprocess: Option<String>,
     python_header: Option<String>,
-  ) -> Vec<Self> {
+    name_template: Option<String>,
+  ) -> Result<Vec<Self>, JobError> {
     let var_map: HashMap<String, &CompleteVar> = variables
       .iter()
       .map(|v| (v.name.clone(), &v.contents))
       .collect();
 
+    // Pick the list elements referenced by index, so that these lists are not swept
+    let command = substitute_list_indices(&command, &var_map)?;
+    let preprocess = preprocess
+      .map(|p| substitute_list_indices(&p, &var_map))
+      .transpose()?;
+    let postprocess = postprocess
+      .map(|p| substitute_list_indices(&p, &var_map))
+      .transpose()?;
+
     // Build dependency graph
-    let dep_graph = DependencyGraph::build(&command, &preprocess, &postprocess, &var_map);
+    let dep_graph = DependencyGraph::build(&command, &preprocess, &postprocess, &var_map)?;
 
     // Resolve variables to their values for this cluster
     let resolved_vars = VariableResolver::resolve_for_cluster(cluster_config, &var_map, &dep_graph);
@@ -282,7 +695,7 @@ impl Job {
     );
 
     // Create jobs for each combination
-    combinations
+    let mut jobs = combinations
       .into_iter()
       .map(|combo| {
         let substituted_command =
@@ -294,10 +707,38 @@ impl Job {
           .as_ref()
           .map(|p| substitute_and_evaluate(p, &combo, &var_map, &dep_graph, &python_header));
 
-        Self {
+        // A map reference left in place would be submitted as is
+        for substituted in [
+          Some(&substituted_command),
+          substituted_preprocess.as_ref(),
+          substituted_postprocess.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+          check_map_keys(substituted, &var_map)?;
+        }
+
+        // Variables are recorded with their types (ex. an integer stays a JSON number)
+        let variables = job_variables_json(&var_map, &combo, &cluster_config.cluster.cluster_name);
+
+        let job_name = match &name_template {
+          Some(template) => {
+            let mut name_values = combo.clone();
+            for (name, values) in &resolved_vars {
+              if let [value] = values.as_slice() {
+                name_values.entry(name.clone()).or_insert(value.clone());
+              }
+            }
+            substitute_and_evaluate(template, &name_values, &var_map, &dep_graph, &python_header)
+          }
+          None => "FIXME".to_string(),
+        };
+
+        Ok(Self {
           // FIXME
           id: 0,
-          job_name: "FIXME".to_string(),
+          job_name,
           archived: None,
           config_id: cluster_config.config.id,
           directory: String::new(),
@@ -309,119 +750,1307 @@ impl Job {
           command: substituted_command,
           preprocess: substituted_preprocess,
           postprocess: substituted_postprocess,
-          variables: json!(var_map),
-        }
+          variables,
+          labels: json!({}),
+          run_id: None,
+          launch_id: None,
+        })
       })
-      .collect()
+      .collect::<Result<Vec<_>, JobError>>()?;
+
+    // Identical jobs would only be submitted twice, the first one is kept
+    let generated = jobs.len();
+    let mut seen = HashSet::new();
+    jobs.retain(|job| {
+      seen.insert((
+        job.command.clone(),
+        job.preprocess.clone(),
+        job.postprocess.clone(),
+      ))
+    });
+    if jobs.len() < generated {
+      log::warn!(
+        "Skipped {} duplicate job(s) of command \"{}\"",
+        generated - jobs.len(),
+        command
+      );
+    }
+    Ok(jobs)
   }
 }
 
-pub fn launch_jobs_from_file(
-  path: &PathBuf,
+/// Replace the variables set from the command line with a single string value.
+/// A list variable is replaced as a whole, variables not defined in the file are added.
+fn override_variables(
+  variables: &LinkedHashMap<String, Variable>,
+  var_overrides: &HashMap<String, String>,
+) -> Vec<Variable> {
+  let mut variables = variables.clone();
+  for (name, value) in var_overrides {
+    variables.replace(
+      name.clone(),
+      Variable {
+        name: name.clone(),
+        contents: CompleteVar::Scalar(Scalar::String(value.clone())),
+      },
+    );
+  }
+  variables
+    .into_iter()
+    .map(|(_, variable)| variable)
+    .collect()
+}
+
+/// Set the environment variables of `env_overrides` (ex. from `--env-file`) in every config,
+/// replacing the ones of the config with the same name
+fn override_env(configs: &mut HashMap<String, Config>, env_overrides: &HashMap<String, String>) {
+  if env_overrides.is_empty() {
+    return;
+  }
+  for config in configs.values_mut() {
+    let mut env = config.env.as_object().cloned().unwrap_or_default();
+    for (key, value) in env_overrides {
+      env.insert(key.clone(), Value::String(value.clone()));
+    }
+    config.env = Value::Object(env);
+  }
+}
+
+/// Path of a jobs file that stands for stdin (ex. `sbatchman launch -`)
+pub const STDIN_PATH: &str = "-";
+
+/// Parse a jobs file and generate the concrete jobs for the given cluster.
+/// The jobs file is read from stdin if `path` is `STDIN_PATH` (`-`).
+/// Each generated job is paired with the config it must be launched with, which includes the
+/// `params_override` of its entry.
+/// `var_overrides` (`--var name=value`) replace the variables of the file with a single value.
+pub fn generate_jobs_from_file<'a>(
+  path: &Path,
+  cluster: &Cluster,
+  configs: &'a HashMap<String, Config>,
+  var_overrides: &HashMap<String, String>,
+) -> Result<Vec<(Job, Cow<'a, Config>)>, JobError> {
+  generate_jobs(&read_jobs_file(path)?, cluster, configs, var_overrides)
+}
+
+/// Parse the entries of a jobs file, read from stdin if `path` is `STDIN_PATH` (`-`)
+fn read_jobs_file(path: &Path) -> Result<Vec<ParsedJob>, JobError> {
+  Ok(if path == Path::new(STDIN_PATH) {
+    // Includes of a file read from stdin are relative to the working directory
+    parse_jobs_from_reader(std::io::stdin().lock(), &std::env::current_dir()?)?
+  } else {
+    parse_jobs_from_file(path)?
+  })
+}
+
+/// `(variable, cluster)` pairs of the `per_cluster` keys of the entries of a jobs file naming
+/// none of `cluster_names`, without duplicates
+fn unknown_cluster_keys_in_jobs(
+  parsed_jobs: &[ParsedJob],
+  cluster_names: &HashSet<String>,
+) -> Vec<(String, String)> {
+  let mut seen = HashSet::new();
+  parsed_jobs
+    .iter()
+    .flat_map(|parsed_job| unknown_cluster_keys(&parsed_job.variables, cluster_names))
+    .filter(|pair| seen.insert(pair.clone()))
+    .collect()
+}
+
+/// Generate the concrete jobs of the parsed entries of a jobs file, see `generate_jobs_from_file`
+fn generate_jobs<'a>(
+  parsed_jobs: &[ParsedJob],
+  cluster: &Cluster,
+  configs: &'a HashMap<String, Config>,
+  var_overrides: &HashMap<String, String>,
+) -> Result<Vec<(Job, Cow<'a, Config>)>, JobError> {
+  validate_config_names(parsed_jobs, configs)?;
+  let mut jobs = vec![];
+  let mut templated = HashSet::new();
+  for parsed_job in parsed_jobs {
+    let config = &configs[&parsed_job.config_name];
+    let config = apply_params_override(config, parsed_job, &cluster.scheduler)?;
+    let variables = override_variables(&parsed_job.variables, var_overrides);
+    let generated = Job::generate_named_from(
+      &ClusterConfig::new(cluster, &config),
+      &variables,
+      parsed_job.command.clone(),
+      parsed_job.preprocess.clone(),
+      parsed_job.postprocess.clone(),
+      parsed_job.python_header.clone(),
+      parsed_job.name_template.clone(),
+    )?;
+    jobs.extend(generated.into_iter().map(|mut job| {
+      if parsed_job.name_template.is_none() {
+        job.job_name = parsed_job.job_name.clone();
+      } else {
+        templated.insert(job.job_name.clone());
+      }
+      job.labels = Value::Object(parsed_job.labels.clone());
+      (job, config.clone())
+    }));
+  }
+
+  // Templated names must be unique, the colliding ones get the id of the job appended
+  let mut name_counts: HashMap<String, usize> = HashMap::new();
+  for (job, _) in &jobs {
+    *name_counts.entry(job.job_name.clone()).or_default() += 1;
+  }
+  for (job, _) in &mut jobs {
+    if templated.contains(&job.job_name) && name_counts[&job.job_name] > 1 {
+      job.job_name = format!("{}-{}", job.job_name, JOB_ID_PLACEHOLDER);
+    }
+  }
+  Ok(jobs)
+}
+
+/// A variable of a jobs file, with the variables its value references
+#[derive(Debug, Serialize, PartialEq)]
+pub struct VariableInfo {
+  pub name: String,
+  pub kind: &'static str,
+  pub dependencies: Vec<String>,
+}
+
+/// Describe the variables of a jobs file, including the ones of its includes, in file order.
+/// The dependencies come from a `DependencyGraph` built for a command using every variable.
+pub fn describe_variables(path: &Path) -> Result<Vec<VariableInfo>, JobError> {
+  let variables = get_include_variables(path)?;
+  let var_map: HashMap<String, &CompleteVar> = variables
+    .iter()
+    .map(|(name, var)| (name.clone(), &var.contents))
+    .collect();
+  let command = variables
+    .keys()
+    .map(|name| format!("${{{}}}", name))
+    .collect::<Vec<_>>()
+    .join(" ");
+  let dep_graph = DependencyGraph::build(&command, &None, &None, &var_map)?;
+  Ok(
+    variables
+      .iter()
+      .map(|(name, var)| VariableInfo {
+        name: name.clone(),
+        kind: var.contents.kind(),
+        dependencies: dep_graph.get_dependencies(name),
+      })
+      .collect(),
+  )
+}
+
+/// Trace the substitution of the command of a job entry of a file, `job_name` or the first one.
+/// `var_overrides` fix the values of the variables, the first value is used for the others.
+pub fn explain_job_from_file(
+  path: &Path,
   db: &mut Database,
   cluster_name: &str,
+  job_name: Option<&str>,
+  var_overrides: &HashMap<String, String>,
+) -> Result<SubstitutionTrace, JobError> {
+  let cluster = db.get_cluster_by_name(cluster_name)?;
+  let configs = db.get_configs_by_cluster(&cluster)?;
+  let parsed_jobs = parse_jobs_from_file(path)?;
+  let parsed_job = match job_name {
+    Some(name) => parsed_jobs
+      .iter()
+      .find(|parsed_job| parsed_job.job_name == name)
+      .ok_or_else(|| JobError::JobEntryNotFound(name.to_string()))?,
+    None => parsed_jobs
+      .first()
+      .ok_or_else(|| JobError::Other("The jobs file has no jobs".to_string()))?,
+  };
+  let config = configs
+    .get(&parsed_job.config_name)
+    .ok_or_else(|| JobError::ConfigNotFound(parsed_job.config_name.clone()))?;
+  let variables = override_variables(&parsed_job.variables, var_overrides);
+  Job::explain(
+    &ClusterConfig::new(&cluster, config),
+    &variables,
+    &parsed_job.command,
+    &parsed_job.python_header,
+  )
+}
+
+/// Check that every job entry references a config of the cluster, so that a typo in a config
+/// name is reported before any job is generated or submitted
+fn validate_config_names(
+  parsed_jobs: &[ParsedJob],
+  configs: &HashMap<String, Config>,
 ) -> Result<(), JobError> {
-  let jobs = crate::core::parsers::parse_jobs_from_file(path)?;
+  match parsed_jobs
+    .iter()
+    .find(|parsed_job| !configs.contains_key(&parsed_job.config_name))
+  {
+    Some(parsed_job) => Err(JobError::ConfigNotFound(parsed_job.config_name.clone())),
+    None => Ok(()),
+  }
+}
+
+/// Merge the `params_override` of a job entry over the flags of its config.
+/// The config is borrowed as-is when the entry does not override any parameter.
+fn apply_params_override<'a>(
+  config: &'a Config,
+  parsed_job: &ParsedJob,
+  scheduler: &DbScheduler,
+) -> Result<Cow<'a, Config>, JobError> {
+  if parsed_job.params_override.is_empty() {
+    return Ok(Cow::Borrowed(config));
+  }
+  let mut flags = config.flags.as_object().cloned().unwrap_or_default();
+  for (key, value) in &parsed_job.params_override {
+    if !scheduler.has_param(key) {
+      return Err(JobError::ParserError(
+        ParserError::InvalidParameterForScheduler(key.clone(), format!("{:?}", scheduler)),
+      ));
+    }
+    match value {
+      Some(value) => flags.insert(key.clone(), value.clone()),
+      None => flags.remove(key),
+    };
+  }
+  Ok(Cow::Owned(Config {
+    flags: Value::Object(flags),
+    ..config.clone()
+  }))
+}
+
+/// Count the jobs that would be generated from a file, without creating or launching them
+pub fn count_jobs_from_file(
+  path: &Path,
+  db: &mut Database,
+  cluster_name: &str,
+  var_overrides: &HashMap<String, String>,
+) -> Result<usize, JobError> {
   let cluster = db.get_cluster_by_name(cluster_name)?;
   let configs = db.get_configs_by_cluster(&cluster)?;
-  let mut to_launch_really = jobs.len();
-  if let Some(max_jobs) = cluster.max_jobs {
-    let enqueued_jobs = get_scheduler(&cluster.scheduler).get_number_of_enqueued_jobs()?;
-    // Number of jobs that can be enqueued without exceeding max_jobs
-    to_launch_really = std::cmp::min(
-      to_launch_really,
-      (max_jobs as usize).saturating_sub(enqueued_jobs),
-    );
+  Ok(generate_jobs_from_file(path, &cluster, &configs, var_overrides)?.len())
+}
+
+/// Values set from the command line over the ones of the jobs file and of the configs
+#[derive(Debug, Default)]
+pub struct LaunchOverrides {
+  /// `--var name=value`, replacing the variables of the file with a single value
+  pub vars: HashMap<String, String>,
+  /// `--env-file`, exported by the job scripts over the `env` of the configs
+  pub env: HashMap<String, String>,
+  /// `--limit`, only the first jobs of the file are created and launched
+  pub limit: Option<usize>,
+  /// `--strict`, the `per_cluster` keys naming unknown clusters are errors instead of warnings
+  pub strict: bool,
+  /// `--submit-arg`, extra arguments of the Slurm and PBS submit commands (ex. `--hold`)
+  pub submit_args: Vec<String>,
+}
+
+/// Outcome of the launch of several jobs
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct LaunchSummary {
+  pub launched: usize,
+  pub virtual_queued: usize,
+  pub failed: usize,
+  /// Ids of the launched, virtually queued and failed jobs, in launch order
+  pub job_ids: Vec<i32>,
+  /// Id stored on all the jobs of a launch from a jobs file, to select them later
+  pub launch_id: Option<String>,
+}
+
+/// File recording the outcome of the last launch, in the .sbatchman directory
+pub const LAUNCH_SUMMARY_FILE: &str = "launch_summary.json";
+
+/// Write the outcome of the launch of the jobs file `path` to `LAUNCH_SUMMARY_FILE` in
+/// `sbatchman_dir`, replacing the one of the previous launch
+pub fn write_launch_summary(
+  sbatchman_dir: &Path,
+  path: &Path,
+  summary: &LaunchSummary,
+) -> Result<(), JobError> {
+  let mut record = json!({
+    "file": path.display().to_string(),
+    "timestamp": get_timestamp_string(),
+  });
+  if let (Some(record), Value::Object(summary)) = (record.as_object_mut(), json!(summary)) {
+    record.extend(summary);
   }
-  let mut iter = jobs.iter();
-  // Launch jobs up to the allowed limit
-  while to_launch_really > 0 {
-    let job = iter.next().unwrap();
-    let config = configs
-      .get(job.config_name)
-      .ok_or(JobError::ConfigNotFound(job.config_name.to_string()))?;
-    launch_job(job, config, &cluster, db, path, false)?;
-    to_launch_really -= 1;
+  fs::write(
+    sbatchman_dir.join(LAUNCH_SUMMARY_FILE),
+    serde_json::to_string_pretty(&record).map_err(std::io::Error::from)?,
+  )?;
+  Ok(())
+}
+
+/// Launch all the jobs generated from a file.
+/// If `max_sweep_size` is set and the file generates more jobs, nothing is launched.
+/// Unless `quiet` is set, a progress bar is shown while the jobs are submitted.
+/// The job directories are created in `output_dir/jobs/<id>`.
+pub fn launch_jobs_from_file(
+  path: &Path,
+  output_dir: &PathBuf,
+  db: &mut Database,
+  cluster_name: &str,
+  overrides: &LaunchOverrides,
+  max_sweep_size: Option<usize>,
+  quiet: bool,
+) -> Result<LaunchSummary, JobError> {
+  let cluster = db.get_cluster_by_name(cluster_name)?;
+  let mut configs = db.get_configs_by_cluster(&cluster)?;
+  override_env(&mut configs, &overrides.env);
+  let parsed_jobs = read_jobs_file(path)?;
+  let cluster_names = db.get_cluster_names()?.into_iter().collect();
+  check_cluster_keys(
+    &unknown_cluster_keys_in_jobs(&parsed_jobs, &cluster_names),
+    overrides.strict,
+  )?;
+  let mut jobs = generate_jobs(&parsed_jobs, &cluster, &configs, &overrides.vars)?;
+  if let Some(limit) = overrides.limit {
+    jobs.truncate(limit);
+  }
+  if let Some(max_sweep_size) = max_sweep_size
+    && jobs.len() > max_sweep_size
+  {
+    return Err(JobError::SweepTooLarge(jobs.len(), max_sweep_size));
   }
-  // Remaining jobs go to virtual queue
-  while let Some(job) = iter.next() {
-    let config = configs
-      .get(job.config_name)
-      .ok_or(JobError::ConfigNotFound(job.config_name.to_string()))?;
-    launch_job(job, config, &cluster, db, path, true)?;
+  let launch_id = new_launch_id();
+  for (job, _) in &mut jobs {
+    job.launch_id = Some(launch_id.clone());
   }
+  let progress = if quiet {
+    ProgressBar::hidden()
+  } else {
+    ProgressBar::new(jobs.len() as u64)
+  };
+  let summary = launch_jobs(
+    &jobs,
+    &cluster,
+    db,
+    output_dir,
+    get_launch_scheduler(&cluster, &overrides.submit_args).as_ref(),
+    &progress,
+  )?;
+  Ok(LaunchSummary {
+    launch_id: Some(launch_id),
+    ..summary
+  })
+}
 
-  return Ok(());
+/// Name of the jobs launched by `run_command`
+pub const RUN_JOB_NAME: &str = "run";
+
+/// Launch a single job running `command` with the config `config_name` of the cluster, without
+/// jobs file. The command is used as is, no variable is substituted.
+pub fn run_command(
+  command: &str,
+  output_dir: &PathBuf,
+  db: &mut Database,
+  cluster_name: &str,
+  config_name: &str,
+) -> Result<LaunchSummary, JobError> {
+  let cluster = db.get_cluster_by_name(cluster_name)?;
+  let configs = db.get_configs_by_cluster(&cluster)?;
+  let config = configs
+    .get(config_name)
+    .ok_or_else(|| JobError::ConfigNotFound(config_name.to_string()))?;
+  let job = Job {
+    id: 0,
+    job_name: RUN_JOB_NAME.to_string(),
+    config_id: config.id,
+    submit_time: None,
+    directory: String::new(),
+    command: command.to_string(),
+    status: Status::Created,
+    job_id: None,
+    end_time: None,
+    preprocess: None,
+    postprocess: None,
+    archived: None,
+    variables: json!({}),
+    labels: json!({}),
+    run_id: None,
+    launch_id: None,
+  };
+  launch_jobs(
+    &[(job, Cow::Borrowed(config))],
+    &cluster,
+    db,
+    output_dir,
+    get_scheduler(&cluster).as_ref(),
+    &ProgressBar::hidden(),
+  )
 }
 
-pub(super) fn launch_job(
-  job: &ParsedJob,
-  config: &Config,
+/// Launch the given jobs with `scheduler`, up to the `max_jobs` of the cluster, waiting its
+/// `submit_interval_ms` between two submissions.
+/// The remaining jobs go to the virtual queue. A job failing submission does not stop the others.
+fn launch_jobs(
+  jobs: &[(Job, Cow<Config>)],
   cluster: &Cluster,
   db: &mut Database,
-  path: &PathBuf,
+  output_dir: &PathBuf,
+  scheduler: &dyn SchedulerTrait,
+  progress: &ProgressBar,
+) -> Result<LaunchSummary, JobError> {
+  let to_launch_really = available_slots(jobs.len(), cluster, scheduler)?;
+  progress.set_style(
+    ProgressStyle::with_template("{bar:40} {pos}/{len} {msg}")
+      .expect("Invalid progress bar template"),
+  );
+
+  let mut summary = LaunchSummary::default();
+  for (i, (job, config)) in jobs.iter().enumerate() {
+    progress.set_message(job.job_name.clone());
+    // Jobs beyond the allowed limit go to the virtual queue
+    let virtual_queue = i >= to_launch_really;
+    if let Some(interval) = cluster.submit_interval_ms
+      && i > 0
+      && !virtual_queue
+    {
+      thread::sleep(Duration::from_millis(interval as u64));
+    }
+    let mut job = create_db_job(job, config.id, db, output_dir)?;
+    let result = submit_db_job(&mut job, config, cluster, db, scheduler, virtual_queue);
+    count_launch_result(&mut summary, &job, result, virtual_queue)?;
+    progress.inc(1);
+  }
+  progress.finish_and_clear();
+
+  Ok(summary)
+}
+
+/// Number of jobs out of `jobs` that can be submitted without exceeding the `max_jobs` of the
+/// cluster
+fn available_slots(
+  jobs: usize,
+  cluster: &Cluster,
+  scheduler: &dyn SchedulerTrait,
+) -> Result<usize, JobError> {
+  let Some(max_jobs) = cluster.max_jobs else {
+    return Ok(jobs);
+  };
+  let enqueued_jobs = scheduler.get_number_of_enqueued_jobs()?;
+  Ok(std::cmp::min(
+    jobs,
+    (max_jobs as usize).saturating_sub(enqueued_jobs),
+  ))
+}
+
+/// Add the outcome of the launch of `job` to `summary`. Only launch errors are tolerated.
+fn count_launch_result(
+  summary: &mut LaunchSummary,
+  job: &Job,
+  result: Result<(), JobError>,
   virtual_queue: bool,
 ) -> Result<(), JobError> {
+  match result {
+    Ok(_) if virtual_queue => summary.virtual_queued += 1,
+    Ok(_) => summary.launched += 1,
+    Err(JobError::LaunchError(e)) => {
+      log::warn!("Failed to launch job \"{}\": {}", job.job_name, e);
+      summary.failed += 1;
+    }
+    Err(e) => return Err(e),
+  }
+  summary.job_ids.push(job.id);
+  Ok(())
+}
+
+/// Outcome of a resume: the jobs launched again and the ones left untouched
+#[derive(Debug, Default, PartialEq)]
+pub struct ResumeSummary {
+  pub launch: LaunchSummary,
+  pub skipped: usize,
+}
+
+/// Statuses of the jobs that a resume submits again
+const RESUMABLE_STATUSES: [Status; 3] = [
+  Status::Created,
+  Status::FailedSubmission,
+  Status::VirtualQueue,
+];
+
+/// Launch the jobs of a file that were not submitted by a previous launch.
+/// Generated jobs are matched to the existing ones by config, command and variables. The existing
+/// jobs that are `RESUMABLE_STATUSES` are submitted again, the other ones are skipped. Jobs
+/// without a match are created as in `launch_jobs_from_file`.
+pub fn resume_jobs_from_file(
+  path: &Path,
+  output_dir: &PathBuf,
+  db: &mut Database,
+  cluster_name: &str,
+  var_overrides: &HashMap<String, String>,
+  quiet: bool,
+) -> Result<ResumeSummary, JobError> {
+  let cluster = db.get_cluster_by_name(cluster_name)?;
+  let configs = db.get_configs_by_cluster(&cluster)?;
+  let mut summary = ResumeSummary::default();
+  let mut to_launch = vec![];
+  for (job, config) in generate_jobs_from_file(path, &cluster, &configs, var_overrides)? {
+    match db.find_job(config.id, &job.command, &job.variables)? {
+      Some(existing) if !RESUMABLE_STATUSES.contains(&existing.status) => summary.skipped += 1,
+      existing => to_launch.push((job, existing, config)),
+    }
+  }
+
+  let scheduler = get_scheduler(&cluster);
+  let to_launch_really = available_slots(to_launch.len(), &cluster, scheduler.as_ref())?;
+  let progress = if quiet {
+    ProgressBar::hidden()
+  } else {
+    ProgressBar::new(to_launch.len() as u64)
+  };
+  progress.set_style(
+    ProgressStyle::with_template("{bar:40} {pos}/{len} {msg}")
+      .expect("Invalid progress bar template"),
+  );
+  for (i, (job, existing, config)) in to_launch.into_iter().enumerate() {
+    progress.set_message(job.job_name.clone());
+    let virtual_queue = i >= to_launch_really;
+    let mut job = match existing {
+      Some(mut existing) => {
+        // Start over from a fresh status, as a newly created job
+        existing.status = Status::Created;
+        existing
+      }
+      None => create_db_job(&job, config.id, db, output_dir)?,
+    };
+    let result = submit_db_job(
+      &mut job,
+      &config,
+      &cluster,
+      db,
+      scheduler.as_ref(),
+      virtual_queue,
+    );
+    count_launch_result(&mut summary.launch, &job, result, virtual_queue)?;
+    progress.inc(1);
+  }
+  progress.finish_and_clear();
+
+  Ok(summary)
+}
+
+/// Insert a copy of `job` in the database and create its directory
+fn create_db_job(
+  job: &Job,
+  config_id: i32,
+  db: &mut Database,
+  path: &PathBuf,
+) -> Result<Job, JobError> {
   let new_job = NewJob {
-    job_name: job.job_name,
-    command: job.command,
-    preprocess: job.preprocess,
-    postprocess: job.postprocess,
-    variables: job.variables,
-    config_id: config.id,
+    job_name: &job.job_name,
+    command: &job.command,
+    preprocess: job.preprocess.as_deref(),
+    postprocess: job.postprocess.as_deref(),
+    variables: &job.variables,
+    labels: &job.labels,
+    launch_id: job.launch_id.as_deref(),
+    config_id,
     status: &Status::Created,
     directory: "",
   };
 
   let mut job = db.create_job(&new_job)?;
+  if job.job_name.contains(JOB_ID_PLACEHOLDER) {
+    job.job_name = job
+      .job_name
+      .replace(JOB_ID_PLACEHOLDER, &job.id.to_string());
+    db.update_job_name(job.id, &job.job_name)?;
+  }
   // Set directory name to ID assigned by the database
-  let path = create_job_dir(path, job.id)?;
-  db.update_job_path(job.id, path.to_str().unwrap())?;
+  let path = create_job_dir(path, job.id, job.run_id.as_deref())?;
+  job.directory = path.to_str().unwrap().to_string();
+  db.update_job_path(job.id, &job.directory)?;
+  Ok(job)
+}
+
+/// Update the status of a job after its submission
+fn handle_launch_result(
+  job: &Job,
+  db: &mut Database,
+  launch_result: Result<(), JobError>,
+) -> Result<(), JobError> {
+  if let Err(e) = launch_result {
+    db.update_job_status(job.id, &Status::FailedSubmission)?;
+    Err(JobError::LaunchError(e.to_string()))
+  } else {
+    // TODO update DB Job (other fields like exit_code etc.)
+    db.update_job_status(job.id, &job.status)?;
+    db.update_job_submit_time(job.id, Utc::now().timestamp() as i32)?;
+    Ok(())
+  }
+}
 
-  // let script = get_scheduler(&cluster.scheduler).create_job_script(&job, config, cluster);
+/// Create a job in the database and launch it with `scheduler`. Returns the created job.
+fn launch_job(
+  job: &Job,
+  config: &Config,
+  cluster: &Cluster,
+  db: &mut Database,
+  path: &PathBuf,
+  scheduler: &dyn SchedulerTrait,
+  virtual_queue: bool,
+) -> Result<Job, JobError> {
+  let mut job = create_db_job(job, config.id, db, path)?;
+  submit_db_job(&mut job, config, cluster, db, scheduler, virtual_queue)?;
+  Ok(job)
+}
+
+/// Launch a job that is already in the database with `scheduler`, or put it in the virtual queue
+fn submit_db_job(
+  job: &mut Job,
+  config: &Config,
+  cluster: &Cluster,
+  db: &mut Database,
+  scheduler: &dyn SchedulerTrait,
+  virtual_queue: bool,
+) -> Result<(), JobError> {
+  // let script = get_scheduler(&cluster).create_job_script(&job, config, cluster);
   if !virtual_queue {
+    let cluster_config = ClusterConfig::new(cluster, config);
     // FIXME: Should we update the submit time here or in the job script?
-    let launch_result = get_scheduler(&cluster.scheduler).launch_job(
-      &mut job,
-      &ClusterConfig {
-        cluster: cluster,
-        config: config,
-      },
+    let launch_result = run_pre_submit_hook(job, &cluster_config)
+      .and_then(|_| scheduler.launch_job(job, &cluster_config));
+
+    handle_launch_result(job, db, launch_result)?;
+  } else {
+    let _ = &r#virtual::VirtualScheduler.launch_job(job, &ClusterConfig { cluster, config });
+    db.update_job_status(job.id, &Status::VirtualQueue)?;
+  }
+  Ok(())
+}
+
+/// Launch again the job with the given id as a new job.
+/// With `regenerate` the script is rebuilt from the current config, otherwise the original
+/// `job.sh` is reused, with its job id and the paths into the original job directory (log,
+/// stdout and stderr) pointed to the new job, see `retarget_script`.
+pub fn rerun_job(
+  db: &mut Database,
+  path: &PathBuf,
+  id: i32,
+  regenerate: bool,
+) -> Result<Job, JobError> {
+  let original = db.get_job_by_id(id)?;
+  let config = db.get_config_by_id(original.config_id)?;
+  let cluster = db.get_cluster_by_id(config.cluster_id)?;
+  if regenerate {
+    let scheduler = get_scheduler(&cluster);
+    return launch_job(
+      &original,
+      &config,
+      &cluster,
+      db,
+      path,
+      scheduler.as_ref(),
+      false,
     );
+  }
 
-    if launch_result.is_err() {
-      db.update_job_status(job.id, &Status::FailedSubmission)?;
-      return Err(JobError::LaunchError("Failed to launch job".to_string()));
-    } else {
-      // TODO update DB Job (other fields like timestamps, exit_code etc.)
+  let script = original.get_script()?;
+  let mut job = create_db_job(&original, config.id, db, path)?;
+  job.prepare_job_directory()?;
+  job.write_launch_log_entries(&config)?;
+  job.write_script(&retarget_script(&script, &original, &job))?;
+  let launch_result = run_pre_submit_hook(&job, &ClusterConfig::new(&cluster, &config))
+    .and_then(|_| get_scheduler(&cluster).submit_job_script(&mut job));
+  handle_launch_result(&job, db, launch_result)?;
+  Ok(job)
+}
+
+/// `script` of `original` made to run as `job`: its `JobId` and the paths into the directory of
+/// `original`, absolute (ex. the log commands) or as stored, are replaced with the ones of `job`
+fn retarget_script(script: &str, original: &Job, job: &Job) -> String {
+  let mut script = script.replace(
+    &format!("# JobId: {}\n", original.id),
+    &format!("# JobId: {}\n", job.id),
+  );
+  let directories = |job: &Job| {
+    let directory = PathBuf::from(&job.directory);
+    let absolute = directory.canonicalize().unwrap_or(directory);
+    [absolute.display().to_string(), job.directory.clone()]
+  };
+  for (from, to) in directories(original).iter().zip(directories(job).iter()) {
+    script = script.replace(from, to);
+  }
+  script
+}
+
+/// Run the `pre_submit_hook` of the cluster, if any, in the job directory with the environment
+/// of the job: the `env` of its config and `SBM_JOB_ID`, `SBM_JOB_NAME` and `SBM_JOB_DIR`.
+/// Its output is recorded in the job log. Fails if the hook exits non-zero, so that the job is
+/// not submitted.
+fn run_pre_submit_hook(job: &Job, cluster_config: &ClusterConfig) -> Result<(), JobError> {
+  let Some(hook) = &cluster_config.cluster.pre_submit_hook else {
+    return Ok(());
+  };
+  let mut command = std::process::Command::new("sh");
+  command.arg("-c").arg(hook).current_dir(&job.directory);
+  if let Some(env) = cluster_config.config.env.as_object() {
+    for (key, value) in env {
+      command.env(
+        key,
+        value
+          .as_str()
+          .map_or_else(|| value.to_string(), str::to_string),
+      );
+    }
+  }
+  let output = command
+    .env("SBM_JOB_ID", job.id.to_string())
+    .env("SBM_JOB_NAME", &job.job_name)
+    .env("SBM_JOB_DIR", &job.directory)
+    .output()
+    .map_err(|e| JobError::SpawnError(format!("Failed to run the pre-submit hook: {}", e)))?;
+
+  let mut hook_output = String::from_utf8_lossy(&output.stdout).into_owned();
+  hook_output.push_str(&String::from_utf8_lossy(&output.stderr));
+  job.write_log_entry(
+    JobLog::PreSubmitHook {
+      exit_code: output.status.code(),
+      output: hook_output,
+    },
+    None,
+  )?;
+  if !output.status.success() {
+    return Err(JobError::PreSubmitHookFailed(format!(
+      "`{}` exited with {}",
+      hook, output.status
+    )));
+  }
+  Ok(())
+}
+
+/// Cancel a job that is not over yet. A submitted job is stopped through its scheduler, then the
+/// job is marked `Canceled`, in its log and in the database.
+pub fn cancel_job(db: &mut Database, id: i32) -> Result<Job, JobError> {
+  let mut job = db.get_job_by_id(id)?;
+  if job.status.is_terminal() {
+    return Err(JobError::NotCancelable(id, job.status));
+  }
+  if matches!(job.status, Status::Queued | Status::Running) {
+    let config = db.get_config_by_id(job.config_id)?;
+    let cluster = db.get_cluster_by_id(config.cluster_id)?;
+    get_scheduler(&cluster).cancel_job(&job)?;
+  }
+  job.write_log_entry(JobLog::Action("cancel".to_string()), None)?;
+  job.status = Status::Canceled;
+  job.write_log_entry(JobLog::StatusUpdate(Status::Canceled), None)?;
+  db.update_job_status(id, &job.status)?;
+  Ok(job)
+}
+
+/// Set the status of several jobs at once (ex. jobs left running by an outage), see
+/// `Database::set_status_for_ids`. The change is logged first in the log of each job, so that a
+/// replay of the logs keeps it. If one of the jobs does not exist, nothing is changed.
+pub fn set_status_for_ids(
+  db: &mut Database,
+  ids: &[i32],
+  status: &Status,
+) -> Result<usize, JobError> {
+  let mut unique_ids = ids.to_vec();
+  unique_ids.sort_unstable();
+  unique_ids.dedup();
+  let jobs = unique_ids
+    .iter()
+    .map(|id| db.get_job_by_id(*id))
+    .collect::<Result<Vec<_>, _>>()?;
+  // Jobs that failed submission may not have a directory, nor a log
+  for job in jobs.iter().filter(|job| !job.directory.is_empty()) {
+    job.write_log_entry(JobLog::Action("set-status".to_string()), None)?;
+    job.write_log_entry(JobLog::StatusUpdate(status.clone()), None)?;
+  }
+  Ok(db.set_status_for_ids(&unique_ids, status)?)
+}
+
+/// Outcome of the cancellation of the jobs of a launch
+#[derive(Debug, Default)]
+pub struct CancelLaunchSummary {
+  pub canceled: Vec<Job>,
+  /// Ids of the jobs that could not be canceled, with the reason
+  pub failed: Vec<(i32, JobError)>,
+}
+
+/// Cancel the jobs of a launch that are not over yet, see `cancel_job`. A job that cannot be
+/// canceled does not stop the cancellation of the other ones, it is reported in the summary.
+pub fn cancel_launch(db: &mut Database, launch_id: &str) -> Result<CancelLaunchSummary, JobError> {
+  let filter = JobFilter {
+    launch_id: Some(launch_id.to_string()),
+    ..Default::default()
+  };
+  let jobs = db.get_jobs(Some(filter))?;
+  if jobs.is_empty() {
+    return Err(JobError::LaunchNotFound(launch_id.to_string()));
+  }
+  let mut summary = CancelLaunchSummary::default();
+  for job in jobs.iter().filter(|job| !job.status.is_terminal()) {
+    match cancel_job(db, job.id) {
+      Ok(job) => summary.canceled.push(job),
+      Err(error) => summary.failed.push((job.id, error)),
+    }
+  }
+  Ok(summary)
+}
+
+/// Delete the jobs with the given status along with their directories.
+/// Returns the number of purged jobs.
+pub fn purge_jobs(db: &mut Database, status: &Status) -> Result<usize, JobError> {
+  if matches!(status, Status::Running | Status::Queued) {
+    return Err(JobError::PurgeActiveStatus(status.clone()));
+  }
+  let jobs = db.delete_jobs_by_status(status)?;
+  remove_job_directories(&jobs)?;
+  Ok(jobs.len())
+}
+
+/// Delete the jobs of `ids` and their directories, returning the number of deleted jobs.
+/// The jobs are not canceled: cancel the active ones first.
+pub fn delete_jobs(db: &mut Database, ids: &[i32]) -> Result<usize, JobError> {
+  let jobs = db.delete_jobs_by_ids(ids)?;
+  remove_job_directories(&jobs)?;
+  Ok(jobs.len())
+}
+
+fn remove_job_directories(jobs: &[Job]) -> Result<(), JobError> {
+  for job in jobs {
+    // Jobs that failed submission may not have a directory
+    if !job.directory.is_empty() && Path::new(&job.directory).exists() {
+      fs::remove_dir_all(&job.directory)?;
+    }
+  }
+  Ok(())
+}
+
+/// Inconsistencies between the database and the job directories, see `check_jobs`
+#[derive(Debug, Default, PartialEq)]
+pub struct DoctorReport {
+  /// Jobs whose directory is missing on disk
+  pub missing_directories: Vec<Job>,
+  /// Directories under `jobs/` that belong to no job
+  pub orphan_directories: Vec<PathBuf>,
+  /// Jobs not over yet whose process is dead
+  pub dead_jobs: Vec<Job>,
+}
+
+impl DoctorReport {
+  pub fn is_healthy(&self) -> bool {
+    self.missing_directories.is_empty()
+      && self.orphan_directories.is_empty()
+      && self.dead_jobs.is_empty()
+  }
+}
+
+/// PID logged by the local scheduler when it started the job
+fn logged_pid(job: &Job) -> Option<u32> {
+  job
+    .parse_log_typed()
+    .ok()?
+    .into_iter()
+    .rev()
+    .find_map(|(_, log)| match log {
+      JobLog::Variable(name, pid) if name == "PID" => pid.parse().ok(),
+      _ => None,
+    })
+}
+
+fn is_process_alive(pid: u32) -> bool {
+  std::process::Command::new("kill")
+    .args(["-0", &pid.to_string()])
+    .stderr(std::process::Stdio::null())
+    .status()
+    .is_ok_and(|status| status.success())
+}
+
+/// Compare the jobs of the database with the job directories under `path/jobs`.
+/// Dead jobs are the jobs not over yet (in the database and in their log) whose logged process
+/// is gone, only local jobs log their process.
+/// With `fix`, dead jobs are marked failed, with their resource usage if the scheduler accounts
+/// for it, and orphan directories are removed.
+pub fn check_jobs(db: &mut Database, path: &Path, fix: bool) -> Result<DoctorReport, JobError> {
+  let mut report = DoctorReport::default();
+  let jobs = db.get_jobs(None)?;
+  let mut job_dir_names = HashSet::new();
+  for job in jobs {
+    // Jobs that failed submission may not have a directory
+    if job.directory.is_empty() {
+      continue;
+    }
+    let directory = Path::new(&job.directory);
+    if let Some(name) = directory.file_name() {
+      job_dir_names.insert(name.to_os_string());
+    }
+    if !directory.is_dir() {
+      report.missing_directories.push(job);
+      continue;
+    }
+    let logged_active = job
+      .get_logged_status()
+      .is_none_or(|status| !status.is_terminal());
+    if !job.status.is_terminal()
+      && logged_active
+      && logged_pid(&job).is_some_and(|pid| !is_process_alive(pid))
+    {
+      report.dead_jobs.push(job);
+    }
+  }
+
+  let jobs_dir = path.join("jobs");
+  if jobs_dir.is_dir() {
+    for entry in fs::read_dir(&jobs_dir)? {
+      let entry = entry?;
+      if entry.file_type()?.is_dir() && !job_dir_names.contains(&entry.file_name()) {
+        report.orphan_directories.push(entry.path());
+      }
+    }
+    report.orphan_directories.sort();
+  }
+
+  if fix {
+    for job in &mut report.dead_jobs {
+      job.status = Status::Failed;
+      job.write_log_entry(JobLog::StatusUpdate(Status::Failed), None)?;
       db.update_job_status(job.id, &job.status)?;
+      let config = db.get_config_by_id(job.config_id)?;
+      let cluster = db.get_cluster_by_id(config.cluster_id)?;
+      record_resource_usage(get_scheduler(&cluster).as_ref(), job)?;
     }
-  } else {
-    let _ = &r#virtual::VirtualScheduler.launch_job(
-      &mut job,
-      &ClusterConfig {
-        cluster: cluster,
-        config: config,
-      },
+    for directory in &report.orphan_directories {
+      fs::remove_dir_all(directory)?;
+    }
+  }
+  Ok(report)
+}
+
+/// Rebuild the `job.sh` of the jobs of `config` that were not launched yet (created or in the
+/// virtual queue) from the current config. The other jobs are left untouched.
+/// Returns the number of regenerated scripts.
+pub fn regenerate_scripts(
+  db: &mut Database,
+  cluster: &Cluster,
+  config: &Config,
+) -> Result<usize, JobError> {
+  let jobs = db.get_jobs(Some(JobFilter {
+    statuses: vec![Status::Created, Status::VirtualQueue],
+    config_ids: vec![config.id],
+    ..Default::default()
+  }))?;
+  let scheduler = get_scheduler(cluster);
+  let cluster_config = ClusterConfig::new(cluster, config);
+  for job in &jobs {
+    let script = scheduler.create_job_script(job, &cluster_config)?;
+    job.prepare_job_directory()?;
+    job.write_script(&script)?;
+  }
+  Ok(jobs.len())
+}
+
+/// Append the resource usage of a finished job to its log, if its scheduler accounts for it.
+/// Failing to get the usage is only logged. The status sync records the usage of the jobs that
+/// finished in one batch instead.
+fn record_resource_usage(scheduler: &dyn SchedulerTrait, job: &Job) -> Result<(), JobError> {
+  match scheduler.get_resource_usage(job) {
+    Ok(Some(usage)) => job.write_log_entry(usage, None)?,
+    Ok(None) => {}
+    Err(e) => log::warn!("Failed to get the resource usage of job #{}: {}", job.id, e),
+  }
+  Ok(())
+}
+
+/// Outcome of a status sync. `queued` and `running` count the jobs still active after the sync.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SyncSummary {
+  pub updated: usize,
+  /// Jobs resubmitted after hitting their time limit
+  pub resubmitted: usize,
+  pub queued: usize,
+  pub running: usize,
+}
+
+impl SyncSummary {
+  /// Whether no job is left queued or running
+  pub fn is_done(&self) -> bool {
+    self.queued == 0 && self.running == 0
+  }
+
+  fn count_active(&mut self, status: &Status) {
+    match status {
+      Status::Queued => self.queued += 1,
+      Status::Running => self.running += 1,
+      _ => {}
+    }
+  }
+}
+
+/// Label of a resubmitted job holding the id of the job it retries
+pub const RETRY_OF_LABEL: &str = "retry_of";
+/// Label of a resubmitted job holding the number of the retry, starting from 1
+pub const RETRY_LABEL: &str = "retry";
+
+/// Resubmit a job that hit its time limit, if its config has a `retry_on_timeout` policy and
+/// retries are left. At the n-th retry the time limit of the config is scaled by
+/// `time_multiplier^n`. The lineage is recorded in the `retry_of` and `retry` labels.
+/// Returns the new job, or `None` if the job is not retried.
+pub fn resubmit_on_timeout(
+  db: &mut Database,
+  path: &PathBuf,
+  job: &Job,
+) -> Result<Option<Job>, JobError> {
+  let config = db.get_config_by_id(job.config_id)?;
+  let Some(policy) = config.retry_policy() else {
+    return Ok(None);
+  };
+  let retry = job
+    .labels
+    .get(RETRY_LABEL)
+    .and_then(Value::as_str)
+    .and_then(|retry| retry.parse::<u32>().ok())
+    .unwrap_or(0)
+    + 1;
+  if retry > policy.max_retries {
+    return Ok(None);
+  }
+
+  // Slurm and the local scheduler use `time`, PBS uses `walltime`
+  let mut flags = config.flags.as_object().cloned().unwrap_or_default();
+  let Some((key, time)) = ["time", "walltime"]
+    .into_iter()
+    .find_map(|key| Some((key, flags.get(key)?.as_str()?.to_string())))
+  else {
+    log::warn!(
+      "Job #{} timed out but config \"{}\" has no time limit to scale",
+      job.id,
+      config.config_name
     );
-    db.update_job_status(job.id, &Status::VirtualQueue)?;
+    return Ok(None);
+  };
+  let seconds = parse_time_to_seconds(&time)? as f64 * policy.time_multiplier.powi(retry as i32);
+  flags.insert(
+    key.to_string(),
+    json!(format_seconds_as_time(seconds.round() as u64)),
+  );
+  let config = Config {
+    flags: Value::Object(flags),
+    ..config
+  };
+
+  let mut labels = job.labels.as_object().cloned().unwrap_or_default();
+  labels.insert(RETRY_OF_LABEL.to_string(), json!(job.id.to_string()));
+  labels.insert(RETRY_LABEL.to_string(), json!(retry.to_string()));
+  let retry_job = Job {
+    labels: Value::Object(labels),
+    ..job.clone()
+  };
+
+  let cluster = db.get_cluster_by_id(config.cluster_id)?;
+  let scheduler = get_scheduler(&cluster);
+  launch_job(
+    &retry_job,
+    &config,
+    &cluster,
+    db,
+    path,
+    scheduler.as_ref(),
+    false,
+  )
+  .map(Some)
+}
+
+/// Update the status of the queued and running jobs from their logs. With `since`, only the jobs
+/// submitted at or after this Unix time, or without a submit time, are synced.
+/// The resource usage of the jobs that finished is recorded in their logs, the jobs that hit
+/// their time limit are resubmitted according to the `retry_on_timeout` policy of their config.
+/// Resubmitted jobs are created in `path/jobs/<id>`.
+pub fn sync_job_statuses(
+  db: &mut Database,
+  path: &PathBuf,
+  since: Option<i32>,
+) -> Result<SyncSummary, JobError> {
+  let mut summary = SyncSummary::default();
+  for (cluster, jobs) in active_jobs_by_cluster(db, since)? {
+    let statuses = query_job_statuses(&jobs, &cluster)?;
+    for (job, status) in jobs.iter().zip(statuses) {
+      apply_job_status(db, path, job, status, &mut summary)?;
+    }
   }
+  Ok(summary)
+}
+
+/// Same as `sync_job_statuses`, with the jobs of each cluster queried by their own thread.
+/// The database is shared behind a mutex, so its writes are serialized.
+pub fn sync_job_statuses_parallel(
+  db: &mut Database,
+  path: &PathBuf,
+  since: Option<i32>,
+) -> Result<SyncSummary, JobError> {
+  let clusters = active_jobs_by_cluster(db, since)?;
+  let state = Mutex::new((db, SyncSummary::default()));
+  thread::scope(|scope| {
+    let handles: Vec<_> = clusters
+      .iter()
+      .map(|(cluster, jobs)| {
+        let state = &state;
+        scope.spawn(move || -> Result<(), JobError> {
+          let statuses = query_job_statuses(jobs, cluster)?;
+          for (job, status) in jobs.iter().zip(statuses) {
+            let mut state = state
+              .lock()
+              .map_err(|_| JobError::Other("A sync thread panicked".to_string()))?;
+            let (db, summary) = &mut *state;
+            apply_job_status(db, path, job, status, summary)?;
+          }
+          Ok(())
+        })
+      })
+      .collect();
+    handles.into_iter().try_for_each(|handle| {
+      handle
+        .join()
+        .map_err(|_| JobError::Other("A sync thread panicked".to_string()))?
+    })
+  })?;
+  let (_, summary) = state
+    .into_inner()
+    .map_err(|_| JobError::Other("A sync thread panicked".to_string()))?;
+  Ok(summary)
+}
+
+/// Queued and running jobs submitted at or after `since` or without a submit time (all of them if
+/// `None`), grouped by cluster
+fn active_jobs_by_cluster(
+  db: &mut Database,
+  since: Option<i32>,
+) -> Result<Vec<(Cluster, Vec<Job>)>, JobError> {
+  let filter = JobFilter {
+    statuses: vec![Status::Queued, Status::Running],
+    submitted_since: since,
+    ..Default::default()
+  };
+  let mut clusters: Vec<(Cluster, Vec<Job>)> = Vec::new();
+  // Config id to the index of its cluster in `clusters`
+  let mut config_clusters: HashMap<i32, usize> = HashMap::new();
+  for job in db.get_jobs(Some(filter))? {
+    let index = match config_clusters.get(&job.config_id) {
+      Some(index) => *index,
+      None => {
+        let config = db.get_config_by_id(job.config_id)?;
+        let index = match clusters.iter().position(|(c, _)| c.id == config.cluster_id) {
+          Some(index) => index,
+          None => {
+            clusters.push((db.get_cluster_by_id(config.cluster_id)?, Vec::new()));
+            clusters.len() - 1
+          }
+        };
+        config_clusters.insert(job.config_id, index);
+        index
+      }
+    };
+    clusters[index].1.push(job);
+  }
+  Ok(clusters)
+}
+
+/// Logged statuses of the active jobs of a cluster, `None` for the ones that did not change. The
+/// resource usage of the jobs that finished is queried in one batch and recorded in their logs.
+fn query_job_statuses(jobs: &[Job], cluster: &Cluster) -> Result<Vec<Option<Status>>, JobError> {
+  let statuses: Vec<Option<Status>> = jobs
+    .iter()
+    .map(|job| {
+      job
+        .get_logged_status()
+        .filter(|status| *status != job.status)
+    })
+    .collect();
+  let finished: Vec<&Job> = jobs
+    .iter()
+    .zip(&statuses)
+    .filter(|(_, status)| status.as_ref().is_some_and(Status::is_terminal))
+    .map(|(job, _)| job)
+    .collect();
+  if !finished.is_empty() {
+    // The resource usage is best effort: failing to get it must not stop the status sync
+    let mut usages = get_scheduler(cluster)
+      .get_resource_usages(&finished)
+      .unwrap_or_else(|e| {
+        log::warn!(
+          "Failed to get the resource usage of the finished jobs: {}",
+          e
+        );
+        HashMap::new()
+      });
+    for job in finished {
+      if let Some(usage) = usages.remove(&job.id) {
+        job.write_log_entry(usage, None)?;
+      }
+    }
+  }
+  Ok(statuses)
+}
+
+/// Store the new status of a job, if any, resubmitting it if it hit its time limit, and count it
+/// in `summary`
+fn apply_job_status(
+  db: &mut Database,
+  path: &PathBuf,
+  job: &Job,
+  status: Option<Status>,
+  summary: &mut SyncSummary,
+) -> Result<(), JobError> {
+  let Some(status) = status else {
+    summary.count_active(&job.status);
+    return Ok(());
+  };
+  db.update_job_status(job.id, &status)?;
+  summary.updated += 1;
+  if status == Status::Timeout {
+    match resubmit_on_timeout(db, path, job) {
+      Ok(Some(retry)) => {
+        summary.resubmitted += 1;
+        summary.count_active(&db.get_job_by_id(retry.id)?.status);
+      }
+      Ok(None) => {}
+      Err(JobError::LaunchError(e)) => {
+        log::warn!("Failed to resubmit job #{}: {}", job.id, e);
+      }
+      Err(e) => return Err(e),
+    }
+  }
+  summary.count_active(&status);
   Ok(())
 }
 
-fn create_job_dir(path: &PathBuf, id: i32) -> Result<PathBuf, JobError> {
+/// Create the directory of a job, `jobs/<id>-<run_id>` or `jobs/<id>` for jobs without run id.
+/// The run id keeps the directories of jobs imported from other databases apart.
+fn create_job_dir(path: &Path, id: i32, run_id: Option<&str>) -> Result<PathBuf, JobError> {
   use std::fs;
   use std::path::Path;
 
-  let dir_path = path.join(format!("jobs/{}", id));
+  let dir_name = match run_id {
+    Some(run_id) => format!("{}-{}", id, run_id),
+    None => id.to_string(),
+  };
+  let dir_path = path.join("jobs").join(dir_name);
   fs::create_dir_all(Path::new(&dir_path))?;
   Ok(dir_path)
 }
 
-fn get_scheduler(scheduler: &DbScheduler) -> Box<dyn SchedulerTrait> {
-  match scheduler {
-    DbScheduler::Slurm => Box::new(slurm::SlurmScheduler),
-    DbScheduler::Pbs => Box::new(pbs::PbsScheduler),
+fn get_scheduler(cluster: &Cluster) -> Box<dyn SchedulerTrait> {
+  get_launch_scheduler(cluster, &[])
+}
+
+/// Scheduler of the cluster, passing `submit_args` to the submit command of Slurm and PBS
+fn get_launch_scheduler(cluster: &Cluster, submit_args: &[String]) -> Box<dyn SchedulerTrait> {
+  if !submit_args.is_empty() && !matches!(cluster.scheduler, DbScheduler::Slurm | DbScheduler::Pbs)
+  {
+    log::warn!(
+      "Ignoring the submit arguments: the {:?} scheduler has no submit command to pass them to",
+      cluster.scheduler
+    );
+  }
+  match cluster.scheduler {
+    DbScheduler::Slurm => Box::new(slurm::SlurmScheduler {
+      submit_args: submit_args.to_vec(),
+      ..Default::default()
+    }),
+    DbScheduler::Pbs => Box::new(pbs::PbsScheduler {
+      submit_args: submit_args.to_vec(),
+      ..Default::default()
+    }),
     DbScheduler::Local => Box::new(local::LocalScheduler::default()),
+    DbScheduler::Custom => Box::new(custom::CustomScheduler::new(cluster)),
   }
 }
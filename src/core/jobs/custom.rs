@@ -0,0 +1,102 @@
+use std::env;
+use std::process::Command;
+
+use regex::Regex;
+
+use crate::core::cluster_configs::ClusterConfig;
+use crate::core::database::models::{Cluster, Status};
+use crate::core::jobs::{JobLog, SchedulerTrait};
+use crate::core::{
+  database::models::Job,
+  jobs::utils::{map_err_adding_description, shell_quote},
+};
+
+use super::JobError;
+
+/// A scheduler not natively supported (ex. LSF), driven by the templates defined in the cluster
+#[derive(Debug, PartialEq)]
+pub struct CustomScheduler {
+  /// Command submitting the script, `{script}` is replaced with the quoted script path
+  pub submit_command: String,
+  /// Regex extracting the job id from the output of the submit command.
+  /// The first capture group is used if any, otherwise the whole match.
+  /// Without a regex, the whole trimmed output is the job id.
+  pub job_id_regex: Option<String>,
+}
+
+impl CustomScheduler {
+  pub fn new(cluster: &Cluster) -> Self {
+    Self {
+      submit_command: cluster.submit_command.clone().unwrap_or_default(),
+      job_id_regex: cluster.job_id_regex.clone(),
+    }
+  }
+
+  /// Extract the job id from the output of the submit command
+  pub fn parse_job_id(&self, output: &str) -> Result<String, JobError> {
+    let Some(job_id_regex) = &self.job_id_regex else {
+      let job_id = output.trim();
+      if job_id.is_empty() {
+        return Err(JobError::ExecutionFailed(
+          "The submit command printed no job id".to_string(),
+        ));
+      }
+      return Ok(job_id.to_string());
+    };
+    let regex = Regex::new(job_id_regex).map_err(|e| JobError::Other(e.to_string()))?;
+    regex
+      .captures(output)
+      .and_then(|captures| captures.get(1).or_else(|| captures.get(0)))
+      .map(|job_id| job_id.as_str().to_string())
+      .ok_or_else(|| {
+        JobError::ExecutionFailed(format!(
+          "Could not find the job id in the output of the submit command: {}",
+          output.trim()
+        ))
+      })
+  }
+}
+
+impl SchedulerTrait for CustomScheduler {
+  fn create_job_script(
+    &self,
+    job: &Job,
+    cluster_config: &ClusterConfig,
+  ) -> Result<String, JobError> {
+    let launch_base_path = env::current_dir()
+      .map_err(|e| map_err_adding_description(e, "Could not get the working directory: {}"))?;
+    Ok(job.generate_script(cluster_config, &launch_base_path, &[]))
+  }
+
+  fn launch_job(&self, job: &mut Job, cluster_config: &ClusterConfig) -> Result<(), JobError> {
+    job.prepare_job_directory()?;
+    job.write_launch_log_entries(cluster_config.config)?;
+
+    let script_content = self.create_job_script(job, cluster_config)?;
+    job.write_script(&script_content)?;
+
+    self.submit_job_script(job)
+  }
+
+  fn submit_job_script(&self, job: &mut Job) -> Result<(), JobError> {
+    job.write_log_entry(JobLog::StatusUpdate(Status::Created), None)?;
+
+    let command = self.submit_command.replace(
+      "{script}",
+      &shell_quote(&job.get_script_path().display().to_string()),
+    );
+    let output = Command::new("sh")
+      .args(["-c", &command])
+      .current_dir(&job.directory)
+      .output()
+      .map_err(|e| JobError::SpawnError(format!("Failed to run \"{}\": {}", command, e)))?;
+    if !output.status.success() {
+      return Err(JobError::ExecutionFailed(
+        String::from_utf8_lossy(&output.stderr).to_string(),
+      ));
+    }
+
+    let job_id = self.parse_job_id(&String::from_utf8_lossy(&output.stdout))?;
+    job.record_submission(job_id)
+  }
+}
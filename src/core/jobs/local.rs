@@ -7,9 +7,10 @@ use crate::core::{database::models::Job, jobs::SchedulerTrait};
 
 use super::JobError;
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, Read};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
 
 #[derive(Debug, PartialEq)]
 pub struct LocalScheduler {
@@ -31,9 +32,15 @@ impl LocalScheduler {
     }
   }
 
-  /// Submit a job locally with optional timeout
+  /// Submit a job locally with optional timeout.
+  /// With `max_output_bytes`, the output of the job goes through a `CappedWriter` instead of being
+  /// redirected to the log files.
   /// Returns (pid, exit_code, timed_out)
-  fn local_submit(&self, job: &Job) -> Result<(u32, Option<i32>, bool), JobError> {
+  fn local_submit(
+    &self,
+    job: &Job,
+    max_output_bytes: Option<usize>,
+  ) -> Result<(u32, Option<i32>, bool), JobError> {
     let stdout_file = File::create(job.get_stdout_path())
       .map_err(|e| map_err_adding_description(e, "Failed to create stdout log: {}"))?;
     let stderr_file = File::create(job.get_stderr_path())
@@ -44,9 +51,18 @@ impl LocalScheduler {
 
     // Prepare the command (with or without timeout)
     let mut cmd = Command::new(script_path);
-    cmd
-      .stdout(Stdio::from(stdout_file))
-      .stderr(Stdio::from(stderr_file));
+    let capped_logs = match max_output_bytes {
+      Some(max_bytes) => {
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        Some((stdout_file, stderr_file, max_bytes))
+      }
+      None => {
+        cmd
+          .stdout(Stdio::from(stdout_file))
+          .stderr(Stdio::from(stderr_file));
+        None
+      }
+    };
     // println!("CMD {:#?}", cmd);
 
     // Run the command
@@ -54,12 +70,26 @@ impl LocalScheduler {
       .spawn()
       .map_err(|e| JobError::SpawnError(format!("Failed to spawn process: {}", e)))?;
 
+    let captures = capped_logs.map(|(stdout_file, stderr_file, max_bytes)| {
+      (
+        capture_output(child.stdout.take(), stdout_file, max_bytes),
+        capture_output(child.stderr.take(), stderr_file, max_bytes),
+      )
+    });
+
     let pid = child.id();
 
     let output = child
       .wait()
       .map_err(|e| JobError::WaitError(format!("Failed to wait for process: {}", e)))?;
 
+    if let Some((stdout, stderr)) = captures {
+      join_capture(stdout)
+        .map_err(|e| map_err_adding_description(e, "Failed to write stdout log"))?;
+      join_capture(stderr)
+        .map_err(|e| map_err_adding_description(e, "Failed to write stderr log"))?;
+    }
+
     let exit_code = output.code();
     // println!("sstsus {:#?}", output);
     // println!("succc {:#?}", output.success());
@@ -71,6 +101,49 @@ impl LocalScheduler {
 
     Ok((pid, exit_code, exit_code == Some(124)))
   }
+
+  fn submit(&self, job: &mut Job, max_output_bytes: Option<usize>) -> Result<(), JobError> {
+    job.write_log_entry(JobLog::StatusUpdate(Status::Created), None)?;
+
+    // Launch the job with full logging
+    let (pid, exit_code, timed_out) = self.local_submit(job, max_output_bytes)?;
+    job.write_log_entry(JobLog::Variable(String::from("PID"), pid.to_string()), None)?;
+
+    // `timeout` exits with 124. The generated scripts log it, a script written by hand may not
+    if timed_out {
+      if job.get_logged_status() != Some(Status::Timeout) {
+        job.write_log_entry(JobLog::StatusUpdate(Status::Timeout), None)?;
+      }
+      job.status = Status::Timeout;
+    }
+
+    if exit_code.is_none() {
+      Err(JobError::ExecutionFailed("Could not run job".to_string()))
+    } else {
+      Ok(())
+    }
+  }
+}
+
+/// Copy the piped output of a job into `file`, keeping at most `max_bytes` of it
+fn capture_output<R: Read + Send + 'static>(
+  output: Option<R>,
+  file: File,
+  max_bytes: usize,
+) -> JoinHandle<io::Result<()>> {
+  thread::spawn(move || {
+    let mut writer = CappedWriter::new(file, max_bytes);
+    if let Some(mut output) = output {
+      io::copy(&mut output, &mut writer)?;
+    }
+    writer.finish().map(|_| ())
+  })
+}
+
+fn join_capture(handle: JoinHandle<io::Result<()>>) -> io::Result<()> {
+  handle
+    .join()
+    .unwrap_or_else(|_| Err(io::Error::other("output capture thread panicked")))
 }
 
 impl SchedulerTrait for LocalScheduler {
@@ -79,73 +152,27 @@ impl SchedulerTrait for LocalScheduler {
     job: &Job,
     cluster_config: &ClusterConfig,
   ) -> Result<String, JobError> {
-    let mut script = cluster_config.generate_script_header(&self.launch_base_path);
-
-    cluster_config.add_environment_variables(&mut script);
-
-    script.push_str("\n# Status update");
-    job.add_log_command(&mut script, JobLog::StatusUpdate(Status::Running), None);
-
-    // Extract time limit from config flags if present
-    let time_limit = cluster_config.config.flags.get("time").and_then(|v| {
-      if let Some(s_str) = v.as_str() {
-        parse_time_to_seconds(s_str).map_or(None, |s| Some(s))
-      } else {
-        None
-      }
-    });
-    job.add_job_commands(&mut script, time_limit);
-
-    script.push_str("\n# Export EXIT CODE");
-    job.add_log_command(
-      &mut script,
-      JobLog::BashVariable("SBM_EXIT_CODE".to_string()),
-      None,
-    );
-
-    script.push_str("\nexit \"${SBM_EXIT_CODE}\"");
-
-    Ok(script)
+    Ok(job.generate_script(cluster_config, &self.launch_base_path, &[]))
   }
 
   fn launch_job(&self, job: &mut Job, cluster_config: &ClusterConfig) -> Result<(), JobError> {
     job.prepare_job_directory()?;
-    job.write_log_entry(JobLog::Metadata(job.clone()), None)?;
+    job.write_launch_log_entries(cluster_config.config)?;
 
     // Create the job script
-    let script_path = job.get_script_path();
     let script_content = self.create_job_script(job, cluster_config)?;
+    job.write_script(&script_content)?;
 
-    // Save script to job directory
-    {
-      // FIXME this seems to be an issue sometimes SpawnError("Failed to spawn process: Text file busy (os error 26)")
-      let mut file = File::create(&script_path)
-        .map_err(|e| map_err_adding_description(e, "Failed to create script file: {}"))?;
-
-      file
-        .write_all(script_content.as_bytes())
-        .map_err(|e| map_err_adding_description(e, "Failed to write script: {}"))?;
-
-      // Explicitly flush and close the file
-      file
-        .flush()
-        .map_err(|e| map_err_adding_description(e, "Failed to flush script: {}"))?;
-    } // File is dropped and closed here
-
-    // Make script executable
-    make_script_executable(&script_path)?;
-
-    job.write_log_entry(JobLog::StatusUpdate(Status::Created), None)?;
-
-    // Launch the job with full logging
-    let (pid, exit_code, _) = self.local_submit(job)?;
-    job.write_log_entry(JobLog::Variable(String::from("PID"), pid.to_string()), None)?;
+    let max_output_bytes = cluster_config
+      .config
+      .max_output_bytes
+      .and_then(|n| usize::try_from(n).ok());
+    self.submit(job, max_output_bytes)
+  }
 
-    return if exit_code.is_none() {
-      Err(JobError::ExecutionFailed("Could not run job".to_string()))
-    } else {
-      Ok(())
-    };
+  fn submit_job_script(&self, job: &mut Job) -> Result<(), JobError> {
+    // The config of the job is not known here, so its output is not capped
+    self.submit(job, None)
   }
 
   fn get_number_of_enqueued_jobs(&self) -> Result<usize, JobError> {
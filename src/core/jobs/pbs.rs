@@ -1,8 +1,46 @@
-use crate::core::{cluster_configs::ClusterConfig, database::models::Job, jobs::SchedulerTrait};
+use std::{env, path::Path, process::Command};
+
+use crate::core::{
+  cluster_configs::ClusterConfig,
+  database::models::{Job, Status},
+  jobs::{
+    CommandRunner, JobLog, SchedulerTrait, SystemCommandRunner,
+    utils::{map_err_adding_description, quote_directive_value},
+  },
+};
 
 use super::JobError;
 
-pub struct PbsScheduler;
+pub struct PbsScheduler {
+  /// Extra arguments of `qsub` for this launch (ex. `-h`), before the script path
+  pub submit_args: Vec<String>,
+  pub runner: Box<dyn CommandRunner>,
+}
+
+impl Default for PbsScheduler {
+  fn default() -> Self {
+    Self {
+      submit_args: vec![],
+      runner: Box::new(SystemCommandRunner),
+    }
+  }
+}
+
+/// Directives naming the job and writing its output to the `stdout.log`/`stderr.log` of its
+/// directory, as the local scheduler does
+pub fn job_directives(job: &Job) -> Result<Vec<String>, JobError> {
+  Ok(vec![
+    format!("#PBS -N {}", quote_directive_value(&job.job_name)?),
+    format!(
+      "#PBS -o {}",
+      quote_directive_value(&job.get_stdout_path().display().to_string())?
+    ),
+    format!(
+      "#PBS -e {}",
+      quote_directive_value(&job.get_stderr_path().display().to_string())?
+    ),
+  ])
+}
 
 impl SchedulerTrait for PbsScheduler {
   fn create_job_script(
@@ -10,16 +48,52 @@ impl SchedulerTrait for PbsScheduler {
     job: &Job,
     cluster_config: &ClusterConfig,
   ) -> Result<String, JobError> {
-    // FIXME implement PBS job script creation logic
-    Ok(String::new())
+    let launch_base_path = env::current_dir()
+      .map_err(|e| map_err_adding_description(e, "Could not get the working directory: {}"))?;
+    Ok(job.generate_script(cluster_config, &launch_base_path, &job_directives(job)?))
   }
 
   fn launch_job(&self, job: &mut Job, cluster_config: &ClusterConfig) -> Result<(), JobError> {
-    Ok(())
+    job.prepare_job_directory()?;
+    job.write_launch_log_entries(cluster_config.config)?;
+
+    let script_content = self.create_job_script(job, cluster_config)?;
+    job.write_script(&script_content)?;
+
+    self.submit_job_script(job)
+  }
+
+  fn submit_job_script(&self, job: &mut Job) -> Result<(), JobError> {
+    job.write_log_entry(JobLog::StatusUpdate(Status::Created), None)?;
+
+    let mut args = self.submit_args.clone();
+    args.push(job.get_script_path().display().to_string());
+    // qsub prints the job id, ex. `123.server`
+    let output = self.runner.run("qsub", &args, Path::new(&job.directory))?;
+    let job_id = output.trim();
+    if job_id.is_empty() {
+      return Err(JobError::ExecutionFailed(
+        "qsub printed no job id".to_string(),
+      ));
+    }
+    job.record_submission(job_id.to_string())
   }
 
   fn get_number_of_enqueued_jobs(&self) -> Result<usize, JobError> {
     // FIXME implement logic to get number of enqueued jobs
     Ok(0)
   }
+
+  fn cancel_job(&self, job: &Job) -> Result<(), JobError> {
+    let Some(job_id) = &job.job_id else {
+      return Ok(());
+    };
+    let output = Command::new("qdel").arg(job_id).output()?;
+    if !output.status.success() {
+      return Err(JobError::ExecutionFailed(
+        String::from_utf8_lossy(&output.stderr).to_string(),
+      ));
+    }
+    Ok(())
+  }
 }
@@ -1,8 +1,85 @@
-use crate::core::{cluster_configs::ClusterConfig, database::models::Job, jobs::SchedulerTrait};
+use std::{collections::HashMap, env, path::Path, process::Command};
+
+use crate::core::{
+  cluster_configs::ClusterConfig,
+  database::models::{Job, Status},
+  jobs::{
+    CommandRunner, JobLog, SchedulerTrait, SystemCommandRunner,
+    utils::{map_err_adding_description, quote_directive_value},
+  },
+};
 
 use super::JobError;
 
-pub struct SlurmScheduler;
+pub struct SlurmScheduler {
+  /// Extra arguments of `sbatch` for this launch (ex. `--hold`), before the script path
+  pub submit_args: Vec<String>,
+  pub runner: Box<dyn CommandRunner>,
+}
+
+impl Default for SlurmScheduler {
+  fn default() -> Self {
+    Self {
+      submit_args: vec![],
+      runner: Box::new(SystemCommandRunner),
+    }
+  }
+}
+
+impl SlurmScheduler {
+  /// Query the accounting of `job_ids` (comma separated) with `sacct`, returning its output.
+  /// The usage is best effort: if sacct fails (ex. accounting is disabled) it is only logged.
+  fn run_sacct(&self, job_ids: &str, format: &str) -> Option<String> {
+    let args = [
+      "-j".to_string(),
+      job_ids.to_string(),
+      format!("--format={}", format),
+      "--noheader".to_string(),
+      "--parsable2".to_string(),
+    ];
+    self
+      .runner
+      .run("sacct", &args, Path::new("."))
+      .inspect_err(|e| {
+        log::warn!(
+          "Failed to get the resource usage of jobs {}: {}",
+          job_ids,
+          e
+        )
+      })
+      .ok()
+  }
+}
+
+/// Job id printed by `sbatch --parsable`: `<id>` or `<id>;<cluster>`
+pub fn parse_sbatch_job_id(output: &str) -> Result<String, JobError> {
+  let job_id = output.trim().split(';').next().unwrap_or_default();
+  if job_id.is_empty() {
+    return Err(JobError::ExecutionFailed(
+      "sbatch printed no job id".to_string(),
+    ));
+  }
+  Ok(job_id.to_string())
+}
+
+/// Directives naming the job and writing its output to the `stdout.log`/`stderr.log` of its
+/// directory, as the local scheduler does, instead of `slurm-<id>.out` in the submit directory
+pub fn job_directives(job: &Job) -> Result<Vec<String>, JobError> {
+  Ok(vec![
+    format!(
+      "#SBATCH --job-name={}",
+      quote_directive_value(&job.job_name)?
+    ),
+    format!(
+      "#SBATCH --output={}",
+      quote_directive_value(&job.get_stdout_path().display().to_string())?
+    ),
+    format!(
+      "#SBATCH --error={}",
+      quote_directive_value(&job.get_stderr_path().display().to_string())?
+    ),
+  ])
+}
 
 impl SchedulerTrait for SlurmScheduler {
   fn create_job_script(
@@ -10,17 +87,163 @@ impl SchedulerTrait for SlurmScheduler {
     job: &Job,
     cluster_config: &ClusterConfig,
   ) -> Result<String, JobError> {
-    // FIXME implement PBS job script creation logic
-    Ok(String::new())
+    let launch_base_path = env::current_dir()
+      .map_err(|e| map_err_adding_description(e, "Could not get the working directory: {}"))?;
+    Ok(job.generate_script(cluster_config, &launch_base_path, &job_directives(job)?))
   }
 
   fn launch_job(&self, job: &mut Job, cluster_config: &ClusterConfig) -> Result<(), JobError> {
-    // FIXME implement PBS job launch logic
-    Ok(())
+    job.prepare_job_directory()?;
+    job.write_launch_log_entries(cluster_config.config)?;
+
+    let script_content = self.create_job_script(job, cluster_config)?;
+    job.write_script(&script_content)?;
+
+    self.submit_job_script(job)
+  }
+
+  fn submit_job_script(&self, job: &mut Job) -> Result<(), JobError> {
+    job.write_log_entry(JobLog::StatusUpdate(Status::Created), None)?;
+
+    let mut args = vec!["--parsable".to_string()];
+    args.extend(self.submit_args.iter().cloned());
+    args.push(job.get_script_path().display().to_string());
+    let output = self
+      .runner
+      .run("sbatch", &args, Path::new(&job.directory))?;
+
+    if self.submit_args.iter().any(|arg| arg == "--test-only") {
+      // sbatch only validates the script and prints no job id: the job stays created
+      log::info!(
+        "Job #{} validated by sbatch --test-only, not submitted",
+        job.id
+      );
+      return Ok(());
+    }
+    job.record_submission(parse_sbatch_job_id(&output)?)
   }
 
   fn get_number_of_enqueued_jobs(&self) -> Result<usize, JobError> {
     // FIXME implement logic to get number of enqueued jobs
     Ok(0)
   }
+
+  fn get_resource_usage(&self, job: &Job) -> Result<Option<JobLog>, JobError> {
+    let Some(job_id) = &job.job_id else {
+      return Ok(None);
+    };
+    let Some(output) = self.run_sacct(job_id, "MaxRSS,TotalCPU,Elapsed") else {
+      return Ok(None);
+    };
+    Ok(Some(parse_sacct_output(&output)))
+  }
+
+  fn get_resource_usages(&self, jobs: &[&Job]) -> Result<HashMap<i32, JobLog>, JobError> {
+    let Some(job_ids) = sacct_job_ids(jobs) else {
+      return Ok(HashMap::new());
+    };
+    let Some(output) = self.run_sacct(&job_ids, "JobID,MaxRSS,TotalCPU,Elapsed") else {
+      return Ok(HashMap::new());
+    };
+    let outputs = split_sacct_output_by_job(&output);
+    Ok(
+      jobs
+        .iter()
+        .filter_map(|job| {
+          let output = outputs.get(job.job_id.as_ref()?)?;
+          Some((job.id, parse_sacct_output(output)))
+        })
+        .collect(),
+    )
+  }
+
+  fn cancel_job(&self, job: &Job) -> Result<(), JobError> {
+    let Some(job_id) = &job.job_id else {
+      return Ok(());
+    };
+    let output = Command::new("scancel").arg(job_id).output()?;
+    if !output.status.success() {
+      return Err(JobError::ExecutionFailed(
+        String::from_utf8_lossy(&output.stderr).to_string(),
+      ));
+    }
+    Ok(())
+  }
+}
+
+/// Comma-separated Slurm ids of `jobs` for a single `sacct -j` call, `None` if no job was
+/// submitted
+pub fn sacct_job_ids(jobs: &[&Job]) -> Option<String> {
+  let ids: Vec<&str> = jobs
+    .iter()
+    .filter_map(|job| job.job_id.as_deref())
+    .collect();
+  (!ids.is_empty()).then(|| ids.join(","))
+}
+
+/// Split the output of `sacct --format=JobID,... --parsable2` by job: the lines of the job and
+/// of its steps (ex. `123.batch`) are grouped under the job id, without the JobID field
+pub fn split_sacct_output_by_job(output: &str) -> HashMap<String, String> {
+  let mut outputs: HashMap<String, String> = HashMap::new();
+  for line in output.lines() {
+    let Some((job_id, fields)) = line.trim().split_once('|') else {
+      continue;
+    };
+    let job_id = job_id.split('.').next().unwrap_or(job_id);
+    let job_output = outputs.entry(job_id.to_string()).or_default();
+    job_output.push_str(fields);
+    job_output.push('\n');
+  }
+  outputs
+}
+
+/// Parse the output of `sacct --format=MaxRSS,TotalCPU,Elapsed --noheader --parsable2`.
+/// sacct prints one line for the job and one for each step: MaxRSS is only reported by the steps,
+/// so the maximum is taken, while CPU time and elapsed time come from the first (job) line.
+pub fn parse_sacct_output(output: &str) -> JobLog {
+  let mut max_rss_kb: Option<u64> = None;
+  let mut cpu_seconds = None;
+  let mut elapsed_seconds = None;
+  for line in output.lines().filter(|line| !line.trim().is_empty()) {
+    let mut fields = line.trim().split('|');
+    if let Some(rss) = fields.next().and_then(parse_memory_kb) {
+      max_rss_kb = Some(max_rss_kb.map_or(rss, |max| max.max(rss)));
+    }
+    cpu_seconds = cpu_seconds.or_else(|| fields.next().and_then(parse_duration_seconds));
+    elapsed_seconds = elapsed_seconds.or_else(|| fields.next().and_then(parse_duration_seconds));
+  }
+  JobLog::ResourceUsage {
+    max_rss_kb,
+    cpu_seconds,
+    elapsed_seconds,
+  }
+}
+
+/// Parse a sacct memory value (ex. `1024K`, `1.5G`) into KB. Values without unit are in bytes.
+fn parse_memory_kb(value: &str) -> Option<u64> {
+  let value = value.trim();
+  let (number, multiplier) = match value.chars().last()? {
+    'K' => (&value[..value.len() - 1], 1.0),
+    'M' => (&value[..value.len() - 1], 1024.0),
+    'G' => (&value[..value.len() - 1], 1024.0 * 1024.0),
+    'T' => (&value[..value.len() - 1], 1024.0 * 1024.0 * 1024.0),
+    _ => (value, 1.0 / 1024.0),
+  };
+  let number: f64 = number.parse().ok()?;
+  Some((number * multiplier).round() as u64)
+}
+
+/// Parse a sacct duration (`[D-][HH:]MM:SS[.mmm]`) into seconds, dropping the milliseconds
+fn parse_duration_seconds(value: &str) -> Option<u64> {
+  let value = value.trim();
+  let (days, time) = match value.split_once('-') {
+    Some((days, time)) => (days.parse::<u64>().ok()?, time),
+    None => (0, value),
+  };
+  let time = time.split('.').next()?;
+  let mut seconds = 0;
+  for part in time.split(':') {
+    seconds = seconds * 60 + part.parse::<u64>().ok()?;
+  }
+  Some(days * 86_400 + seconds)
 }
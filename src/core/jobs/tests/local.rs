@@ -1,6 +1,6 @@
-use std::fs;
+use std::{fs, io::Write};
 
-use chrono::{Datelike, Timelike};
+use chrono::{Datelike, Duration, Local, NaiveDate, TimeZone, Timelike, Utc};
 use serde::Deserialize;
 use serde_json::json;
 use tempfile::TempDir;
@@ -9,11 +9,16 @@ use crate::core::{
   cluster_configs::ClusterConfig,
   database::models::Status,
   jobs::{
-    JobLog, SchedulerTrait,
+    JobError, JobLog, LogTail, SchedulerTrait,
     local::LocalScheduler,
     tests::{create_test_cluster, create_test_config, create_test_config_timeout, create_test_job},
-    utils::parse_timestamp,
+    utils::{
+      CappedWriter, TRUNCATION_MARKER, format_seconds_as_time, format_unix_time,
+      get_timestamp_string, parse_duration_to_seconds, parse_time_to_seconds, parse_timestamp,
+      shell_join,
+    },
   },
+  sbatchman_configs::DisplayTimezone,
 };
 
 fn as_u64_coerce(v: &serde_json::Value) -> Option<u64> {
@@ -41,6 +46,35 @@ fn test_job_launch() {
   );
 }
 
+#[test]
+fn test_job_launch_custom_shell() {
+  let temp_dir = TempDir::new().unwrap();
+  let mut job = create_test_job(1, temp_dir.path().join("job").to_str().unwrap());
+  let mut config = create_test_config(1);
+  config.shell = Some("bash".to_string());
+  let cluster = create_test_cluster(1);
+  let cluster_config = ClusterConfig::new(&cluster, &config);
+
+  let scheduler = LocalScheduler::default();
+  let script = scheduler.create_job_script(&job, &cluster_config).unwrap();
+  assert!(script.starts_with("#!/usr/bin/env bash\n"));
+
+  scheduler.launch_job(&mut job, &cluster_config).unwrap();
+  // The script is run as written, through its own shebang
+  let without_timestamp = |script: &str| {
+    script
+      .lines()
+      .filter(|line| !line.starts_with("# GeneratedAt"))
+      .collect::<Vec<_>>()
+      .join("\n")
+  };
+  assert_eq!(
+    without_timestamp(&job.get_script().unwrap()),
+    without_timestamp(&script)
+  );
+  assert!(job.get_stdout().unwrap().contains("Hello World"));
+}
+
 #[test]
 fn test_job_launch_timeout() {
   let path = "./test_job_timeout";
@@ -54,14 +88,11 @@ fn test_job_launch_timeout() {
   let res = local_scheduler.launch_job(&mut job, &ClusterConfig::new(&cluster, &config));
 
   assert!(res.is_ok());
-  let logs = job.read_log_entries().expect("Could not read logs");
+  let logs = job.parse_log_typed().expect("Could not read logs");
   assert!(
-    logs.iter().any(
-      |s| serde_json::from_value::<JobLog>(s.clone()).is_ok_and(|log| match log {
-        JobLog::StatusUpdate(Status::Timeout) => true,
-        _ => false,
-      })
-    )
+    logs
+      .iter()
+      .any(|(_, log)| *log == JobLog::StatusUpdate(Status::Timeout))
   );
 }
 
@@ -125,6 +156,86 @@ fn test_launch_job_creates_logs() {
   assert_eq!(events.iter().filter(|v| **v == "StatusUpdate").count(), 3);
 }
 
+#[test]
+fn test_launch_logs_environment_snapshot() {
+  let temp_dir = TempDir::new().unwrap();
+  let job_dir = temp_dir.path().join("job_environment");
+  let mut job = create_test_job(1, job_dir.to_str().unwrap());
+  let mut config = create_test_config(1);
+  config.env_snapshot = Some(json!(["PATH", "SBM_UNSET_PREFIX_"]));
+  config.flags = json!({"partition": "gpu"});
+  config.env = json!({"OMP_NUM_THREADS": "4"});
+  let cluster = create_test_cluster(1);
+
+  let scheduler = LocalScheduler {
+    launch_base_path: temp_dir.path().to_path_buf(),
+  };
+  scheduler
+    .launch_job(&mut job, &ClusterConfig::new(&cluster, &config))
+    .unwrap();
+
+  // The snapshot comes right after the metadata
+  let logs = job.parse_log_typed().unwrap();
+  assert!(matches!(logs[0].1, JobLog::Metadata(_)));
+  let JobLog::Environment(variables) = &logs[1].1 else {
+    panic!("Expected an Environment entry, got {:?}", logs[1].1);
+  };
+  assert_eq!(variables.get("PATH"), std::env::var("PATH").ok().as_ref());
+  assert!(variables.keys().all(|name| name.starts_with("PATH")));
+
+  // Along with the resolved config
+  let log = job.get_log().unwrap();
+  let entry: serde_json::Value = serde_json::from_str(log.lines().nth(1).unwrap()).unwrap();
+  assert_eq!(
+    entry["additional"]["config"],
+    json!({"flags": {"partition": "gpu"}, "env": {"OMP_NUM_THREADS": "4"}})
+  );
+}
+
+#[test]
+fn test_parse_log_typed() {
+  let temp_dir = TempDir::new().unwrap();
+  let job_dir = temp_dir.path().join("job_typed_logs");
+  let mut job = create_test_job(1, job_dir.to_str().unwrap());
+  let config = create_test_config(1);
+  let cluster = create_test_cluster(1);
+
+  let scheduler = LocalScheduler {
+    launch_base_path: temp_dir.path().to_path_buf(),
+  };
+  scheduler
+    .launch_job(&mut job, &ClusterConfig::new(&cluster, &config))
+    .unwrap();
+
+  let logs = job.parse_log_typed().unwrap();
+  // The PID is logged by SbatchMan while the script runs, so its position may vary
+  let is_pid = |log: &JobLog| matches!(log, JobLog::Variable(name, _) if name == "PID");
+  assert_eq!(logs.iter().filter(|(_, log)| is_pid(log)).count(), 1);
+
+  let mut variants = logs.iter().map(|(_, log)| log).filter(|log| !is_pid(log));
+  assert!(matches!(variants.next(), Some(JobLog::Metadata(metadata)) if metadata.id == 1));
+  assert_eq!(
+    variants.next(),
+    Some(&JobLog::StatusUpdate(Status::Created))
+  );
+  assert_eq!(
+    variants.next(),
+    Some(&JobLog::StatusUpdate(Status::Running))
+  );
+  assert_eq!(
+    variants.next(),
+    Some(&JobLog::StatusUpdate(Status::Completed))
+  );
+  assert_eq!(
+    variants.next(),
+    Some(&JobLog::Variable(
+      "SBM_EXIT_CODE".to_string(),
+      "0".to_string()
+    ))
+  );
+  assert_eq!(variants.next(), None);
+}
+
 #[test]
 fn test_launch_job_creates_stdout_stderr() {
   let temp_dir = TempDir::new().unwrap();
@@ -191,6 +302,32 @@ fn test_launch_job_failing_command() {
   );
 }
 
+#[test]
+fn test_launch_job_multi_line_command_exit_code() {
+  let temp_dir = TempDir::new().unwrap();
+  let job_dir = temp_dir.path().join("job_multi_line");
+  let mut job = create_test_job(1, job_dir.to_str().unwrap());
+  job.command = "true\n$(exit 5)".to_string();
+  let mut config = create_test_config(1);
+  config.flags = json!({"time": "00:00:30"});
+  let cluster = create_test_cluster(1);
+
+  let scheduler = LocalScheduler {
+    launch_base_path: temp_dir.path().to_path_buf(),
+  };
+  scheduler
+    .launch_job(&mut job, &ClusterConfig::new(&cluster, &config))
+    .unwrap();
+
+  let entries = job.read_log_entries().unwrap();
+  let exit_code = entries
+    .iter()
+    .find(|e| e["type"] == "BashVariable" && e["data"].get("SBM_EXIT_CODE").is_some())
+    .expect("Could not find exit code log");
+  assert_eq!(as_u64_coerce(&exit_code["data"]["SBM_EXIT_CODE"]), Some(5));
+  assert_eq!(job.get_logged_status(), Some(Status::Failed));
+}
+
 #[test]
 fn test_launch_job_with_timeout() {
   let temp_dir = TempDir::new().unwrap();
@@ -210,15 +347,35 @@ fn test_launch_job_with_timeout() {
 
   let entries = job.read_log_entries().unwrap();
 
-  let timeout_entry = entries
+  let timeout_entries = entries
     .iter()
-    .find(|e| e["type"] == "StatusUpdate" && e["data"] == "Timeout");
-  assert!(timeout_entry.is_some());
+    .filter(|e| e["type"] == "StatusUpdate" && e["data"] == "Timeout")
+    .count();
+  assert_eq!(timeout_entries, 1);
+  assert_eq!(job.status, Status::Timeout);
+}
+
+#[test]
+fn test_submit_script_exiting_124_logs_timeout() {
+  let temp_dir = TempDir::new().unwrap();
+  let job_dir = temp_dir.path().join("job_exit_124");
+  let mut job = create_test_job(1, job_dir.to_str().unwrap());
+  job.prepare_job_directory().unwrap();
+  // A script without the status updates of the generated ones
+  job.write_script("#!/bin/bash\nexit 124\n").unwrap();
+
+  let scheduler = LocalScheduler {
+    launch_base_path: temp_dir.path().to_path_buf(),
+  };
+  scheduler.submit_job_script(&mut job).unwrap();
+
+  assert_eq!(job.status, Status::Timeout);
+  assert_eq!(job.get_logged_status(), Some(Status::Timeout));
 }
 
 #[test]
 fn test_parse_valid_timestamp() {
-  let ts_str = "2025-10-28 09:40:12.366";
+  let ts_str = "2025-10-28T09:40:12.366Z";
   let dt = parse_timestamp(ts_str).expect("Failed to parse timestamp");
 
   assert_eq!(dt.year(), 2025);
@@ -227,7 +384,95 @@ fn test_parse_valid_timestamp() {
   assert_eq!(dt.hour(), 9);
   assert_eq!(dt.minute(), 40);
   assert_eq!(dt.second(), 12);
-  assert_eq!(dt.and_utc().timestamp_subsec_millis(), 366);
+  assert_eq!(dt.timestamp_subsec_millis(), 366);
+}
+
+#[test]
+fn test_parse_timestamp_with_offset() {
+  let dt = parse_timestamp("2025-10-28T11:40:12.366+02:00").unwrap();
+  assert_eq!(dt, parse_timestamp("2025-10-28T09:40:12.366Z").unwrap());
+}
+
+#[test]
+fn test_parse_legacy_local_timestamp() {
+  let dt = parse_timestamp("2025-10-28 09:40:12.366").expect("Failed to parse timestamp");
+
+  let naive = NaiveDate::from_ymd_opt(2025, 10, 28)
+    .unwrap()
+    .and_hms_milli_opt(9, 40, 12, 366)
+    .unwrap();
+  let expected = Local.from_local_datetime(&naive).earliest().unwrap();
+  assert_eq!(dt, expected.with_timezone(&Utc));
+}
+
+#[test]
+fn test_timestamps_are_utc_and_monotonic() {
+  let first = get_timestamp_string();
+  let second = get_timestamp_string();
+  assert!(first.ends_with('Z'));
+
+  // Mixed formats compare on the same time scale
+  let legacy = Local::now() - Duration::hours(1);
+  let legacy = legacy
+    .naive_local()
+    .format("%Y-%m-%d %H:%M:%S%.3f")
+    .to_string();
+  let parsed: Vec<_> = [legacy, first, second]
+    .iter()
+    .map(|ts| parse_timestamp(ts).unwrap())
+    .collect();
+  assert!(parsed.windows(2).all(|pair| pair[0] <= pair[1]));
+}
+
+#[test]
+fn test_format_seconds_as_time() {
+  assert_eq!(format_seconds_as_time(90), "00:01:30");
+  assert_eq!(format_seconds_as_time(5400), "01:30:00");
+  assert_eq!(format_seconds_as_time(90_000), "1-01:00:00");
+  assert_eq!(parse_time_to_seconds("1-01:00:00").unwrap(), 90_000);
+}
+
+#[test]
+fn test_parse_duration_to_seconds() {
+  assert_eq!(parse_duration_to_seconds("90s").unwrap(), 90);
+  assert_eq!(parse_duration_to_seconds("12h").unwrap(), 43_200);
+  assert_eq!(parse_duration_to_seconds("7d").unwrap(), 604_800);
+  assert_eq!(parse_duration_to_seconds("01:30:00").unwrap(), 5400);
+  assert!(matches!(
+    parse_duration_to_seconds("99999999999999999d"),
+    Err(JobError::InvalidTimeFormat(_))
+  ));
+  assert!(parse_duration_to_seconds("12x").is_err());
+}
+
+#[test]
+fn test_shell_join() {
+  let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+  assert_eq!(
+    shell_join(&words(&["./train.sh", "--lr=0.1"])),
+    "./train.sh --lr=0.1"
+  );
+  assert_eq!(
+    shell_join(&words(&["echo", "a b", "it's", "$HOME", ""])),
+    "echo 'a b' 'it'\\''s' '$HOME' ''"
+  );
+}
+
+#[test]
+fn test_format_unix_time() {
+  assert_eq!(
+    format_unix_time(1_700_000_000, DisplayTimezone::Utc),
+    "2023-11-14 22:13:20 UTC"
+  );
+  assert_eq!(
+    format_unix_time(0, DisplayTimezone::Utc),
+    "1970-01-01 00:00:00 UTC"
+  );
+  let local = Local.timestamp_opt(1_700_000_000, 0).unwrap();
+  assert_eq!(
+    format_unix_time(1_700_000_000, DisplayTimezone::Local),
+    local.format("%Y-%m-%d %H:%M:%S").to_string()
+  );
 }
 
 #[test]
@@ -298,3 +543,98 @@ fn test_launch_job_preprocessor_postprocessor() {
   assert!(temp_dir.path().join("main.txt").exists());
   assert!(temp_dir.path().join("post.txt").exists());
 }
+
+// ============================================================================
+// Tests for the output size cap
+// ============================================================================
+
+#[test]
+fn test_capped_writer_keeps_head_and_tail() {
+  let mut writer = CappedWriter::new(Vec::new(), 10);
+  for chunk in [&b"0123"[..], b"456789", b"abcdefghij"] {
+    writer.write_all(chunk).unwrap();
+  }
+  let written = writer.finish().unwrap();
+  assert_eq!(
+    String::from_utf8(written).unwrap(),
+    format!("01234{}fghij", TRUNCATION_MARKER)
+  );
+
+  let mut writer = CappedWriter::new(Vec::new(), 10);
+  writer.write_all(b"0123456789").unwrap();
+  assert_eq!(writer.finish().unwrap(), b"0123456789");
+}
+
+#[test]
+fn test_launch_job_caps_output() {
+  let temp_dir = TempDir::new().unwrap();
+  let job_dir = temp_dir.path().join("job_capped_output");
+  let mut job = create_test_job(1, job_dir.to_str().unwrap());
+  job.command = String::from("seq 1 100000; seq 1 100000 >&2; echo last");
+  let mut config = create_test_config(1);
+  config.max_output_bytes = Some(1000);
+  let cluster = create_test_cluster(1);
+
+  let scheduler = LocalScheduler {
+    launch_base_path: temp_dir.path().to_path_buf(),
+  };
+  scheduler
+    .launch_job(&mut job, &ClusterConfig::new(&cluster, &config))
+    .unwrap();
+
+  let max_len = 1000 + TRUNCATION_MARKER.len() as u64;
+  let stdout_len = fs::metadata(job.get_stdout_path()).unwrap().len();
+  let stderr_len = fs::metadata(job.get_stderr_path()).unwrap().len();
+  assert!(stdout_len <= max_len, "stdout.log has {} bytes", stdout_len);
+  assert!(stderr_len <= max_len, "stderr.log has {} bytes", stderr_len);
+
+  let stdout = fs::read_to_string(job.get_stdout_path()).unwrap();
+  assert!(stdout.starts_with("1\n2\n3\n"));
+  assert!(stdout.contains(TRUNCATION_MARKER));
+  assert!(stdout.ends_with("100000\nlast\n"));
+}
+
+#[test]
+fn test_log_tail_follows_appends_and_truncation() {
+  let temp_dir = TempDir::new().unwrap();
+  let job = create_test_job(1, temp_dir.path().to_str().unwrap());
+  let mut tail = LogTail::new(job.get_log_path());
+  assert!(tail.read_new_entries().unwrap().is_empty());
+
+  job
+    .write_log_entry(JobLog::StatusUpdate(Status::Queued), None)
+    .unwrap();
+  let entries = tail.read_new_entries().unwrap();
+  assert_eq!(entries.len(), 1);
+  assert_eq!(entries[0].1, JobLog::StatusUpdate(Status::Queued));
+
+  // A line still being written is returned once complete
+  let line = r#"{"type":"StatusUpdate","data":"Running","timestamp":"2025-10-20T08:00:00.000Z"}"#;
+  let mut file = fs::OpenOptions::new()
+    .append(true)
+    .open(job.get_log_path())
+    .unwrap();
+  write!(file, "{}", &line[..20]).unwrap();
+  assert!(tail.read_new_entries().unwrap().is_empty());
+  writeln!(file, "{}", &line[20..]).unwrap();
+  let entries = tail.read_new_entries().unwrap();
+  assert_eq!(entries[0].1, JobLog::StatusUpdate(Status::Running));
+
+  // A truncated log is read again from the start
+  fs::write(job.get_log_path(), "").unwrap();
+  job
+    .write_log_entry(JobLog::StatusUpdate(Status::Completed), None)
+    .unwrap();
+  let entries = tail.read_new_entries().unwrap();
+  assert_eq!(entries.len(), 1);
+  assert_eq!(entries[0].1, JobLog::StatusUpdate(Status::Completed));
+
+  // An invalid line is skipped, the following entries are still read
+  writeln!(file, "not json").unwrap();
+  job
+    .write_log_entry(JobLog::StatusUpdate(Status::Failed), None)
+    .unwrap();
+  let entries = tail.read_new_entries().unwrap();
+  assert_eq!(entries.len(), 1);
+  assert_eq!(entries[0].1, JobLog::StatusUpdate(Status::Failed));
+}
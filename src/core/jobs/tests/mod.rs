@@ -1,14 +1,36 @@
-use crate::core::cluster_configs::ClusterConfig;
-use crate::core::database::models::{Cluster, Config, Job, Scheduler, Status};
+use crate::core::cluster_configs::{ClusterConfig, JOB_ID_PLACEHOLDER};
+use crate::core::database::Database;
+use crate::core::database::models::{
+  Cluster, Config, Job, NewCluster, NewConfig, NewJob, Scheduler, Status,
+};
+use crate::core::jobs::custom::CustomScheduler;
 use crate::core::jobs::local::LocalScheduler;
+use crate::core::jobs::pbs::{self, PbsScheduler};
+use crate::core::jobs::slurm::{
+  self, SlurmScheduler, parse_sacct_output, sacct_job_ids, split_sacct_output_by_job,
+};
 use crate::core::jobs::utils::parse_time_to_seconds;
-use crate::core::jobs::{JobError, SchedulerTrait};
-
+use crate::core::jobs::{
+  CommandRunner, JobError, JobFilter, JobLog, LaunchOverrides, LaunchSummary, ResumeSummary,
+  SchedulerTrait, SyncSummary, VariableInfo, active_jobs_by_cluster, cancel_job, cancel_launch,
+  check_jobs, count_jobs_from_file, delete_jobs, describe_variables, generate_jobs_from_file,
+  launch_jobs, launch_jobs_from_file, purge_jobs, record_resource_usage, regenerate_scripts,
+  rerun_job, resume_jobs_from_file, set_status_for_ids, sync_job_statuses,
+  sync_job_statuses_parallel,
+};
+use crate::core::parsers::ParserError;
+use crate::core::sbatchman_configs::tests::init_sbatchman_for_tests;
+
+use indicatif::ProgressBar;
 use log::debug;
 use serde::Deserialize;
 use serde_json::json;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tempfile::TempDir;
 
 mod local;
@@ -23,12 +45,9 @@ fn create_test_job(id: i32, directory: &str) -> Job {
     directory: directory.to_string(),
     command: "echo 'Hello World'".to_string(),
     status: Status::Queued,
-    job_id: None,
-    end_time: None,
-    preprocess: None,
-    postprocess: None,
-    archived: None,
     variables: json!({}),
+    labels: json!({}),
+    ..Default::default()
   }
 }
 
@@ -39,6 +58,7 @@ fn create_test_config(id: i32) -> Config {
     cluster_id: 1,
     flags: json!({}),
     env: json!({}),
+    ..Default::default()
   }
 }
 
@@ -49,6 +69,7 @@ fn create_test_config_timeout(id: i32, timeout_s: u32) -> Config {
     cluster_id: 1,
     flags: json!({"time": format!("00:00:{:02}", timeout_s)}),
     env: json!({}),
+    ..Default::default()
   }
 }
 
@@ -58,6 +79,7 @@ fn create_test_cluster(id: i32) -> Cluster {
     cluster_name: "test_cluster".to_string(),
     scheduler: Scheduler::Local,
     max_jobs: Some(10),
+    ..Default::default()
   }
 }
 
@@ -220,7 +242,7 @@ fn test_add_job_commands_only_main() {
   let mut script = String::new();
   let job = create_test_job(1, temp_dir.path().to_str().unwrap());
 
-  job.add_job_commands(&mut script, None);
+  job.add_job_commands(&mut script, &create_test_config(1), None);
 
   assert!(script.contains("# Main command"));
   assert!(script.contains("echo 'Hello World'"));
@@ -228,6 +250,25 @@ fn test_add_job_commands_only_main() {
   assert!(!script.contains("# Postprocessing"));
 }
 
+#[test]
+fn test_add_job_commands_multi_line_command() {
+  let temp_dir = TempDir::new().unwrap();
+  let mut job = create_test_job(1, temp_dir.path().to_str().unwrap());
+  job.command = "cd build\nmake\nmake test".to_string();
+
+  let mut script = String::new();
+  job.add_job_commands(&mut script, &create_test_config(1), None);
+  // Each command on its own line, the exit code is the one of the last command
+  assert!(script.contains("# Main command\ncd build\nmake\nmake test\n\nSBM_EXIT_CODE=$?\n"));
+
+  // With a time limit, the whole block is run by `timeout`
+  let mut script = String::new();
+  job.add_job_commands(&mut script, &create_test_config(1), Some(60));
+  assert!(script.contains(
+    "# Main command\ntimeout 60 bash -c 'cd build\nmake\nmake test'\n\nSBM_EXIT_CODE=$?\n"
+  ));
+}
+
 #[test]
 fn test_add_job_commands_with_preprocessing() {
   let temp_dir = TempDir::new().unwrap();
@@ -235,7 +276,7 @@ fn test_add_job_commands_with_preprocessing() {
   let mut job = create_test_job(1, temp_dir.path().to_str().unwrap());
   job.preprocess = Some("echo 'Starting preprocessing'".to_string());
 
-  job.add_job_commands(&mut script, None);
+  job.add_job_commands(&mut script, &create_test_config(1), None);
 
   assert!(script.contains("# Preprocessing"));
   assert!(script.contains("echo 'Starting preprocessing'"));
@@ -249,7 +290,7 @@ fn test_add_job_commands_with_postprocessing() {
   let mut job = create_test_job(1, temp_dir.path().to_str().unwrap());
   job.postprocess = Some("echo 'Cleanup complete'".to_string());
 
-  job.add_job_commands(&mut script, None);
+  job.add_job_commands(&mut script, &create_test_config(1), None);
 
   assert!(script.contains("# Main command"));
   assert!(script.contains("# Postprocessing"));
@@ -264,7 +305,7 @@ fn test_add_job_commands_full_pipeline() {
   job.preprocess = Some("echo 'Pre'".to_string());
   job.postprocess = Some("echo 'Post'".to_string());
 
-  job.add_job_commands(&mut script, None);
+  job.add_job_commands(&mut script, &create_test_config(1), None);
 
   // Check order
   let pre_pos = script.find("echo 'Pre'").unwrap();
@@ -283,13 +324,53 @@ fn test_add_job_commands_empty_strings_ignored() {
   job.preprocess = Some("".to_string());
   job.postprocess = Some("".to_string());
 
-  job.add_job_commands(&mut script, None);
+  job.add_job_commands(&mut script, &create_test_config(1), None);
 
   assert!(!script.contains("# Preprocessing"));
   assert!(!script.contains("# Postprocessing"));
   assert!(script.contains("# Main command"));
 }
 
+#[test]
+fn test_add_job_commands_default_processing_order() {
+  let temp_dir = TempDir::new().unwrap();
+  let mut script = String::new();
+  let mut job = create_test_job(1, temp_dir.path().to_str().unwrap());
+  job.preprocess = Some("echo 'Job pre'".to_string());
+  job.postprocess = Some("echo 'Job post'".to_string());
+  let mut config = create_test_config(1);
+  config.default_preprocess = Some("source ~/.bashrc".to_string());
+  config.default_postprocess = Some("echo 'Default post'".to_string());
+
+  job.add_job_commands(&mut script, &config, None);
+
+  let default_pre_pos = script.find("source ~/.bashrc").unwrap();
+  let pre_pos = script.find("echo 'Job pre'").unwrap();
+  let main_pos = script.find("echo 'Hello World'").unwrap();
+  let default_post_pos = script.find("echo 'Default post'").unwrap();
+  let post_pos = script.find("echo 'Job post'").unwrap();
+
+  assert!(default_pre_pos < pre_pos);
+  assert!(pre_pos < main_pos);
+  assert!(main_pos < default_post_pos);
+  assert!(default_post_pos < post_pos);
+  assert_eq!(script.matches("# Preprocessing").count(), 1);
+}
+
+#[test]
+fn test_add_job_commands_only_default_processing() {
+  let temp_dir = TempDir::new().unwrap();
+  let mut script = String::new();
+  let job = create_test_job(1, temp_dir.path().to_str().unwrap());
+  let mut config = create_test_config(1);
+  config.default_preprocess = Some("source ~/.bashrc".to_string());
+
+  job.add_job_commands(&mut script, &config, None);
+
+  assert!(script.contains("# Preprocessing\nsource ~/.bashrc\n"));
+  assert!(!script.contains("# Postprocessing"));
+}
+
 // ============================================================================
 // Tests for write_log_entry
 // ============================================================================
@@ -392,6 +473,57 @@ fn test_add_job_commands_empty_strings_ignored() {
 //   assert_eq!(entry["job"]["variables"]["key"], "value");
 // }
 
+// ============================================================================
+// Tests for parse_sacct_output
+// ============================================================================
+
+#[test]
+fn test_parse_sacct_output() {
+  // Job line, batch step and extern step
+  let output = "|00:01:30.500|00:02:00\n2048K|00:01:29.100|00:02:00\n1.5M|00:00:00.001|00:02:01\n";
+
+  assert_eq!(
+    parse_sacct_output(output),
+    JobLog::ResourceUsage {
+      max_rss_kb: Some(2048),
+      cpu_seconds: Some(90),
+      elapsed_seconds: Some(120),
+    }
+  );
+}
+
+#[test]
+fn test_parse_sacct_output_days_and_missing_values() {
+  let output = "|1-02:00:00|\n";
+
+  assert_eq!(
+    parse_sacct_output(output),
+    JobLog::ResourceUsage {
+      max_rss_kb: None,
+      cpu_seconds: Some(93_600),
+      elapsed_seconds: None,
+    }
+  );
+}
+
+#[test]
+fn test_resource_usage_is_read_back_from_log() {
+  let temp_dir = TempDir::new().unwrap();
+  let job = create_test_job(1, temp_dir.path().to_str().unwrap());
+  let usage = parse_sacct_output("4G|10:00|20:00\n");
+
+  assert_eq!(job.get_resource_usage(), None);
+  job.write_log_entry(usage, None).unwrap();
+  assert_eq!(
+    job.get_resource_usage(),
+    Some(JobLog::ResourceUsage {
+      max_rss_kb: Some(4 * 1024 * 1024),
+      cpu_seconds: Some(600),
+      elapsed_seconds: Some(1200),
+    })
+  );
+}
+
 // ============================================================================
 // Tests for LocalScheduler::create_job_script
 // ============================================================================
@@ -419,6 +551,44 @@ fn test_create_job_script_basic() {
   assert!(script.contains("SBM_EXIT_CODE=$?"));
 }
 
+#[test]
+fn test_create_job_script_metadata() {
+  let temp_dir = TempDir::new().unwrap();
+  let job_dir = temp_dir.path().join("job_meta");
+  let job = create_test_job(7, job_dir.to_str().unwrap());
+  let config = create_test_config(1);
+  let cluster = create_test_cluster(1);
+
+  let scheduler = LocalScheduler {
+    launch_base_path: temp_dir.path().to_path_buf(),
+  };
+  let script = scheduler
+    .create_job_script(&job, &ClusterConfig::new(&cluster, &config))
+    .unwrap();
+
+  assert!(script.contains("# JobId: 7\n"));
+  assert!(!script.contains(JOB_ID_PLACEHOLDER));
+  assert!(script.contains(&format!("# Cluster: {:?}\n", cluster.cluster_name)));
+  assert!(script.contains(&format!("# ConfigName: {:?}\n", config.config_name)));
+  assert!(script.contains("# Scheduler: Local\n"));
+  assert!(script.contains("# GeneratedAt: "));
+}
+
+#[test]
+fn test_generate_script_header_keeps_job_id_placeholder() {
+  let config = create_test_config(1);
+  let cluster = create_test_cluster(1);
+
+  let header = ClusterConfig::new(&cluster, &config).generate_script_header(Path::new("/work_dir"));
+
+  assert!(header.starts_with("#!/bin/bash\n"));
+  assert!(header.contains(&format!("# JobId: {}\n", JOB_ID_PLACEHOLDER)));
+  // Metadata comes before the working directory change
+  let metadata = header.find("# --- Metadata ---").unwrap();
+  let cd = header.find("cd \"/work_dir\"").unwrap();
+  assert!(metadata < cd);
+}
+
 #[test]
 fn test_create_job_script_with_environment() {
   let temp_dir = TempDir::new().unwrap();
@@ -438,6 +608,231 @@ fn test_create_job_script_with_environment() {
   assert!(script.contains("export TEST_VAR=\"test_value\""));
 }
 
+#[test]
+fn test_create_job_script_timeout_only_for_local() {
+  let temp_dir = TempDir::new().unwrap();
+  let job = create_test_job(3, temp_dir.path().join("job3").to_str().unwrap());
+  let mut config = create_test_config(1);
+  config.flags = json!({"time": "00:01:30"});
+  let mut cluster = create_test_cluster(1);
+
+  let scheduler = LocalScheduler {
+    launch_base_path: temp_dir.path().to_path_buf(),
+  };
+  let script = scheduler
+    .create_job_script(&job, &ClusterConfig::new(&cluster, &config))
+    .unwrap();
+  assert!(script.contains("timeout 90 echo 'Hello World'"));
+
+  // The other schedulers rely on the native time limit set by the directives
+  for scheduler in [Scheduler::Slurm, Scheduler::Pbs, Scheduler::Custom] {
+    cluster.scheduler = scheduler;
+    let cluster_config = ClusterConfig::new(&cluster, &config);
+    assert_eq!(cluster_config.script_time_limit(), None);
+  }
+  cluster.submit_command = Some("bsub < {script}".to_string());
+  let script = CustomScheduler::new(&cluster)
+    .create_job_script(&job, &ClusterConfig::new(&cluster, &config))
+    .unwrap();
+  assert!(script.contains("\necho 'Hello World'"));
+  assert!(!script.contains("timeout "));
+}
+
+#[test]
+fn test_create_job_script_output_directives() {
+  let temp_dir = TempDir::new().unwrap();
+  let job_dir = temp_dir.path().join("job4");
+  let job = create_test_job(4, job_dir.to_str().unwrap());
+  let config = create_test_config(1);
+  let mut cluster = create_test_cluster(1);
+
+  let cases: [(Scheduler, Box<dyn SchedulerTrait>, [String; 3]); 2] = [
+    (
+      Scheduler::Slurm,
+      Box::new(SlurmScheduler::default()),
+      [
+        "#SBATCH --job-name=test_job_4".to_string(),
+        format!("#SBATCH --output={}/stdout.log", job_dir.display()),
+        format!("#SBATCH --error={}/stderr.log", job_dir.display()),
+      ],
+    ),
+    (
+      Scheduler::Pbs,
+      Box::new(PbsScheduler::default()),
+      [
+        "#PBS -N test_job_4".to_string(),
+        format!("#PBS -o {}/stdout.log", job_dir.display()),
+        format!("#PBS -e {}/stderr.log", job_dir.display()),
+      ],
+    ),
+  ];
+  for (scheduler_type, scheduler, directives) in cases {
+    cluster.scheduler = scheduler_type;
+    let script = scheduler
+      .create_job_script(&job, &ClusterConfig::new(&cluster, &config))
+      .unwrap();
+
+    // The scheduler only reads the directives before the first command
+    let cd = script.find("cd \"").unwrap();
+    for directive in directives {
+      let position = script.find(&format!("{}\n", directive)).unwrap();
+      assert!(position < cd, "{} after the cd", directive);
+    }
+    assert!(script.contains("# JobId: 4\n"));
+    assert!(script.contains("\necho 'Hello World'"));
+  }
+}
+
+#[test]
+fn test_job_directives_quote_whitespace() {
+  let temp_dir = TempDir::new().unwrap();
+  let job_dir = temp_dir.path().join("my job");
+  let mut job = create_test_job(4, job_dir.to_str().unwrap());
+  job.job_name = "sweep run".to_string();
+
+  assert_eq!(
+    slurm::job_directives(&job).unwrap(),
+    vec![
+      "#SBATCH --job-name=\"sweep run\"".to_string(),
+      format!("#SBATCH --output=\"{}/stdout.log\"", job_dir.display()),
+      format!("#SBATCH --error=\"{}/stderr.log\"", job_dir.display()),
+    ]
+  );
+  assert_eq!(
+    pbs::job_directives(&job).unwrap()[0],
+    "#PBS -N \"sweep run\""
+  );
+
+  job.job_name = "say \"hi\"".to_string();
+  assert!(matches!(
+    slurm::job_directives(&job),
+    Err(JobError::InvalidDirectiveValue(_))
+  ));
+  assert!(matches!(
+    pbs::job_directives(&job),
+    Err(JobError::InvalidDirectiveValue(_))
+  ));
+}
+
+/// Command runner recording the command lines instead of running them
+struct RecordingRunner {
+  commands: Arc<Mutex<Vec<Vec<String>>>>,
+  output: String,
+}
+
+impl CommandRunner for RecordingRunner {
+  fn run(&self, program: &str, args: &[String], _dir: &Path) -> Result<String, JobError> {
+    let mut command = vec![program.to_string()];
+    command.extend(args.iter().cloned());
+    self.commands.lock().unwrap().push(command);
+    Ok(self.output.clone())
+  }
+}
+
+/// Command runner whose commands all fail, like sacct when the accounting is disabled
+struct FailingRunner;
+
+impl CommandRunner for FailingRunner {
+  fn run(&self, program: &str, _args: &[String], _dir: &Path) -> Result<String, JobError> {
+    Err(JobError::ExecutionFailed(format!(
+      "{}: Slurm accounting storage is disabled",
+      program
+    )))
+  }
+}
+
+#[test]
+fn test_submit_args_passed_to_submit_command() {
+  let temp_dir = TempDir::new().unwrap();
+  let config = create_test_config(1);
+  let mut cluster = create_test_cluster(1);
+
+  let commands = Arc::new(Mutex::new(vec![]));
+  let runner = |output: &str| {
+    Box::new(RecordingRunner {
+      commands: commands.clone(),
+      output: output.to_string(),
+    })
+  };
+  let cases: [(Scheduler, Box<dyn SchedulerTrait>, &str); 2] = [
+    (
+      Scheduler::Slurm,
+      Box::new(SlurmScheduler {
+        submit_args: vec!["--hold".to_string(), "--exclusive".to_string()],
+        runner: runner("1234;cluster\n"),
+      }),
+      "1234",
+    ),
+    (
+      Scheduler::Pbs,
+      Box::new(PbsScheduler {
+        submit_args: vec!["-h".to_string()],
+        runner: runner("99.server\n"),
+      }),
+      "99.server",
+    ),
+  ];
+  for (i, (scheduler_type, scheduler, job_id)) in cases.into_iter().enumerate() {
+    cluster.scheduler = scheduler_type;
+    let job_dir = temp_dir.path().join(format!("job{}", i));
+    let mut job = create_test_job(i as i32, job_dir.to_str().unwrap());
+    scheduler
+      .launch_job(&mut job, &ClusterConfig::new(&cluster, &config))
+      .unwrap();
+    assert_eq!(job.job_id.as_deref(), Some(job_id));
+    assert_eq!(job.status, Status::Queued);
+  }
+
+  let script = |i: usize| {
+    temp_dir
+      .path()
+      .join(format!("job{}/job.sh", i))
+      .display()
+      .to_string()
+  };
+  // The extra arguments go to the submit command, not into the script
+  assert_eq!(
+    *commands.lock().unwrap(),
+    vec![
+      vec![
+        "sbatch".to_string(),
+        "--parsable".to_string(),
+        "--hold".to_string(),
+        "--exclusive".to_string(),
+        script(0),
+      ],
+      vec!["qsub".to_string(), "-h".to_string(), script(1)],
+    ]
+  );
+  assert!(!fs::read_to_string(script(0)).unwrap().contains("--hold"));
+}
+
+#[test]
+fn test_sbatch_test_only_submits_nothing() {
+  let temp_dir = TempDir::new().unwrap();
+  let config = create_test_config(1);
+  let mut cluster = create_test_cluster(1);
+  cluster.scheduler = Scheduler::Slurm;
+  let scheduler = SlurmScheduler {
+    submit_args: vec!["--test-only".to_string()],
+    // The start estimate of --test-only goes to stderr
+    runner: Box::new(RecordingRunner {
+      commands: Arc::new(Mutex::new(vec![])),
+      output: String::new(),
+    }),
+  };
+  let mut job = create_test_job(1, temp_dir.path().join("job").to_str().unwrap());
+  job.status = Status::Created;
+
+  scheduler
+    .launch_job(&mut job, &ClusterConfig::new(&cluster, &config))
+    .unwrap();
+
+  assert_eq!(job.job_id, None);
+  assert_eq!(job.status, Status::Created);
+  assert_eq!(job.get_logged_status(), Some(Status::Created));
+}
+
 // ============================================================================
 // Integration Tests
 // ============================================================================
@@ -472,3 +867,1459 @@ fn test_log_allows_database_reconstruction() {
 }
 
 // TODO add more
+
+// ============================================================================
+// Tests for job generation from files
+// ============================================================================
+
+fn get_test_path(p: &str) -> PathBuf {
+  PathBuf::from("src/core/jobs/tests/files").join(p)
+}
+
+#[test]
+fn test_generate_jobs_from_file_cartesian_product() {
+  let cluster = create_test_cluster(1);
+  let configs = HashMap::from([("test_config".to_string(), create_test_config(1))]);
+
+  let jobs = generate_jobs_from_file(
+    &get_test_path("sweep.yaml"),
+    &cluster,
+    &configs,
+    &HashMap::new(),
+  )
+  .unwrap();
+
+  // 3 sizes x 2 modes for the first entry, 2 modes for the second one
+  assert_eq!(jobs.len(), 8);
+  let full_sweep: Vec<&str> = jobs
+    .iter()
+    .filter(|(job, _)| job.job_name == "full_sweep")
+    .map(|(job, _)| job.command.as_str())
+    .collect();
+  assert_eq!(full_sweep.len(), 6);
+  assert!(full_sweep.contains(&"echo sweep 3 slow"));
+  assert!(
+    jobs
+      .iter()
+      .filter(|(job, _)| job.job_name == "fixed_size")
+      .all(|(job, _)| job.command.starts_with("echo sweep 8 "))
+  );
+}
+
+#[test]
+fn test_generate_jobs_from_file_labels() {
+  let cluster = create_test_cluster(1);
+  let configs = HashMap::from([("test_config".to_string(), create_test_config(1))]);
+
+  let jobs = generate_jobs_from_file(
+    &get_test_path("sweep.yaml"),
+    &cluster,
+    &configs,
+    &HashMap::new(),
+  )
+  .unwrap();
+
+  for (job, _) in &jobs {
+    if job.job_name == "full_sweep" {
+      assert_eq!(
+        job.labels,
+        json!({"experiment": "sweep", "model": "resnet"})
+      );
+    } else {
+      assert_eq!(
+        job.labels,
+        json!({"experiment": "sweep", "model": "vit", "run": "2"})
+      );
+    }
+  }
+}
+
+#[test]
+fn test_generate_jobs_from_file_var_overrides() {
+  let cluster = create_test_cluster(1);
+  let configs = HashMap::from([("test_config".to_string(), create_test_config(1))]);
+  // `label` is a scalar, `mode` a list
+  let var_overrides = HashMap::from([
+    ("label".to_string(), "quick".to_string()),
+    ("mode".to_string(), "medium".to_string()),
+  ]);
+
+  let jobs = generate_jobs_from_file(
+    &get_test_path("sweep.yaml"),
+    &cluster,
+    &configs,
+    &var_overrides,
+  )
+  .unwrap();
+
+  // The list of modes is replaced by the single value: 3 sizes for the first entry, 1 for the second
+  assert_eq!(jobs.len(), 4);
+  let commands: Vec<&str> = jobs.iter().map(|(job, _)| job.command.as_str()).collect();
+  assert_eq!(
+    commands,
+    vec![
+      "echo quick 1 medium",
+      "echo quick 2 medium",
+      "echo quick 3 medium",
+      "echo quick 8 medium"
+    ]
+  );
+}
+
+/// Create a database with a local `test_cluster` having a single `test_config`
+pub(crate) fn create_test_db(dir: &TempDir) -> Database {
+  let mut db = Database::new(dir.path()).unwrap();
+  let cluster = db
+    .create_cluster(&NewCluster {
+      cluster_name: "test_cluster".to_string(),
+      scheduler: Scheduler::Local,
+      ..Default::default()
+    })
+    .unwrap();
+  db.create_cluster_config(&NewConfig {
+    config_name: "test_config".to_string(),
+    cluster_id: cluster.id,
+    flags: json!({}),
+    env: json!({}),
+    ..Default::default()
+  })
+  .unwrap();
+  db
+}
+
+#[test]
+fn test_count_jobs_from_file() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+
+  let count = count_jobs_from_file(
+    &get_test_path("sweep.yaml"),
+    &mut db,
+    "test_cluster",
+    &HashMap::new(),
+  )
+  .unwrap();
+  assert_eq!(count, 8);
+  // Counting must not create any job
+  assert!(db.get_jobs(None).unwrap().is_empty());
+}
+
+#[test]
+fn test_generate_jobs_from_file_missing_config() {
+  let cluster = create_test_cluster(1);
+  let configs = HashMap::from([("other_config".to_string(), create_test_config(1))]);
+
+  let result = generate_jobs_from_file(
+    &get_test_path("sweep.yaml"),
+    &cluster,
+    &configs,
+    &HashMap::new(),
+  );
+  assert!(matches!(result, Err(JobError::ConfigNotFound(name)) if name == "test_config"));
+}
+
+#[test]
+fn test_generate_jobs_from_file_params_override() {
+  let mut cluster = create_test_cluster(1);
+  cluster.scheduler = Scheduler::Slurm;
+  let mut config = create_test_config(1);
+  config.flags = json!({"mem": "8G", "time": "01:00:00"});
+  let configs = HashMap::from([("test_config".to_string(), config)]);
+
+  let jobs = generate_jobs_from_file(
+    &get_test_path("params_override.yaml"),
+    &cluster,
+    &configs,
+    &HashMap::new(),
+  )
+  .unwrap();
+  assert_eq!(jobs.len(), 4);
+
+  let header_of = |name: &str| -> Vec<String> {
+    jobs
+      .iter()
+      .filter(|(job, _)| job.job_name == name)
+      .map(|(_, config)| {
+        let header = ClusterConfig::new(&cluster, config).generate_script_header(Path::new("/"));
+        // Drop the generation timestamp to compare the headers
+        header
+          .lines()
+          .filter(|line| !line.starts_with("# GeneratedAt:"))
+          .collect::<Vec<_>>()
+          .join("\n")
+      })
+      .collect()
+  };
+  let default_headers = header_of("default_mem");
+  let big_headers = header_of("big_mem");
+
+  assert!(default_headers[0].contains("#SBATCH --mem=8G"));
+  assert_eq!(default_headers[0], default_headers[1]);
+  for header in &big_headers {
+    assert!(header.contains("#SBATCH --mem=64G"));
+    assert!(!header.contains("#SBATCH --mem=8G"));
+    // Flags that are not overridden are kept
+    assert!(header.contains("#SBATCH --time=01:00:00"));
+    assert_ne!(header, &default_headers[0]);
+  }
+}
+
+#[test]
+fn test_generate_jobs_from_file_params_override_invalid_for_scheduler() {
+  // `mem` is not a parameter of the local scheduler
+  let cluster = create_test_cluster(1);
+  let configs = HashMap::from([("test_config".to_string(), create_test_config(1))]);
+
+  let result = generate_jobs_from_file(
+    &get_test_path("params_override.yaml"),
+    &cluster,
+    &configs,
+    &HashMap::new(),
+  );
+  assert!(matches!(
+    result,
+    Err(JobError::ParserError(ParserError::InvalidParameterForScheduler(param, _))) if param == "mem"
+  ));
+}
+
+#[test]
+fn test_launch_jobs_from_file_missing_config_launches_nothing() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+
+  let result = launch_jobs_from_file(
+    &get_test_path("missing_config.yaml"),
+    &sbatchman_path,
+    &mut db,
+    "test_cluster",
+    &LaunchOverrides::default(),
+    None,
+    true,
+  );
+  assert!(matches!(result, Err(JobError::ConfigNotFound(name)) if name == "missing_config"));
+  // The valid entry comes first, but it must not be launched either
+  assert!(db.get_jobs(None).unwrap().is_empty());
+  assert!(!sbatchman_path.join("jobs").exists());
+}
+
+#[test]
+fn test_launch_jobs_from_file_refuses_large_sweep() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+
+  let result = launch_jobs_from_file(
+    &get_test_path("sweep.yaml"),
+    &sbatchman_path,
+    &mut db,
+    "test_cluster",
+    &LaunchOverrides::default(),
+    Some(5),
+    true,
+  );
+  assert!(matches!(result, Err(JobError::SweepTooLarge(8, 5))));
+  assert!(db.get_jobs(None).unwrap().is_empty());
+}
+
+#[test]
+fn test_launch_jobs_from_file_confirmed_large_sweep() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+
+  // No maximum sweep size, as with `--yes`
+  launch_jobs_from_file(
+    &get_test_path("sweep.yaml"),
+    &sbatchman_path,
+    &mut db,
+    "test_cluster",
+    &LaunchOverrides::default(),
+    None,
+    true,
+  )
+  .unwrap();
+  assert_eq!(db.get_jobs(None).unwrap().len(), 8);
+}
+
+#[test]
+fn test_launch_jobs_with_name_template() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+
+  launch_jobs_from_file(
+    &get_test_path("name_template.yaml"),
+    &dir.path().to_path_buf(),
+    &mut db,
+    "test_cluster",
+    &LaunchOverrides::default(),
+    None,
+    true,
+  )
+  .unwrap();
+
+  let jobs = db.get_jobs(None).unwrap();
+  let names: Vec<String> = jobs.iter().map(|job| job.job_name.clone()).collect();
+  assert_eq!(
+    names,
+    vec![
+      "resnet-1".to_string(),
+      "resnet-2".to_string(),
+      format!("resnet-run-{}", jobs[2].id),
+      format!("resnet-run-{}", jobs[3].id),
+    ]
+  );
+}
+
+#[test]
+fn test_launch_jobs_from_file_with_limit() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+
+  let summary = launch_jobs_from_file(
+    &get_test_path("sweep_10.yaml"),
+    &dir.path().to_path_buf(),
+    &mut db,
+    "test_cluster",
+    &LaunchOverrides {
+      limit: Some(3),
+      ..Default::default()
+    },
+    None,
+    true,
+  )
+  .unwrap();
+
+  assert_eq!(summary.launched, 3);
+  let commands: Vec<String> = db
+    .get_jobs(None)
+    .unwrap()
+    .into_iter()
+    .map(|job| job.command)
+    .collect();
+  assert_eq!(commands, vec!["echo 1", "echo 2", "echo 3"]);
+}
+
+#[test]
+fn test_launch_jobs_from_file_unknown_cluster_key() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let launch = |db: &mut Database, strict: bool| {
+    launch_jobs_from_file(
+      &get_test_path("per_cluster_typo.yaml"),
+      &dir.path().to_path_buf(),
+      db,
+      "test_cluster",
+      &LaunchOverrides {
+        strict,
+        ..Default::default()
+      },
+      None,
+      true,
+    )
+  };
+
+  let result = launch(&mut db, true);
+  assert!(matches!(
+    result,
+    Err(JobError::ParserError(ParserError::UnknownClusterKey(variable, cluster)))
+      if variable == "seed" && cluster == "test_clsuter"
+  ));
+  assert!(db.get_jobs(None).unwrap().is_empty());
+
+  // Without `strict` the typo is only a warning
+  assert_eq!(launch(&mut db, false).unwrap().launched, 1);
+}
+
+#[test]
+fn test_resume_jobs_from_file() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+  let path = get_test_path("sweep.yaml");
+
+  launch_jobs_from_file(
+    &path,
+    &sbatchman_path,
+    &mut db,
+    "test_cluster",
+    &LaunchOverrides::default(),
+    None,
+    true,
+  )
+  .unwrap();
+  let jobs = db.get_jobs(None).unwrap();
+  assert_eq!(jobs.len(), 8);
+  // Two submissions failed, the other jobs are done
+  for (i, job) in jobs.iter().enumerate() {
+    let status = if i < 2 {
+      Status::FailedSubmission
+    } else {
+      Status::Completed
+    };
+    db.update_job_status(job.id, &status).unwrap();
+  }
+
+  let summary = resume_jobs_from_file(
+    &path,
+    &sbatchman_path,
+    &mut db,
+    "test_cluster",
+    &HashMap::new(),
+    true,
+  )
+  .unwrap();
+  assert_eq!(
+    summary,
+    ResumeSummary {
+      launch: LaunchSummary {
+        launched: 2,
+        virtual_queued: 0,
+        failed: 0,
+        job_ids: vec![jobs[0].id, jobs[1].id],
+        launch_id: None,
+      },
+      skipped: 6,
+    }
+  );
+
+  // The failed jobs are submitted again in place, no job is duplicated
+  let resumed = db.get_jobs(None).unwrap();
+  assert_eq!(resumed.len(), 8);
+  let statuses: Vec<Status> = resumed.iter().map(|job| job.status.clone()).collect();
+  assert_eq!(&statuses[..2], &[Status::Created, Status::Created]);
+  assert!(
+    statuses[2..]
+      .iter()
+      .all(|status| *status == Status::Completed)
+  );
+}
+
+#[test]
+fn test_resume_jobs_from_file_legacy_variables() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+  let config = db.get_config_with_cluster("test_config").unwrap().0;
+  // A job completed before the variables were stored as typed values
+  db.create_job(&NewJob {
+    job_name: "full_sweep",
+    config_id: config.id,
+    command: "echo sweep 1 fast",
+    status: &Status::Completed,
+    variables: &json!({
+      "size": {"List": [{"Int": 1}, {"Int": 2}, {"Int": 3}]},
+      "mode": {"List": [{"String": "fast"}, {"String": "slow"}]},
+      "label": {"Scalar": {"String": "sweep"}},
+    }),
+    labels: &json!({}),
+    ..Default::default()
+  })
+  .unwrap();
+
+  let summary = resume_jobs_from_file(
+    &get_test_path("sweep.yaml"),
+    &sbatchman_path,
+    &mut db,
+    "test_cluster",
+    &HashMap::new(),
+    true,
+  )
+  .unwrap();
+  assert_eq!(summary.skipped, 1);
+  assert_eq!(summary.launch.launched, 7);
+  assert_eq!(db.get_jobs(None).unwrap().len(), 8);
+}
+
+/// Scheduler failing the submission of the jobs whose command contains "fail"
+struct MockScheduler {
+  enqueued_jobs: usize,
+}
+
+impl SchedulerTrait for MockScheduler {
+  fn create_job_script(
+    &self,
+    _job: &Job,
+    _cluster_config: &ClusterConfig,
+  ) -> Result<String, JobError> {
+    Ok(String::new())
+  }
+
+  fn launch_job(&self, job: &mut Job, _cluster_config: &ClusterConfig) -> Result<(), JobError> {
+    if job.command.contains("fail") {
+      return Err(JobError::LaunchError("mock failure".to_string()));
+    }
+    job.status = Status::Queued;
+    Ok(())
+  }
+
+  fn submit_job_script(&self, _job: &mut Job) -> Result<(), JobError> {
+    Ok(())
+  }
+
+  fn get_number_of_enqueued_jobs(&self) -> Result<usize, JobError> {
+    Ok(self.enqueued_jobs)
+  }
+}
+
+/// Scheduler accounting the same resource usage for every job
+struct AccountingScheduler;
+
+impl SchedulerTrait for AccountingScheduler {
+  fn create_job_script(
+    &self,
+    _job: &Job,
+    _cluster_config: &ClusterConfig,
+  ) -> Result<String, JobError> {
+    Ok(String::new())
+  }
+
+  fn launch_job(&self, _job: &mut Job, _cluster_config: &ClusterConfig) -> Result<(), JobError> {
+    Ok(())
+  }
+
+  fn submit_job_script(&self, _job: &mut Job) -> Result<(), JobError> {
+    Ok(())
+  }
+
+  fn get_resource_usage(&self, _job: &Job) -> Result<Option<JobLog>, JobError> {
+    Ok(Some(JobLog::ResourceUsage {
+      max_rss_kb: Some(1024),
+      cpu_seconds: Some(60),
+      elapsed_seconds: Some(90),
+    }))
+  }
+}
+
+#[test]
+fn test_record_resource_usage() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let job = create_db_job_with_status(&mut db, &dir, Status::Failed);
+
+  record_resource_usage(&LocalScheduler::default(), &job).unwrap();
+  assert_eq!(job.get_resource_usage(), None);
+
+  record_resource_usage(&AccountingScheduler, &job).unwrap();
+  assert_eq!(
+    job.get_resource_usage(),
+    Some(JobLog::ResourceUsage {
+      max_rss_kb: Some(1024),
+      cpu_seconds: Some(60),
+      elapsed_seconds: Some(90),
+    })
+  );
+}
+
+#[test]
+fn test_failing_sacct_is_not_fatal() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let mut job = create_db_job_with_status(&mut db, &dir, Status::Completed);
+  job.job_id = Some("101".to_string());
+  let scheduler = SlurmScheduler {
+    submit_args: vec![],
+    runner: Box::new(FailingRunner),
+  };
+
+  assert!(scheduler.get_resource_usage(&job).unwrap().is_none());
+  assert!(scheduler.get_resource_usages(&[&job]).unwrap().is_empty());
+  record_resource_usage(&scheduler, &job).unwrap();
+  assert_eq!(job.get_resource_usage(), None);
+}
+
+#[test]
+fn test_launch_jobs_summary() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+  let mut cluster = db.get_cluster_by_name("test_cluster").unwrap();
+  // 1 job already enqueued: only 4 of the 6 jobs can be submitted
+  cluster.max_jobs = Some(5);
+  let configs = db.get_configs_by_cluster(&cluster).unwrap();
+  let config = &configs["test_config"];
+  let jobs: Vec<(Job, Cow<Config>)> = ["ok", "fail", "ok", "ok", "fail", "ok"]
+    .iter()
+    .enumerate()
+    .map(|(i, command)| {
+      let mut job = create_test_job(i as i32, "");
+      job.command = command.to_string();
+      (job, Cow::Borrowed(config))
+    })
+    .collect();
+
+  let summary = launch_jobs(
+    &jobs,
+    &cluster,
+    &mut db,
+    &sbatchman_path,
+    &MockScheduler { enqueued_jobs: 1 },
+    &ProgressBar::hidden(),
+  )
+  .unwrap();
+
+  // The second failing job is in the virtual queue, so it is not submitted
+  assert_eq!(
+    summary,
+    LaunchSummary {
+      launched: 3,
+      virtual_queued: 2,
+      failed: 1,
+      job_ids: vec![1, 2, 3, 4, 5, 6],
+      launch_id: None,
+    }
+  );
+  let failed = JobFilter {
+    statuses: vec![Status::FailedSubmission],
+    ..Default::default()
+  };
+  assert_eq!(db.get_jobs(Some(failed)).unwrap().len(), 1);
+  assert_eq!(db.get_jobs(None).unwrap().len(), 6);
+}
+
+/// Scheduler recording when each job is submitted
+#[derive(Default)]
+struct TimedScheduler {
+  launches: Mutex<Vec<Instant>>,
+}
+
+impl SchedulerTrait for TimedScheduler {
+  fn create_job_script(
+    &self,
+    _job: &Job,
+    _cluster_config: &ClusterConfig,
+  ) -> Result<String, JobError> {
+    Ok(String::new())
+  }
+
+  fn launch_job(&self, job: &mut Job, _cluster_config: &ClusterConfig) -> Result<(), JobError> {
+    self.launches.lock().unwrap().push(Instant::now());
+    job.status = Status::Queued;
+    Ok(())
+  }
+
+  fn submit_job_script(&self, _job: &mut Job) -> Result<(), JobError> {
+    Ok(())
+  }
+
+  fn get_number_of_enqueued_jobs(&self) -> Result<usize, JobError> {
+    Ok(0)
+  }
+}
+
+#[test]
+fn test_launch_jobs_waits_submit_interval() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+  let mut cluster = db.get_cluster_by_name("test_cluster").unwrap();
+  cluster.submit_interval_ms = Some(50);
+  let configs = db.get_configs_by_cluster(&cluster).unwrap();
+  let config = &configs["test_config"];
+  let jobs: Vec<(Job, Cow<Config>)> = (0..3)
+    .map(|i| (create_test_job(i, ""), Cow::Borrowed(config)))
+    .collect();
+
+  let scheduler = TimedScheduler::default();
+  let summary = launch_jobs(
+    &jobs,
+    &cluster,
+    &mut db,
+    &sbatchman_path,
+    &scheduler,
+    &ProgressBar::hidden(),
+  )
+  .unwrap();
+
+  assert_eq!(summary.launched, 3);
+  let launches = scheduler.launches.into_inner().unwrap();
+  assert_eq!(launches.len(), 3);
+  for pair in launches.windows(2) {
+    assert!(pair[1] - pair[0] >= Duration::from_millis(50));
+  }
+}
+
+#[test]
+fn test_launch_local_job_timeout_updates_db() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+  let cluster = db.get_cluster_by_name("test_cluster").unwrap();
+  let mut config = db.get_configs_by_cluster(&cluster).unwrap()["test_config"].clone();
+  config.flags = json!({"time": "00:00:01"});
+  let mut job = create_test_job(0, "");
+  job.command = "sleep 10".to_string();
+
+  launch_jobs(
+    &[(job, Cow::Owned(config))],
+    &cluster,
+    &mut db,
+    &sbatchman_path,
+    &LocalScheduler::new(sbatchman_path.clone()),
+    &ProgressBar::hidden(),
+  )
+  .unwrap();
+
+  assert_eq!(db.get_jobs(None).unwrap()[0].status, Status::Timeout);
+}
+
+#[test]
+fn test_get_jobs_filter_by_label() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+
+  launch_jobs_from_file(
+    &get_test_path("sweep.yaml"),
+    &sbatchman_path,
+    &mut db,
+    "test_cluster",
+    &LaunchOverrides::default(),
+    None,
+    true,
+  )
+  .unwrap();
+
+  let filter = |labels: &[(&str, &str)]| JobFilter {
+    labels: labels
+      .iter()
+      .map(|(k, v)| (k.to_string(), v.to_string()))
+      .collect(),
+    ..Default::default()
+  };
+  let jobs = db.get_jobs(Some(filter(&[("model", "resnet")]))).unwrap();
+  assert_eq!(jobs.len(), 6);
+  assert!(jobs.iter().all(|job| job.job_name == "full_sweep"));
+  assert_eq!(
+    db.get_jobs(Some(filter(&[("experiment", "sweep"), ("model", "vit")])))
+      .unwrap()
+      .len(),
+    2
+  );
+  assert!(
+    db.get_jobs(Some(filter(&[("model", "bert")])))
+      .unwrap()
+      .is_empty()
+  );
+}
+
+// ============================================================================
+// Tests for purge_jobs
+// ============================================================================
+
+/// Insert a job with the given status and a directory containing its script
+fn create_db_job_with_status(db: &mut Database, dir: &TempDir, status: Status) -> Job {
+  create_db_job_for_config(db, dir, 1, status)
+}
+
+fn create_db_job_for_config(
+  db: &mut Database,
+  dir: &TempDir,
+  config_id: i32,
+  status: Status,
+) -> Job {
+  let index = db.get_jobs(None).unwrap().len();
+  let directory = dir.path().join(format!("jobs/{}", index));
+  fs::create_dir_all(&directory).unwrap();
+  fs::write(directory.join("job.sh"), "echo 'Hello World'").unwrap();
+  db.create_job(&NewJob {
+    job_name: "purge_test",
+    config_id,
+    directory: directory.to_str().unwrap(),
+    command: "echo 'Hello World'",
+    status: &status,
+    preprocess: None,
+    postprocess: None,
+    variables: &json!({}),
+    labels: &json!({}),
+    launch_id: None,
+  })
+  .unwrap()
+}
+
+#[test]
+fn test_purge_jobs_by_status() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let failed_1 = create_db_job_with_status(&mut db, &dir, Status::FailedSubmission);
+  let failed_2 = create_db_job_with_status(&mut db, &dir, Status::FailedSubmission);
+  let completed = create_db_job_with_status(&mut db, &dir, Status::Completed);
+  let running = create_db_job_with_status(&mut db, &dir, Status::Running);
+
+  let purged = purge_jobs(&mut db, &Status::FailedSubmission).unwrap();
+
+  assert_eq!(purged, 2);
+  assert!(!Path::new(&failed_1.directory).exists());
+  assert!(!Path::new(&failed_2.directory).exists());
+  let remaining: Vec<i32> = db.get_jobs(None).unwrap().iter().map(|j| j.id).collect();
+  assert_eq!(remaining, vec![completed.id, running.id]);
+  assert!(Path::new(&completed.directory).exists());
+  assert!(Path::new(&running.directory).exists());
+}
+
+#[test]
+fn test_purge_jobs_refuses_active_statuses() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let running = create_db_job_with_status(&mut db, &dir, Status::Running);
+  create_db_job_with_status(&mut db, &dir, Status::Queued);
+
+  for status in [Status::Running, Status::Queued] {
+    let result = purge_jobs(&mut db, &status);
+    assert!(matches!(result, Err(JobError::PurgeActiveStatus(s)) if s == status));
+  }
+  assert_eq!(db.get_jobs(None).unwrap().len(), 2);
+  assert!(Path::new(&running.directory).exists());
+}
+
+#[test]
+fn test_delete_jobs() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let deleted = create_db_job_with_status(&mut db, &dir, Status::Completed);
+  let kept = create_db_job_with_status(&mut db, &dir, Status::Completed);
+  let created = create_db_job_with_status(&mut db, &dir, Status::Created);
+
+  let count = delete_jobs(&mut db, &[deleted.id, created.id, 999]).unwrap();
+
+  assert_eq!(count, 2);
+  assert!(!Path::new(&deleted.directory).exists());
+  assert!(!Path::new(&created.directory).exists());
+  assert_eq!(db.get_jobs(None).unwrap(), vec![kept.clone()]);
+  assert!(Path::new(&kept.directory).exists());
+}
+
+#[test]
+fn test_check_jobs_missing_directory() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let kept = create_db_job_with_status(&mut db, &dir, Status::Completed);
+  let deleted = create_db_job_with_status(&mut db, &dir, Status::Completed);
+  fs::remove_dir_all(&deleted.directory).unwrap();
+
+  let report = check_jobs(&mut db, dir.path(), true).unwrap();
+
+  assert_eq!(report.missing_directories, vec![deleted]);
+  assert!(report.orphan_directories.is_empty());
+  assert!(report.dead_jobs.is_empty());
+  assert!(Path::new(&kept.directory).is_dir());
+}
+
+#[test]
+fn test_check_jobs_orphan_directories() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  create_db_job_with_status(&mut db, &dir, Status::Completed);
+  let orphan = dir.path().join("jobs").join("42-orphan");
+  fs::create_dir_all(&orphan).unwrap();
+
+  let report = check_jobs(&mut db, dir.path(), false).unwrap();
+  assert_eq!(report.orphan_directories, vec![orphan.clone()]);
+  assert!(orphan.is_dir());
+
+  check_jobs(&mut db, dir.path(), true).unwrap();
+  assert!(!orphan.exists());
+  assert!(check_jobs(&mut db, dir.path(), false).unwrap().is_healthy());
+}
+
+#[test]
+fn test_check_jobs_dead_process() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let mut finished = std::process::Command::new("true").spawn().unwrap();
+  let dead_pid = finished.id();
+  finished.wait().unwrap();
+  let dead = create_db_job_with_status(&mut db, &dir, Status::Running);
+  let alive = create_db_job_with_status(&mut db, &dir, Status::Running);
+  for (job, pid) in [(&dead, dead_pid), (&alive, std::process::id())] {
+    job
+      .write_log_entry(JobLog::StatusUpdate(Status::Running), None)
+      .unwrap();
+    job
+      .write_log_entry(JobLog::Variable("PID".to_string(), pid.to_string()), None)
+      .unwrap();
+  }
+
+  let report = check_jobs(&mut db, dir.path(), false).unwrap();
+  assert_eq!(report.dead_jobs, vec![dead.clone()]);
+  assert_eq!(db.get_job_by_id(dead.id).unwrap().status, Status::Running);
+
+  check_jobs(&mut db, dir.path(), true).unwrap();
+  assert_eq!(db.get_job_by_id(dead.id).unwrap().status, Status::Failed);
+  assert_eq!(dead.get_logged_status(), Some(Status::Failed));
+  assert_eq!(db.get_job_by_id(alive.id).unwrap().status, Status::Running);
+  assert!(check_jobs(&mut db, dir.path(), false).unwrap().is_healthy());
+}
+
+#[test]
+fn test_cancel_job() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let queued = create_db_job_with_status(&mut db, &dir, Status::VirtualQueue);
+  let completed = create_db_job_with_status(&mut db, &dir, Status::Completed);
+
+  let canceled = cancel_job(&mut db, queued.id).unwrap();
+  assert_eq!(canceled.status, Status::Canceled);
+  assert_eq!(
+    db.get_job_by_id(queued.id).unwrap().status,
+    Status::Canceled
+  );
+  let logs = queued.parse_log_typed().unwrap();
+  let last_entries: Vec<&JobLog> = logs.iter().rev().take(2).map(|(_, log)| log).collect();
+  assert_eq!(
+    last_entries,
+    vec![
+      &JobLog::StatusUpdate(Status::Canceled),
+      &JobLog::Action("cancel".to_string())
+    ]
+  );
+
+  let result = cancel_job(&mut db, completed.id);
+  assert!(
+    matches!(result, Err(JobError::NotCancelable(id, Status::Completed)) if id == completed.id)
+  );
+}
+
+#[test]
+fn test_set_status_for_ids_logs_the_change() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let first = create_db_job_with_status(&mut db, &dir, Status::Running);
+  let second = create_db_job_with_status(&mut db, &dir, Status::Running);
+
+  let updated = set_status_for_ids(&mut db, &[first.id, second.id], &Status::Failed).unwrap();
+
+  assert_eq!(updated, 2);
+  for job in [&first, &second] {
+    assert_eq!(db.get_job_by_id(job.id).unwrap().status, Status::Failed);
+    assert_eq!(job.get_logged_status(), Some(Status::Failed));
+    let logs = job.parse_log_typed().unwrap();
+    let last_entries: Vec<&JobLog> = logs.iter().rev().take(2).map(|(_, log)| log).collect();
+    assert_eq!(
+      last_entries,
+      vec![
+        &JobLog::StatusUpdate(Status::Failed),
+        &JobLog::Action("set-status".to_string())
+      ]
+    );
+  }
+
+  // An unknown id changes nothing, not even the logs
+  assert!(set_status_for_ids(&mut db, &[first.id, 999], &Status::Completed).is_err());
+  assert_eq!(db.get_job_by_id(first.id).unwrap().status, Status::Failed);
+  assert_eq!(first.get_logged_status(), Some(Status::Failed));
+}
+
+#[test]
+fn test_cancel_launch_goes_on_after_a_failure() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let mut create_launch_job = |name: &str, status: Status| {
+    let directory = dir.path().join("jobs").join(name);
+    fs::create_dir_all(&directory).unwrap();
+    db.create_job(&NewJob {
+      job_name: name,
+      config_id: 1,
+      directory: directory.to_str().unwrap(),
+      command: "echo 'Hello World'",
+      status: &status,
+      variables: &json!({}),
+      labels: &json!({}),
+      launch_id: Some("launch"),
+      ..Default::default()
+    })
+    .unwrap()
+  };
+  let first = create_launch_job("first", Status::VirtualQueue);
+  let broken = create_launch_job("broken", Status::Created);
+  let last = create_launch_job("last", Status::VirtualQueue);
+  let completed = create_launch_job("completed", Status::Completed);
+  let other = create_db_job_with_status(&mut db, &dir, Status::VirtualQueue);
+  // The cancellation of a job without a directory cannot be logged
+  fs::remove_dir_all(&broken.directory).unwrap();
+
+  let summary = cancel_launch(&mut db, "launch").unwrap();
+
+  let canceled: Vec<i32> = summary.canceled.iter().map(|job| job.id).collect();
+  assert_eq!(canceled, vec![first.id, last.id]);
+  let failed: Vec<i32> = summary.failed.iter().map(|(id, _)| *id).collect();
+  assert_eq!(failed, vec![broken.id]);
+  let status = |db: &mut Database, id: i32| db.get_job_by_id(id).unwrap().status;
+  assert_eq!(status(&mut db, last.id), Status::Canceled);
+  assert_eq!(status(&mut db, broken.id), Status::Created);
+  assert_eq!(status(&mut db, completed.id), Status::Completed);
+  assert_eq!(status(&mut db, other.id), Status::VirtualQueue);
+
+  assert!(matches!(
+    cancel_launch(&mut db, "missing"),
+    Err(JobError::LaunchNotFound(launch_id)) if launch_id == "missing"
+  ));
+}
+
+#[test]
+fn test_regenerate_scripts_of_pending_jobs() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let created = create_db_job_with_status(&mut db, &dir, Status::Created);
+  let virtual_queued = create_db_job_with_status(&mut db, &dir, Status::VirtualQueue);
+  let completed = create_db_job_with_status(&mut db, &dir, Status::Completed);
+  let cluster = db.get_cluster_by_name("test_cluster").unwrap();
+  let mut config = db.get_config_by_id(1).unwrap();
+  config.env = json!({"FIXED": "yes"});
+
+  let count = regenerate_scripts(&mut db, &cluster, &config).unwrap();
+
+  assert_eq!(count, 2);
+  for job in [&created, &virtual_queued] {
+    let script = job.get_script().unwrap();
+    assert!(script.contains("export FIXED=\"yes\"\n"));
+    assert!(script.contains("echo 'Hello World'"));
+  }
+  // Already launched jobs keep their script
+  assert_eq!(completed.get_script().unwrap(), "echo 'Hello World'");
+}
+
+// ============================================================================
+// Tests for sync_job_statuses
+// ============================================================================
+
+#[test]
+fn test_sync_job_statuses_from_logs() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let started = create_db_job_with_status(&mut db, &dir, Status::Queued);
+  let finished = create_db_job_with_status(&mut db, &dir, Status::Running);
+  let waiting = create_db_job_with_status(&mut db, &dir, Status::Queued);
+  let failed = create_db_job_with_status(&mut db, &dir, Status::Failed);
+
+  started
+    .write_log_entry(JobLog::StatusUpdate(Status::Running), None)
+    .unwrap();
+  finished
+    .write_log_entry(JobLog::StatusUpdate(Status::Running), None)
+    .unwrap();
+  finished
+    .write_log_entry(JobLog::StatusUpdate(Status::Completed), None)
+    .unwrap();
+  // A partially written entry is ignored
+  fs::write(
+    waiting.get_log_path(),
+    "{\"type\":\"StatusUpdate\",\"data\":\"Comp",
+  )
+  .unwrap();
+
+  let summary = sync_job_statuses(&mut db, &dir.path().to_path_buf(), None).unwrap();
+
+  assert_eq!(
+    summary,
+    SyncSummary {
+      updated: 2,
+      resubmitted: 0,
+      queued: 1,
+      running: 1
+    }
+  );
+  assert!(!summary.is_done());
+  let status = |db: &mut Database, job: &Job| db.get_job_by_id(job.id).unwrap().status;
+  assert_eq!(status(&mut db, &started), Status::Running);
+  assert_eq!(status(&mut db, &finished), Status::Completed);
+  assert_eq!(status(&mut db, &waiting), Status::Queued);
+  assert_eq!(status(&mut db, &failed), Status::Failed);
+}
+
+#[test]
+fn test_sync_job_statuses_parallel_across_clusters() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let other_cluster = db
+    .create_cluster(&NewCluster {
+      cluster_name: "other_cluster".to_string(),
+      scheduler: Scheduler::Local,
+      ..Default::default()
+    })
+    .unwrap();
+  let other_config = db
+    .create_cluster_config(&NewConfig {
+      config_name: "other_config".to_string(),
+      cluster_id: other_cluster.id,
+      flags: json!({}),
+      env: json!({}),
+      ..Default::default()
+    })
+    .unwrap();
+  let mut jobs = Vec::new();
+  for config_id in [1, other_config.id] {
+    for _ in 0..3 {
+      jobs.push(create_db_job_for_config(
+        &mut db,
+        &dir,
+        config_id,
+        Status::Queued,
+      ));
+    }
+  }
+  // The first job of each cluster is still queued, the others are over
+  for (i, job) in jobs.iter().enumerate() {
+    if i % 3 != 0 {
+      job
+        .write_log_entry(JobLog::StatusUpdate(Status::Completed), None)
+        .unwrap();
+    }
+  }
+
+  let summary = sync_job_statuses_parallel(&mut db, &dir.path().to_path_buf(), None).unwrap();
+
+  assert_eq!(
+    summary,
+    SyncSummary {
+      updated: 4,
+      resubmitted: 0,
+      queued: 2,
+      running: 0
+    }
+  );
+  let statuses: Vec<Status> = jobs
+    .iter()
+    .map(|job| db.get_job_by_id(job.id).unwrap().status)
+    .collect();
+  assert_eq!(
+    statuses,
+    vec![
+      Status::Queued,
+      Status::Completed,
+      Status::Completed,
+      Status::Queued,
+      Status::Completed,
+      Status::Completed
+    ]
+  );
+}
+
+#[test]
+fn test_sync_resubmits_on_timeout() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let config = db
+    .create_cluster_config(&NewConfig {
+      config_name: "retry_config".to_string(),
+      cluster_id: 1,
+      flags: json!({"time": "00:01:00"}),
+      env: json!({}),
+      retry_on_timeout: Some(json!({"max_retries": 1, "time_multiplier": 1.5})),
+      ..Default::default()
+    })
+    .unwrap();
+  let directory = dir.path().join("jobs/timeout");
+  fs::create_dir_all(&directory).unwrap();
+  let job = db
+    .create_job(&NewJob {
+      job_name: "timeout_test",
+      config_id: config.id,
+      directory: directory.to_str().unwrap(),
+      command: "echo 'Hello World'",
+      status: &Status::Running,
+      variables: &json!({}),
+      labels: &json!({"experiment": "retry"}),
+      ..Default::default()
+    })
+    .unwrap();
+  job
+    .write_log_entry(JobLog::StatusUpdate(Status::Timeout), None)
+    .unwrap();
+
+  let summary = sync_job_statuses(&mut db, &dir.path().to_path_buf(), None).unwrap();
+
+  assert_eq!(summary.updated, 1);
+  assert_eq!(summary.resubmitted, 1);
+  let jobs = db.get_jobs(None).unwrap();
+  assert_eq!(jobs.len(), 2);
+  assert_eq!(jobs[0].status, Status::Timeout);
+  let retry = &jobs[1];
+  assert_eq!(retry.config_id, config.id);
+  assert_eq!(retry.labels["retry_of"], json!(job.id.to_string()));
+  assert_eq!(retry.labels["retry"], json!("1"));
+  assert_eq!(retry.labels["experiment"], json!("retry"));
+  assert!(retry.get_script().unwrap().contains("timeout 90 echo"));
+
+  // The retry times out too, but no retries are left
+  db.update_job_status(retry.id, &Status::Running).unwrap();
+  retry
+    .write_log_entry(JobLog::StatusUpdate(Status::Timeout), None)
+    .unwrap();
+  let summary = sync_job_statuses(&mut db, &dir.path().to_path_buf(), None).unwrap();
+  assert_eq!(summary.resubmitted, 0);
+  assert_eq!(db.get_jobs(None).unwrap().len(), 2);
+}
+
+#[test]
+fn test_sync_since_batches_in_window_active_jobs() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let jobs = [
+    ("101", Some(5000), Status::Queued),
+    ("102", Some(6000), Status::Running),
+    ("103", Some(4000), Status::Running),
+    ("104", Some(7000), Status::Completed),
+    // Submitted before the submit times were recorded
+    ("105", None, Status::Running),
+  ];
+  for (index, (job_id, submit_time, status)) in jobs.into_iter().enumerate() {
+    let mut job = create_test_job(0, dir.path().join(index.to_string()).to_str().unwrap());
+    job.job_id = Some(job_id.to_string());
+    job.submit_time = submit_time;
+    job.status = status;
+    db.import_job(&job, 1).unwrap();
+  }
+
+  let active: Vec<Job> = active_jobs_by_cluster(&mut db, Some(5000))
+    .unwrap()
+    .into_iter()
+    .flat_map(|(_, jobs)| jobs)
+    .collect();
+  let active: Vec<&Job> = active.iter().collect();
+  assert_eq!(sacct_job_ids(&active).as_deref(), Some("101,102,105"));
+
+  let all = active_jobs_by_cluster(&mut db, None).unwrap();
+  assert_eq!(all.iter().map(|(_, jobs)| jobs.len()).sum::<usize>(), 4);
+  assert_eq!(sacct_job_ids(&[]), None);
+}
+
+#[test]
+fn test_split_sacct_output_by_job() {
+  let output = "101|||00:01:00\n101.batch|2048K|00:00:30|00:01:00\n102|||00:02:00\n";
+  let outputs = split_sacct_output_by_job(output);
+  assert_eq!(outputs.len(), 2);
+  assert_eq!(outputs["101"], "||00:01:00\n2048K|00:00:30|00:01:00\n");
+  assert_eq!(outputs["102"], "||00:02:00\n");
+}
+
+// ============================================================================
+// Tests for rerun_job
+// ============================================================================
+
+/// Launch the job of `single.yaml` and return it
+fn launch_single_job(db: &mut Database, sbatchman_path: &PathBuf) -> Job {
+  launch_jobs_from_file(
+    &get_test_path("single.yaml"),
+    sbatchman_path,
+    db,
+    "test_cluster",
+    &LaunchOverrides::default(),
+    None,
+    true,
+  )
+  .unwrap();
+  db.get_jobs(None).unwrap().remove(0)
+}
+
+#[test]
+fn test_job_directories_unique_across_databases() {
+  let dirs = [init_sbatchman_for_tests(), init_sbatchman_for_tests()];
+  let jobs: Vec<Job> = dirs
+    .iter()
+    .map(|dir| {
+      let mut db = create_test_db(dir);
+      launch_single_job(&mut db, &dir.path().to_path_buf())
+    })
+    .collect();
+
+  // Both databases assign the same id, the run ids differ
+  assert_eq!(jobs[0].id, jobs[1].id);
+  assert_ne!(jobs[0].run_id, jobs[1].run_id);
+  let run_id = jobs[0].run_id.as_ref().unwrap();
+  assert!(
+    jobs[0]
+      .directory
+      .ends_with(&format!("jobs/{}-{}", jobs[0].id, run_id))
+  );
+
+  // Merge the job directories of the two databases
+  let merged = TempDir::new().unwrap();
+  for job in &jobs {
+    let name = Path::new(&job.directory).file_name().unwrap();
+    let dest = merged.path().join("jobs").join(name);
+    assert!(!dest.exists());
+    fs::create_dir_all(&dest).unwrap();
+    fs::copy(job.get_script_path(), dest.join("job.sh")).unwrap();
+  }
+  assert_eq!(fs::read_dir(merged.path().join("jobs")).unwrap().count(), 2);
+}
+
+#[test]
+fn test_rerun_job_reuses_original_script() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+  let original = launch_single_job(&mut db, &sbatchman_path);
+  let original_log = original.get_log().unwrap();
+  let original_stdout = original.get_stdout().unwrap();
+
+  let rerun = rerun_job(&mut db, &sbatchman_path, original.id, false).unwrap();
+
+  assert_ne!(rerun.id, original.id);
+  assert_eq!(db.get_jobs(None).unwrap().len(), 2);
+  // The script is the original one, writing to the log and outputs of the new job
+  let script = rerun.get_script().unwrap();
+  assert!(script.contains(&format!("# JobId: {}\n", rerun.id)));
+  assert!(!script.contains(&original.directory));
+  assert!(rerun.get_stdout().unwrap().contains("hello"));
+  assert_eq!(rerun.get_logged_status(), original.get_logged_status());
+  assert_eq!(original.get_log().unwrap(), original_log);
+  assert_eq!(original.get_stdout().unwrap(), original_stdout);
+}
+
+#[test]
+fn test_rerun_job_regenerates_script() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let sbatchman_path = dir.path().to_path_buf();
+  let original = launch_single_job(&mut db, &sbatchman_path);
+
+  let rerun = rerun_job(&mut db, &sbatchman_path, original.id, true).unwrap();
+
+  assert_ne!(rerun.id, original.id);
+  assert_eq!(db.get_jobs(None).unwrap().len(), 2);
+  let script = rerun.get_script().unwrap();
+  assert_ne!(script, original.get_script().unwrap());
+  assert!(script.contains(&format!("# JobId: {}\n", rerun.id)));
+  assert!(script.contains("echo \"hello\""));
+}
+
+#[test]
+fn test_rerun_job_missing_job() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+
+  let result = rerun_job(&mut db, &dir.path().to_path_buf(), 42, false);
+  assert!(matches!(result, Err(JobError::DatabaseError(_))));
+}
+
+#[test]
+fn test_failing_pre_submit_hook_prevents_submission() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = Database::new(dir.path()).unwrap();
+  let cluster = db
+    .create_cluster(&NewCluster {
+      cluster_name: "hook_cluster".to_string(),
+      scheduler: Scheduler::Local,
+      pre_submit_hook: Some("echo \"denied $SBM_JOB_NAME\"; exit 3".to_string()),
+      ..Default::default()
+    })
+    .unwrap();
+  db.create_cluster_config(&NewConfig {
+    config_name: "test_config".to_string(),
+    cluster_id: cluster.id,
+    flags: json!({}),
+    env: json!({}),
+    ..Default::default()
+  })
+  .unwrap();
+
+  let _ = launch_jobs_from_file(
+    &get_test_path("single.yaml"),
+    &dir.path().to_path_buf(),
+    &mut db,
+    "hook_cluster",
+    &LaunchOverrides::default(),
+    None,
+    true,
+  );
+
+  let jobs = db.get_jobs(None).unwrap();
+  assert_eq!(jobs.len(), 1);
+  assert_eq!(jobs[0].status, Status::FailedSubmission);
+  // The job never ran
+  assert!(!Path::new(&jobs[0].directory).join("job.sh").exists());
+  let logs = jobs[0].parse_log_typed().unwrap();
+  assert!(logs.iter().any(|(_, log)| *log
+    == JobLog::PreSubmitHook {
+      exit_code: Some(3),
+      output: "denied single\n".to_string(),
+    }));
+}
+
+// ============================================================================
+// Tests for CustomScheduler
+// ============================================================================
+
+fn create_lsf_scheduler(submit_command: &str) -> CustomScheduler {
+  CustomScheduler {
+    submit_command: submit_command.to_string(),
+    job_id_regex: Some(r"Job <(\d+)>".to_string()),
+  }
+}
+
+#[test]
+fn test_custom_scheduler_parse_job_id() {
+  let scheduler = create_lsf_scheduler("bsub < {script}");
+  let job_id = scheduler
+    .parse_job_id("Job <1234> is submitted to queue <normal>.\n")
+    .unwrap();
+  assert_eq!(job_id, "1234");
+  assert!(matches!(
+    scheduler.parse_job_id("Request aborted by esub"),
+    Err(JobError::ExecutionFailed(_))
+  ));
+
+  // Without a regex the whole output is the job id
+  let scheduler = CustomScheduler {
+    submit_command: "submit {script}".to_string(),
+    job_id_regex: None,
+  };
+  assert_eq!(scheduler.parse_job_id(" 98765\n").unwrap(), "98765");
+}
+
+#[test]
+fn test_custom_scheduler_launch_job() {
+  let temp_dir = TempDir::new().unwrap();
+  // The script path is quoted in the submit command
+  let job_dir = temp_dir.path().join("custom job's dir");
+  let mut job = create_test_job(1, job_dir.to_str().unwrap());
+  let mut cluster = create_test_cluster(1);
+  cluster.scheduler = Scheduler::Custom;
+  cluster.header_prefix = Some("#BSUB".to_string());
+  let mut config = create_test_config(1);
+  config.flags = json!({"q": "normal"});
+
+  // Fake submit command checking that the script exists
+  let scheduler =
+    create_lsf_scheduler("test -x {script} && echo 'Job <42> is submitted to queue <normal>.'");
+  scheduler
+    .launch_job(&mut job, &ClusterConfig::new(&cluster, &config))
+    .unwrap();
+
+  assert_eq!(job.job_id.as_deref(), Some("42"));
+  assert_eq!(job.status, Status::Queued);
+  assert!(job.get_script().unwrap().contains("#BSUB -q normal\n"));
+}
+
+#[test]
+fn test_describe_variables() {
+  let variables = describe_variables(&get_test_path("derived_variables.yaml")).unwrap();
+
+  assert_eq!(
+    variables,
+    vec![
+      VariableInfo {
+        name: "size".to_string(),
+        kind: "List",
+        dependencies: vec![],
+      },
+      VariableInfo {
+        name: "output".to_string(),
+        kind: "Scalar",
+        dependencies: vec!["size".to_string()],
+      },
+      VariableInfo {
+        name: "nodes".to_string(),
+        kind: "ClusterMap",
+        dependencies: vec![],
+      },
+      VariableInfo {
+        name: "args".to_string(),
+        kind: "StandardMap",
+        dependencies: vec!["output".to_string(), "nodes".to_string()],
+      },
+    ]
+  );
+}
@@ -1,13 +1,15 @@
 use std::collections::HashMap;
 
 use pyo3::Python;
+use serde_json::json;
 
 use crate::core::{
   cluster_configs::ClusterConfig,
   database::models::{Cluster, Job},
   jobs::{
+    JobError,
     tests::{create_test_cluster, create_test_config},
-    variable_substitutions::{get_variables_dependency, scalar_to_string},
+    variable_substitutions::{DependencyGraph, get_variables_dependency, scalar_to_string},
   },
   parsers::variables::{BasicVar, ClusterMap, CompleteVar, Scalar, Variable},
 };
@@ -93,7 +95,8 @@ fn test_simple_variable_substitution() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "Hello World");
@@ -122,12 +125,59 @@ fn test_multiple_scalar_variables() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "Hello World");
 }
 
+#[test]
+fn test_duplicate_list_values_generate_one_job() {
+  let cl = create_test_cluster(1);
+  let cf = create_test_config(1);
+  let cluster = ClusterConfig::new(&cl, &cf);
+  let variables = vec![
+    test_variable(
+      "SEED",
+      CompleteVar::List(vec![Scalar::Int(1), Scalar::Int(1), Scalar::Int(2)]),
+    ),
+    test_variable(
+      "MODE",
+      CompleteVar::List(vec![
+        Scalar::String("train".to_string()),
+        Scalar::String("eval".to_string()),
+      ]),
+    ),
+  ];
+
+  let jobs = Job::generate_from(
+    &cluster,
+    &variables,
+    "run --seed ${SEED}".to_string(),
+    Some("prepare ${MODE}".to_string()),
+    None,
+    None,
+  )
+  .unwrap();
+
+  // Only the duplicated seed is dropped, the preprocess still tells the modes apart
+  let mut triples: Vec<(&str, Option<&str>)> = jobs
+    .iter()
+    .map(|job| (job.command.as_str(), job.preprocess.as_deref()))
+    .collect();
+  triples.sort();
+  assert_eq!(
+    triples,
+    vec![
+      ("run --seed 1", Some("prepare eval")),
+      ("run --seed 1", Some("prepare train")),
+      ("run --seed 2", Some("prepare eval")),
+      ("run --seed 2", Some("prepare train")),
+    ]
+  );
+}
+
 #[test]
 fn test_list_variable_cartesian_product() {
   let cl = create_test_cluster(1);
@@ -145,7 +195,8 @@ fn test_list_variable_cartesian_product() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 3);
   assert_eq!(jobs[0].command, "Value: 1");
@@ -176,7 +227,8 @@ fn test_multiple_list_variables_cartesian_product() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 4);
   let commands: Vec<_> = jobs.iter().map(|j| j.command.as_str()).collect();
@@ -218,7 +270,8 @@ fn test_cluster_map_resolution() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "Config: value_a");
@@ -252,7 +305,8 @@ fn test_cluster_map_default_value() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "Config: default");
@@ -286,7 +340,8 @@ fn test_cluster_map_with_list() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 2);
   assert_eq!(jobs[0].command, "Value: 1");
@@ -324,7 +379,8 @@ fn test_standard_map_substitution() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "value1");
@@ -351,12 +407,164 @@ fn test_standard_map_literal_key() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "result");
 }
 
+#[test]
+fn test_standard_map_missing_key() {
+  let cl = create_test_cluster(1);
+  let cf = create_test_config(1);
+  let cluster = ClusterConfig::new(&cl, &cf);
+
+  let mut map = HashMap::new();
+  map.insert(
+    "impl1".to_string(),
+    BasicVar::Scalar(Scalar::String("--fast".to_string())),
+  );
+  let variables = vec![
+    test_variable("ARGS", CompleteVar::StandardMap(map)),
+    test_variable(
+      "IMPL",
+      CompleteVar::List(vec![
+        Scalar::String("impl1".to_string()),
+        Scalar::String("impl3".to_string()),
+      ]),
+    ),
+  ];
+
+  // Literal key
+  let result = Job::generate_from(
+    &cluster,
+    &variables,
+    "run ${ARGS}[impl3]".to_string(),
+    None,
+    None,
+    None,
+  );
+  assert!(matches!(
+    result,
+    Err(JobError::MissingMapKey(map, key)) if map == "ARGS" && key == "impl3"
+  ));
+
+  // Variable key, only one of its values is missing from the map
+  let result = Job::generate_from(
+    &cluster,
+    &variables,
+    "run".to_string(),
+    None,
+    Some("echo ${ARGS}[${IMPL}]".to_string()),
+    None,
+  );
+  assert_eq!(
+    result.unwrap_err().to_string(),
+    "Map variable \"ARGS\" has no key \"impl3\""
+  );
+}
+
+#[test]
+fn test_list_index() {
+  let cl = create_test_cluster(1);
+  let cf = create_test_config(1);
+  let cluster = ClusterConfig::new(&cl, &cf);
+  let variables = vec![test_variable(
+    "NODES",
+    CompleteVar::List(vec![Scalar::Int(1), Scalar::Int(2), Scalar::Int(4)]),
+  )];
+
+  // A list only referenced by index is not swept
+  let jobs = Job::generate_from(
+    &cluster,
+    &variables,
+    "run -N ${NODES}[0] --max ${NODES}[2]".to_string(),
+    None,
+    None,
+    None,
+  )
+  .unwrap();
+  assert_eq!(jobs.len(), 1);
+  assert_eq!(jobs[0].command, "run -N 1 --max 4");
+
+  // It still is when it is also referenced as a whole
+  let jobs = Job::generate_from(
+    &cluster,
+    &variables,
+    "run -N ${NODES} --min ${NODES}[0]".to_string(),
+    None,
+    None,
+    None,
+  )
+  .unwrap();
+  let commands: Vec<&str> = jobs.iter().map(|job| job.command.as_str()).collect();
+  assert_eq!(
+    commands,
+    vec!["run -N 1 --min 1", "run -N 2 --min 1", "run -N 4 --min 1"]
+  );
+}
+
+#[test]
+fn test_list_index_out_of_range() {
+  let cl = create_test_cluster(1);
+  let cf = create_test_config(1);
+  let cluster = ClusterConfig::new(&cl, &cf);
+  let variables = vec![test_variable(
+    "NODES",
+    CompleteVar::List(vec![Scalar::Int(1), Scalar::Int(2)]),
+  )];
+
+  let result = Job::generate_from(
+    &cluster,
+    &variables,
+    "run".to_string(),
+    Some("echo ${NODES}[2]".to_string()),
+    None,
+    None,
+  );
+  assert!(matches!(
+    result,
+    Err(JobError::ListIndexOutOfRange(name, index, 2)) if name == "NODES" && index == "2"
+  ));
+}
+
+#[test]
+fn test_explain_substitution_stages() {
+  let cl = create_test_cluster(1);
+  let cf = create_test_config(1);
+  let cluster = ClusterConfig::new(&cl, &cf);
+
+  let mut map = HashMap::new();
+  map.insert(
+    "fast".to_string(),
+    BasicVar::Scalar(Scalar::String("--opt".to_string())),
+  );
+  let variables = vec![
+    test_variable("ARGS", CompleteVar::StandardMap(map)),
+    test_variable(
+      "IMPL",
+      CompleteVar::Scalar(Scalar::String("fast".to_string())),
+    ),
+    test_variable("N", CompleteVar::List(vec![Scalar::Int(3), Scalar::Int(4)])),
+  ];
+
+  Python::initialize();
+  let trace = Job::explain(
+    &cluster,
+    &variables,
+    "run ${ARGS}[${IMPL}] --n !py ${N} * 2 !py",
+    &None,
+  )
+  .unwrap();
+
+  assert_eq!(trace.template, "run ${ARGS}[${IMPL}] --n !py ${N} * 2 !py");
+  assert_eq!(trace.after_simple, "run ${ARGS}[fast] --n !py 3 * 2 !py");
+  assert_eq!(trace.after_maps, "run --opt --n !py 3 * 2 !py");
+  assert_eq!(trace.after_python, "run --opt --n 6");
+  assert_eq!(trace.final_command(), "run --opt --n 6");
+}
+
 #[test]
 fn test_preprocess_and_postprocess() {
   let cl = create_test_cluster(1);
@@ -374,7 +582,8 @@ fn test_preprocess_and_postprocess() {
     Some("pre ${NAME}".to_string()),
     Some("post ${NAME}".to_string()),
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "main test");
@@ -399,7 +608,8 @@ fn test_mixed_literal_and_variable() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "./exec_run");
@@ -423,7 +633,8 @@ fn test_python_evaluation_simple() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "['0', '1', '2']");
@@ -446,7 +657,8 @@ fn test_python_with_header() {
     None,
     None,
     Some(header),
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "10");
@@ -475,12 +687,38 @@ fn test_dependency_graph_simple() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "hello_world");
 }
 
+#[test]
+fn test_dependency_graph_edges() {
+  let top = CompleteVar::Scalar(Scalar::String("${MIDDLE}/out".to_string()));
+  let middle = CompleteVar::Scalar(Scalar::String("${BASE}_run".to_string()));
+  let base = CompleteVar::Scalar(Scalar::String("data".to_string()));
+  let var_map = HashMap::from([
+    ("TOP".to_string(), &top),
+    ("MIDDLE".to_string(), &middle),
+    ("BASE".to_string(), &base),
+  ]);
+
+  let graph = DependencyGraph::build("ls ${TOP}", &None, &None, &var_map).unwrap();
+
+  assert_eq!(
+    graph.edges(),
+    &HashMap::from([
+      ("TOP".to_string(), vec!["MIDDLE".to_string()]),
+      ("MIDDLE".to_string(), vec!["BASE".to_string()]),
+      ("BASE".to_string(), vec![]),
+    ])
+  );
+  assert!(graph.has_dependencies("TOP"));
+  assert!(!graph.has_dependencies("BASE"));
+}
+
 #[test]
 fn test_empty_variables() {
   let cl = create_test_cluster(1);
@@ -495,7 +733,8 @@ fn test_empty_variables() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "static command");
@@ -521,16 +760,59 @@ fn test_variable_storage_in_job() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
+  assert_eq!(jobs[0].variables["VAR1"], json!("value1"));
+  assert_eq!(jobs[0].variables["VAR2"], json!(42));
+}
+
+#[test]
+fn test_variable_storage_keeps_types() {
+  let mut cl = create_test_cluster(1);
+  cl.cluster_name = "cluster_a".to_string();
+  let cf = create_test_config(1);
+  let cluster = ClusterConfig::new(&cl, &cf);
+  let per_cluster = HashMap::from([(
+    "cluster_a".to_string(),
+    BasicVar::List(vec![Scalar::Int(1), Scalar::Int(2)]),
+  )]);
+  let variables = vec![
+    test_variable(
+      "RATE",
+      CompleteVar::List(vec![Scalar::Float(0.5), Scalar::Bool(true)]),
+    ),
+    test_variable(
+      "NODES",
+      CompleteVar::ClusterMap(ClusterMap {
+        default: None,
+        per_cluster,
+      }),
+    ),
+  ];
+
+  let jobs = Job::generate_from(
+    &cluster,
+    &variables,
+    "${RATE} ${NODES}".to_string(),
+    None,
+    None,
+    None,
+  )
+  .unwrap();
+
+  assert_eq!(jobs.len(), 4);
+  // Each job records its own values of the swept variables
+  let variables: Vec<_> = jobs.iter().map(|job| job.variables.clone()).collect();
   assert_eq!(
-    jobs[0].variables.get("VAR1"),
-    Some(&serde_json::from_str(r#"{"Scalar": {"String": "value1"}}"#).unwrap())
-  );
-  assert_eq!(
-    jobs[0].variables.get("VAR2"),
-    Some(&serde_json::from_str(r#"{"Scalar": {"Int": 42}}"#).unwrap())
+    variables,
+    vec![
+      json!({"RATE": 0.5, "NODES": 1}),
+      json!({"RATE": true, "NODES": 1}),
+      json!({"RATE": 0.5, "NODES": 2}),
+      json!({"RATE": true, "NODES": 2}),
+    ]
   );
 }
 
@@ -570,11 +852,55 @@ fn test_cartesian_product_with_three_lists() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 8); // 2 * 2 * 2 = 8
 }
 
+#[test]
+fn test_cartesian_product_stable_ordering() {
+  let cl = create_test_cluster(1);
+  let cf = create_test_config(1);
+  let cluster = ClusterConfig::new(&cl, &cf);
+  let variables = vec![
+    test_variable("Y", CompleteVar::List(vec![Scalar::Int(1), Scalar::Int(2)])),
+    test_variable(
+      "X",
+      CompleteVar::List(vec![
+        Scalar::String("a".to_string()),
+        Scalar::String("b".to_string()),
+      ]),
+    ),
+    test_variable(
+      "Z",
+      CompleteVar::List(vec![Scalar::Bool(true), Scalar::Bool(false)]),
+    ),
+  ];
+
+  let generate = || -> Vec<String> {
+    Job::generate_from(
+      &cluster,
+      &variables,
+      "${X}-${Y}-${Z}".to_string(),
+      None,
+      None,
+      None,
+    )
+    .unwrap()
+    .into_iter()
+    .map(|j| j.command)
+    .collect()
+  };
+
+  // Variables are iterated by name, the last one changing fastest
+  let first = generate();
+  assert_eq!(first[..3], ["a-1-true", "a-1-false", "a-2-true"]);
+  for _ in 0..10 {
+    assert_eq!(generate(), first);
+  }
+}
+
 #[test]
 fn test_bool_scalar_type() {
   let cl = create_test_cluster(1);
@@ -592,7 +918,8 @@ fn test_bool_scalar_type() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "flag=true");
@@ -615,7 +942,8 @@ fn test_float_scalar_type() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "pi=3.14159");
@@ -644,8 +972,31 @@ fn test_file_and_directory_types() {
     None,
     None,
     None,
-  );
+  )
+  .unwrap();
 
   assert_eq!(jobs.len(), 1);
   assert_eq!(jobs[0].command, "process input.txt in /data");
 }
+
+#[test]
+fn test_circular_variables_error() {
+  let cl = create_test_cluster(1);
+  let cf = create_test_config(1);
+  let cluster = ClusterConfig::new(&cl, &cf);
+  let variables = vec![
+    test_variable("A", CompleteVar::Scalar(Scalar::String("${B}".to_string()))),
+    test_variable("B", CompleteVar::Scalar(Scalar::String("${A}".to_string()))),
+  ];
+
+  let result = Job::generate_from(
+    &cluster,
+    &variables,
+    "echo ${A}".to_string(),
+    None,
+    None,
+    None,
+  );
+
+  assert!(matches!(result, Err(JobError::CircularVariables(cycle)) if cycle == "A -> B -> A"));
+}
@@ -1,14 +1,16 @@
-use chrono::Local;
-use chrono::{DateTime, NaiveDateTime, NaiveTime, ParseError, Timelike, Utc};
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, ParseError, TimeZone, Timelike, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use std::collections::VecDeque;
 use std::fs::create_dir_all;
 use std::io::{Error, Write};
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use uuid::Uuid;
 
 use crate::core::database::models::Status;
 use crate::core::jobs::JobLog;
+use crate::core::sbatchman_configs::DisplayTimezone;
 use crate::core::{database::models::Job, jobs::JobError};
 
 pub fn map_err_adding_description(error: Error, description: &str) -> JobError {
@@ -87,6 +89,43 @@ pub fn escape_for_printf(s: &str) -> String {
   result
 }
 
+/// `s` in single quotes, to be passed to the shell as a single word
+pub fn shell_quote(s: &str) -> String {
+  format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Command line running `words` as given (ex. the argv of `run`), the words with characters
+/// special to the shell are quoted
+pub fn shell_join(words: &[String]) -> String {
+  words
+    .iter()
+    .map(|word| {
+      let plain = !word.is_empty()
+        && word
+          .chars()
+          .all(|c| c.is_ascii_alphanumeric() || "_-./:=,+%@".contains(c));
+      if plain {
+        word.clone()
+      } else {
+        shell_quote(word)
+      }
+    })
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Value of a scheduler directive (ex. `#SBATCH --output=<value>`), in double quotes if it has
+/// whitespace. Quotes and line breaks cannot be written in a directive, they are rejected.
+pub fn quote_directive_value(value: &str) -> Result<String, JobError> {
+  if value.contains(['"', '\n', '\r']) {
+    return Err(JobError::InvalidDirectiveValue(value.to_string()));
+  }
+  if value.contains(char::is_whitespace) {
+    return Ok(format!("\"{}\"", value));
+  }
+  Ok(value.to_string())
+}
+
 pub fn serialize_log_entry(log: JobLog, additional_data: Option<serde_json::Value>) -> Value {
   let mut log_entry = match log {
     JobLog::BashVariable(var_name) => {
@@ -114,6 +153,13 @@ pub fn serialize_log_entry(log: JobLog, additional_data: Option<serde_json::Valu
   log_entry
 }
 
+/// New id of a launch: its UTC time followed by a random suffix (ex. `20251101-093000-1a2b3c4d`),
+/// so that the ids sort by launch time and launches in the same second differ
+pub fn new_launch_id() -> String {
+  let suffix = Uuid::new_v4().simple().to_string();
+  format!("{}-{}", Utc::now().format("%Y%m%d-%H%M%S"), &suffix[..8])
+}
+
 /// Parse time string in format "HH:MM:SS" or "D-HH:MM:SS" to seconds
 /// Compatible with SLURM, PBS, and local schedulers
 pub fn parse_time_to_seconds(time_str: &str) -> Result<u64, JobError> {
@@ -137,8 +183,74 @@ pub fn parse_time_to_seconds(time_str: &str) -> Result<u64, JobError> {
   Ok(total_seconds)
 }
 
-pub fn parse_timestamp(timestamp: &str) -> Result<NaiveDateTime, ParseError> {
-  NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.3f")
+/// Parse a duration such as `90s`, `30m`, `12h` or `7d` to seconds. Time limits (`HH:MM:SS` or
+/// `D-HH:MM:SS`) are accepted too.
+pub fn parse_duration_to_seconds(duration: &str) -> Result<u64, JobError> {
+  let multiplier = match duration.chars().last() {
+    Some('s') => 1,
+    Some('m') => 60,
+    Some('h') => 3600,
+    Some('d') => 86_400,
+    _ => return parse_time_to_seconds(duration),
+  };
+  let number: u64 = duration[..duration.len() - 1]
+    .parse()
+    .map_err(|_| JobError::InvalidTimeFormat(duration.to_string()))?;
+  number
+    .checked_mul(multiplier)
+    .ok_or_else(|| JobError::InvalidTimeFormat(duration.to_string()))
+}
+
+/// Format seconds as "HH:MM:SS", or "D-HH:MM:SS" for one day or more
+pub fn format_seconds_as_time(seconds: u64) -> String {
+  let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+  let time = format!(
+    "{:02}:{:02}:{:02}",
+    seconds / 3600,
+    seconds % 3600 / 60,
+    seconds % 60
+  );
+  if days > 0 {
+    format!("{}-{}", days, time)
+  } else {
+    time
+  }
+}
+
+/// Format unix seconds (ex. `submit_time`) as a date in `timezone`, ex. `2023-11-14 22:13:20 UTC`
+pub fn format_unix_time(seconds: i64, timezone: DisplayTimezone) -> String {
+  let Some(time) = DateTime::from_timestamp(seconds, 0) else {
+    return seconds.to_string();
+  };
+  match timezone {
+    DisplayTimezone::Local => time
+      .with_timezone(&Local)
+      .format("%Y-%m-%d %H:%M:%S")
+      .to_string(),
+    DisplayTimezone::Utc => time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+  }
+}
+
+/// Format of the log timestamps: UTC, with milliseconds (ex. `2025-10-28T09:40:12.366Z`)
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+/// Format of the timestamps written by older versions, in local time without offset
+const LEGACY_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// Parse a log timestamp. RFC 3339 timestamps (UTC or with an explicit offset) are expected,
+/// timestamps of older logs without offset are read as local time.
+pub fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>, ParseError> {
+  if let Ok(datetime) = DateTime::parse_from_rfc3339(timestamp) {
+    return Ok(datetime.with_timezone(&Utc));
+  }
+  let naive = NaiveDateTime::parse_from_str(timestamp, LEGACY_TIMESTAMP_FORMAT)?;
+  // A local time skipped by a DST change has no mapping, fall back to UTC
+  Ok(
+    Local
+      .from_local_datetime(&naive)
+      .earliest()
+      .map_or_else(|| naive.and_utc(), |datetime| datetime.with_timezone(&Utc)),
+  )
 }
 
 pub fn ensure_executable(path: &Path) -> Result<(), JobError> {
@@ -156,8 +268,9 @@ pub fn ensure_executable(path: &Path) -> Result<(), JobError> {
   Ok(())
 }
 
+/// Current time formatted as a log timestamp
 pub fn get_timestamp_string() -> String {
-  Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string()
+  get_timestamp().format(TIMESTAMP_FORMAT).to_string()
 }
 
 /// Make a script file executable (Unix only)
@@ -212,3 +325,69 @@ pub fn make_script_executable(_script_path: &Path) -> Result<(), JobError> {
 //     Some(variables)
 //   }
 // }
+
+/// Marker written between the head and the tail of a truncated output
+pub const TRUNCATION_MARKER: &str = "\n[...truncated...]\n";
+
+/// Writer keeping at most `max_bytes` of what is written to it: the first half goes straight to
+/// `inner`, the last half is buffered and written by `finish`, after a `TRUNCATION_MARKER` if
+/// anything in between was dropped.
+pub struct CappedWriter<W: Write> {
+  inner: W,
+  head_left: usize,
+  tail: VecDeque<u8>,
+  tail_size: usize,
+  truncated: bool,
+}
+
+impl<W: Write> CappedWriter<W> {
+  pub fn new(inner: W, max_bytes: usize) -> Self {
+    let head_size = max_bytes / 2;
+    Self {
+      inner,
+      head_left: head_size,
+      tail: VecDeque::new(),
+      tail_size: max_bytes - head_size,
+      truncated: false,
+    }
+  }
+
+  /// Write the buffered tail and return the inner writer
+  pub fn finish(mut self) -> std::io::Result<W> {
+    if self.truncated {
+      self.inner.write_all(TRUNCATION_MARKER.as_bytes())?;
+    }
+    let (first, second) = self.tail.as_slices();
+    self.inner.write_all(first)?;
+    self.inner.write_all(second)?;
+    self.inner.flush()?;
+    Ok(self.inner)
+  }
+}
+
+impl<W: Write> Write for CappedWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    let head = buf.len().min(self.head_left);
+    self.inner.write_all(&buf[..head])?;
+    self.head_left -= head;
+
+    let rest = &buf[head..];
+    if rest.len() >= self.tail_size {
+      self.truncated |= !self.tail.is_empty() || rest.len() > self.tail_size;
+      self.tail.clear();
+      self.tail.extend(&rest[rest.len() - self.tail_size..]);
+    } else {
+      self.tail.extend(rest);
+      if self.tail.len() > self.tail_size {
+        let excess = self.tail.len() - self.tail_size;
+        self.tail.drain(..excess);
+        self.truncated = true;
+      }
+    }
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.inner.flush()
+  }
+}
@@ -4,9 +4,12 @@ use std::{
 };
 
 use pyo3::{PyResult, Python, types::PyDict};
+use serde::Serialize;
+use serde_json::{Value, json};
 
 use crate::core::{
   cluster_configs::ClusterConfig,
+  jobs::JobError,
   parsers::variables::{BasicVar, CompleteVar, Scalar},
 };
 
@@ -17,6 +20,33 @@ pub fn substitute_and_evaluate(
   dep_graph: &DependencyGraph,
   python_header: &Option<String>,
 ) -> String {
+  trace_substitution(template, values, var_map, dep_graph, python_header).after_python
+}
+
+/// Result of each stage of the substitution of a template
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SubstitutionTrace {
+  pub template: String,
+  pub after_simple: String,
+  pub after_maps: String,
+  pub after_python: String,
+}
+
+impl SubstitutionTrace {
+  /// The fully substituted template
+  pub fn final_command(&self) -> &str {
+    &self.after_python
+  }
+}
+
+/// Same as `substitute_and_evaluate`, keeping the intermediate results
+pub fn trace_substitution(
+  template: &str,
+  values: &HashMap<String, String>,
+  var_map: &HashMap<String, &CompleteVar>,
+  dep_graph: &DependencyGraph,
+  python_header: &Option<String>,
+) -> SubstitutionTrace {
   // First, add all dependent variables to the values map
   let mut all_values = values.clone();
 
@@ -34,17 +64,24 @@ pub fn substitute_and_evaluate(
   let resolved_values = resolve_dependencies(&all_values, dep_graph);
 
   // First, substitute simple variables
-  let mut result = Substitutor::substitute_simple(template, &resolved_values);
+  let after_simple = Substitutor::substitute_simple(template, &resolved_values);
 
   // Then, substitute map references
-  result = Substitutor::substitute_maps(&result, &resolved_values, var_map);
+  let after_maps = Substitutor::substitute_maps(&after_simple, &resolved_values, var_map);
 
   // Finally, evaluate Python expressions
-  if result.contains("!py") {
-    result = PythonEvaluator::evaluate(&result, python_header);
+  let after_python = if after_maps.contains("!py") {
+    PythonEvaluator::evaluate(&after_maps, python_header)
+  } else {
+    after_maps.clone()
+  };
+
+  SubstitutionTrace {
+    template: template.to_string(),
+    after_simple,
+    after_maps,
+    after_python,
   }
-
-  result
 }
 
 fn get_initial_value(var: &CompleteVar) -> Option<String> {
@@ -113,12 +150,14 @@ pub struct DependencyGraph {
 }
 
 impl DependencyGraph {
+  /// Build the graph of the variables used by the job.
+  /// Variables referencing each other cyclically (ex. `A=${B}`, `B=${A}`) are an error.
   pub fn build(
     command: &str,
     preprocess: &Option<String>,
     postprocess: &Option<String>,
     var_map: &HashMap<String, &CompleteVar>,
-  ) -> Self {
+  ) -> Result<Self, JobError> {
     let mut dependencies = HashMap::new();
 
     // Collect all strings to analyze
@@ -142,7 +181,51 @@ impl DependencyGraph {
     // Expand dependencies transitively
     Self::expand_transitive_dependencies(&mut dependencies, var_map);
 
-    DependencyGraph { dependencies }
+    let graph = DependencyGraph { dependencies };
+    if let Some(cycle) = graph.find_cycle() {
+      return Err(JobError::CircularVariables(cycle.join(" -> ")));
+    }
+    Ok(graph)
+  }
+
+  /// Find a cycle of variables, returned as the path from a variable back to itself
+  fn find_cycle(&self) -> Option<Vec<String>> {
+    // Variables whose dependencies have been fully explored without finding a cycle
+    let mut visited = HashSet::new();
+    let mut names: Vec<_> = self.dependencies.keys().collect();
+    names.sort();
+    for name in names {
+      let mut path = vec![];
+      if let Some(cycle) = self.find_cycle_from(name, &mut path, &mut visited) {
+        return Some(cycle);
+      }
+    }
+    None
+  }
+
+  fn find_cycle_from(
+    &self,
+    name: &String,
+    path: &mut Vec<String>,
+    visited: &mut HashSet<String>,
+  ) -> Option<Vec<String>> {
+    if let Some(start) = path.iter().position(|n| n == name) {
+      let mut cycle = path[start..].to_vec();
+      cycle.push(name.clone());
+      return Some(cycle);
+    }
+    if visited.contains(name) {
+      return None;
+    }
+    path.push(name.clone());
+    for dep in self.get_dependencies(name) {
+      if let Some(cycle) = self.find_cycle_from(&dep, path, visited) {
+        return Some(cycle);
+      }
+    }
+    path.pop();
+    visited.insert(name.clone());
+    None
   }
 
   fn expand_transitive_dependencies(
@@ -239,7 +322,13 @@ impl DependencyGraph {
     deps
   }
 
-  fn has_dependencies(&self, var_name: &str) -> bool {
+  /// Variables used by the job, each with the variables directly referenced by its value
+  pub fn edges(&self) -> &HashMap<String, Vec<String>> {
+    &self.dependencies
+  }
+
+  /// Whether the value of `var_name` references other variables
+  pub fn has_dependencies(&self, var_name: &str) -> bool {
     self
       .dependencies
       .get(var_name)
@@ -247,7 +336,8 @@ impl DependencyGraph {
       .unwrap_or(false)
   }
 
-  fn get_dependencies(&self, var_name: &str) -> Vec<String> {
+  /// Variables directly referenced by the value of `var_name`
+  pub fn get_dependencies(&self, var_name: &str) -> Vec<String> {
     self.dependencies.get(var_name).cloned().unwrap_or_default()
   }
 }
@@ -369,7 +459,9 @@ impl CartesianGenerator {
       return vec![HashMap::new()];
     }
 
-    let var_names: Vec<_> = vars.keys().cloned().collect();
+    // Sort the variable names so that the jobs are always generated in the same order
+    let mut var_names: Vec<_> = vars.keys().cloned().collect();
+    var_names.sort();
     let var_values: Vec<_> = var_names.iter().map(|n| &vars[n]).collect();
 
     Self::cartesian_product_recursive(&var_names, &var_values, 0, &mut HashMap::new())
@@ -435,8 +527,7 @@ impl Substitutor {
   ) -> String {
     let mut result = template.to_string();
 
-    // Pattern: ${MAP_VAR}[${KEY_VAR}] or ${MAP_VAR}[literal_key]
-    let re = regex::Regex::new(r"\$\{([^}]+)\}\[([^\]]+)\]").unwrap();
+    let re = map_reference_regex();
 
     // Keep substituting until no more changes (handles nested substitutions)
     loop {
@@ -485,6 +576,69 @@ impl Substitutor {
   }
 }
 
+/// Replace the references to an element of a list variable (ex. `${NODES}[0]`) with the element.
+/// This runs before the other substitutions, so that a list only referenced by index is not
+/// expanded into a sweep. An index past the end of the list is an error.
+pub fn substitute_list_indices(
+  template: &str,
+  var_map: &HashMap<String, &CompleteVar>,
+) -> Result<String, JobError> {
+  let re = regex::Regex::new(r"\$\{([^}]+)\}\[(\d+)\]").unwrap();
+  let mut error = None;
+  let result = re.replace_all(template, |caps: &regex::Captures| {
+    let Some(CompleteVar::List(list)) = var_map.get(&caps[1]) else {
+      return caps[0].to_string();
+    };
+    let element = caps[2]
+      .parse::<usize>()
+      .ok()
+      .and_then(|index| list.get(index));
+    match element.and_then(scalar_to_string) {
+      Some(value) => value,
+      None => {
+        error.get_or_insert(JobError::ListIndexOutOfRange(
+          caps[1].to_string(),
+          caps[2].to_string(),
+          list.len(),
+        ));
+        caps[0].to_string()
+      }
+    }
+  });
+  match error {
+    Some(error) => Err(error),
+    None => Ok(result.into_owned()),
+  }
+}
+
+/// Pattern of a map reference: ${MAP_VAR}[${KEY_VAR}] or ${MAP_VAR}[literal_key]
+fn map_reference_regex() -> regex::Regex {
+  regex::Regex::new(r"\$\{([^}]+)\}\[([^\]]+)\]").unwrap()
+}
+
+/// Check a substituted template for references to keys missing from a map variable.
+/// Keys still containing a variable could not be resolved and are not checked.
+pub fn check_map_keys(
+  substituted: &str,
+  var_map: &HashMap<String, &CompleteVar>,
+) -> Result<(), JobError> {
+  for caps in map_reference_regex().captures_iter(substituted) {
+    let (map_name, key) = (&caps[1], &caps[2]);
+    if key.contains("${") {
+      continue;
+    }
+    if let Some(CompleteVar::StandardMap(map)) = var_map.get(map_name)
+      && !map.contains_key(key)
+    {
+      return Err(JobError::MissingMapKey(
+        map_name.to_string(),
+        key.to_string(),
+      ));
+    }
+  }
+  Ok(())
+}
+
 // Module for Python evaluation
 pub struct PythonEvaluator;
 
@@ -542,6 +696,31 @@ pub fn scalar_to_string(scalar: &Scalar) -> Option<String> {
   }
 }
 
+/// Typed JSON values of the variables for the job of `combo`: a swept variable is recorded with
+/// the value of this job only, the other ones as defined for `cluster_name`.
+pub fn job_variables_json(
+  var_map: &HashMap<String, &CompleteVar>,
+  combo: &HashMap<String, String>,
+  cluster_name: &String,
+) -> Value {
+  Value::Object(
+    var_map
+      .iter()
+      .map(|(name, var)| {
+        let value = match combo.get(name) {
+          Some(value) => var
+            .scalars(cluster_name)
+            .into_iter()
+            .find(|scalar| scalar_to_string(scalar).as_ref() == Some(value))
+            .map_or_else(|| json!(value), Scalar::to_json),
+          None => var.to_json(cluster_name),
+        };
+        (name.clone(), value)
+      })
+      .collect(),
+  )
+}
+
 pub fn get_all_variable_dependencies(
   dep_graph: &DependencyGraph,
   command: &String,
@@ -20,4 +20,8 @@ impl SchedulerTrait for VirtualScheduler {
   fn launch_job(&self, job: &mut Job, cluster_config: &ClusterConfig) -> Result<(), JobError> {
     Ok(())
   }
+
+  fn submit_job_script(&self, _job: &mut Job) -> Result<(), JobError> {
+    Ok(())
+  }
 }
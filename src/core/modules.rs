@@ -0,0 +1,87 @@
+use std::{collections::HashSet, process::Command};
+
+use crate::core::database::models::Config;
+
+#[cfg(test)]
+mod tests;
+
+/// Modules requested by a config through the `modules` param (ex. `gcc/12 openmpi`).
+/// Modules can be separated by spaces or commas.
+pub fn get_config_modules(config: &Config) -> Vec<String> {
+  config
+    .flags
+    .get("modules")
+    .and_then(|modules| modules.as_str())
+    .map(|modules| {
+      modules
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|module| !module.is_empty())
+        .map(str::to_string)
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Parse the output of `module avail` (terse or not, Lmod or Environment Modules) into the set
+/// of available modules. Both the full name (`gcc/12.1.0`) and the name without version (`gcc`)
+/// are included, so that a module can be loaded by its default version.
+pub fn parse_module_avail(output: &str) -> HashSet<String> {
+  let mut modules = HashSet::new();
+  for line in output.lines() {
+    let line = line.trim();
+    // Skip the headers (ex. `---- /opt/modulefiles ----` or `/opt/modulefiles:`), the legend
+    // (ex. `D:  Default Module`) and the hints printed after the list
+    let is_legend = line
+      .split_whitespace()
+      .next()
+      .is_some_and(|token| token.ends_with(':'));
+    if line.starts_with('-') || is_legend || line.contains('"') {
+      continue;
+    }
+    for token in line.split_whitespace() {
+      // Markers like `(D)` or `(L)` follow the module they refer to
+      if token.starts_with('(') {
+        continue;
+      }
+      // Environment Modules mark the default version as `gcc/12(default)`
+      let module = token.split('(').next().unwrap_or(token);
+      modules.insert(module.to_string());
+      if let Some((name, _)) = module.split_once('/') {
+        modules.insert(name.to_string());
+      }
+    }
+  }
+  modules
+}
+
+/// Modules available on this machine, or `None` if the `module` command is not available.
+/// `module` is usually a shell function, so it is run through a login shell.
+pub fn get_available_modules() -> Option<HashSet<String>> {
+  let output = Command::new("bash")
+    .args([
+      "-lc",
+      "command -v module >/dev/null && module -t avail 2>&1",
+    ])
+    .output()
+    .ok()?;
+  if !output.status.success() {
+    return None;
+  }
+  Some(parse_module_avail(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Modules of the configs that are not in `available`, as `(config name, module)` pairs
+pub fn find_missing_modules(
+  configs: &[Config],
+  available: &HashSet<String>,
+) -> Vec<(String, String)> {
+  configs
+    .iter()
+    .flat_map(|config| {
+      get_config_modules(config)
+        .into_iter()
+        .filter(|module| !available.contains(module))
+        .map(|module| (config.config_name.clone(), module))
+    })
+    .collect()
+}
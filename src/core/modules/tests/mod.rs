@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+
+use serde_json::json;
+
+use crate::core::{
+  database::models::Config,
+  modules::{find_missing_modules, get_config_modules, parse_module_avail},
+};
+
+fn create_config(name: &str, modules: &str) -> Config {
+  Config {
+    id: 1,
+    config_name: name.to_string(),
+    cluster_id: 1,
+    flags: json!({"modules": modules}),
+    env: json!({}),
+    ..Default::default()
+  }
+}
+
+fn to_set(modules: &[&str]) -> HashSet<String> {
+  modules.iter().map(|m| m.to_string()).collect()
+}
+
+#[test]
+fn test_parse_module_avail_terse() {
+  let output = "/opt/modulefiles:\ngcc/11.2.0\ngcc/12.1.0(default)\nopenmpi/4.1.4\ncmake\n";
+  assert_eq!(
+    parse_module_avail(output),
+    to_set(&[
+      "gcc/11.2.0",
+      "gcc/12.1.0",
+      "gcc",
+      "openmpi/4.1.4",
+      "openmpi",
+      "cmake"
+    ])
+  );
+}
+
+#[test]
+fn test_parse_module_avail_lmod() {
+  let output = "
+----------------------- /opt/modulefiles -----------------------
+   cuda/12.2 (L)    gcc/12.1.0 (D)    python/3.11
+
+  Where:
+   D:  Default Module
+   L:  Module is loaded
+
+Use \"module spider\" to find all possible modules and extensions.
+";
+  assert_eq!(
+    parse_module_avail(output),
+    to_set(&[
+      "cuda/12.2",
+      "cuda",
+      "gcc/12.1.0",
+      "gcc",
+      "python/3.11",
+      "python"
+    ])
+  );
+}
+
+#[test]
+fn test_find_missing_modules() {
+  let configs = vec![
+    create_config("cpu", "gcc/12.1.0, openmpi"),
+    create_config("gpu", "gcc cuda/11.8"),
+  ];
+  assert_eq!(
+    get_config_modules(&configs[0]),
+    vec!["gcc/12.1.0", "openmpi"]
+  );
+
+  let available = to_set(&["gcc/12.1.0", "gcc", "openmpi/4.1.4", "openmpi"]);
+  assert_eq!(
+    find_missing_modules(&configs, &available),
+    vec![("gpu".to_string(), "cuda/11.8".to_string())]
+  );
+}
@@ -1,4 +1,5 @@
 mod configs;
+mod env_file;
 mod includes;
 mod jobs;
 mod utils;
@@ -9,8 +10,10 @@ mod tests;
 
 use thiserror::Error;
 
-pub use configs::parse_clusters_configs_from_file;
-pub use jobs::{ParsedJob, parse_jobs_from_file};
+pub use configs::{parse_clusters_configs_from_file, unknown_cluster_keys_in_file};
+pub use env_file::parse_env_file;
+pub use includes::get_include_variables;
+pub use jobs::{ParsedJob, parse_jobs_from_file, parse_jobs_from_reader};
 
 #[derive(Error, Debug)]
 pub enum ParserError {
@@ -34,8 +37,22 @@ pub enum ParserError {
   WrongType(String, String),
   #[error("Include error: {0} is neither a string nor a sequence")]
   IncludeWrongType(String),
-  #[error("Scheduler \"{0}\" is invalid. Valid options are: Local, Slurm, Pbs")]
+  #[error("Cannot resolve include {0:?} (cleaned up as {1:?}): {2}")]
+  IncludeNotFound(String, String, std::io::Error),
+  #[error("Scheduler \"{0}\" is invalid. Valid options are: Local, Slurm, Pbs, Custom")]
   InvalidScheduler(String),
   #[error("Invalid parameter \"{0}\" for scheduler {1:?}")]
   InvalidParameterForScheduler(String, String),
+  #[error("Unknown key \"{0}\", did you mean \"{1}\"?")]
+  UnknownKey(String, String),
+  #[error("Config \"{0}\" extends unknown config \"{1}\"")]
+  UnknownExtends(String, String),
+  #[error("Circular extends between configs: {0}")]
+  CircularExtends(String),
+  #[error("Invalid regex \"{0}\": {1}")]
+  InvalidRegex(String, String),
+  #[error("Invalid line {0} of env file, expected KEY=VALUE: {1}")]
+  InvalidEnvLine(usize, String),
+  #[error("Variable \"{0}\" has a per_cluster value for unknown cluster \"{1}\"")]
+  UnknownClusterKey(String, String),
 }
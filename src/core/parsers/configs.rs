@@ -6,19 +6,21 @@ use std::{
 
 use hashlink::LinkedHashMap;
 use once_cell::sync::Lazy;
-use saphyr::YamlOwned;
-use serde_json::json;
+use regex::Regex;
+use saphyr::{ScalarOwned, YamlOwned};
+use serde_json::{Value, json};
 
 use crate::core::{
-  database::models::{NewCluster, NewClusterConfig, NewConfig, Scheduler},
+  database::models::{NewCluster, NewClusterConfig, NewConfig, RetryPolicy, Scheduler},
+  jobs::variable_substitutions::{recursive_substitute, scalar_to_string},
   parsers::{
     ParserError,
     includes::get_include_variables,
     utils::{
-      load_yaml_from_file, lookup_mapping, lookup_sequence, lookup_str, to_mapping, to_string,
-      value_from_str, yaml_lookup,
+      check_known_keys, load_yaml_from_file, lookup_mapping, lookup_optional_str, lookup_sequence,
+      lookup_str, to_mapping, to_sequence, to_string, value_from_str, yaml_lookup,
     },
-    variables::{Variable, parse_variables},
+    variables::{BasicVar, CompleteVar, Variable, parse_variables, unknown_cluster_keys},
   },
 };
 
@@ -37,19 +39,103 @@ impl Scheduler {
     ])
   });
 
-  fn has_param(&self, param: &str) -> bool {
+  pub(crate) fn has_param(&self, param: &str) -> bool {
     match self {
       Scheduler::Local => Self::LOCAL_PARAMS.contains(param),
       Scheduler::Slurm => Self::SLURM_PARAMS.contains(param),
       Scheduler::Pbs => Self::PBS_PARAMS.contains(param),
+      // Custom schedulers are site-specific, any parameter is emitted as a directive
+      Scheduler::Custom => true,
     }
   }
+
+  /// Flag holding the time limit of the jobs. `None` for custom schedulers, whose flags are
+  /// site-specific.
+  fn time_param(&self) -> Option<&'static str> {
+    match self {
+      Scheduler::Local | Scheduler::Slurm => Some("time"),
+      Scheduler::Pbs => Some("walltime"),
+      Scheduler::Custom => None,
+    }
+  }
+
+  /// Whether jobs without a time limit run until a site default instead of a limit of their own
+  fn requires_time_limit(&self) -> bool {
+    matches!(self, Scheduler::Slurm | Scheduler::Pbs)
+  }
 }
 
-#[derive(Default)]
+/// A parameter value. `None` marks an explicit unset (YAML `null` or `!unset`), which removes an
+/// inherited value when merging.
+pub(crate) type ParamValue = Option<Value>;
+
+#[derive(Default, Clone)]
 struct Parameters {
-  options: HashMap<String, String>,
-  env: HashMap<String, String>,
+  options: HashMap<String, ParamValue>,
+  env: HashMap<String, ParamValue>,
+}
+
+impl Parameters {
+  /// Merge `other` over `self`. Values in `other` win, explicit unsets remove the inherited key.
+  fn merge(&self, other: &Parameters) -> Parameters {
+    let merge_map = |base: &HashMap<String, ParamValue>, over: &HashMap<String, ParamValue>| {
+      let mut merged = base.clone();
+      for (key, value) in over {
+        match value {
+          Some(_) => merged.insert(key.clone(), value.clone()),
+          None => merged.remove(key),
+        };
+      }
+      merged
+    };
+    Parameters {
+      options: merge_map(&self.options, &other.options),
+      env: merge_map(&self.env, &other.env),
+    }
+  }
+
+  /// Put `other` over `self`, keeping the explicit unsets so that they still apply when
+  /// the result is merged over the cluster defaults
+  fn overlay(&self, other: &Parameters) -> Parameters {
+    let mut overlaid = self.clone();
+    overlaid.options.extend(other.options.clone());
+    overlaid.env.extend(other.env.clone());
+    overlaid
+  }
+
+  fn options_to_json(&self) -> Value {
+    Self::to_json(&self.options)
+  }
+
+  fn env_to_json(&self) -> Value {
+    Self::to_json(&self.env)
+  }
+
+  /// Convert to a JSON object, dropping unset keys
+  fn to_json(map: &HashMap<String, ParamValue>) -> Value {
+    Value::Object(
+      map
+        .iter()
+        .filter_map(|(k, v)| v.as_ref().map(|v| (k.clone(), v.clone())))
+        .collect(),
+    )
+  }
+}
+
+/// Convert a YAML param node to a JSON value. `null` and `!unset` are mapped to `None`.
+pub(crate) fn parse_param_value(yaml: &YamlOwned) -> Result<ParamValue, ParserError> {
+  match yaml {
+    YamlOwned::Value(ScalarOwned::Null) => Ok(None),
+    YamlOwned::Tagged(tag, _) if tag.suffix == "unset" => Ok(None),
+    YamlOwned::Value(ScalarOwned::String(s)) => Ok(Some(json!(s))),
+    YamlOwned::Value(ScalarOwned::Integer(i)) => Ok(Some(json!(i))),
+    YamlOwned::Value(ScalarOwned::FloatingPoint(f)) => Ok(Some(json!(**f))),
+    YamlOwned::Value(ScalarOwned::Boolean(b)) => Ok(Some(json!(b))),
+    _ => Err(ParserError::WrongType(
+      format!("{:?}", yaml),
+      "string, integer, float, boolean, null or !unset".to_string(),
+    )),
+  }
 }
 
 // Takes as input a mapping and returns an object containing the list of options and env variables
@@ -64,7 +150,7 @@ fn parse_params(
     let mut env = HashMap::new();
     for (key_node, value_node) in env_mapping {
       let key = to_string(key_node)?;
-      let value = to_string(value_node)?;
+      let value = parse_param_value(value_node)?;
       env.insert(key, value);
     }
     params.env = env;
@@ -83,45 +169,328 @@ fn parse_params(
         format!("{:?}", scheduler),
       ));
     }
-    let value = to_string(value_node)?;
+    let value = parse_param_value(value_node)?;
     params.options.insert(key, value);
   }
   Ok(params)
 }
 
+/// Params and variables of a config, including the ones inherited through `extends`.
+/// The cluster defaults are not included.
+#[derive(Default, Clone)]
+struct ConfigEntry {
+  params: Parameters,
+  variables: LinkedHashMap<String, Variable>,
+  processing: DefaultProcessing,
+  retry_on_timeout: Option<RetryPolicy>,
+  max_output_bytes: Option<i64>,
+  shell: Option<String>,
+  extra_headers: Option<Vec<String>>,
+  env_snapshot: Option<Vec<String>>,
+}
+
+/// Commands prepended/appended to the pre/postprocessing of every job
+#[derive(Default, Clone)]
+struct DefaultProcessing {
+  preprocess: Option<String>,
+  postprocess: Option<String>,
+}
+
+impl DefaultProcessing {
+  fn parse(node: &YamlOwned) -> Result<Self, ParserError> {
+    Ok(Self {
+      preprocess: lookup_optional_str(node, "default_preprocess")?,
+      postprocess: lookup_optional_str(node, "default_postprocess")?,
+    })
+  }
+
+  /// Put `other` over `self`, the keys set in `other` win
+  fn overlay(&self, other: &DefaultProcessing) -> DefaultProcessing {
+    DefaultProcessing {
+      preprocess: other.preprocess.clone().or_else(|| self.preprocess.clone()),
+      postprocess: other
+        .postprocess
+        .clone()
+        .or_else(|| self.postprocess.clone()),
+    }
+  }
+
+  /// Run `self` (the cluster defaults) before `other`
+  fn chain(&self, other: &DefaultProcessing) -> DefaultProcessing {
+    let join = |first: &Option<String>, second: &Option<String>| match (first, second) {
+      (Some(first), Some(second)) => Some(format!("{}\n{}", first, second)),
+      _ => first.clone().or_else(|| second.clone()),
+    };
+    DefaultProcessing {
+      preprocess: join(&self.preprocess, &other.preprocess),
+      postprocess: join(&self.postprocess, &other.postprocess),
+    }
+  }
+}
+
+/// Parse the `max_output_bytes` cap of the job logs of a config, if any
+fn parse_max_output_bytes(config: &YamlOwned) -> Result<Option<i64>, ParserError> {
+  let Some(max_output_bytes) = yaml_lookup(config, "max_output_bytes") else {
+    return Ok(None);
+  };
+  max_output_bytes
+    .as_integer()
+    .filter(|n| *n > 0)
+    .map(Some)
+    .ok_or_else(|| {
+      ParserError::WrongType(
+        format!("{:?}", max_output_bytes),
+        "positive integer".to_string(),
+      )
+    })
+}
+
+/// Parse the `submit_interval_ms` pause between the submissions to a cluster, if any
+fn parse_submit_interval_ms(cluster: &YamlOwned) -> Result<Option<i32>, ParserError> {
+  let Some(interval) = yaml_lookup(cluster, "submit_interval_ms") else {
+    return Ok(None);
+  };
+  interval
+    .as_integer()
+    .and_then(|ms| i32::try_from(ms).ok())
+    .filter(|ms| *ms >= 0)
+    .map(Some)
+    .ok_or_else(|| {
+      ParserError::WrongType(
+        format!("{:?}", interval),
+        "non-negative integer".to_string(),
+      )
+    })
+}
+
+/// Parse a list of strings of a config (ex. the raw `extra_headers` lines), if any
+fn parse_string_list(config: &YamlOwned, key: &str) -> Result<Option<Vec<String>>, ParserError> {
+  let Some(list) = yaml_lookup(config, key) else {
+    return Ok(None);
+  };
+  to_sequence(list)?
+    .iter()
+    .map(to_string)
+    .collect::<Result<Vec<_>, _>>()
+    .map(Some)
+}
+
+/// Time multiplier of a `retry_on_timeout` policy that does not set it
+const DEFAULT_TIME_MULTIPLIER: f64 = 2.0;
+
+/// Parse the `retry_on_timeout` policy of a config, if any
+fn parse_retry_policy(config: &YamlOwned) -> Result<Option<RetryPolicy>, ParserError> {
+  let Some(policy) = yaml_lookup(config, "retry_on_timeout") else {
+    return Ok(None);
+  };
+  check_known_keys(policy, &["max_retries", "time_multiplier"])?;
+  let max_retries = yaml_lookup(policy, "max_retries")
+    .ok_or_else(|| ParserError::MissingKey("max_retries".to_string()))?;
+  let max_retries = max_retries
+    .as_integer()
+    .and_then(|n| u32::try_from(n).ok())
+    .ok_or_else(|| {
+      ParserError::WrongType(
+        format!("{:?}", max_retries),
+        "non-negative integer".to_string(),
+      )
+    })?;
+  let time_multiplier = match yaml_lookup(policy, "time_multiplier") {
+    Some(multiplier) => multiplier
+      .as_floating_point()
+      .or_else(|| multiplier.as_integer().map(|n| n as f64))
+      .filter(|m| *m >= 1.0)
+      .ok_or_else(|| {
+        ParserError::WrongType(
+          format!("{:?}", multiplier),
+          "number greater than or equal to 1".to_string(),
+        )
+      })?,
+    None => DEFAULT_TIME_MULTIPLIER,
+  };
+  Ok(Some(RetryPolicy {
+    max_retries,
+    time_multiplier,
+  }))
+}
+
+/// Values of the scalar variables, the cluster maps resolved for `cluster_name`. Lists are left
+/// out: a config is not expanded into one config per value.
+fn scalar_values(
+  variables: &LinkedHashMap<String, Variable>,
+  cluster_name: &str,
+) -> HashMap<String, String> {
+  variables
+    .iter()
+    .filter_map(|(name, variable)| {
+      let scalar = match &variable.contents {
+        CompleteVar::Scalar(scalar) => scalar,
+        CompleteVar::ClusterMap(map) => match map.get(&cluster_name.to_string())? {
+          BasicVar::Scalar(scalar) => scalar,
+          BasicVar::List(_) => return None,
+        },
+        _ => return None,
+      };
+      Some((name.clone(), scalar_to_string(scalar)?))
+    })
+    .collect()
+}
+
+/// Parse a config of `cluster_name`. `inherited_variables` are the top-level variables
+/// overridden by the cluster ones.
 fn parse_config(
   config: &YamlOwned,
   scheduler: &Scheduler,
-  top_variables: &LinkedHashMap<String, Variable>,
-  cluster_variables: &LinkedHashMap<String, Variable>,
+  cluster_name: &str,
+  inherited_variables: &LinkedHashMap<String, Variable>,
   cluster_params: &Parameters,
-) -> Result<NewConfig, ParserError> {
-  // Parse variables
-  let cluster_variables = match lookup_mapping(config, "variables") {
-    Ok(variables) => parse_variables(variables)?,
-    Err(_) => LinkedHashMap::new(),
-  };
+  cluster_processing: &DefaultProcessing,
+  parent: Option<&ConfigEntry>,
+) -> Result<(NewConfig, ConfigEntry), ParserError> {
+  let parent = parent.cloned().unwrap_or_default();
 
-  // Parse params (options and env)
-  let cluster_params = match lookup_mapping(config, "params") {
-    Ok(defaults) => parse_params(defaults, &scheduler)?,
-    Err(_) => Parameters::default(),
+  // Parse variables. They override the ones of the extended config
+  let mut config_variables = parent.variables;
+  if let Ok(variables) = lookup_mapping(config, "variables") {
+    for (name, variable) in parse_variables(variables)? {
+      config_variables.replace(name, variable);
+    }
+  }
+
+  // Parse params (options and env). They override the ones of the extended config
+  let config_params = match lookup_mapping(config, "params") {
+    Ok(params) => parent.params.overlay(&parse_params(params, scheduler)?),
+    Err(_) => parent.params,
   };
 
-  // Name
-  let name = lookup_str(config, "name")?;
-  // TODO: substitute variables in name
+  // Config params override the cluster defaults. Explicitly unset keys are dropped.
+  let params = cluster_params.merge(&config_params);
+
+  // Default pre/postprocessing. They override the ones of the extended config and run after the
+  // ones of the cluster
+  let config_processing = parent
+    .processing
+    .overlay(&DefaultProcessing::parse(config)?);
+  let processing = cluster_processing.chain(&config_processing);
+
+  // Resubmission on timeout. It overrides the one of the extended config
+  let retry_on_timeout = parse_retry_policy(config)?.or(parent.retry_on_timeout);
+
+  // Size cap of the job logs. It overrides the one of the extended config
+  let max_output_bytes = parse_max_output_bytes(config)?.or(parent.max_output_bytes);
+
+  // Shell of the job scripts. It overrides the one of the extended config, the one of the cluster
+  // is used by `parse_cluster` if none is set
+  let shell = lookup_optional_str(config, "shell")?.or(parent.shell);
 
-  // TODO: Generate correct flags and env by merging top_variables, cluster_variables, cluster_params, and config-specific ones
-  let flags: Vec<String> = vec![];
-  let env: Vec<String> = vec![];
+  // Raw header lines of the job scripts. They override the ones of the extended config
+  let extra_headers = parse_string_list(config, "extra_headers")?.or(parent.extra_headers);
 
-  Ok(NewConfig {
+  // Prefixes of the environment variables logged at launch. They override the ones of the
+  // extended config
+  let env_snapshot = parse_string_list(config, "env_snapshot")?.or(parent.env_snapshot);
+
+  // Effective variables: top-level < cluster < config, the later ones win
+  let mut var_map = inherited_variables.clone();
+  for (name, variable) in &config_variables {
+    var_map.replace(name.clone(), variable.clone());
+  }
+
+  // Name, with the `${var}` of the scalar variables substituted
+  let name = recursive_substitute(
+    &lookup_str(config, "name")?,
+    &scalar_values(&var_map, cluster_name),
+  );
+
+  // TODO: substitute var_map in flags and env
+  let new_config = NewConfig {
     config_name: name,
     cluster_id: 0,
-    flags: json!(flags),
-    env: json!(env),
-  })
+    flags: params.options_to_json(),
+    env: params.env_to_json(),
+    default_preprocess: processing.preprocess,
+    default_postprocess: processing.postprocess,
+    retry_on_timeout: retry_on_timeout.as_ref().map(|policy| json!(policy)),
+    max_output_bytes,
+    shell: shell.clone(),
+    // Describes this config only, it is not inherited through `extends`
+    description: lookup_optional_str(config, "description")?,
+    extra_headers: extra_headers.as_ref().map(|headers| json!(headers)),
+    env_snapshot: env_snapshot.as_ref().map(|prefixes| json!(prefixes)),
+  };
+  let entry = ConfigEntry {
+    params: config_params,
+    variables: config_variables,
+    processing: config_processing,
+    retry_on_timeout,
+    max_output_bytes,
+    shell,
+    extra_headers,
+    env_snapshot,
+  };
+  Ok((new_config, entry))
+}
+
+/// Order in which the configs must be parsed: each config comes after the one it `extends`.
+/// Each index is paired with the one of its parent, matched on the names as written (before the
+/// variables are substituted). Returns an error if a config extends an unknown config or if there
+/// is a cycle.
+fn extends_order(configs: &[YamlOwned]) -> Result<Vec<(usize, Option<usize>)>, ParserError> {
+  let names = configs
+    .iter()
+    .map(|config| lookup_str(config, "name"))
+    .collect::<Result<Vec<_>, _>>()?;
+  let extends = configs
+    .iter()
+    .map(|config| lookup_optional_str(config, "extends"))
+    .collect::<Result<Vec<_>, _>>()?;
+  let indices: HashMap<&str, usize> = names
+    .iter()
+    .enumerate()
+    .map(|(i, name)| (name.as_str(), i))
+    .collect();
+
+  let parents = extends
+    .iter()
+    .enumerate()
+    .map(|(i, parent)| {
+      parent
+        .as_ref()
+        .map(|parent| {
+          indices
+            .get(parent.as_str())
+            .copied()
+            .ok_or_else(|| ParserError::UnknownExtends(names[i].clone(), parent.clone()))
+        })
+        .transpose()
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let mut order = vec![];
+  let mut ordered = vec![false; configs.len()];
+  for start in 0..configs.len() {
+    // Follow the chain of `extends` up to a config already ordered or without parent
+    let mut chain: Vec<usize> = vec![];
+    let mut current = Some(start);
+    while let Some(i) = current {
+      if ordered[i] {
+        break;
+      }
+      if let Some(pos) = chain.iter().position(|&c| c == i) {
+        let mut cycle: Vec<&str> = chain[pos..].iter().map(|&c| names[c].as_str()).collect();
+        cycle.push(&names[i]);
+        return Err(ParserError::CircularExtends(cycle.join(" -> ")));
+      }
+      chain.push(i);
+      current = parents[i];
+    }
+    // Parents first
+    for &i in chain.iter().rev() {
+      ordered[i] = true;
+      order.push((i, parents[i]));
+    }
+  }
+  Ok(order)
 }
 
 fn parse_cluster(
@@ -129,52 +498,143 @@ fn parse_cluster(
   cluster: &saphyr::YamlOwned,
   top_variables: &LinkedHashMap<String, Variable>,
 ) -> Result<NewClusterConfig, ParserError> {
-  // Parse scheduler
-  let scheduler_str = lookup_str(cluster, "scheduler")?;
-  let scheduler = Scheduler::from_str(&scheduler_str)
-    .map_err(|_| ParserError::InvalidScheduler(scheduler_str.clone()))?;
-
-  // Parse cluster-level variables
-  let cluster_variables = match lookup_mapping(cluster, "variables") {
-    Ok(variables) => parse_variables(variables)?,
-    Err(_) => LinkedHashMap::new(),
+  // Parse scheduler. Without one, the cluster runs its jobs locally
+  let scheduler = match lookup_optional_str(cluster, "scheduler")? {
+    Some(scheduler_str) => Scheduler::from_str(&scheduler_str)
+      .map_err(|_| ParserError::InvalidScheduler(scheduler_str.clone()))?,
+    None => {
+      log::warn!(
+        "Cluster \"{}\" sets no scheduler, using the Local one",
+        cluster_name
+      );
+      Scheduler::Local
+    }
   };
 
-  // Parse cluster-level default params (options and env)
-  let cluster_params = match lookup_mapping(cluster, "defaults") {
+  // Parse cluster-level variables. They override the top-level ones
+  let mut variables = top_variables.clone();
+  if let Ok(cluster_variables) = lookup_mapping(cluster, "variables") {
+    for (name, variable) in parse_variables(cluster_variables)? {
+      variables.replace(name, variable);
+    }
+  }
+
+  // Parse cluster-level default params (options and env). `defaults` is accepted as an alias
+  let cluster_params = match lookup_mapping(cluster, "default_params")
+    .or_else(|_| lookup_mapping(cluster, "defaults"))
+  {
     Ok(defaults) => parse_params(defaults, &scheduler)?,
     Err(_) => Parameters::default(),
   };
 
+  // Cluster-level default pre/postprocessing, run before the ones of the configs
+  let cluster_processing = DefaultProcessing::parse(cluster)?;
+
+  // Cluster-level shell of the job scripts, for the configs that do not set one
+  let cluster_shell = lookup_optional_str(cluster, "shell")?;
+
+  // Cluster-level time limit, for the configs that do not set one
+  let default_time = lookup_optional_str(cluster, "default_time")?;
+
   // Max jobs
   let max_jobs = yaml_lookup(cluster, "max_jobs")
     .and_then(|n| n.as_integer())
     .map(|i| i as i32);
 
+  // Templates of custom schedulers
+  let header_prefix = lookup_optional_str(cluster, "header_prefix")?;
+  let submit_command = lookup_optional_str(cluster, "submit_command")?;
+  let job_id_regex = lookup_optional_str(cluster, "job_id_regex")?;
+  let pre_submit_hook = lookup_optional_str(cluster, "pre_submit_hook")?;
+  let submit_interval_ms = parse_submit_interval_ms(cluster)?;
+  if scheduler == Scheduler::Custom {
+    if header_prefix.is_none() {
+      return Err(ParserError::MissingKey("header_prefix".to_string()));
+    }
+    if submit_command.is_none() {
+      return Err(ParserError::MissingKey("submit_command".to_string()));
+    }
+  }
+  if let Some(regex) = &job_id_regex {
+    Regex::new(regex).map_err(|e| ParserError::InvalidRegex(regex.clone(), e.to_string()))?;
+  }
+
   // Configs
   let mut parsed_cluster = NewClusterConfig {
     cluster: NewCluster {
-      cluster_name: cluster_name,
+      cluster_name,
       scheduler: scheduler.clone(),
       max_jobs,
+      header_prefix,
+      submit_command,
+      job_id_regex,
+      pre_submit_hook,
+      submit_interval_ms,
     },
     configs: vec![],
   };
 
+  // Configs are parsed after the ones they extend, but returned in the order of the file
   let configs = lookup_sequence(cluster, "configs")?;
-  for config in configs.iter() {
-    parsed_cluster.configs.push(parse_config(
-      config,
+  let mut parsed_configs: HashMap<usize, (NewConfig, ConfigEntry)> = HashMap::new();
+  for (i, parent) in extends_order(configs)? {
+    let parsed = parse_config(
+      &configs[i],
       &scheduler,
-      top_variables,
-      &cluster_variables,
+      &parsed_cluster.cluster.cluster_name,
+      &variables,
       &cluster_params,
-    )?);
+      &cluster_processing,
+      parent.map(|parent| &parsed_configs[&parent].1),
+    )?;
+    parsed_configs.insert(i, parsed);
+  }
+  for i in 0..configs.len() {
+    if let Some((mut new_config, _)) = parsed_configs.remove(&i) {
+      new_config.shell = new_config.shell.or_else(|| cluster_shell.clone());
+      apply_default_time(
+        &mut new_config,
+        &scheduler,
+        &default_time,
+        &parsed_cluster.cluster.cluster_name,
+      );
+      parsed_cluster.configs.push(new_config);
+    }
   }
 
   Ok(parsed_cluster)
 }
 
+/// Set the `default_time` of the cluster on a config without time limit. Warns if the config is
+/// still unbounded on a scheduler that needs a time limit.
+fn apply_default_time(
+  config: &mut NewConfig,
+  scheduler: &Scheduler,
+  default_time: &Option<String>,
+  cluster_name: &str,
+) {
+  let Some(time_param) = scheduler.time_param() else {
+    return;
+  };
+  let Some(flags) = config.flags.as_object_mut() else {
+    return;
+  };
+  if flags.contains_key(time_param) {
+    return;
+  }
+  match default_time {
+    Some(default_time) => {
+      flags.insert(time_param.to_string(), json!(default_time));
+    }
+    None if scheduler.requires_time_limit() => log::warn!(
+      "Config \"{}\" of cluster \"{}\" has no time limit, its jobs run until the scheduler default",
+      config.config_name,
+      cluster_name
+    ),
+    None => {}
+  }
+}
+
 /// Parse cluster configurations from a YAML file
 pub fn parse_clusters_configs_from_file(root: &Path) -> Result<Vec<NewClusterConfig>, ParserError> {
   let variables = get_include_variables(root)?;
@@ -191,3 +651,37 @@ pub fn parse_clusters_configs_from_file(root: &Path) -> Result<Vec<NewClusterCon
   }
   Ok(parsed_clusters)
 }
+
+/// `(variable, cluster)` pairs of the `per_cluster` keys of the variables of a clusters
+/// configuration file (top-level, cluster and config ones) naming neither a cluster of the file
+/// nor one of `known_clusters`
+pub fn unknown_cluster_keys_in_file(
+  root: &Path,
+  known_clusters: &[String],
+) -> Result<Vec<(String, String)>, ParserError> {
+  let yaml = load_yaml_from_file(root)?;
+  let clusters = lookup_mapping(&yaml, "clusters").map_err(|_| ParserError::EmptyClusterConfig)?;
+  let mut cluster_names: HashSet<String> = known_clusters.iter().cloned().collect();
+  for cluster_name in clusters.keys() {
+    cluster_names.insert(to_string(cluster_name)?);
+  }
+
+  let mut unknown = unknown_cluster_keys(&get_include_variables(root)?, &cluster_names);
+  for cluster in clusters.values() {
+    if let Ok(variables) = lookup_mapping(cluster, "variables") {
+      unknown.extend(unknown_cluster_keys(
+        &parse_variables(variables)?,
+        &cluster_names,
+      ));
+    }
+    for config in lookup_sequence(cluster, "configs")? {
+      if let Ok(variables) = lookup_mapping(config, "variables") {
+        unknown.extend(unknown_cluster_keys(
+          &parse_variables(variables)?,
+          &cluster_names,
+        ));
+      }
+    }
+  }
+  Ok(unknown)
+}
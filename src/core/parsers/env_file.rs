@@ -0,0 +1,30 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::core::parsers::ParserError;
+
+/// Parse a `.env` file of `KEY=VALUE` lines. Empty lines and `#` comments are skipped, an
+/// `export ` prefix is allowed and quotes around the value are removed.
+pub fn parse_env_file(path: &Path) -> Result<HashMap<String, String>, ParserError> {
+  let content = fs::read_to_string(path)?;
+  let mut env = HashMap::new();
+  for (number, line) in content.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let invalid = || ParserError::InvalidEnvLine(number + 1, line.to_string());
+    let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+    let key = key.trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+      return Err(invalid());
+    }
+    let value = value.trim();
+    let value = ['"', '\'']
+      .iter()
+      .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+      .unwrap_or(value);
+    env.insert(key.to_string(), value.to_string());
+  }
+  Ok(env)
+}
@@ -6,14 +6,18 @@ use crate::core::parsers::utils::{load_yaml_from_file, lookup_mapping, yaml_look
 use crate::core::parsers::variables::{Variable, parse_variables};
 use hashlink::LinkedHashMap;
 use log::debug;
+use saphyr::YamlOwned;
 
-/// Push a file to the include list, checking for circular includes
+/// Push a file to the include list, checking for circular includes.
+/// Surrounding whitespace and control characters (ex. the `\r` of Windows line endings) are
+/// trimmed from the path.
 fn push_file_to_include_list(
-  file: &str,
+  raw_file: &str,
   file_path: &Path,
   included_files: &mut Vec<PathBuf>,
   to_include: &mut Vec<PathBuf>,
 ) -> Result<(), ParserError> {
+  let file = raw_file.trim_matches(|c: char| c.is_whitespace() || c.is_control());
   let path = if Path::new(file).is_absolute() {
     // Absolute path
     PathBuf::from(file)
@@ -27,7 +31,8 @@ fn push_file_to_include_list(
       )))?
       .join(file)
   };
-  let canonical_path = fs::canonicalize(&path)?;
+  let canonical_path = fs::canonicalize(&path)
+    .map_err(|e| ParserError::IncludeNotFound(raw_file.to_string(), file.to_string(), e))?;
   // If file is already in the list of included files, we have a circular include, raise an error
   if included_files.contains(&canonical_path) {
     return Err(ParserError::CircularInclude(file.to_string()));
@@ -39,11 +44,30 @@ fn push_file_to_include_list(
 /// Collect all variables from included YAML files. The function performs a depth-first traversal of includes. Variables from later includes override those earlier ones.
 pub fn get_include_variables<'a>(
   root: &Path,
+) -> Result<LinkedHashMap<String, Variable>, ParserError> {
+  collect_include_variables(fs::canonicalize(root)?, None)
+}
+
+/// Same as `get_include_variables`, for a document that is not read from a file (ex. stdin).
+/// Relative includes are resolved against `base_dir`.
+pub fn get_include_variables_from_yaml(
+  yaml: &YamlOwned,
+  base_dir: &Path,
+) -> Result<LinkedHashMap<String, Variable>, ParserError> {
+  // Includes are resolved against the parent of the root path, which does not need to exist
+  collect_include_variables(base_dir.join("-"), Some(yaml.clone()))
+}
+
+/// Collect the variables starting from `root`. If `root_yaml` is given, it is used instead of
+/// loading `root`. `root` must be canonical.
+fn collect_include_variables(
+  root: PathBuf,
+  mut root_yaml: Option<YamlOwned>,
 ) -> Result<LinkedHashMap<String, Variable>, ParserError> {
   // Keep track of included files to prevent circular includes
   let mut included_files = vec![];
   // Start with the initial file
-  let mut to_include = vec![fs::canonicalize(root)?];
+  let mut to_include = vec![root];
   // Final variables collection
   let mut variables = LinkedHashMap::new();
 
@@ -51,7 +75,10 @@ pub fn get_include_variables<'a>(
   while let Some(current_path) = to_include.pop() {
     debug!("Loading included variables from file: {:?}", &current_path);
 
-    let yaml = load_yaml_from_file(&current_path)?;
+    let yaml = match root_yaml.take() {
+      Some(yaml) => yaml,
+      None => load_yaml_from_file(&current_path)?,
+    };
 
     // Parse variables from the current file
     if let Ok(yaml_variables) = lookup_mapping(&yaml, "variables") {
@@ -79,7 +106,8 @@ pub fn get_include_variables<'a>(
         return Err(ParserError::IncludeWrongType(format!("{:?}", node)));
       }
     }
-    included_files.push(fs::canonicalize(current_path)?);
+    // Paths in the include stack are already canonical
+    included_files.push(current_path);
   }
 
   Ok(variables)
@@ -1,22 +1,193 @@
-use std::{
-  fs::{self},
-  path::PathBuf,
-  str::FromStr,
+use std::io::Read;
+use std::path::Path;
+
+use std::collections::HashMap;
+
+use hashlink::LinkedHashMap;
+use saphyr::YamlOwned;
+use serde_json::{Map, Value};
+
+use crate::core::parsers::{
+  ParserError,
+  configs::{ParamValue, parse_param_value},
+  includes::{get_include_variables, get_include_variables_from_yaml},
+  utils::{
+    check_known_keys, load_yaml_from_file, load_yaml_from_str, lookup_mapping,
+    lookup_optional_commands, lookup_optional_str, lookup_sequence, lookup_str, to_string,
+  },
+  variables::{Variable, parse_variables},
 };
 
-use crate::core::{database::models::Job, jobs::JobError, parsers::ParserError};
-use std::path::Path;
+/// A job entry of a jobs file. Variables are not expanded yet: a single entry generates
+/// one job for each combination of the values of its variables.
+#[derive(Debug)]
+pub struct ParsedJob {
+  pub job_name: String,
+  pub config_name: String,
+  pub command: String,
+  pub preprocess: Option<String>,
+  pub postprocess: Option<String>,
+  pub python_header: Option<String>,
+  /// Effective variables of the job: job-level variables override file-level (and included) ones
+  pub variables: LinkedHashMap<String, Variable>,
+  /// Labels attached to every job generated by this entry (ex. `experiment: ablation`)
+  pub labels: Map<String, Value>,
+  /// Scheduler parameters merged over the config flags for the jobs of this entry only.
+  /// `None` removes a flag of the config.
+  pub params_override: HashMap<String, ParamValue>,
+  /// Name of the generated jobs with the variables substituted (ex. `${model}-${seed}`), used
+  /// instead of `job_name`
+  pub name_template: Option<String>,
+}
+
+/// Keys allowed at the top level of a jobs file
+const TOP_LEVEL_KEYS: &[&str] = &[
+  "include",
+  "variables",
+  "command",
+  "preprocess",
+  "postprocess",
+  "python_header",
+  "labels",
+  "name_template",
+  "jobs",
+];
+
+/// Keys allowed in a job entry
+const JOB_KEYS: &[&str] = &[
+  "name",
+  "config",
+  "command",
+  "preprocess",
+  "postprocess",
+  "variables",
+  "labels",
+  "params_override",
+  "name_template",
+];
+
+/// Fields that can be set at the top level of the jobs file and overridden by each job
+struct JobDefaults {
+  command: Option<String>,
+  preprocess: Option<String>,
+  postprocess: Option<String>,
+  python_header: Option<String>,
+  labels: Map<String, Value>,
+  name_template: Option<String>,
+}
+
+/// Parse a `labels` mapping on top of `labels`. Values are stored as strings,
+/// `null` and `!unset` remove a label inherited from the top level of the file.
+fn parse_labels(yaml: &YamlOwned, labels: &mut Map<String, Value>) -> Result<(), ParserError> {
+  if let Ok(labels_node) = lookup_mapping(yaml, "labels") {
+    for (key_node, value_node) in labels_node {
+      let key = to_string(key_node)?;
+      match parse_param_value(value_node)? {
+        Some(Value::String(s)) => labels.insert(key, Value::String(s)),
+        Some(value) => labels.insert(key, Value::String(value.to_string())),
+        None => labels.remove(&key),
+      };
+    }
+  }
+  Ok(())
+}
 
-pub struct ParsedJob<'a> {
-  pub job_name: &'a str,
-  pub config_name: &'a str,
-  pub command: &'a str,
-  pub preprocess: Option<&'a str>,
-  pub postprocess: Option<&'a str>,
-  pub variables: &'a serde_json::Value,
+fn parse_job(
+  job: &YamlOwned,
+  defaults: &JobDefaults,
+  file_variables: &LinkedHashMap<String, Variable>,
+) -> Result<ParsedJob, ParserError> {
+  check_known_keys(job, JOB_KEYS)?;
+  let job_name = lookup_str(job, "name")?;
+  let config_name = lookup_str(job, "config")?;
+  let command = lookup_optional_commands(job, "command")?
+    .or_else(|| defaults.command.clone())
+    .ok_or(ParserError::MissingKey("command".to_string()))?;
+  let preprocess =
+    lookup_optional_commands(job, "preprocess")?.or_else(|| defaults.preprocess.clone());
+  let postprocess =
+    lookup_optional_commands(job, "postprocess")?.or_else(|| defaults.postprocess.clone());
+  let name_template =
+    lookup_optional_str(job, "name_template")?.or_else(|| defaults.name_template.clone());
+
+  // Job variables override the ones defined at file level
+  let mut variables = file_variables.clone();
+  if let Ok(job_variables) = lookup_mapping(job, "variables") {
+    for (name, variable) in parse_variables(job_variables)? {
+      variables.replace(name, variable);
+    }
+  }
+
+  let mut labels = defaults.labels.clone();
+  parse_labels(job, &mut labels)?;
+
+  // Parameters are validated against the scheduler when the jobs are generated for a cluster
+  let mut params_override = HashMap::new();
+  if let Ok(params_node) = lookup_mapping(job, "params_override") {
+    for (key_node, value_node) in params_node {
+      params_override.insert(to_string(key_node)?, parse_param_value(value_node)?);
+    }
+  }
+
+  Ok(ParsedJob {
+    job_name,
+    config_name,
+    command,
+    preprocess,
+    postprocess,
+    python_header: defaults.python_header.clone(),
+    variables,
+    labels,
+    params_override,
+    name_template,
+  })
+}
+
+/// Parse the jobs defined in a YAML file. Variables from included files are available to all jobs.
+/// Unknown keys (ex. a misspelled `comand`) are rejected instead of being silently ignored.
+pub fn parse_jobs_from_file(path: &Path) -> Result<Vec<ParsedJob>, ParserError> {
+  let variables = get_include_variables(path)?;
+  let yaml = load_yaml_from_file(path)?;
+  parse_jobs(&yaml, &variables)
 }
 
-pub fn parse_jobs_from_file(path: &PathBuf) -> Result<Vec<ParsedJob<'_>>, ParserError> {
-  // FIXME implement job parsing logic
-  Ok(vec![])
+/// Parse the jobs defined in a YAML string (ex. read from stdin).
+/// Relative includes are resolved against `base_dir`.
+pub fn parse_jobs_from_str(text: &str, base_dir: &Path) -> Result<Vec<ParsedJob>, ParserError> {
+  let yaml = load_yaml_from_str(text)?;
+  let variables = get_include_variables_from_yaml(&yaml, base_dir)?;
+  parse_jobs(&yaml, &variables)
+}
+
+/// Parse the jobs defined in YAML read from `reader`, see `parse_jobs_from_str`
+pub fn parse_jobs_from_reader(
+  mut reader: impl Read,
+  base_dir: &Path,
+) -> Result<Vec<ParsedJob>, ParserError> {
+  let mut text = String::new();
+  reader.read_to_string(&mut text)?;
+  parse_jobs_from_str(&text, base_dir)
+}
+
+fn parse_jobs(
+  yaml: &YamlOwned,
+  variables: &LinkedHashMap<String, Variable>,
+) -> Result<Vec<ParsedJob>, ParserError> {
+  check_known_keys(yaml, TOP_LEVEL_KEYS)?;
+
+  let mut labels = Map::new();
+  parse_labels(yaml, &mut labels)?;
+  let defaults = JobDefaults {
+    command: lookup_optional_commands(yaml, "command")?,
+    preprocess: lookup_optional_commands(yaml, "preprocess")?,
+    postprocess: lookup_optional_commands(yaml, "postprocess")?,
+    python_header: lookup_optional_str(yaml, "python_header")?,
+    labels,
+    name_template: lookup_optional_str(yaml, "name_template")?,
+  };
+
+  lookup_sequence(yaml, "jobs")?
+    .iter()
+    .map(|job| parse_job(job, &defaults, variables))
+    .collect()
 }
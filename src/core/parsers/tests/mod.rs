@@ -1,11 +1,17 @@
-use crate::core::parsers::{
-  includes::get_include_variables,
-  variables::{BasicVar, CompleteVar, Scalar},
+use crate::core::{
+  cluster_configs::ClusterConfig,
+  database::models::{Cluster, Config, NewCluster, NewConfig, Scheduler},
+  parsers::{
+    includes::get_include_variables,
+    variables::{BasicVar, CompleteVar, Scalar},
+  },
 };
 
 use super::*;
+use serde_json::json;
 use std::{
   collections::HashMap,
+  fs,
   path::{Path, PathBuf},
 };
 
@@ -223,11 +229,36 @@ fn test_get_include_variables_include_missing_file() {
   let result = get_include_variables(&path);
   assert!(result.is_err());
   match result.err().unwrap() {
-    ParserError::IoError(_) => {} // Correct error type
-    e => panic!("Expected IoError, got {:?}", e),
+    ParserError::IncludeNotFound(raw, cleaned, _) => {
+      assert_eq!(raw, "does_not_exist.yaml");
+      assert_eq!(cleaned, "does_not_exist.yaml");
+    }
+    e => panic!("Expected IncludeNotFound, got {:?}", e),
   }
 }
 
+#[test]
+fn test_get_include_variables_crlf_include() {
+  let dir = tempfile::TempDir::new().unwrap();
+  fs::copy(
+    get_test_path("recursive_vars.yaml"),
+    dir.path().join("recursive_vars.yaml"),
+  )
+  .unwrap();
+  // Written on Windows: CRLF line endings, and a `\r` left in the quoted include path
+  let path = dir.path().join("crlf.yaml");
+  fs::write(
+    &path,
+    "include:\r\n  - \"recursive_vars.yaml\\r\"\r\nvariables:\r\n  own: value\r\n",
+  )
+  .unwrap();
+
+  let variables = get_include_variables(&path).unwrap();
+  assert_eq!(variables.len(), 2);
+  assert!(variables.contains_key("recursive"));
+  assert!(variables.contains_key("own"));
+}
+
 #[test]
 fn test_get_include_variables_no_include() {
   let path = get_test_path("no_include.yaml");
@@ -273,3 +304,535 @@ fn test_get_include_variables_circular_4() {
   let path = get_test_path("circular4.yaml");
   test_get_include_variables_circular_include(&path);
 }
+
+#[test]
+fn test_parse_clusters_configs_unset_default_params() {
+  let path = get_test_path("unset_params.yaml");
+
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  assert_eq!(clusters.len(), 1);
+  let configs = &clusters[0].configs;
+  assert_eq!(configs.len(), 3);
+
+  assert_eq!(
+    configs[0].flags,
+    json!({"exclusive": true, "time": "01:00:00"})
+  );
+  assert_eq!(configs[0].env, json!({"CUSTOM_VAR": "default_value"}));
+  assert_eq!(configs[1].flags, json!({"time": "01:00:00"}));
+  assert_eq!(configs[1].env, json!({}));
+  assert_eq!(configs[2].flags, json!({"time": "00:10:00"}));
+}
+
+#[test]
+fn test_unset_default_param_emits_no_header() {
+  let path = get_test_path("unset_params.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  let cluster = Cluster {
+    id: 1,
+    cluster_name: clusters[0].cluster.cluster_name.clone(),
+    scheduler: clusters[0].cluster.scheduler.clone(),
+    max_jobs: clusters[0].cluster.max_jobs,
+    ..Default::default()
+  };
+  let to_config = |new_config: &NewConfig| Config {
+    id: 1,
+    config_name: new_config.config_name.clone(),
+    cluster_id: cluster.id,
+    flags: new_config.flags.clone(),
+    env: new_config.env.clone(),
+    default_preprocess: new_config.default_preprocess.clone(),
+    default_postprocess: new_config.default_postprocess.clone(),
+    retry_on_timeout: new_config.retry_on_timeout.clone(),
+    max_output_bytes: new_config.max_output_bytes,
+    shell: new_config.shell.clone(),
+    description: new_config.description.clone(),
+    extra_headers: new_config.extra_headers.clone(),
+    env_snapshot: new_config.env_snapshot.clone(),
+  };
+
+  let exclusive = to_config(&clusters[0].configs[0]);
+  let header = ClusterConfig::new(&cluster, &exclusive).generate_script_header(Path::new("."));
+  assert!(header.contains("#SBATCH --exclusive\n"));
+  assert!(header.contains("#SBATCH --time=01:00:00\n"));
+
+  let shared = to_config(&clusters[0].configs[1]);
+  let header = ClusterConfig::new(&cluster, &shared).generate_script_header(Path::new("."));
+  assert!(!header.contains("--exclusive"));
+  assert!(header.contains("#SBATCH --time=01:00:00\n"));
+}
+
+#[test]
+fn test_parse_clusters_configs_extends() {
+  let path = get_test_path("extends.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  let configs = &clusters[0].configs;
+
+  // The order of the file is kept
+  let names: Vec<&str> = configs.iter().map(|c| c.config_name.as_str()).collect();
+  assert_eq!(names, vec!["gpu_long", "gpu", "base", "shared"]);
+
+  // Single level
+  assert_eq!(
+    configs[1].flags,
+    json!({"account": "lab", "partition": "compute", "time": "01:00:00", "exclusive": true, "gpus": 1})
+  );
+  assert_eq!(
+    configs[1].env,
+    json!({"OMP_NUM_THREADS": 4, "CUDA_VISIBLE_DEVICES": "0"})
+  );
+  assert_eq!(
+    configs[3].flags,
+    json!({"account": "lab", "partition": "compute", "time": "01:00:00"})
+  );
+
+  // Two levels
+  assert_eq!(
+    configs[0].flags,
+    json!({"account": "lab", "partition": "compute", "time": "12:00:00", "exclusive": true, "gpus": 1})
+  );
+  assert_eq!(configs[0].env, configs[1].env);
+}
+
+#[test]
+fn test_parse_clusters_configs_extends_templated_name() {
+  let path = get_test_path("extends_templated.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  let configs = &clusters[0].configs;
+
+  // The parent is found by the name as written, not the substituted one
+  assert_eq!(configs[0].config_name, "small_compute");
+  assert_eq!(
+    configs[0].flags,
+    json!({"time": "01:00:00", "mem": "4G", "cpus_per_task": 2})
+  );
+}
+
+#[test]
+fn test_parse_clusters_configs_default_processing() {
+  let path = get_test_path("default_processing.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  let configs = &clusters[0].configs;
+
+  assert_eq!(
+    configs[0].default_preprocess.as_deref(),
+    Some("source ~/.bashrc")
+  );
+  assert_eq!(
+    configs[0].default_postprocess.as_deref(),
+    Some("echo \"cluster done\"")
+  );
+
+  // Cluster defaults come first
+  assert_eq!(
+    configs[1].default_preprocess.as_deref(),
+    Some("source ~/.bashrc\nsource .venv/bin/activate")
+  );
+  assert_eq!(
+    configs[1].default_postprocess.as_deref(),
+    Some("echo \"cluster done\"\ndeactivate")
+  );
+
+  assert_eq!(configs[2].default_preprocess, configs[1].default_preprocess);
+  assert_eq!(
+    configs[2].default_postprocess,
+    configs[1].default_postprocess
+  );
+}
+
+#[test]
+fn test_parse_clusters_configs_retry_on_timeout() {
+  let path = get_test_path("retry_on_timeout.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  let configs = &clusters[0].configs;
+
+  assert_eq!(
+    configs[0].retry_on_timeout,
+    Some(json!({"max_retries": 2, "time_multiplier": 1.5}))
+  );
+  assert_eq!(configs[1].retry_on_timeout, configs[0].retry_on_timeout);
+  assert_eq!(
+    configs[2].retry_on_timeout,
+    Some(json!({"max_retries": 1, "time_multiplier": 2.0}))
+  );
+  assert_eq!(configs[3].retry_on_timeout, None);
+}
+
+#[test]
+fn test_parse_clusters_configs_retry_on_timeout_invalid() {
+  let path = get_test_path("retry_on_timeout_invalid.yaml");
+  let result = parse_clusters_configs_from_file(&path);
+  assert!(matches!(result, Err(ParserError::WrongType(_, _))));
+}
+
+#[test]
+fn test_parse_clusters_configs_max_output_bytes() {
+  let path = get_test_path("max_output_bytes.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  let caps: Vec<Option<i64>> = clusters[0]
+    .configs
+    .iter()
+    .map(|config| config.max_output_bytes)
+    .collect();
+  assert_eq!(caps, vec![Some(1048576), Some(1048576), None]);
+}
+
+#[test]
+fn test_parse_clusters_configs_submit_interval() {
+  let clusters = parse_clusters_configs_from_file(&get_test_path("submit_interval.yaml")).unwrap();
+  let intervals: Vec<Option<i32>> = clusters
+    .iter()
+    .map(|cluster| cluster.cluster.submit_interval_ms)
+    .collect();
+  assert_eq!(intervals, vec![Some(500), None]);
+
+  let result = parse_clusters_configs_from_file(&get_test_path("submit_interval_invalid.yaml"));
+  assert!(matches!(
+    result,
+    Err(ParserError::WrongType(_, expected)) if expected == "non-negative integer"
+  ));
+}
+
+#[test]
+fn test_parse_clusters_configs_missing_scheduler() {
+  let clusters =
+    parse_clusters_configs_from_file(&get_test_path("missing_scheduler.yaml")).unwrap();
+  assert_eq!(clusters[0].cluster.cluster_name, "laptop");
+  assert_eq!(clusters[0].cluster.scheduler, Scheduler::Local);
+  assert_eq!(clusters[0].configs[0].config_name, "cpu");
+}
+
+#[test]
+fn test_parse_clusters_configs_shell() {
+  let path = get_test_path("shell.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  let shells: Vec<Vec<Option<&str>>> = clusters
+    .iter()
+    .map(|cluster| {
+      cluster
+        .configs
+        .iter()
+        .map(|config| config.shell.as_deref())
+        .collect()
+    })
+    .collect();
+  assert_eq!(
+    shells,
+    vec![vec![Some("zsh"), Some("zsh"), Some("/bin/sh")], vec![None]]
+  );
+}
+
+#[test]
+fn test_parse_clusters_configs_extra_headers() {
+  let path = get_test_path("extra_headers.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  let configs = &clusters[0].configs;
+  let headers = json!(["#SBATCH --constraint=gpu", "#SBATCH --exact"]);
+  assert_eq!(configs[0].extra_headers, Some(headers.clone()));
+  assert_eq!(configs[1].extra_headers, Some(headers));
+  assert_eq!(configs[2].extra_headers, None);
+
+  // The raw lines come after the generated directives
+  let header = parsed_script_header(&clusters[0].cluster, &configs[0]);
+  let time = header.find("#SBATCH --time=00:10:00\n").unwrap();
+  let constraint = header.find("#SBATCH --constraint=gpu\n").unwrap();
+  assert!(time < constraint);
+  assert!(header.contains("#SBATCH --exact\n"));
+  assert!(!parsed_script_header(&clusters[0].cluster, &configs[2]).contains("--constraint"));
+}
+
+#[test]
+fn test_unknown_cluster_keys_in_file() {
+  let path = get_test_path("per_cluster_typo.yaml");
+  assert_eq!(
+    unknown_cluster_keys_in_file(&path, &[]).unwrap(),
+    vec![
+      ("gpus".to_string(), "clsuterA".to_string()),
+      ("threads".to_string(), "clusterB".to_string()),
+    ]
+  );
+  // Clusters already in the database are known
+  assert_eq!(
+    unknown_cluster_keys_in_file(&path, &["clusterB".to_string()]).unwrap(),
+    vec![("gpus".to_string(), "clsuterA".to_string())]
+  );
+}
+
+#[test]
+fn test_parse_clusters_configs_extends_cycle() {
+  let path = get_test_path("extends_cycle.yaml");
+  let result = parse_clusters_configs_from_file(&path);
+  assert!(matches!(
+    result,
+    Err(ParserError::CircularExtends(cycle)) if cycle == "first -> second -> third -> first"
+  ));
+}
+
+#[test]
+fn test_parse_clusters_configs_variables_in_names() {
+  let path = get_test_path("config_variables.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+
+  let names: Vec<Vec<&str>> = clusters
+    .iter()
+    .map(|cluster| {
+      cluster
+        .configs
+        .iter()
+        .map(|config| config.config_name.as_str())
+        .collect()
+    })
+    .collect();
+  assert_eq!(
+    names,
+    vec![
+      vec!["cfg_large_fp64", "default_small_fp64_1"],
+      vec!["default_small_fp32_4"],
+    ]
+  );
+}
+
+/// Script header of a parsed config, as if its cluster and config were stored in the database
+fn parsed_script_header(new_cluster: &NewCluster, new_config: &NewConfig) -> String {
+  let cluster = Cluster {
+    id: 1,
+    cluster_name: new_cluster.cluster_name.clone(),
+    scheduler: new_cluster.scheduler.clone(),
+    max_jobs: new_cluster.max_jobs,
+    header_prefix: new_cluster.header_prefix.clone(),
+    submit_command: new_cluster.submit_command.clone(),
+    job_id_regex: new_cluster.job_id_regex.clone(),
+    pre_submit_hook: new_cluster.pre_submit_hook.clone(),
+    submit_interval_ms: new_cluster.submit_interval_ms,
+  };
+  let config = Config {
+    id: 1,
+    config_name: new_config.config_name.clone(),
+    cluster_id: cluster.id,
+    flags: new_config.flags.clone(),
+    env: new_config.env.clone(),
+    default_preprocess: new_config.default_preprocess.clone(),
+    default_postprocess: new_config.default_postprocess.clone(),
+    retry_on_timeout: new_config.retry_on_timeout.clone(),
+    max_output_bytes: new_config.max_output_bytes,
+    shell: new_config.shell.clone(),
+    description: new_config.description.clone(),
+    extra_headers: new_config.extra_headers.clone(),
+    env_snapshot: new_config.env_snapshot.clone(),
+  };
+  ClusterConfig::new(&cluster, &config).generate_script_header(Path::new("."))
+}
+
+#[test]
+fn test_parse_clusters_configs_default_time() {
+  let path = get_test_path("default_time.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+
+  let headers: Vec<String> = clusters[0]
+    .configs
+    .iter()
+    .map(|config| parsed_script_header(&clusters[0].cluster, config))
+    .collect();
+  // The time of the config wins over the default of the cluster
+  assert!(headers[0].contains("#SBATCH --time=00:10:00\n"));
+  assert!(!headers[0].contains("--time=01:00:00"));
+  assert!(headers[1].contains("#SBATCH --time=01:00:00\n"));
+  assert!(headers[1].contains("#SBATCH --partition=cpu\n"));
+  // PBS limits the time with `walltime`
+  assert_eq!(clusters[1].configs[0].flags["walltime"], "02:00:00");
+}
+
+#[test]
+fn test_parse_custom_scheduler_header() {
+  let path = get_test_path("custom_scheduler.yaml");
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  let new_cluster = &clusters[0].cluster;
+  assert_eq!(new_cluster.scheduler, Scheduler::Custom);
+  assert_eq!(
+    new_cluster.submit_command.as_deref(),
+    Some("bsub < {script}")
+  );
+  assert_eq!(new_cluster.job_id_regex.as_deref(), Some(r"Job <(\d+)>"));
+
+  let header = parsed_script_header(new_cluster, &clusters[0].configs[0]);
+  assert!(header.contains("#BSUB -q normal\n"));
+  assert!(header.contains("#BSUB -n 4\n"));
+  assert!(header.contains("#BSUB -W 02:00\n"));
+  assert!(header.contains("#BSUB -x\n"));
+}
+
+#[test]
+fn test_parse_custom_scheduler_missing_submit_command() {
+  let path = get_test_path("custom_scheduler_missing_submit.yaml");
+  let result = parse_clusters_configs_from_file(&path);
+  assert!(matches!(result, Err(ParserError::MissingKey(key)) if key == "submit_command"));
+}
+
+#[test]
+fn test_parse_jobs_from_reader() {
+  let yaml = "
+include: recursive_vars.yaml
+command: echo {recursive}
+jobs:
+  - name: first
+    config: cpu
+  - name: second
+    config: gpu
+    command: echo {recursive} twice
+";
+  let jobs = parse_jobs_from_reader(std::io::Cursor::new(yaml), &get_test_path("")).unwrap();
+
+  let names: Vec<(&str, &str)> = jobs
+    .iter()
+    .map(|job| (job.job_name.as_str(), job.config_name.as_str()))
+    .collect();
+  assert_eq!(names, vec![("first", "cpu"), ("second", "gpu")]);
+  assert_eq!(jobs[0].command, "echo {recursive}");
+  assert_eq!(jobs[1].command, "echo {recursive} twice");
+  // The include is resolved against the base directory
+  assert!(jobs[0].variables.contains_key("recursive"));
+}
+
+#[test]
+fn test_parse_jobs_command_sequences() {
+  let yaml = "
+preprocess:
+  - module load cuda
+  - source venv/bin/activate
+jobs:
+  - name: steps
+    config: cpu
+    command:
+      - ./configure
+      - make
+      - make test
+    postprocess: [rm -rf build]
+  - name: single
+    config: cpu
+    command: make
+";
+  let jobs = parse_jobs_from_reader(std::io::Cursor::new(yaml), &get_test_path("")).unwrap();
+
+  assert_eq!(jobs[0].command, "./configure\nmake\nmake test");
+  assert_eq!(
+    jobs[0].preprocess.as_deref(),
+    Some("module load cuda\nsource venv/bin/activate")
+  );
+  assert_eq!(jobs[0].postprocess.as_deref(), Some("rm -rf build"));
+  assert_eq!(jobs[1].command, "make");
+  assert_eq!(jobs[1].preprocess, jobs[0].preprocess);
+}
+
+#[test]
+fn test_parse_jobs_from_file_variables_and_configs() {
+  let jobs = parse_jobs_from_file(&get_test_path("parsed_jobs.yaml")).unwrap();
+
+  let fields: Vec<_> = jobs
+    .iter()
+    .map(|job| {
+      (
+        job.job_name.as_str(),
+        job.config_name.as_str(),
+        job.command.as_str(),
+        job.preprocess.as_deref(),
+        job.postprocess.as_deref(),
+      )
+    })
+    .collect();
+  assert_eq!(
+    fields,
+    vec![
+      (
+        "train",
+        "gpu_config",
+        "python train.py --model {model} --seed {seed}",
+        Some("module load {model}"),
+        None
+      ),
+      (
+        "train_large",
+        "gpu_config",
+        "python train.py --model {model} --seed {seed}",
+        Some("module load {model}"),
+        Some("echo {model} done")
+      ),
+      (
+        "eval",
+        "cpu_config",
+        "python eval.py {recursive}",
+        Some("module load {model}"),
+        None
+      ),
+    ]
+  );
+
+  let model = |job: &ParsedJob| job.variables["model"].contents.clone();
+  let small = CompleteVar::Scalar(Scalar::String("small".to_string()));
+  assert_eq!(model(&jobs[0]), small);
+  assert_eq!(
+    model(&jobs[1]),
+    CompleteVar::Scalar(Scalar::String("large".to_string()))
+  );
+  assert_eq!(model(&jobs[2]), small);
+  for job in &jobs {
+    let names: Vec<&str> = job.variables.keys().map(String::as_str).collect();
+    assert_eq!(names, vec!["seed", "model", "recursive"]);
+    assert_eq!(
+      job.variables["seed"].contents,
+      CompleteVar::List(vec![Scalar::Int(1), Scalar::Int(2)])
+    );
+  }
+}
+
+#[test]
+fn test_parse_jobs_unknown_job_key() {
+  let result = parse_jobs_from_file(&get_test_path("unknown_job_key.yaml"));
+  assert!(matches!(
+    result,
+    Err(ParserError::UnknownKey(key, nearest)) if key == "comand" && nearest == "command"
+  ));
+}
+
+#[test]
+fn test_parse_jobs_unknown_top_level_key() {
+  let result = parse_jobs_from_file(&get_test_path("unknown_top_level_key.yaml"));
+  assert!(matches!(
+    result,
+    Err(ParserError::UnknownKey(key, nearest)) if key == "variable" && nearest == "variables"
+  ));
+}
+
+#[test]
+fn test_parse_clusters_configs_anchors_and_merge_keys() {
+  let path = get_test_path("anchors.yaml");
+
+  let clusters = parse_clusters_configs_from_file(&path).unwrap();
+  let configs = &clusters[0].configs;
+  assert_eq!(configs.len(), 3);
+
+  let gpu_params = json!({"partition": "gpu", "gpus": 1, "time": "02:00:00"});
+  assert_eq!(configs[0].flags, gpu_params);
+  assert_eq!(configs[1].flags, gpu_params);
+  assert_eq!(
+    configs[2].flags,
+    json!({"partition": "gpu", "gpus": 4, "time": "02:00:00"})
+  );
+}
+
+#[test]
+fn test_parse_env_file() {
+  let env = parse_env_file(&get_test_path("prod.env")).unwrap();
+  assert_eq!(
+    env,
+    HashMap::from([
+      ("MODE".to_string(), "prod".to_string()),
+      ("TOKEN".to_string(), "abc def".to_string()),
+    ])
+  );
+
+  let result = parse_env_file(&get_test_path("invalid.env"));
+  assert!(matches!(
+    result,
+    Err(ParserError::InvalidEnvLine(2, line)) if line == "not a variable"
+  ));
+}
@@ -60,6 +60,30 @@ pub fn lookup_str(yaml: &YamlOwned, key: &str) -> Result<String, ParserError> {
   }
 }
 
+/// Lookup mapping by key and return string, or `None` if the key is missing
+pub fn lookup_optional_str(yaml: &YamlOwned, key: &str) -> Result<Option<String>, ParserError> {
+  yaml_lookup(yaml, key).map(to_string).transpose()
+}
+
+/// Lookup mapping by key and return a command: either a string or a sequence of strings joined
+/// with newlines (one command per line). Returns `None` if the key is missing.
+pub fn lookup_optional_commands(
+  yaml: &YamlOwned,
+  key: &str,
+) -> Result<Option<String>, ParserError> {
+  match yaml_lookup(yaml, key) {
+    Some(YamlOwned::Sequence(commands)) => Ok(Some(
+      commands
+        .iter()
+        .map(to_string)
+        .collect::<Result<Vec<_>, _>>()?
+        .join("\n"),
+    )),
+    Some(command) => to_string(command).map(Some),
+    None => Ok(None),
+  }
+}
+
 /// Lookup mapping by key and return sequence
 pub fn lookup_sequence<'a>(
   yaml: &'a YamlOwned,
@@ -89,12 +113,102 @@ pub fn lookup_mapping<'a>(
 }
 
 /// Load YAML from a file. Returns the first document in the file.
+/// Aliases are resolved by the loader, merge keys (`<<: *anchor`) are resolved here.
 pub fn load_yaml_from_file(path: &Path) -> Result<YamlOwned, ParserError> {
-  let text = fs::read_to_string(path)?;
-  let yaml = YamlOwned::load_from_str(&text)
+  load_yaml_from_str(&fs::read_to_string(path)?)
+}
+
+/// Load YAML from a string. Returns the first document, with merge keys resolved.
+pub fn load_yaml_from_str(text: &str) -> Result<YamlOwned, ParserError> {
+  let yaml = YamlOwned::load_from_str(text)
     .map_err(ParserError::YamlParseFailed)?
     .into_iter() // Take the first document
     .next()
     .ok_or(ParserError::YamlEmpty)?;
-  Ok(yaml)
+  resolve_merge_keys(yaml)
+}
+
+/// Recursively replace merge keys (`<<`) with the entries of the merged mappings.
+/// Keys defined explicitly in a mapping win over merged ones and, when a sequence of mappings
+/// is merged, the first mappings win over the following ones.
+fn resolve_merge_keys(yaml: YamlOwned) -> Result<YamlOwned, ParserError> {
+  match yaml {
+    YamlOwned::Mapping(map) => {
+      let merge_key = value_from_str("<<");
+      let explicit_keys: Vec<YamlOwned> =
+        map.keys().filter(|k| **k != merge_key).cloned().collect();
+      let mut resolved = LinkedHashMap::new();
+      for (key, value) in map {
+        let value = resolve_merge_keys(value)?;
+        if key != merge_key {
+          resolved.insert(key, value);
+          continue;
+        }
+        let merged = match value {
+          YamlOwned::Mapping(merged) => vec![merged],
+          YamlOwned::Sequence(seq) => seq
+            .into_iter()
+            .map(|merged| match merged {
+              YamlOwned::Mapping(merged) => Ok(merged),
+              other => Err(ParserError::WrongType(
+                format!("{:?}", other),
+                "mapping".to_string(),
+              )),
+            })
+            .collect::<Result<_, _>>()?,
+          other => {
+            return Err(ParserError::WrongType(
+              format!("{:?}", other),
+              "mapping or sequence of mappings".to_string(),
+            ));
+          }
+        };
+        for (merged_key, merged_value) in merged.into_iter().flatten() {
+          if !explicit_keys.contains(&merged_key) && !resolved.contains_key(&merged_key) {
+            resolved.insert(merged_key, merged_value);
+          }
+        }
+      }
+      Ok(YamlOwned::Mapping(resolved))
+    }
+    YamlOwned::Sequence(seq) => Ok(YamlOwned::Sequence(
+      seq
+        .into_iter()
+        .map(resolve_merge_keys)
+        .collect::<Result<_, _>>()?,
+    )),
+    other => Ok(other),
+  }
+}
+
+/// Return an error for the first key of the mapping that is not in `known_keys`,
+/// suggesting the closest known key
+pub fn check_known_keys(yaml: &YamlOwned, known_keys: &[&str]) -> Result<(), ParserError> {
+  for key_node in to_mapping(yaml)?.keys() {
+    let key = to_string(key_node)?;
+    if !known_keys.contains(&key.as_str()) {
+      let nearest = known_keys
+        .iter()
+        .min_by_key(|known| edit_distance(&key, known))
+        .map(|known| known.to_string())
+        .unwrap_or_default();
+      return Err(ParserError::UnknownKey(key, nearest));
+    }
+  }
+  Ok(())
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b: Vec<char> = b.chars().collect();
+  let mut previous: Vec<usize> = (0..=b.len()).collect();
+  for (i, ca) in a.chars().enumerate() {
+    let mut current = vec![i + 1];
+    for (j, cb) in b.iter().enumerate() {
+      let substitution = previous[j] + usize::from(ca != *cb);
+      current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+    }
+    previous = current;
+  }
+  previous[b.len()]
 }
@@ -1,12 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::core::parsers::utils::value_from_str;
 use crate::core::parsers::{ParserError, utils::to_string};
 use hashlink::LinkedHashMap;
 use saphyr::{ScalarOwned as YamlOwnedScalar, Tag, YamlOwned};
 use serde::Serialize;
+use serde_json::{Value, json};
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, PartialEq, Serialize, Clone)]
 pub enum Scalar {
   String(String),
   Int(i64),
@@ -17,25 +18,48 @@ pub enum Scalar {
   Python(String),
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+impl Scalar {
+  /// Value as typed JSON (ex. `42` for `Int(42)`). Files, directories and Python expressions are
+  /// stored as written.
+  pub fn to_json(&self) -> Value {
+    match self {
+      Scalar::String(s) | Scalar::File(s) | Scalar::Directory(s) | Scalar::Python(s) => json!(s),
+      Scalar::Int(i) => json!(i),
+      Scalar::Float(f) => json!(f),
+      Scalar::Bool(b) => json!(b),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Serialize, Clone)]
 pub enum BasicVar {
   Scalar(Scalar),
   List(Vec<Scalar>),
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, PartialEq, Serialize, Clone)]
 pub struct ClusterMap {
   pub default: Option<BasicVar>,
   pub per_cluster: HashMap<String, BasicVar>,
 }
 
+impl BasicVar {
+  /// Value as typed JSON, a list becomes an array
+  pub fn to_json(&self) -> Value {
+    match self {
+      BasicVar::Scalar(scalar) => scalar.to_json(),
+      BasicVar::List(list) => list.iter().map(Scalar::to_json).collect(),
+    }
+  }
+}
+
 impl ClusterMap {
   pub fn get(&self, cluster_name: &String) -> Option<&BasicVar> {
     self.per_cluster.get(cluster_name).or(self.default.as_ref())
   }
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, PartialEq, Serialize, Clone)]
 pub enum CompleteVar {
   Scalar(Scalar),
   List(Vec<Scalar>),
@@ -43,7 +67,49 @@ pub enum CompleteVar {
   ClusterMap(ClusterMap),
 }
 
-#[derive(Debug)]
+impl CompleteVar {
+  /// Name of the kind of variable (ex. `List`)
+  pub fn kind(&self) -> &'static str {
+    match self {
+      CompleteVar::Scalar(_) => "Scalar",
+      CompleteVar::List(_) => "List",
+      CompleteVar::StandardMap(_) => "StandardMap",
+      CompleteVar::ClusterMap(_) => "ClusterMap",
+    }
+  }
+
+  /// Value as typed JSON. A cluster map is resolved for `cluster_name` (`null` if it has no
+  /// value for it).
+  pub fn to_json(&self, cluster_name: &String) -> Value {
+    match self {
+      CompleteVar::Scalar(scalar) => scalar.to_json(),
+      CompleteVar::List(list) => list.iter().map(Scalar::to_json).collect(),
+      CompleteVar::StandardMap(map) => map
+        .iter()
+        .map(|(key, value)| (key.clone(), value.to_json()))
+        .collect(),
+      CompleteVar::ClusterMap(cluster_map) => cluster_map
+        .get(cluster_name)
+        .map_or(Value::Null, BasicVar::to_json),
+    }
+  }
+
+  /// Values the variable can take on `cluster_name`, one per job of a sweep. Empty for a map.
+  pub fn scalars(&self, cluster_name: &String) -> Vec<&Scalar> {
+    match self {
+      CompleteVar::Scalar(scalar) => vec![scalar],
+      CompleteVar::List(list) => list.iter().collect(),
+      CompleteVar::StandardMap(_) => vec![],
+      CompleteVar::ClusterMap(cluster_map) => match cluster_map.get(cluster_name) {
+        Some(BasicVar::Scalar(scalar)) => vec![scalar],
+        Some(BasicVar::List(list)) => list.iter().collect(),
+        None => vec![],
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
 pub struct Variable {
   pub name: String,
   pub contents: CompleteVar,
@@ -55,6 +121,45 @@ impl PartialEq for Variable {
   }
 }
 
+/// `(variable, cluster)` pairs of the `per_cluster` keys naming none of `cluster_names`, ex. a
+/// typo that makes the value unreachable
+pub fn unknown_cluster_keys(
+  variables: &LinkedHashMap<String, Variable>,
+  cluster_names: &HashSet<String>,
+) -> Vec<(String, String)> {
+  let mut unknown = vec![];
+  for (name, variable) in variables {
+    if let CompleteVar::ClusterMap(cluster_map) = &variable.contents {
+      let mut keys: Vec<&String> = cluster_map
+        .per_cluster
+        .keys()
+        .filter(|key| !cluster_names.contains(*key))
+        .collect();
+      keys.sort();
+      unknown.extend(keys.into_iter().map(|key| (name.clone(), key.clone())));
+    }
+  }
+  unknown
+}
+
+/// Warn about each unknown `per_cluster` key, or fail on the first one if `strict`
+pub fn check_cluster_keys(unknown: &[(String, String)], strict: bool) -> Result<(), ParserError> {
+  if strict && let Some((variable, cluster)) = unknown.first() {
+    return Err(ParserError::UnknownClusterKey(
+      variable.clone(),
+      cluster.clone(),
+    ));
+  }
+  for (variable, cluster) in unknown {
+    log::warn!(
+      "Variable \"{}\" has a per_cluster value for unknown cluster \"{}\"",
+      variable,
+      cluster
+    );
+  }
+  Ok(())
+}
+
 /// Helper macro to create WrongType ParserError
 macro_rules! wrong_type_err {
   ($value:expr, $expected:expr) => {
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::core::database::Database;
 use serde::{Deserialize, Serialize};
@@ -19,11 +19,32 @@ pub enum SbatchmanConfigError {
   SbatchmanConfigNotFound,
   #[error("Database error: {0}")]
   DatabaseError(#[from] crate::core::database::StorageError),
+  #[error("Sbatchman directory {0:?} does not exist")]
+  SbatchmanDirMissing(PathBuf),
+}
+
+/// Maximum number of jobs a single launch can generate without explicit confirmation
+pub const DEFAULT_MAX_SWEEP_SIZE: usize = 1000;
+
+/// Timezone the timestamps of the jobs are displayed in
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayTimezone {
+  #[default]
+  Local,
+  Utc,
 }
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct SbatchmanConfig {
   pub cluster_name: Option<String>,
+  /// Overrides `DEFAULT_MAX_SWEEP_SIZE`
+  pub max_sweep_size: Option<usize>,
+  /// Directory where the `jobs/<id>` directories are created instead of `.sbatchman`
+  /// (ex. a scratch filesystem). Relative paths are relative to the project root.
+  pub output_dir: Option<PathBuf>,
+  /// Timezone of the displayed timestamps (`local` or `utc`), local by default
+  pub timezone: Option<DisplayTimezone>,
 }
 
 /// Initializes the .sbatchman directory at the specified path:
@@ -38,6 +59,58 @@ pub fn init_sbatchman_dir(path: &PathBuf) -> Result<(), SbatchmanConfigError> {
   Ok(())
 }
 
+/// Example clusters configuration and jobs file, by file name
+const EXAMPLE_FILES: [(&str, &str); 2] = [
+  ("clusters.yaml", include_str!("examples/clusters.yaml")),
+  ("jobs.yaml", include_str!("examples/jobs.yaml")),
+];
+
+/// Writes the example clusters configuration and jobs file into `path`, leaving the existing
+/// files untouched. Returns the paths of the written files.
+pub fn write_example_files(path: &Path) -> Result<Vec<PathBuf>, SbatchmanConfigError> {
+  let mut written = vec![];
+  for (name, contents) in EXAMPLE_FILES {
+    let file = path.join(name);
+    if file.exists() {
+      log::warn!(
+        "{} already exists, the example is not written",
+        file.display()
+      );
+      continue;
+    }
+    std::fs::write(&file, contents)?;
+    written.push(file);
+  }
+  Ok(written)
+}
+
+/// Environment variable selecting the .sbatchman directory instead of searching for it
+pub const SBATCHMAN_DIR_ENV: &str = "SBATCHMAN_DIR";
+
+/// Environment variable selecting the cluster used when none is passed explicitly, over the
+/// cluster stored in the local and global configurations
+pub const SBATCHMAN_CLUSTER_ENV: &str = "SBATCHMAN_CLUSTER";
+
+/// Cluster name set with `SBATCHMAN_CLUSTER`, if not empty
+pub fn get_cluster_name_env() -> Option<String> {
+  std::env::var(SBATCHMAN_CLUSTER_ENV)
+    .ok()
+    .filter(|name| !name.is_empty())
+}
+
+/// The .sbatchman directory to use: `explicit` (ex. `--db`) wins over `SBATCHMAN_DIR`, which wins
+/// over the one found by `get_sbatchman_dir`. An explicitly selected directory must exist.
+pub fn resolve_sbatchman_dir(explicit: Option<&Path>) -> Result<PathBuf, SbatchmanConfigError> {
+  let env_dir = std::env::var_os(SBATCHMAN_DIR_ENV)
+    .filter(|dir| !dir.is_empty())
+    .map(PathBuf::from);
+  match explicit.map(Path::to_path_buf).or(env_dir) {
+    Some(dir) if dir.is_dir() => Ok(dir),
+    Some(dir) => Err(SbatchmanConfigError::SbatchmanDirMissing(dir)),
+    None => get_sbatchman_dir(),
+  }
+}
+
 /// Searches for the .sbatchman directory starting from the current working directory
 /// and moving up the directory tree until it finds it or reaches the user's home directory.
 pub fn get_sbatchman_dir() -> Result<PathBuf, SbatchmanConfigError> {
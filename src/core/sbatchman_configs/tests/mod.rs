@@ -1,5 +1,5 @@
-use crate::core::{sbatchman_configs::{
-  get_sbatchman_config_global, get_sbatchman_config_local, init_sbatchman_config_local, set_sbatchman_config_global, set_sbatchman_config_local
+use crate::core::{SbatchmanError, jobs::JobError, sbatchman_configs::{
+  SbatchmanConfigError, get_sbatchman_config_global, get_sbatchman_config_local, init_sbatchman_config_local, set_sbatchman_config_global, set_sbatchman_config_local
 }};
 
 #[test]
@@ -29,11 +29,28 @@ fn set_and_get_cluster_name_test_local() {
 
 #[test]
 fn set_and_get_cluster_name_test_global() {
+  let _env = crate::core::tests::lock_cluster_env();
   let mut config = get_sbatchman_config_global().unwrap();
   config.cluster_name = Some("test_cluster_global".to_string());
   assert!(set_sbatchman_config_global(&config).is_ok());
   assert_eq!(get_sbatchman_config_global().expect("No config file found").cluster_name.expect("No cluster name found"), "test_cluster_global");
 }
 
-
-
+#[test]
+fn config_error_converts_to_job_and_sbatchman_errors() {
+  let job_error: JobError = SbatchmanConfigError::SbatchmanDirNotFound.into();
+  assert!(matches!(
+    job_error,
+    JobError::ConfigError(SbatchmanConfigError::SbatchmanDirNotFound)
+  ));
+  let error: SbatchmanError = SbatchmanConfigError::SbatchmanDirNotFound.into();
+  assert!(matches!(
+    error,
+    SbatchmanError::ConfigError(SbatchmanConfigError::SbatchmanDirNotFound)
+  ));
+  let error: SbatchmanError = job_error.into();
+  assert!(matches!(
+    error,
+    SbatchmanError::JobError(JobError::ConfigError(_))
+  ));
+}
@@ -0,0 +1,70 @@
+use serde_json::{Value, json};
+use tiny_http::{Header, Response, Server};
+
+use crate::core::database::{Database, StorageError};
+
+#[cfg(test)]
+mod tests;
+
+/// Status code and JSON body of the response to `method url`. Only `GET` is allowed:
+/// - `/jobs`: all the jobs
+/// - `/jobs/{id}`: a single job
+/// - `/clusters`: all the clusters, sorted by name
+pub fn handle_request(db: &mut Database, method: &str, url: &str) -> (u16, Value) {
+  if method != "GET" {
+    return error(405, format!("Method {} not allowed", method));
+  }
+  // The query string is ignored
+  let path = url
+    .split('?')
+    .next()
+    .unwrap_or_default()
+    .trim_end_matches('/');
+  let segments: Vec<&str> = path.split('/').skip(1).collect();
+  let result = match segments.as_slice() {
+    ["jobs"] => db.get_jobs(None).map(|jobs| json!(jobs)),
+    ["jobs", id] => match id.parse::<i32>() {
+      Ok(id) => match db.get_job_by_id(id) {
+        Err(e @ StorageError::JobNotFound(_)) => return error(404, e.to_string()),
+        result => result.map(|job| json!(job)),
+      },
+      Err(_) => return error(400, format!("Invalid job id \"{}\"", id)),
+    },
+    ["clusters"] => get_clusters(db),
+    _ => return error(404, format!("Unknown endpoint {}", path)),
+  };
+  match result {
+    Ok(body) => (200, body),
+    Err(e) => error(500, e.to_string()),
+  }
+}
+
+fn get_clusters(db: &mut Database) -> Result<Value, StorageError> {
+  let clusters = db
+    .get_cluster_names()?
+    .iter()
+    .map(|name| db.get_cluster_by_name(name))
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(json!(clusters))
+}
+
+fn error(status: u16, message: String) -> (u16, Value) {
+  (status, json!({ "error": message }))
+}
+
+/// Serve the read-only endpoints of `handle_request` on `host:port` until the process is stopped
+pub fn serve(db: &mut Database, host: &str, port: u16) -> std::io::Result<()> {
+  let server = Server::http((host, port)).map_err(std::io::Error::other)?;
+  let content_type = Header::from_bytes("Content-Type", "application/json")
+    .expect("The Content-Type header is valid");
+  for request in server.incoming_requests() {
+    let (status, body) = handle_request(db, request.method().as_str(), request.url());
+    let response = Response::from_string(body.to_string())
+      .with_status_code(status)
+      .with_header(content_type.clone());
+    if let Err(e) = request.respond(response) {
+      log::warn!("Could not send the response: {}", e);
+    }
+  }
+  Ok(())
+}
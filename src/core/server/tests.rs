@@ -0,0 +1,116 @@
+use diesel::{Connection, SqliteConnection, connection::SimpleConnection};
+use serde_json::json;
+
+use crate::core::{
+  database::{models::*, *},
+  jobs,
+  sbatchman_configs::tests::init_sbatchman_for_tests,
+  server::handle_request,
+};
+
+/// Database of `jobs::tests::create_test_db` with two jobs
+fn create_test_db(dir: &tempfile::TempDir) -> Database {
+  let mut db = jobs::tests::create_test_db(dir);
+  let (config, _) = db.get_config_with_cluster("test_config").unwrap();
+  for (job_name, status) in [("first", Status::Completed), ("second", Status::Running)] {
+    db.create_job(&NewJob {
+      job_name,
+      config_id: config.id,
+      directory: "",
+      command: "echo",
+      status: &status,
+      variables: &json!({}),
+      labels: &json!({}),
+      ..Default::default()
+    })
+    .unwrap();
+  }
+  db
+}
+
+#[test]
+fn get_jobs() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+
+  let (status, body) = handle_request(&mut db, "GET", "/jobs");
+
+  assert_eq!(status, 200);
+  let jobs = body.as_array().unwrap();
+  assert_eq!(jobs.len(), 2);
+  assert_eq!(jobs[0]["job_name"], "first");
+  assert_eq!(jobs[1]["status"], "Running");
+  assert!(jobs[0]["id"].is_i64());
+  // The query string and a trailing slash are ignored
+  assert_eq!(
+    handle_request(&mut db, "GET", "/jobs/?status=running").1,
+    body
+  );
+}
+
+#[test]
+fn get_job_by_id() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  let id = db.get_jobs(None).unwrap()[1].id;
+
+  let (status, body) = handle_request(&mut db, "GET", &format!("/jobs/{}", id));
+  assert_eq!(status, 200);
+  assert_eq!(body["id"], id);
+  assert_eq!(body["job_name"], "second");
+
+  let (status, body) = handle_request(&mut db, "GET", "/jobs/999");
+  assert_eq!(status, 404);
+  assert!(body["error"].is_string());
+  assert_eq!(handle_request(&mut db, "GET", "/jobs/abc").0, 400);
+}
+
+#[test]
+fn get_job_by_id_database_error() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+  // Another connection breaks the database under the server
+  SqliteConnection::establish(dir.path().join("sbatchman.db").to_str().unwrap())
+    .unwrap()
+    .batch_execute("PRAGMA foreign_keys = OFF; DROP TABLE jobs")
+    .unwrap();
+
+  let (status, body) = handle_request(&mut db, "GET", "/jobs/1");
+  assert_eq!(status, 500);
+  assert!(body["error"].is_string());
+}
+
+#[test]
+fn get_clusters() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+
+  let (status, body) = handle_request(&mut db, "GET", "/clusters");
+
+  assert_eq!(status, 200);
+  assert_eq!(
+    body,
+    json!([{
+      "id": 1,
+      "cluster_name": "test_cluster",
+      "scheduler": "Local",
+      "max_jobs": null,
+      "header_prefix": null,
+      "submit_command": null,
+      "job_id_regex": null,
+      "pre_submit_hook": null,
+      "submit_interval_ms": null,
+    }])
+  );
+}
+
+#[test]
+fn read_only_and_unknown_endpoints() {
+  let dir = init_sbatchman_for_tests();
+  let mut db = create_test_db(&dir);
+
+  assert_eq!(handle_request(&mut db, "POST", "/jobs").0, 405);
+  assert_eq!(handle_request(&mut db, "DELETE", "/jobs/1").0, 405);
+  assert_eq!(handle_request(&mut db, "GET", "/configs").0, 404);
+  assert_eq!(db.get_jobs(None).unwrap().len(), 2);
+}
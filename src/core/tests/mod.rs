@@ -1 +1,634 @@
+use std::{
+  collections::HashMap,
+  path::Path,
+  sync::{Mutex, MutexGuard},
+};
 
+use serde_json::json;
+use tempfile::TempDir;
+
+use crate::core::{
+  Sbatchman, SbatchmanError,
+  database::{
+    Database,
+    models::{NewCluster, NewConfig, Scheduler, Status},
+  },
+  jobs::{JobError, JobFilter, LAUNCH_SUMMARY_FILE, LaunchOverrides, RUN_JOB_NAME},
+  parsers::{ParserError, parse_clusters_configs_from_file, parse_jobs_from_file},
+  sbatchman_configs::{
+    SBATCHMAN_CLUSTER_ENV, SBATCHMAN_DIR_ENV, SbatchmanConfig, SbatchmanConfigError,
+    get_sbatchman_config_global, get_sbatchman_config_local, set_sbatchman_config_global,
+    tests::init_sbatchman_for_tests, write_example_files,
+  },
+};
+
+/// Create a Sbatchman instance working in `dir`, with default configurations (no cluster set)
+pub fn sbatchman_for_tests(dir: &TempDir) -> Sbatchman {
+  Sbatchman {
+    db: Database::new(dir.path()).unwrap(),
+    path: dir.path().to_path_buf(),
+    config_global: SbatchmanConfig::default(),
+    config_local: SbatchmanConfig::default(),
+  }
+}
+
+/// Set the cluster of the local configuration of `sbatchman`, without writing it to disk
+pub fn set_local_cluster_for_tests(sbatchman: &mut Sbatchman, name: &str) {
+  sbatchman.config_local.cluster_name = Some(name.to_string());
+}
+
+/// Serializes the tests that set `SBATCHMAN_CLUSTER` with the ones relying on the configured cluster
+static CLUSTER_ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Hold this while a test sets `SBATCHMAN_CLUSTER` or expects the configured cluster to be used
+pub fn lock_cluster_env() -> MutexGuard<'static, ()> {
+  CLUSTER_ENV_LOCK
+    .lock()
+    .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[test]
+fn test_clusters_configs_to_json() {
+  let json =
+    Sbatchman::clusters_configs_to_json("src/core/parsers/tests/files/unset_params.yaml").unwrap();
+
+  let clusters = json.as_array().unwrap();
+  assert_eq!(clusters.len(), 1);
+  assert_eq!(clusters[0]["cluster"]["cluster_name"], "clusterA");
+  assert_eq!(clusters[0]["cluster"]["scheduler"], "Slurm");
+
+  let config_names: Vec<&str> = clusters[0]["configs"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|c| c["config_name"].as_str().unwrap())
+    .collect();
+  assert_eq!(
+    config_names,
+    vec!["exclusive_config", "shared_config", "shared_config_null"]
+  );
+  assert_eq!(clusters[0]["configs"][1]["flags"]["time"], "01:00:00");
+}
+
+/// Add a local `test_cluster` with a single `test_config` to the database of `sbatchman`
+fn add_local_cluster(sbatchman: &mut Sbatchman) {
+  add_local_cluster_with_env(sbatchman, json!({}));
+}
+
+/// Like `add_local_cluster`, with `env` as the environment of `test_config`
+fn add_local_cluster_with_env(sbatchman: &mut Sbatchman, env: serde_json::Value) {
+  let cluster = sbatchman
+    .db
+    .create_cluster(&NewCluster {
+      cluster_name: "test_cluster".to_string(),
+      scheduler: Scheduler::Local,
+      ..Default::default()
+    })
+    .unwrap();
+  sbatchman
+    .db
+    .create_cluster_config(&NewConfig {
+      config_name: "test_config".to_string(),
+      cluster_id: cluster.id,
+      flags: json!({}),
+      env,
+      ..Default::default()
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_launch_jobs_in_output_dir() {
+  let dir = TempDir::new().unwrap();
+  let output_dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  add_local_cluster(&mut sbatchman);
+
+  sbatchman
+    .launch_jobs_from_file(
+      "src/core/jobs/tests/files/single.yaml",
+      &Some("test_cluster".to_string()),
+      &LaunchOverrides::default(),
+      &Some(output_dir.path().to_str().unwrap().to_string()),
+      false,
+      true,
+    )
+    .unwrap();
+
+  let job = sbatchman.get_jobs(None).unwrap().remove(0);
+  assert_eq!(
+    job.directory,
+    output_dir
+      .path()
+      .join(format!("jobs/{}-{}", job.id, job.run_id.as_ref().unwrap()))
+      .to_str()
+      .unwrap()
+  );
+  assert!(job.get_stdout().unwrap().contains("hello"));
+  // The database stays in the sbatchman directory
+  assert!(!dir.path().join("jobs").exists());
+  assert!(!output_dir.path().join("sbatchman.db").exists());
+}
+
+#[test]
+fn test_get_output_dir() {
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  assert_eq!(sbatchman.get_output_dir(&None).unwrap(), dir.path());
+
+  // Relative to the project root
+  sbatchman.config_global.output_dir = Some("scratch".into());
+  assert_eq!(
+    sbatchman.get_output_dir(&None).unwrap(),
+    dir.path().parent().unwrap().join("scratch")
+  );
+  // The local configuration wins over the global one
+  sbatchman.config_local.output_dir = Some("/scratch/local".into());
+  assert_eq!(
+    sbatchman.get_output_dir(&None).unwrap(),
+    Path::new("/scratch/local")
+  );
+  // The command line wins over the configurations
+  assert_eq!(
+    sbatchman
+      .get_output_dir(&Some("/scratch/cli".to_string()))
+      .unwrap(),
+    Path::new("/scratch/cli")
+  );
+}
+
+#[test]
+fn test_unknown_cluster_lists_available_clusters() {
+  let _env = lock_cluster_env();
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  let err = sbatchman
+    .set_cluster_name("missing_cluster", true)
+    .unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "Unknown cluster \"missing_cluster\", no clusters are configured yet, add them with \
+     `sbatchman configure`"
+  );
+
+  add_local_cluster(&mut sbatchman);
+
+  let err = sbatchman
+    .count_jobs_from_file(
+      "src/core/parsers/tests/files/unset_params.yaml",
+      &Some("missing_cluster".to_string()),
+      &HashMap::new(),
+    )
+    .unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "Unknown cluster \"missing_cluster\", available clusters: test_cluster"
+  );
+
+  let err = sbatchman
+    .set_cluster_name("missing_cluster", true)
+    .unwrap_err();
+  assert!(matches!(err, SbatchmanError::UnknownCluster(..)));
+  assert_eq!(sbatchman.get_cluster_name(), None);
+}
+
+#[test]
+fn test_set_cluster_name_writes_its_scope() {
+  let _env = lock_cluster_env();
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  add_local_cluster(&mut sbatchman);
+  // The global configuration is the user's one, its cluster is restored at the end
+  let mut global = get_sbatchman_config_global().unwrap();
+  let previous_cluster = global.cluster_name.replace("global_cluster".to_string());
+  set_sbatchman_config_global(&global).unwrap();
+
+  sbatchman.set_cluster_name("test_cluster", true).unwrap();
+  let local = get_sbatchman_config_local(&dir.path().to_path_buf()).unwrap();
+  let global_after_local = get_sbatchman_config_global().unwrap();
+
+  sbatchman.set_cluster_name("test_cluster", false).unwrap();
+  let mut global_after_global = get_sbatchman_config_global().unwrap();
+  let cluster_after_global = global_after_global.cluster_name.take();
+  global_after_global.cluster_name = previous_cluster;
+  set_sbatchman_config_global(&global_after_global).unwrap();
+
+  assert_eq!(local.cluster_name.as_deref(), Some("test_cluster"));
+  assert_eq!(
+    global_after_local.cluster_name.as_deref(),
+    Some("global_cluster")
+  );
+  assert_eq!(cluster_after_global.as_deref(), Some("test_cluster"));
+}
+
+#[test]
+fn test_cluster_name_precedence() {
+  let _env = lock_cluster_env();
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  assert_eq!(sbatchman.get_cluster_name(), None);
+
+  sbatchman.config_global.cluster_name = Some("global_cluster".to_string());
+  assert_eq!(
+    sbatchman.get_cluster_name(),
+    Some("global_cluster".to_string())
+  );
+
+  sbatchman.config_local.cluster_name = Some("local_cluster".to_string());
+  assert_eq!(
+    sbatchman.get_cluster_name(),
+    Some("local_cluster".to_string())
+  );
+  assert_eq!(
+    sbatchman.get_cluster_name_global(),
+    Some("global_cluster".to_string())
+  );
+}
+
+#[test]
+fn test_launch_uses_local_cluster_first() {
+  let _env = lock_cluster_env();
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  add_local_cluster(&mut sbatchman);
+  sbatchman.config_global.cluster_name = Some("global_cluster".to_string());
+  sbatchman.config_local.cluster_name = Some("test_cluster".to_string());
+
+  let count = sbatchman
+    .count_jobs_from_file(
+      "src/core/jobs/tests/files/single.yaml",
+      &None,
+      &HashMap::new(),
+    )
+    .unwrap();
+  assert_eq!(count, 1);
+}
+
+#[test]
+fn test_launch_uses_cluster_env_var() {
+  let _env = lock_cluster_env();
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  add_local_cluster(&mut sbatchman);
+  sbatchman.config_local.cluster_name = Some("local_cluster".to_string());
+
+  // SAFETY: the tests reading SBATCHMAN_CLUSTER hold `lock_cluster_env`
+  unsafe { std::env::set_var(SBATCHMAN_CLUSTER_ENV, "test_cluster") };
+  let from_env = sbatchman.launch_jobs_from_file(
+    "src/core/jobs/tests/files/single.yaml",
+    &None,
+    &LaunchOverrides::default(),
+    &None,
+    false,
+    true,
+  );
+  // The explicit cluster still wins over the environment
+  let explicit = sbatchman.count_jobs_from_file(
+    "src/core/jobs/tests/files/single.yaml",
+    &Some("missing_cluster".to_string()),
+    &HashMap::new(),
+  );
+  unsafe { std::env::remove_var(SBATCHMAN_CLUSTER_ENV) };
+
+  // `local_cluster` does not exist, so the launch only succeeds on the cluster of the environment
+  assert_eq!(from_env.unwrap().launched, 1);
+  assert!(matches!(explicit, Err(SbatchmanError::UnknownCluster(..))));
+  assert_eq!(
+    sbatchman.get_cluster_name(),
+    Some("local_cluster".to_string())
+  );
+}
+
+#[test]
+fn test_launch_jobs_with_env_file() {
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  add_local_cluster_with_env(&mut sbatchman, json!({"MODE": "dev", "KEEP": "yes"}));
+
+  sbatchman
+    .launch_jobs_from_file(
+      "src/core/jobs/tests/files/single.yaml",
+      &Some("test_cluster".to_string()),
+      &LaunchOverrides {
+        env: Sbatchman::parse_env_file("src/core/parsers/tests/files/prod.env").unwrap(),
+        ..Default::default()
+      },
+      &None,
+      false,
+      true,
+    )
+    .unwrap();
+
+  let script = sbatchman.get_jobs(None).unwrap()[0].get_script().unwrap();
+  assert!(script.contains("export MODE=\"prod\"\n"));
+  assert!(!script.contains("export MODE=\"dev\""));
+  assert!(script.contains("export KEEP=\"yes\"\n"));
+  assert!(script.contains("export TOKEN=\"abc def\"\n"));
+}
+
+#[test]
+fn test_dump_jobs_jsonl() {
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  add_local_cluster(&mut sbatchman);
+  sbatchman
+    .launch_jobs_from_file(
+      "src/core/jobs/tests/files/sweep.yaml",
+      &Some("test_cluster".to_string()),
+      &LaunchOverrides::default(),
+      &None,
+      true,
+      true,
+    )
+    .unwrap();
+
+  let mut out = Vec::new();
+  let count = sbatchman.dump_jobs_jsonl(&mut out).unwrap();
+  let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+  assert_eq!(count, 8);
+  assert_eq!(lines.len(), sbatchman.get_jobs(None).unwrap().len());
+
+  let job: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+  let mut keys: Vec<&str> = job
+    .as_object()
+    .unwrap()
+    .keys()
+    .map(String::as_str)
+    .collect();
+  keys.sort();
+  assert_eq!(
+    keys,
+    vec![
+      "archived",
+      "cluster_name",
+      "command",
+      "config_id",
+      "config_name",
+      "directory",
+      "end_time",
+      "id",
+      "job_id",
+      "job_name",
+      "labels",
+      "launch_id",
+      "postprocess",
+      "preprocess",
+      "run_id",
+      "status",
+      "submit_time",
+      "variables",
+    ]
+  );
+  assert_eq!(job["config_name"], "test_config");
+  assert_eq!(job["cluster_name"], "test_cluster");
+}
+
+#[test]
+fn test_export_job() {
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  add_local_cluster(&mut sbatchman);
+  sbatchman
+    .launch_jobs_from_file(
+      "src/core/jobs/tests/files/sweep.yaml",
+      &Some("test_cluster".to_string()),
+      &LaunchOverrides::default(),
+      &None,
+      true,
+      true,
+    )
+    .unwrap();
+  let job = sbatchman.get_jobs(None).unwrap().remove(0);
+
+  let document = sbatchman.export_job(job.id).unwrap();
+
+  assert_eq!(document["job"]["id"], job.id);
+  assert_eq!(document["config"]["id"], job.config_id);
+  assert_eq!(document["cluster"]["cluster_name"], "test_cluster");
+  let script = document["script"].as_str().unwrap();
+  assert_eq!(script, job.get_script().unwrap());
+  assert!(script.contains(&job.command));
+  let logs = document["logs"].as_array().unwrap();
+  assert!(
+    logs
+      .iter()
+      .any(|entry| entry["type"] == "StatusUpdate" && entry["timestamp"].is_string())
+  );
+}
+
+#[test]
+fn test_launch_writes_summary() {
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  add_local_cluster(&mut sbatchman);
+
+  let summary = sbatchman
+    .launch_jobs_from_file(
+      "src/core/jobs/tests/files/sweep.yaml",
+      &Some("test_cluster".to_string()),
+      &LaunchOverrides::default(),
+      &None,
+      true,
+      true,
+    )
+    .unwrap();
+
+  let record: serde_json::Value =
+    serde_json::from_str(&std::fs::read_to_string(dir.path().join(LAUNCH_SUMMARY_FILE)).unwrap())
+      .unwrap();
+  let job_ids: Vec<i32> = sbatchman
+    .get_jobs(None)
+    .unwrap()
+    .iter()
+    .map(|job| job.id)
+    .collect();
+  assert_eq!(summary.job_ids, job_ids);
+  assert_eq!(record["file"], "src/core/jobs/tests/files/sweep.yaml");
+  assert_eq!(record["launched"], 8);
+  assert_eq!(record["virtual_queued"], 0);
+  assert_eq!(record["failed"], 0);
+  assert_eq!(record["job_ids"], json!(job_ids));
+  assert!(record["timestamp"].is_string());
+}
+
+#[test]
+fn test_launches_get_distinct_launch_ids() {
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  add_local_cluster(&mut sbatchman);
+
+  let mut launch = || {
+    sbatchman
+      .launch_jobs_from_file(
+        "src/core/jobs/tests/files/sweep.yaml",
+        &Some("test_cluster".to_string()),
+        &LaunchOverrides::default(),
+        &None,
+        true,
+        true,
+      )
+      .unwrap()
+  };
+  let first = launch();
+  let second = launch();
+
+  let first_id = first.launch_id.clone().unwrap();
+  let second_id = second.launch_id.clone().unwrap();
+  assert_ne!(first_id, second_id);
+  for (launch_id, summary) in [(first_id, first), (second_id, second)] {
+    let job_ids: Vec<i32> = sbatchman
+      .get_jobs(Some(JobFilter {
+        launch_id: Some(launch_id),
+        ..Default::default()
+      }))
+      .unwrap()
+      .iter()
+      .map(|job| job.id)
+      .collect();
+    assert_eq!(job_ids, summary.job_ids);
+  }
+  assert_eq!(sbatchman.get_jobs(None).unwrap().len(), 16);
+}
+
+#[test]
+fn test_open_sbatchman_dir_override() {
+  let env_dir = init_sbatchman_for_tests();
+  let flag_dir = init_sbatchman_for_tests();
+
+  // SAFETY: no other test reads or writes SBATCHMAN_DIR
+  unsafe { std::env::set_var(SBATCHMAN_DIR_ENV, env_dir.path()) };
+  let from_env = Sbatchman::new();
+  // The explicit directory (`--db`) wins over the environment
+  let from_flag = Sbatchman::open(Some(flag_dir.path()), false);
+  let missing = Sbatchman::open(Some(&flag_dir.path().join("missing")), false);
+  unsafe { std::env::remove_var(SBATCHMAN_DIR_ENV) };
+
+  assert_eq!(from_env.unwrap().path, env_dir.path());
+  assert!(env_dir.path().join("sbatchman.db").is_file());
+  assert_eq!(from_flag.unwrap().path, flag_dir.path());
+  assert!(matches!(
+    missing,
+    Err(SbatchmanError::ConfigError(
+      SbatchmanConfigError::SbatchmanDirMissing(_)
+    ))
+  ));
+}
+
+#[test]
+fn test_import_config_description() {
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+
+  sbatchman
+    .import_clusters_configs_from_file("src/core/parsers/tests/files/description.yaml", false)
+    .unwrap();
+
+  let cluster = sbatchman.db.get_cluster_by_name("clusterA").unwrap();
+  let descriptions: Vec<(String, Option<String>)> = sbatchman
+    .db
+    .get_configs_by_cluster_sorted(&cluster)
+    .unwrap()
+    .into_iter()
+    .map(|config| (config.config_name, config.description))
+    .collect();
+  assert_eq!(
+    descriptions,
+    vec![
+      (
+        "documented".to_string(),
+        Some("Small runs for debugging".to_string())
+      ),
+      ("undocumented".to_string(), None),
+    ]
+  );
+}
+
+#[test]
+fn test_import_unknown_cluster_key() {
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  let path = "src/core/parsers/tests/files/per_cluster_typo.yaml";
+
+  let result = sbatchman.import_clusters_configs_from_file(path, true);
+  assert!(matches!(
+    result,
+    Err(SbatchmanError::ParserError(ParserError::UnknownClusterKey(variable, cluster)))
+      if variable == "gpus" && cluster == "clsuterA"
+  ));
+  assert!(sbatchman.db.get_cluster_names().unwrap().is_empty());
+
+  // Without `strict` the typos are only warnings
+  sbatchman
+    .import_clusters_configs_from_file(path, false)
+    .unwrap();
+  assert_eq!(sbatchman.db.get_cluster_names().unwrap(), vec!["clusterA"]);
+}
+
+#[test]
+fn test_run_command() {
+  let dir = TempDir::new().unwrap();
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  add_local_cluster(&mut sbatchman);
+
+  let summary = sbatchman
+    .run_command(
+      "test_config",
+      "echo ad-hoc",
+      &Some("test_cluster".to_string()),
+      &None,
+    )
+    .unwrap();
+
+  assert_eq!(summary.launched, 1);
+  let job = sbatchman.get_job(summary.job_ids[0]).unwrap();
+  assert_eq!(job.job_name, RUN_JOB_NAME);
+  assert_eq!(job.command, "echo ad-hoc");
+  // The local job ran to completion before the launch returned
+  assert_eq!(job.get_logged_status(), Some(Status::Completed));
+  assert!(job.get_stdout().unwrap().contains("ad-hoc"));
+
+  let result = sbatchman.run_command("missing", "true", &Some("test_cluster".to_string()), &None);
+  assert!(matches!(
+    result,
+    Err(SbatchmanError::JobError(JobError::ConfigNotFound(name))) if name == "missing"
+  ));
+}
+
+#[test]
+fn test_example_files_parse_and_launch() {
+  let dir = TempDir::new().unwrap();
+  let written = write_example_files(dir.path()).unwrap();
+  let clusters_file = dir.path().join("clusters.yaml");
+  let jobs_file = dir.path().join("jobs.yaml");
+  assert_eq!(written, vec![clusters_file.clone(), jobs_file.clone()]);
+
+  let clusters = parse_clusters_configs_from_file(&clusters_file).unwrap();
+  assert_eq!(clusters[0].cluster.scheduler, Scheduler::Local);
+  let jobs = parse_jobs_from_file(&jobs_file).unwrap();
+  assert_eq!(jobs.len(), 2);
+  // `greeting` comes from the included clusters configuration
+  assert!(jobs[0].variables.contains_key("greeting"));
+
+  let mut sbatchman = sbatchman_for_tests(&dir);
+  sbatchman
+    .import_clusters_configs_from_file(clusters_file.to_str().unwrap(), true)
+    .unwrap();
+  let summary = sbatchman
+    .launch_jobs_from_file(
+      jobs_file.to_str().unwrap(),
+      &Some("local".to_string()),
+      &LaunchOverrides::default(),
+      &None,
+      true,
+      true,
+    )
+    .unwrap();
+  assert_eq!(summary.launched, 3);
+  assert_eq!(summary.failed, 0);
+  for job in sbatchman.get_jobs(None).unwrap() {
+    assert_eq!(job.get_logged_status(), Some(Status::Completed));
+  }
+
+  // The files written on a previous init are kept
+  assert!(write_example_files(dir.path()).unwrap().is_empty());
+}
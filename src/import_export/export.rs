@@ -1,5 +1,6 @@
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
 use flate2::Compression;
@@ -9,14 +10,24 @@ use walkdir::WalkDir;
 use zip::{ZipWriter, write::FileOptions};
 
 // Make sure sbatchman_configs is public in core/mod.rs
-use crate::core::sbatchman_configs::get_sbatchman_dir;
+use crate::core::sbatchman_configs::resolve_sbatchman_dir;
 
-/// Export the .sbatchman directory into either "zip" or "tar.gz"
+#[cfg(test)]
+mod tests;
+
+/// Export the .sbatchman directory into either "zip", "tar.gz" or "dir"
 /// Default is "tar.gz" if `format` is None or invalid.
-pub fn export(format: Option<&str>, compressed_filename: Option<&str>) {
+/// "dir" copies the directory uncompressed next to .sbatchman (ex. for rsync).
+/// `sbatchman_dir` (ex. `--db`) overrides the discovered .sbatchman directory.
+pub fn export(
+  format: Option<&str>,
+  compressed_filename: Option<&str>,
+  sbatchman_dir: Option<&Path>,
+) {
   // Determine format
   let format = match format {
     Some("zip") => "zip",
+    Some("dir") => "dir",
     _ => "tar.gz", // default
   };
 
@@ -26,7 +37,7 @@ pub fn export(format: Option<&str>, compressed_filename: Option<&str>) {
   };
 
   // Locate .sbatchman directory
-  let sbatch_dir = match get_sbatchman_dir() {
+  let sbatch_dir = match resolve_sbatchman_dir(sbatchman_dir) {
     Ok(path) => path,
     Err(e) => {
       eprintln!("❌ Could not find .sbatchman directory: {:?}", e);
@@ -54,6 +65,17 @@ pub fn export(format: Option<&str>, compressed_filename: Option<&str>) {
 
   let ts = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
 
+  if format == "dir" {
+    let out_name = format!("{}_{}_{}", filename, clustername, ts);
+    let out_path = sbatch_dir.with_file_name(out_name);
+    println!("📁 Copying .sbatchman → {}", out_path.display());
+    match copy_dir(&sbatch_dir, &out_path) {
+      Ok(_) => println!("✅ Directory copied successfully!"),
+      Err(e) => eprintln!("❌ Failed to copy directory: {}", e),
+    }
+    return;
+  }
+
   let out_name = format!("{}_{}_{}_.{}", filename, clustername, ts, format);
   let out_path = match env::home_dir() {
     Some(cd) => cd.join(&out_name),
@@ -94,6 +116,8 @@ fn create_zip(src_dir: &Path, dest_file: &Path) -> Result<(), Box<dyn std::error
     let path = entry.path();
     let name = path.strip_prefix(src_dir).unwrap();
 
+    // Keep the Unix mode, otherwise extracted job scripts are not executable anymore
+    let options = options.unix_permissions(entry.metadata()?.permissions().mode());
     if path.is_file() {
       zip.start_file(name.to_string_lossy(), options)?;
       let mut f = File::open(path)?;
@@ -108,7 +132,10 @@ fn create_zip(src_dir: &Path, dest_file: &Path) -> Result<(), Box<dyn std::error
 }
 
 // ---- TAR.GZ creation ----
-fn create_tar_gz(src_dir: &Path, dest_file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+pub(super) fn create_tar_gz(
+  src_dir: &Path,
+  dest_file: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
   let tar_gz = File::create(dest_file)?;
   let enc = GzEncoder::new(tar_gz, Compression::default());
   let mut tar = Builder::new(enc);
@@ -119,6 +146,31 @@ fn create_tar_gz(src_dir: &Path, dest_file: &Path) -> Result<(), Box<dyn std::er
     .and_then(|n| n.to_str())
     .unwrap_or(".sbatchman");
 
+  // The mode of the files is kept by the tar headers
   tar.append_dir_all(dir_name, src_dir)?;
+  tar.into_inner()?.finish()?;
+  Ok(())
+}
+
+// ---- Uncompressed copy ----
+/// Copy `src_dir` into the new directory `dest_dir`, preserving the permissions
+/// (ex. job scripts stay executable)
+pub(super) fn copy_dir(src_dir: &Path, dest_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+  if dest_dir.exists() {
+    return Err(format!("{} already exists", dest_dir.display()).into());
+  }
+  for entry in WalkDir::new(src_dir) {
+    let entry = entry?;
+    let path = entry.path();
+    let dest = dest_dir.join(path.strip_prefix(src_dir)?);
+
+    if entry.file_type().is_dir() {
+      fs::create_dir_all(&dest)?;
+      fs::set_permissions(&dest, entry.metadata()?.permissions())?;
+    } else {
+      // fs::copy also copies the permissions
+      fs::copy(path, &dest)?;
+    }
+  }
   Ok(())
 }
@@ -0,0 +1,105 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+use crate::import_export::export::{copy_dir, create_tar_gz, create_zip};
+
+/// Create a `.sbatchman`-like directory holding an executable job script
+fn create_dir_with_script() -> TempDir {
+  let dir = TempDir::new().unwrap();
+  let job_dir = dir.path().join("jobs/1");
+  fs::create_dir_all(&job_dir).unwrap();
+  fs::write(job_dir.join("log.jsonb"), "{}\n").unwrap();
+  fs::set_permissions(job_dir.join("log.jsonb"), fs::Permissions::from_mode(0o640)).unwrap();
+  fs::write(job_dir.join("job.sh"), "#!/bin/bash\necho hello\n").unwrap();
+  fs::set_permissions(job_dir.join("job.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+  dir
+}
+
+/// Relative paths of all the entries under `dir`
+fn relative_entries(dir: &Path) -> BTreeSet<PathBuf> {
+  WalkDir::new(dir)
+    .into_iter()
+    .map(|entry| {
+      entry
+        .unwrap()
+        .path()
+        .strip_prefix(dir)
+        .unwrap()
+        .to_path_buf()
+    })
+    .collect()
+}
+
+#[test]
+fn test_copy_dir_preserves_tree_and_permissions() {
+  let src = TempDir::new().unwrap();
+  let job_dir = src.path().join("jobs/1");
+  fs::create_dir_all(&job_dir).unwrap();
+  fs::write(src.path().join("sbatchman.db"), "db").unwrap();
+  fs::write(job_dir.join("log.jsonb"), "{}\n").unwrap();
+  fs::write(job_dir.join("job.sh"), "#!/bin/bash\necho hello\n").unwrap();
+  fs::set_permissions(job_dir.join("job.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+
+  let dest_parent = TempDir::new().unwrap();
+  let dest = dest_parent.path().join("sbatchman_copy");
+  copy_dir(src.path(), &dest).unwrap();
+
+  assert_eq!(relative_entries(&dest), relative_entries(src.path()));
+  assert_eq!(
+    fs::read_to_string(dest.join("jobs/1/job.sh")).unwrap(),
+    "#!/bin/bash\necho hello\n"
+  );
+  let mode = fs::metadata(dest.join("jobs/1/job.sh"))
+    .unwrap()
+    .permissions()
+    .mode();
+  assert_eq!(mode & 0o777, 0o755);
+}
+
+#[test]
+fn test_copy_dir_refuses_existing_destination() {
+  let src = TempDir::new().unwrap();
+  let dest = TempDir::new().unwrap();
+  assert!(copy_dir(src.path(), dest.path()).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_create_zip_preserves_mode() {
+  let src = create_dir_with_script();
+  let out = TempDir::new().unwrap();
+  let archive = out.path().join("sbatchman.zip");
+  create_zip(src.path(), &archive).unwrap();
+
+  let mut zip = zip::ZipArchive::new(fs::File::open(&archive).unwrap()).unwrap();
+  let dest = out.path().join("extracted");
+  zip.extract(&dest).unwrap();
+
+  let mode = |p: &str| fs::metadata(dest.join(p)).unwrap().permissions().mode() & 0o777;
+  assert_eq!(mode("jobs/1/job.sh"), 0o755);
+  assert_eq!(mode("jobs/1/log.jsonb"), 0o640);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_create_tar_gz_preserves_mode() {
+  let src = create_dir_with_script();
+  let out = TempDir::new().unwrap();
+  let archive = out.path().join("sbatchman.tar.gz");
+  create_tar_gz(src.path(), &archive).unwrap();
+
+  let gz = flate2::read::GzDecoder::new(fs::File::open(&archive).unwrap());
+  let dest = out.path().join("extracted");
+  tar::Archive::new(gz).unpack(&dest).unwrap();
+
+  // The archive root is named after the source directory
+  let root = dest.join(src.path().file_name().unwrap());
+  let mode = |p: &str| fs::metadata(root.join(p)).unwrap().permissions().mode() & 0o777;
+  assert_eq!(mode("jobs/1/job.sh"), 0o755);
+  assert_eq!(mode("jobs/1/log.jsonb"), 0o640);
+}
@@ -1,3 +1,357 @@
-pub fn import() {
-  // Implementation for import functionality
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uuid::Uuid;
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+use crate::core::SbatchmanError;
+use crate::core::database::models::{Job, NewCluster, NewConfig};
+use crate::core::database::{Database, StorageError};
+use crate::core::sbatchman_configs::resolve_sbatchman_dir;
+use crate::import_export::export::copy_dir;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+  #[error("IO Error: {0}")]
+  Io(#[from] io::Error),
+  #[error("Storage Error: {0}")]
+  Storage(#[from] StorageError),
+  #[error("Could not extract {0}: {1}")]
+  Extract(String, String),
+  #[error("No sbatchman.db found in {0}")]
+  MissingDatabase(String),
+  #[error("Job #{0} of the archive belongs to config #{1}, which is not in the archive")]
+  MissingConfig(i32, i32),
+  #[error("Path {} is not valid UTF-8", .0.display())]
+  NonUtf8Path(PathBuf),
+}
+
+/// A cluster or config of the archive
+#[derive(Debug, PartialEq)]
+pub struct PlannedEntry {
+  /// Cluster name, or `cluster/config` for configs
+  pub name: String,
+  /// Already in the local database, the local one is kept
+  pub exists: bool,
+}
+
+/// A job of the archive whose id is already taken by a local job. It is imported with a new id.
+#[derive(Debug, PartialEq)]
+pub struct JobConflict {
+  pub id: i32,
+  pub job_name: String,
+  pub local_job_name: String,
+}
+
+/// What an import merges (or would merge, for a dry run) into the local database
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportPlan {
+  pub clusters: Vec<PlannedEntry>,
+  pub configs: Vec<PlannedEntry>,
+  /// Number of jobs imported
+  pub jobs: usize,
+  /// Number of jobs skipped because they were already imported (same `run_id`)
+  pub skipped_jobs: usize,
+  pub conflicts: Vec<JobConflict>,
+}
+
+/// Import an archive created by `export` (tar.gz, zip or directory) into the .sbatchman directory.
+/// With `dry_run`, only print what would be imported. `sbatchman_dir` (ex. `--db`) overrides the
+/// discovered .sbatchman directory.
+pub fn import(
+  archive: &str,
+  dry_run: bool,
+  sbatchman_dir: Option<&Path>,
+) -> Result<(), SbatchmanError> {
+  let sbatch_dir = resolve_sbatchman_dir(sbatchman_dir)?;
+  let mut db = Database::new(&sbatch_dir)?;
+
+  println!("📦 Importing {} → {}", archive, sbatch_dir.display());
+  let plan = import_archive(Path::new(archive), &sbatch_dir, &mut db, dry_run)?;
+  print!("{}", format_plan(&plan, dry_run));
+  if dry_run {
+    println!("ℹ️  Dry run, nothing was written");
+  } else {
+    println!("✅ Archive imported successfully!");
+  }
+  Ok(())
+}
+
+/// Merge the clusters, configs and jobs of `archive` into `db`, copying the job directories into
+/// `sbatch_dir`. Clusters and configs are matched by name, the local ones are kept. Jobs already
+/// imported (same `run_id`) are skipped, the others get a new id. Jobs without `run_id` are given
+/// one derived from their config, directory and submit time, so they are imported only once.
+/// The database is left untouched if the import fails.
+/// With `dry_run`, nothing is written and the returned plan tells what would be imported.
+pub fn import_archive(
+  archive: &Path,
+  sbatch_dir: &Path,
+  db: &mut Database,
+  dry_run: bool,
+) -> Result<ImportPlan, ImportError> {
+  let staging = StagingDir::new()?;
+  extract(archive, &staging.0)
+    .map_err(|e| ImportError::Extract(archive.display().to_string(), e.to_string()))?;
+  let source_dir = find_sbatchman_dir(&staging.0)
+    .ok_or_else(|| ImportError::MissingDatabase(archive.display().to_string()))?;
+  // The archive is opened from the staging copy, so its migrations never touch the original
+  let mut source = Database::new(&source_dir)?;
+
+  // The job directories copied so far, removed if the import fails
+  let mut created_dirs = vec![];
+  let result = db.transaction(|db| {
+    merge_archive(
+      &mut source,
+      &source_dir,
+      sbatch_dir,
+      db,
+      dry_run,
+      &mut created_dirs,
+    )
+  });
+  if result.is_err() {
+    for dir in created_dirs {
+      let _ = fs::remove_dir_all(dir);
+    }
+  }
+  result
+}
+
+/// Body of `import_archive`, run in a transaction of `db`
+fn merge_archive(
+  source: &mut Database,
+  source_dir: &Path,
+  sbatch_dir: &Path,
+  db: &mut Database,
+  dry_run: bool,
+  created_dirs: &mut Vec<PathBuf>,
+) -> Result<ImportPlan, ImportError> {
+  let mut plan = ImportPlan::default();
+  // Config ids of the archive mapped to the local ones
+  let mut config_ids = HashMap::new();
+  // Config ids of the archive mapped to their `cluster/config` names
+  let mut config_names = HashMap::new();
+  for cluster_name in source.get_cluster_names()? {
+    let cluster = source.get_cluster_by_name(&cluster_name)?;
+    let exists = db.cluster_name_exists(&cluster_name)?;
+    let local_cluster = match (exists, dry_run) {
+      (true, _) => Some(db.get_cluster_by_name(&cluster_name)?),
+      (false, true) => None,
+      (false, false) => Some(db.create_cluster(&NewCluster {
+        cluster_name: cluster.cluster_name.clone(),
+        scheduler: cluster.scheduler.clone(),
+        max_jobs: cluster.max_jobs,
+        header_prefix: cluster.header_prefix.clone(),
+        submit_command: cluster.submit_command.clone(),
+        job_id_regex: cluster.job_id_regex.clone(),
+        pre_submit_hook: cluster.pre_submit_hook.clone(),
+        submit_interval_ms: cluster.submit_interval_ms,
+      })?),
+    };
+    let local_configs = match &local_cluster {
+      Some(local_cluster) => db.get_configs_by_cluster(local_cluster)?,
+      None => HashMap::new(),
+    };
+
+    for config in source.get_configs_by_cluster_sorted(&cluster)? {
+      let local_config = local_configs.get(&config.config_name);
+      let name = format!("{}/{}", cluster_name, config.config_name);
+      config_names.insert(config.id, name.clone());
+      plan.configs.push(PlannedEntry {
+        name,
+        exists: local_config.is_some(),
+      });
+      let local_id = match (local_config, &local_cluster) {
+        (Some(local_config), _) => local_config.id,
+        (None, Some(local_cluster)) if !dry_run => {
+          db.create_cluster_config(&NewConfig {
+            config_name: config.config_name.clone(),
+            cluster_id: local_cluster.id,
+            flags: config.flags.clone(),
+            env: config.env.clone(),
+            default_preprocess: config.default_preprocess.clone(),
+            default_postprocess: config.default_postprocess.clone(),
+            retry_on_timeout: config.retry_on_timeout.clone(),
+            max_output_bytes: config.max_output_bytes,
+            shell: config.shell.clone(),
+            description: config.description.clone(),
+            extra_headers: config.extra_headers.clone(),
+            env_snapshot: config.env_snapshot.clone(),
+          })?
+          .id
+        }
+        _ => continue,
+      };
+      config_ids.insert(config.id, local_id);
+    }
+    plan.clusters.push(PlannedEntry {
+      name: cluster_name,
+      exists,
+    });
+  }
+
+  let local_jobs = db.get_jobs(None)?;
+  let local_run_ids: HashSet<String> = local_jobs.iter().filter_map(|j| j.run_id.clone()).collect();
+  let local_names: HashMap<i32, String> =
+    local_jobs.into_iter().map(|j| (j.id, j.job_name)).collect();
+  for mut job in source.get_jobs(None)? {
+    let config_name = config_names
+      .get(&job.config_id)
+      .ok_or(ImportError::MissingConfig(job.id, job.config_id))?;
+    if job.run_id.is_none() {
+      job.run_id = Some(derived_run_id(config_name, &job));
+    }
+    if job
+      .run_id
+      .as_ref()
+      .is_some_and(|run_id| local_run_ids.contains(run_id))
+    {
+      plan.skipped_jobs += 1;
+      continue;
+    }
+    plan.jobs += 1;
+    if let Some(local_job_name) = local_names.get(&job.id) {
+      plan.conflicts.push(JobConflict {
+        id: job.id,
+        job_name: job.job_name.clone(),
+        local_job_name: local_job_name.clone(),
+      });
+    }
+    if dry_run {
+      continue;
+    }
+
+    let config_id = *config_ids
+      .get(&job.config_id)
+      .ok_or(ImportError::MissingConfig(job.id, job.config_id))?;
+    let imported = db.import_job(&job, config_id)?;
+    // Same layout as the directories of the launched jobs, `jobs/<id>-<run_id>`
+    let dir = sbatch_dir.join("jobs").join(format!(
+      "{}-{}",
+      imported.id,
+      imported.run_id.as_deref().unwrap_or_default()
+    ));
+    let source_job_dir = Path::new(&job.directory)
+      .file_name()
+      .map(|name| source_dir.join("jobs").join(name));
+    created_dirs.push(dir.clone());
+    match source_job_dir {
+      Some(source_job_dir) if source_job_dir.is_dir() => {
+        copy_dir(&source_job_dir, &dir).map_err(|e| io::Error::other(e.to_string()))?
+      }
+      _ => fs::create_dir_all(&dir)?,
+    }
+    let directory = dir
+      .to_str()
+      .ok_or_else(|| ImportError::NonUtf8Path(dir.clone()))?;
+    db.update_job_path(imported.id, directory)?;
+  }
+
+  Ok(plan)
+}
+
+/// `run_id` of a job of an archive created before the jobs had one. It only depends on the
+/// `cluster/config` name, directory and submit time of the job, so that importing the archive
+/// again finds the job already imported.
+fn derived_run_id(config_name: &str, job: &Job) -> String {
+  let mut hasher = Sha256::new();
+  hasher.update(config_name);
+  hasher.update([0]);
+  hasher.update(&job.directory);
+  hasher.update([0]);
+  hasher.update(job.submit_time.map(|t| t.to_string()).unwrap_or_default());
+  let mut bytes = [0; 16];
+  bytes.copy_from_slice(&hasher.finalize()[..16]);
+  Uuid::from_bytes(bytes).to_string()
+}
+
+/// Human readable report of an import, listing the job id conflicts
+pub fn format_plan(plan: &ImportPlan, dry_run: bool) -> String {
+  let mut out = String::new();
+  let verb = if dry_run { "would be" } else { "were" };
+  let entry_status = |entry: &PlannedEntry| {
+    if entry.exists {
+      "exists, kept local"
+    } else {
+      "new"
+    }
+  };
+
+  out.push_str("Clusters:\n");
+  for cluster in &plan.clusters {
+    out.push_str(&format!("  {} ({})\n", cluster.name, entry_status(cluster)));
+  }
+  out.push_str("Configs:\n");
+  for config in &plan.configs {
+    out.push_str(&format!("  {} ({})\n", config.name, entry_status(config)));
+  }
+  out.push_str(&format!(
+    "Jobs: {} {} imported, {} already imported\n",
+    plan.jobs, verb, plan.skipped_jobs
+  ));
+  if !plan.conflicts.is_empty() {
+    out.push_str(&format!(
+      "Job id conflicts ({} {} given a new id):\n",
+      plan.conflicts.len(),
+      verb
+    ));
+    for conflict in &plan.conflicts {
+      out.push_str(&format!(
+        "  #{} \"{}\" collides with local job \"{}\"\n",
+        conflict.id, conflict.job_name, conflict.local_job_name
+      ));
+    }
+  }
+  out
+}
+
+/// Temporary directory the archive is extracted to, removed when dropped
+struct StagingDir(PathBuf);
+
+impl StagingDir {
+  fn new() -> io::Result<Self> {
+    let path = env::temp_dir().join(format!("sbatchman-import-{}", Uuid::new_v4()));
+    fs::create_dir_all(&path)?;
+    Ok(StagingDir(path))
+  }
+}
+
+impl Drop for StagingDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.0);
+  }
+}
+
+/// Extract a zip or tar.gz archive into `dest`, or copy it if it is a directory
+fn extract(archive: &Path, dest: &Path) -> Result<(), Box<dyn std::error::Error>> {
+  if archive.is_dir() {
+    return copy_dir(archive, &dest.join(".sbatchman"));
+  }
+  let file = File::open(archive)?;
+  if archive.extension().is_some_and(|ext| ext == "zip") {
+    ZipArchive::new(file)?.extract(dest)?;
+  } else {
+    tar::Archive::new(GzDecoder::new(file)).unpack(dest)?;
+  }
+  Ok(())
+}
+
+/// Directory holding the `sbatchman.db` of an extracted archive
+fn find_sbatchman_dir(root: &Path) -> Option<PathBuf> {
+  WalkDir::new(root)
+    .max_depth(2)
+    .into_iter()
+    .filter_map(Result::ok)
+    .find(|entry| entry.file_name() == "sbatchman.db")
+    .and_then(|entry| entry.path().parent().map(Path::to_path_buf))
 }
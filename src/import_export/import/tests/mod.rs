@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use diesel::{Connection, SqliteConnection, connection::SimpleConnection};
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+use crate::core::database::Database;
+use crate::core::database::models::{NewCluster, NewConfig, NewJob, Scheduler, Status};
+use crate::import_export::export::create_tar_gz;
+use crate::import_export::import::{
+  ImportError, JobConflict, PlannedEntry, format_plan, import, import_archive,
+};
+
+/// Create a cluster with the given configs, returning the config ids
+fn create_cluster(db: &mut Database, cluster_name: &str, config_names: &[&str]) -> Vec<i32> {
+  let cluster = db
+    .create_cluster(&NewCluster {
+      cluster_name: cluster_name.to_string(),
+      scheduler: Scheduler::Local,
+      ..Default::default()
+    })
+    .unwrap();
+  config_names
+    .iter()
+    .map(|config_name| {
+      db.create_cluster_config(&NewConfig {
+        config_name: config_name.to_string(),
+        cluster_id: cluster.id,
+        flags: serde_json::json!({}),
+        env: serde_json::json!({}),
+        ..Default::default()
+      })
+      .unwrap()
+      .id
+    })
+    .collect()
+}
+
+/// Create a job with a directory holding a `stdout.log`
+fn create_job(db: &mut Database, dir: &Path, config_id: i32, job_name: &str) {
+  let job = db
+    .create_job(&NewJob {
+      job_name,
+      config_id,
+      directory: "",
+      command: "echo hello",
+      status: &Status::Completed,
+      variables: &serde_json::json!({}),
+      labels: &serde_json::json!({}),
+      ..Default::default()
+    })
+    .unwrap();
+  let job_dir = dir
+    .join("jobs")
+    .join(format!("{}-{}", job.id, job.run_id.unwrap()));
+  fs::create_dir_all(&job_dir).unwrap();
+  fs::write(job_dir.join("stdout.log"), job_name).unwrap();
+  db.update_job_path(job.id, job_dir.to_str().unwrap())
+    .unwrap();
+}
+
+/// A .sbatchman directory with clusters `shared` (configs `cpu`, `gpu`) and `remote` (config
+/// `big`), and the jobs `a`, `b` and `c`
+fn create_source() -> TempDir {
+  let dir = TempDir::new().unwrap();
+  let mut db = Database::new(dir.path()).unwrap();
+  let shared = create_cluster(&mut db, "shared", &["cpu", "gpu"]);
+  let remote = create_cluster(&mut db, "remote", &["big"]);
+  create_job(&mut db, dir.path(), shared[0], "a");
+  create_job(&mut db, dir.path(), shared[1], "b");
+  create_job(&mut db, dir.path(), remote[0], "c");
+  dir
+}
+
+/// A .sbatchman directory with cluster `shared` (config `cpu`) and the job `local`
+fn create_target() -> TempDir {
+  let dir = TempDir::new().unwrap();
+  let mut db = Database::new(dir.path()).unwrap();
+  let shared = create_cluster(&mut db, "shared", &["cpu"]);
+  create_job(&mut db, dir.path(), shared[0], "local");
+  dir
+}
+
+/// Run `sql` on the database of the .sbatchman directory `dir`, bypassing `Database`
+fn execute_sql(dir: &Path, sql: &str) {
+  SqliteConnection::establish(dir.join("sbatchman.db").to_str().unwrap())
+    .unwrap()
+    .batch_execute(sql)
+    .unwrap();
+}
+
+/// Contents of the files under `dir`, the SQLite files excepted
+fn file_contents(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+  WalkDir::new(dir)
+    .into_iter()
+    .map(Result::unwrap)
+    .filter(|entry| {
+      entry.file_type().is_file()
+        && !entry
+          .file_name()
+          .to_string_lossy()
+          .starts_with("sbatchman.db")
+    })
+    .map(|entry| {
+      (
+        entry.path().strip_prefix(dir).unwrap().to_path_buf(),
+        fs::read(entry.path()).unwrap(),
+      )
+    })
+    .collect()
+}
+
+/// Names of the clusters, configs and jobs of a database
+fn db_contents(db: &mut Database) -> (Vec<String>, Vec<String>, Vec<String>) {
+  let clusters = db.get_cluster_names().unwrap();
+  let mut configs = vec![];
+  for cluster_name in &clusters {
+    let cluster = db.get_cluster_by_name(cluster_name).unwrap();
+    for config in db.get_configs_by_cluster_sorted(&cluster).unwrap() {
+      configs.push(format!("{}/{}", cluster_name, config.config_name));
+    }
+  }
+  let jobs = db
+    .get_jobs(None)
+    .unwrap()
+    .into_iter()
+    .map(|job| format!("{} {} {}", job.id, job.job_name, job.directory))
+    .collect();
+  (clusters, configs, jobs)
+}
+
+#[test]
+fn test_dry_run_reports_without_writing() {
+  let source = create_source();
+  let target = create_target();
+  let mut db = Database::new(target.path()).unwrap();
+  let files_before = file_contents(target.path());
+  let db_before = db_contents(&mut db);
+
+  let plan = import_archive(source.path(), target.path(), &mut db, true).unwrap();
+
+  assert_eq!(
+    plan.clusters,
+    vec![
+      PlannedEntry {
+        name: "remote".to_string(),
+        exists: false
+      },
+      PlannedEntry {
+        name: "shared".to_string(),
+        exists: true
+      },
+    ]
+  );
+  assert_eq!(
+    plan.configs,
+    vec![
+      PlannedEntry {
+        name: "remote/big".to_string(),
+        exists: false
+      },
+      PlannedEntry {
+        name: "shared/cpu".to_string(),
+        exists: true
+      },
+      PlannedEntry {
+        name: "shared/gpu".to_string(),
+        exists: false
+      },
+    ]
+  );
+  assert_eq!(plan.jobs, 3);
+  assert_eq!(plan.skipped_jobs, 0);
+  assert_eq!(
+    plan.conflicts,
+    vec![JobConflict {
+      id: 1,
+      job_name: "a".to_string(),
+      local_job_name: "local".to_string()
+    }]
+  );
+  let report = format_plan(&plan, true);
+  assert!(report.contains("Jobs: 3 would be imported"));
+  assert!(report.contains("#1 \"a\" collides with local job \"local\""));
+
+  assert_eq!(file_contents(target.path()), files_before);
+  assert_eq!(db_contents(&mut db), db_before);
+}
+
+#[test]
+fn test_import_tar_gz() {
+  let source = create_source();
+  let archive_dir = TempDir::new().unwrap();
+  let archive = archive_dir.path().join("sbatchman.tar.gz");
+  create_tar_gz(source.path(), &archive).unwrap();
+  let target = create_target();
+  let mut db = Database::new(target.path()).unwrap();
+
+  let plan = import_archive(&archive, target.path(), &mut db, false).unwrap();
+  assert_eq!(plan.jobs, 3);
+
+  let (clusters, configs, _) = db_contents(&mut db);
+  assert_eq!(clusters, vec!["remote", "shared"]);
+  assert_eq!(configs, vec!["remote/big", "shared/cpu", "shared/gpu"]);
+  let jobs = db.get_jobs(None).unwrap();
+  assert_eq!(jobs.len(), 4);
+  for job in &jobs[1..] {
+    assert_eq!(job.status, Status::Completed);
+    let dir = Path::new(&job.directory);
+    assert!(dir.starts_with(target.path()));
+    assert_eq!(
+      fs::read_to_string(dir.join("stdout.log")).unwrap(),
+      job.job_name
+    );
+  }
+
+  // Importing the same archive again skips the jobs already imported
+  let plan = import_archive(&archive, target.path(), &mut db, false).unwrap();
+  assert_eq!((plan.jobs, plan.skipped_jobs), (0, 3));
+  assert_eq!(db.get_jobs(None).unwrap().len(), 4);
+}
+
+#[test]
+fn test_failed_import_is_rolled_back() {
+  let source = create_source();
+  // The last job belongs to a config that is not in the archive
+  execute_sql(
+    source.path(),
+    "PRAGMA foreign_keys = OFF; UPDATE jobs SET config_id = 999 WHERE job_name = 'c'",
+  );
+  let target = create_target();
+  let mut db = Database::new(target.path()).unwrap();
+  let files_before = file_contents(target.path());
+  let db_before = db_contents(&mut db);
+
+  let result = import_archive(source.path(), target.path(), &mut db, false);
+
+  assert!(matches!(result, Err(ImportError::MissingConfig(3, 999))));
+  assert_eq!(file_contents(target.path()), files_before);
+  assert_eq!(db_contents(&mut db), db_before);
+}
+
+#[test]
+fn test_import_reports_failures() {
+  let target = create_target();
+  let archive = target.path().join("missing.tar.gz");
+
+  assert!(import(archive.to_str().unwrap(), false, Some(target.path())).is_err());
+}
+
+#[test]
+fn test_jobs_without_run_id_are_imported_once() {
+  let source = create_source();
+  // Jobs created before the `run_id` column
+  execute_sql(source.path(), "UPDATE jobs SET run_id = NULL");
+  let target = create_target();
+  let mut db = Database::new(target.path()).unwrap();
+
+  let plan = import_archive(source.path(), target.path(), &mut db, false).unwrap();
+  assert_eq!((plan.jobs, plan.skipped_jobs), (3, 0));
+  let plan = import_archive(source.path(), target.path(), &mut db, false).unwrap();
+  assert_eq!((plan.jobs, plan.skipped_jobs), (0, 3));
+  assert_eq!(db.get_jobs(None).unwrap().len(), 4);
+}
@@ -1,8 +1,16 @@
+use std::process::ExitCode;
+
 mod cli;
 pub mod core;
 mod import_export;
 mod tui;
 
-fn main() {
-  cli::main();
+fn main() -> ExitCode {
+  match cli::main() {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(error) => {
+      eprintln!("❌ {:#}", error);
+      ExitCode::FAILURE
+    }
+  }
 }
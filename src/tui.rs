@@ -1,4 +1,6 @@
 mod examples;
+#[cfg(test)]
+mod tests;
 
 use ratatui::crossterm::{
   event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
@@ -20,19 +22,13 @@ use std::{collections::HashMap, io};
 
 use crate::{
   core::{
-    Sbatchman, SbatchmanError, database::models::{Cluster, Config, Job, Status}, jobs::JobFilter
+    Sbatchman, SbatchmanError, database::models::{Cluster, Config, Job, Status}, jobs::{JobFilter, JobLog, utils::format_unix_time},
+    sbatchman_configs::DisplayTimezone,
   },
   tui::examples::generate_sample_data,
 };
 
 impl Status {
-  fn is_finished(&self) -> bool {
-    matches!(
-      self,
-      Status::Completed | Status::Failed | Status::Timeout | Status::FailedSubmission
-    )
-  }
-
   fn is_active(&self) -> bool {
     matches!(self, Status::Running)
   }
@@ -49,10 +45,11 @@ impl Status {
       Status::Failed | Status::FailedSubmission => Color::Red,
       Status::Timeout => Color::Magenta,
       Status::Created => Color::Gray,
+      Status::Canceled => Color::DarkGray,
     }
   }
 
-  fn all_variants() -> Vec<Status> {
+  pub(crate) fn all_variants() -> Vec<Status> {
     vec![
       Status::Created,
       Status::Queued,
@@ -62,6 +59,7 @@ impl Status {
       Status::Failed,
       Status::FailedSubmission,
       Status::Timeout,
+      Status::Canceled,
     ]
   }
 }
@@ -84,10 +82,11 @@ pub enum ColumnType {
   EndTime,
   // FIXME ExitCode,
   JobId,
+  Labels,
 }
 
 impl ColumnType {
-  fn name(&self) -> &str {
+  pub(crate) fn name(&self) -> &str {
     match self {
       ColumnType::Id => "ID",
       ColumnType::JobName => "Job Name",
@@ -97,6 +96,7 @@ impl ColumnType {
       ColumnType::EndTime => "End Time",
       // FIXME ColumnType::ExitCode => "Exit Code",
       ColumnType::JobId => "Job ID",
+      ColumnType::Labels => "Labels",
     }
   }
 
@@ -110,10 +110,46 @@ impl ColumnType {
       ColumnType::EndTime => 15,
       // FIXME ColumnType::ExitCode => 10,
       ColumnType::JobId => 12,
+      ColumnType::Labels => 30,
+    }
+  }
+
+  /// Text of the column for `job`, times are shown in `timezone`
+  pub(crate) fn value(&self, job: &Job, timezone: DisplayTimezone) -> String {
+    match self {
+      ColumnType::Id => job.id.to_string(),
+      ColumnType::JobName => job.job_name.clone(),
+      ColumnType::ConfigId => job.config_id.to_string(),
+      ColumnType::Status => format!("{:?}", job.status),
+      ColumnType::SubmitTime => job
+        .submit_time
+        .map(|t| format_unix_time(t.into(), timezone))
+        .unwrap_or_default(),
+      ColumnType::EndTime => job
+        .end_time
+        .map(|t| format_unix_time(t.into(), timezone))
+        .unwrap_or_default(),
+      // FIXME ColumnType::ExitCode => job.exit_code.map(|c| c.to_string()).unwrap_or_default(),
+      ColumnType::JobId => job.job_id.clone().unwrap_or_default(),
+      ColumnType::Labels => format_labels(&job.labels),
     }
   }
 }
 
+/// Format job labels as `key=value` pairs
+fn format_labels(labels: &serde_json::Value) -> String {
+  labels
+    .as_object()
+    .map(|labels| {
+      labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value.as_str().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join(", ")
+    })
+    .unwrap_or_default()
+}
+
 impl Default for ColumnConfig {
   fn default() -> Self {
     Self {
@@ -154,6 +190,7 @@ pub enum JobTab {
 pub enum FilterSection {
   Status,
   Config,
+  Launch,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -165,8 +202,9 @@ pub enum ActionTarget {
 pub struct App {
   mode: AppMode,
   jobs: Vec<Job>,
-  configs: HashMap<String, Config>,
+  configs: Vec<Config>,
   cluster: Cluster,
+  timezone: DisplayTimezone,
   job_table_state: TableState,
   column_config: ColumnConfig,
   job_filter: JobFilter,
@@ -176,6 +214,9 @@ pub struct App {
   column_list_state: ListState,
   filter_status_list_state: ListState,
   filter_config_list_state: ListState,
+  filter_launch_list_state: ListState,
+  /// Number of jobs of each status, counted by the database
+  status_counts: HashMap<Status, usize>,
   selected_action_list_state: ListState,
   all_action_list_state: ListState,
   action_target: ActionTarget,
@@ -184,18 +225,21 @@ pub struct App {
   confirmation_message: String,
   pending_action: Option<(String, ActionTarget)>,
   current_log: Option<String>,
+  current_resource_usage: Option<String>,
   current_script: Option<String>,
+  /// Outcome of the last job action, shown above the help bar
+  status_message: Option<String>,
 }
 
 impl App {
   pub fn new(sbatchman: &mut Sbatchman) -> Result<Self, SbatchmanError> {
-    let jobs = sbatchman.get_jobs(None).unwrap_or(vec![]);
     let (cluster, configs) = sbatchman.get_this_cluster_configs()?;
     let mut app = Self {
       mode: AppMode::JobMonitoring(JobTab::Finished),
-      jobs,
+      jobs: vec![],
       configs,
       cluster,
+      timezone: sbatchman.get_display_timezone(),
       job_table_state: TableState::default(),
       column_config: ColumnConfig::default(),
       job_filter: JobFilter::default(),
@@ -205,6 +249,8 @@ impl App {
       column_list_state: ListState::default(),
       filter_status_list_state: ListState::default(),
       filter_config_list_state: ListState::default(),
+      filter_launch_list_state: ListState::default(),
+      status_counts: HashMap::new(),
       selected_action_list_state: ListState::default(),
       all_action_list_state: ListState::default(),
       action_target: ActionTarget::Selected,
@@ -213,14 +259,24 @@ impl App {
       confirmation_message: String::new(),
       pending_action: None,
       current_log: None,
+      current_resource_usage: None,
       current_script: None,
+      status_message: None,
     };
+    app.reload_jobs(sbatchman);
     app.job_table_state.select(Some(0));
     app.selected_action_list_state.select(Some(0));
     app.all_action_list_state.select(Some(0));
     Ok(app)
   }
 
+  /// Load the jobs and their counts by status from the database. Both are always reloaded
+  /// together, so the summary never shows counts of stale jobs.
+  fn reload_jobs(&mut self, sbatchman: &mut Sbatchman) {
+    self.jobs = sbatchman.get_jobs(None).unwrap_or(vec![]);
+    self.status_counts = sbatchman.count_jobs_by_status(None).unwrap_or_default();
+  }
+
   fn get_filtered_jobs(&self, tab: JobTab) -> Vec<&Job> {
     self
       .jobs
@@ -228,7 +284,7 @@ impl App {
       .filter(|job| {
         // Filter by tab
         let tab_match = match tab {
-          JobTab::Finished => job.status.is_finished(),
+          JobTab::Finished => job.status.is_terminal(),
           JobTab::Active => job.status.is_active(),
           JobTab::Queued => job.status.is_queued(),
         };
@@ -249,18 +305,65 @@ impl App {
           return false;
         }
 
+        // Apply launch filter
+        if let Some(launch_id) = &self.job_filter.launch_id
+          && job.launch_id.as_ref() != Some(launch_id)
+        {
+          return false;
+        }
+
+        // Apply labels filter
+        if !self.job_filter.matches_labels(job) {
+          return false;
+        }
+
         true
       })
       .collect()
   }
 
+  /// Distinct launch ids of the jobs, most recent launch first
+  fn launch_ids(&self) -> Vec<String> {
+    let mut launch_ids: Vec<String> = self
+      .jobs
+      .iter()
+      .filter_map(|job| job.launch_id.clone())
+      .collect();
+    launch_ids.sort();
+    launch_ids.dedup();
+    launch_ids.reverse();
+    launch_ids
+  }
+
   fn get_job_counts(&self) -> (usize, usize, usize, usize) {
+    // Without filters, the counts of the database avoid going through all the jobs
+    if !self.has_filters() {
+      let count = |matches: fn(&Status) -> bool| -> usize {
+        self
+          .status_counts
+          .iter()
+          .filter(|(status, _)| matches(status))
+          .map(|(_, count)| count)
+          .sum()
+      };
+      let finished = count(Status::is_terminal);
+      let active = count(Status::is_active);
+      let queued = count(Status::is_queued);
+      return (finished, active, queued, finished + active + queued);
+    }
     let finished = self.get_filtered_jobs(JobTab::Finished).len();
     let active = self.get_filtered_jobs(JobTab::Active).len();
     let queued = self.get_filtered_jobs(JobTab::Queued).len();
     (finished, active, queued, finished + active + queued)
   }
 
+  fn has_filters(&self) -> bool {
+    !self.job_filter.statuses.is_empty()
+      || !self.job_filter.config_ids.is_empty()
+      || !self.job_filter.labels.is_empty()
+      || self.job_filter.launch_id.is_some()
+  }
+
   fn selected_job(&self, tab: JobTab) -> Option<&Job> {
     let jobs = self.get_filtered_jobs(tab);
     self
@@ -269,7 +372,11 @@ impl App {
       .and_then(|i| jobs.get(i).copied())
   }
 
-  pub fn run(&mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
+  pub fn run(
+    &mut self,
+    sbatchman: &mut Sbatchman,
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+  ) -> io::Result<()> {
     loop {
       terminal.draw(|f| self.draw(f))?;
 
@@ -278,7 +385,7 @@ impl App {
           if key.code == KeyCode::Char('q') && matches!(self.mode, AppMode::JobMonitoring(_)) {
             return Ok(());
           }
-          self.handle_input(key.code, key.modifiers);
+          self.handle_input(sbatchman, key.code, key.modifiers);
         }
         Event::Mouse(mouse) => {
           self.handle_mouse(mouse);
@@ -384,6 +491,11 @@ impl App {
               let max = self.configs.len().saturating_sub(1);
               self.filter_config_list_state.select(Some((i + 1).min(max)));
             }
+            FilterSection::Launch => {
+              let i = self.filter_launch_list_state.selected().unwrap_or(0);
+              let max = self.launch_ids().len().saturating_sub(1);
+              self.filter_launch_list_state.select(Some((i + 1).min(max)));
+            }
           },
           MouseEventKind::ScrollUp => match current_section {
             FilterSection::Status => {
@@ -398,6 +510,12 @@ impl App {
                 .filter_config_list_state
                 .select(Some(i.saturating_sub(1)));
             }
+            FilterSection::Launch => {
+              let i = self.filter_launch_list_state.selected().unwrap_or(0);
+              self
+                .filter_launch_list_state
+                .select(Some(i.saturating_sub(1)));
+            }
           },
           _ => {}
         }
@@ -419,7 +537,7 @@ impl App {
     }
   }
 
-  fn handle_input(&mut self, key: KeyCode, modifiers: KeyModifiers) {
+  fn handle_input(&mut self, sbatchman: &mut Sbatchman, key: KeyCode, _modifiers: KeyModifiers) {
     match &self.mode {
       AppMode::JobMonitoring(tab) => {
         let current_tab = *tab;
@@ -439,14 +557,16 @@ impl App {
           KeyCode::Enter => {
             if !self.show_actions_popup && !self.show_confirmation_popup {
               if let Some(job) = self.selected_job(current_tab) {
+                let resource_usage = job.get_resource_usage().and_then(format_resource_usage);
                 self.current_log = job.get_stdout().ok();
+                self.current_resource_usage = resource_usage;
                 self.log_scroll = 0;
                 self.mode = AppMode::LogViewer;
               }
             } else if self.show_confirmation_popup {
               // Confirm action
               if let Some((action, target)) = self.pending_action.take() {
-                self.execute_action(&action, target, current_tab);
+                self.execute_action(sbatchman, &action, target, current_tab);
               }
               self.show_confirmation_popup = false;
               self.show_actions_popup = false;
@@ -645,7 +765,8 @@ impl App {
           KeyCode::Tab => {
             self.mode = AppMode::FilterConfig(match current_section {
               FilterSection::Status => FilterSection::Config,
-              FilterSection::Config => FilterSection::Status,
+              FilterSection::Config => FilterSection::Launch,
+              FilterSection::Launch => FilterSection::Status,
             });
           }
           KeyCode::Down => match current_section {
@@ -659,6 +780,11 @@ impl App {
               let max = self.configs.len().saturating_sub(1);
               self.filter_config_list_state.select(Some((i + 1).min(max)));
             }
+            FilterSection::Launch => {
+              let i = self.filter_launch_list_state.selected().unwrap_or(0);
+              let max = self.launch_ids().len().saturating_sub(1);
+              self.filter_launch_list_state.select(Some((i + 1).min(max)));
+            }
           },
           KeyCode::Up => match current_section {
             FilterSection::Status => {
@@ -673,6 +799,12 @@ impl App {
                 .filter_config_list_state
                 .select(Some(i.saturating_sub(1)));
             }
+            FilterSection::Launch => {
+              let i = self.filter_launch_list_state.selected().unwrap_or(0);
+              self
+                .filter_launch_list_state
+                .select(Some(i.saturating_sub(1)));
+            }
           },
           KeyCode::Char(' ') | KeyCode::Enter => match current_section {
             FilterSection::Status => {
@@ -686,14 +818,30 @@ impl App {
               }
             }
             FilterSection::Config => {
-              if let Some(i) = self.filter_config_list_state.selected() {
-                // TODO
-                // let config_id = self.configs[i].id;
-                // if self.job_filter.config_ids.contains(&config_id) {
-                //   self.job_filter.config_ids.retain(|id| id != &config_id);
-                // } else {
-                //   self.job_filter.config_ids.push(config_id);
-                // }
+              if let Some(config_id) = self
+                .filter_config_list_state
+                .selected()
+                .and_then(|i| self.configs.get(i))
+                .map(|config| config.id)
+              {
+                if self.job_filter.config_ids.contains(&config_id) {
+                  self.job_filter.config_ids.retain(|id| id != &config_id);
+                } else {
+                  self.job_filter.config_ids.push(config_id);
+                }
+              }
+            }
+            FilterSection::Launch => {
+              if let Some(launch_id) = self
+                .filter_launch_list_state
+                .selected()
+                .and_then(|i| self.launch_ids().get(i).cloned())
+              {
+                if self.job_filter.launch_id.as_ref() == Some(&launch_id) {
+                  self.job_filter.launch_id = None;
+                } else {
+                  self.job_filter.launch_id = Some(launch_id);
+                }
               }
             }
           },
@@ -737,16 +885,25 @@ impl App {
         }
       }
       "Cancel All Jobs" => {
-        let count = self.get_filtered_jobs(tab).len();
-        format!("Cancel {} visible job(s) in this view?", count)
+        let (jobs, skipped) = partition_bulk_action_jobs(&self.get_filtered_jobs(tab));
+        let count = jobs.iter().filter(|job| !job.status.is_terminal()).count();
+        format!(
+          "Cancel {} visible job(s) in this view?{}",
+          count,
+          skipped_running_clause(skipped.len())
+        )
       }
       "Archive All Jobs" => {
         let count = self.get_filtered_jobs(tab).len();
         format!("Archive {} visible job(s) in this view?", count)
       }
       "Delete All Jobs" => {
-        let count = self.get_filtered_jobs(tab).len();
-        format!("⚠ DELETE {} visible job(s)? This cannot be undone!", count)
+        let (jobs, skipped) = partition_bulk_action_jobs(&self.get_filtered_jobs(tab));
+        format!(
+          "⚠ DELETE {} visible job(s)?{} This cannot be undone!",
+          jobs.len(),
+          skipped_running_clause(skipped.len())
+        )
       }
       _ => return,
     };
@@ -756,52 +913,98 @@ impl App {
     self.show_confirmation_popup = true;
   }
 
-  fn execute_action(&mut self, action_name: &str, target: ActionTarget, tab: JobTab) {
-    // TODO: Implement actual job actions here
+  fn execute_action(
+    &mut self,
+    sbatchman: &mut Sbatchman,
+    action_name: &str,
+    target: ActionTarget,
+    tab: JobTab,
+  ) {
+    // TODO: Implement the remaining job actions here
     match (action_name, target) {
       ("Cancel Job", ActionTarget::Selected) => {
-        if let Some(job) = self.selected_job(tab) {
-          println!("Cancelling job #{}", job.id);
-          // Call actual cancel logic
+        if let Some(id) = self.selected_job(tab).map(|job| job.id) {
+          self.status_message = Some(match sbatchman.cancel_job(id) {
+            Ok(_) => format!("Job #{} canceled", id),
+            Err(e) => format!("Failed to cancel job #{}: {}", id, e),
+          });
+          self.reload_jobs(sbatchman);
         }
       }
-      ("Archive Job", ActionTarget::Selected) => {
-        if let Some(job) = self.selected_job(tab) {
-          println!("Archiving job #{}", job.id);
-          // Call actual archive logic
-        }
+      ("Archive Job", ActionTarget::Selected) | ("Archive All Jobs", ActionTarget::All) => {
+        self.status_message = Some("Archiving jobs is not supported yet".to_string());
       }
       ("Re-run Job", ActionTarget::Selected) => {
-        if let Some(job) = self.selected_job(tab) {
-          println!("Re-running job #{}", job.id);
-          // Call actual re-run logic
+        if let Some(id) = self.selected_job(tab).map(|job| job.id) {
+          self.status_message = Some(match sbatchman.rerun_job(id, false) {
+            Ok(job) => format!("Job #{} re-run as job #{}", id, job.id),
+            Err(e) => format!("Failed to re-run job #{}: {}", id, e),
+          });
+          self.reload_jobs(sbatchman);
         }
       }
       ("Delete Job", ActionTarget::Selected) => {
         if let Some(job) = self.selected_job(tab) {
-          println!("Deleting job #{}", job.id);
-          // Call actual delete logic
+          let job = (job.id, job.status.is_terminal());
+          self.delete_jobs(sbatchman, &[job]);
         }
       }
       ("Cancel All Jobs", ActionTarget::All) => {
-        let jobs = self.get_filtered_jobs(tab);
-        println!("Cancelling {} jobs", jobs.len());
-        // Call actual cancel all logic
-      }
-      ("Archive All Jobs", ActionTarget::All) => {
-        let jobs = self.get_filtered_jobs(tab);
-        println!("Archiving {} jobs", jobs.len());
-        // Call actual archive all logic
+        let (jobs, _) = partition_bulk_action_jobs(&self.get_filtered_jobs(tab));
+        let ids: Vec<i32> = jobs
+          .iter()
+          .filter(|job| !job.status.is_terminal())
+          .map(|job| job.id)
+          .collect();
+        let errors: Vec<String> = ids
+          .iter()
+          .filter_map(|&id| {
+            let error = sbatchman.cancel_job(id).err()?;
+            Some(format!("job #{}: {}", id, error))
+          })
+          .collect();
+        self.status_message = Some(bulk_action_outcome(
+          "Canceled",
+          ids.len() - errors.len(),
+          &errors,
+        ));
+        self.reload_jobs(sbatchman);
       }
       ("Delete All Jobs", ActionTarget::All) => {
-        let jobs = self.get_filtered_jobs(tab);
-        println!("Deleting {} jobs", jobs.len());
-        // Call actual delete all logic
+        let (jobs, _) = partition_bulk_action_jobs(&self.get_filtered_jobs(tab));
+        let jobs: Vec<(i32, bool)> = jobs
+          .iter()
+          .map(|job| (job.id, job.status.is_terminal()))
+          .collect();
+        self.delete_jobs(sbatchman, &jobs);
       }
       _ => {}
     }
   }
 
+  /// Delete `jobs` (id, whether it is over), canceling the active ones first so that they do not
+  /// run without a job in the database, and show the outcome in the status line
+  fn delete_jobs(&mut self, sbatchman: &mut Sbatchman, jobs: &[(i32, bool)]) {
+    let mut errors = vec![];
+    let mut ids = vec![];
+    for &(id, terminal) in jobs {
+      if !terminal && let Err(e) = sbatchman.cancel_job(id) {
+        errors.push(format!("job #{}: {}", id, e));
+        continue;
+      }
+      ids.push(id);
+    }
+    let deleted = match sbatchman.delete_jobs(&ids) {
+      Ok(deleted) => deleted,
+      Err(e) => {
+        errors.push(e.to_string());
+        0
+      }
+    };
+    self.status_message = Some(bulk_action_outcome("Deleted", deleted, &errors));
+    self.reload_jobs(sbatchman);
+  }
+
   fn draw(&mut self, f: &mut Frame) {
     match &self.mode {
       AppMode::JobMonitoring(tab) => {
@@ -878,7 +1081,10 @@ impl App {
     ];
 
     // Add filter info if any filters are active
-    if !self.job_filter.statuses.is_empty() || !self.job_filter.config_ids.is_empty() {
+    if !self.job_filter.statuses.is_empty()
+      || !self.job_filter.config_ids.is_empty()
+      || self.job_filter.launch_id.is_some()
+    {
       counts_spans.push(Span::raw("  |  "));
       counts_spans.push(Span::styled(
         "Filters: ",
@@ -912,6 +1118,16 @@ impl App {
         );
         counts_spans.push(Span::styled(config_str, Style::default().fg(Color::Cyan)));
       }
+
+      if let Some(launch_id) = &self.job_filter.launch_id {
+        if !self.job_filter.statuses.is_empty() || !self.job_filter.config_ids.is_empty() {
+          counts_spans.push(Span::raw(" | "));
+        }
+        counts_spans.push(Span::styled(
+          format!("Launch: {}", launch_id),
+          Style::default().fg(Color::Green),
+        ));
+      }
     }
 
     let counts_line = Line::from(counts_spans);
@@ -945,20 +1161,10 @@ impl App {
           .columns
           .iter()
           .map(|col| {
+            let cell = Cell::from(col.value(job, self.timezone));
             match col {
-              ColumnType::Id => Cell::from(job.id.to_string()),
-              ColumnType::JobName => Cell::from(job.job_name.clone()),
-              ColumnType::ConfigId => Cell::from(job.config_id.to_string()),
-              ColumnType::Status => Cell::from(format!("{:?}", job.status))
-                .style(Style::default().fg(job.status.color())),
-              ColumnType::SubmitTime => {
-                Cell::from(job.submit_time.map(|t| t.to_string()).unwrap_or_default())
-              }
-              ColumnType::EndTime => {
-                Cell::from(job.end_time.map(|t| t.to_string()).unwrap_or_default())
-              }
-              // FIXME ColumnType::ExitCode => Cell::from(job.exit_code.map(|c| c.to_string()).unwrap_or_default()),
-              ColumnType::JobId => Cell::from(job.job_id.clone().unwrap_or_default()),
+              ColumnType::Status => cell.style(Style::default().fg(job.status.color())),
+              _ => cell,
             }
           })
           .collect();
@@ -989,10 +1195,16 @@ impl App {
 
     f.render_stateful_widget(table, chunks[2], &mut self.job_table_state);
 
-    // Help bar
-    let help = Paragraph::new("q: Quit | Tab: Switch Tab | ↑↓: Navigate | Enter: Logs | s: Script | a: Actions | m: Menu | c: Columns | f: Filters")
-            .style(Style::default().fg(Color::Gray))
-            .alignment(Alignment::Center);
+    // Help bar, below the outcome of the last action
+    let help = Line::styled(
+      "q: Quit | Tab: Switch Tab | ↑↓: Navigate | Enter: Logs | s: Script | a: Actions | m: Menu | c: Columns | f: Filters",
+      Style::default().fg(Color::Gray),
+    );
+    let status = Line::styled(
+      self.status_message.clone().unwrap_or_default(),
+      Style::default().fg(Color::Yellow),
+    );
+    let help = Paragraph::new(vec![status, help]).alignment(Alignment::Center);
     f.render_widget(help, chunks[3]);
   }
 
@@ -1003,12 +1215,12 @@ impl App {
       .split(f.area());
 
     let log_text = self.current_log.as_deref().unwrap_or("No log available");
+    let title = match &self.current_resource_usage {
+      Some(usage) => format!("Log Viewer (stdout) | {}", usage),
+      None => "Log Viewer (stdout)".to_string(),
+    };
     let paragraph = Paragraph::new(log_text)
-      .block(
-        Block::default()
-          .borders(Borders::ALL)
-          .title("Log Viewer (stdout)"),
-      )
+      .block(Block::default().borders(Borders::ALL).title(title))
       .wrap(Wrap { trim: false })
       .scroll((self.log_scroll, 0));
     f.render_widget(paragraph, chunks[0]);
@@ -1072,11 +1284,12 @@ impl App {
     let rows: Vec<Row> = self
       .configs
       .iter()
-      .map(|(cfg_name, cfg)| {
+      .map(|cfg| {
         Row::new(vec![
           Cell::from(cfg.id.to_string()),
-          Cell::from(cfg_name.clone()),
+          Cell::from(cfg.config_name.clone()),
           Cell::from(cfg.cluster_id.to_string()),
+          Cell::from(cfg.description.clone().unwrap_or_default()),
         ])
       })
       .collect();
@@ -1087,10 +1300,11 @@ impl App {
         Constraint::Length(8),
         Constraint::Length(30),
         Constraint::Length(12),
+        Constraint::Min(20),
       ],
     )
     .header(
-      Row::new(vec!["ID", "Name", "Cluster ID"])
+      Row::new(vec!["ID", "Name", "Cluster ID", "Description"])
         .style(Style::default().add_modifier(Modifier::BOLD)),
     )
     .block(
@@ -1163,7 +1377,11 @@ impl App {
 
     let chunks = Layout::default()
       .direction(Direction::Horizontal)
-      .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+      .constraints([
+        Constraint::Percentage(30),
+        Constraint::Percentage(35),
+        Constraint::Percentage(35),
+      ])
       .split(area);
 
     // Status filter list
@@ -1203,12 +1421,12 @@ impl App {
       .configs
       .iter()
       .map(|config| {
-        let checked = if self.job_filter.config_ids.contains(&config.1.id) {
+        let checked = if self.job_filter.config_ids.contains(&config.id) {
           "[x]"
         } else {
           "[ ]"
         };
-        let text = format!("{} {} (ID: {})", checked, config.0, config.1.id);
+        let text = format!("{} {} (ID: {})", checked, config.config_name, config.id);
         ListItem::new(text)
       })
       .collect();
@@ -1231,13 +1449,55 @@ impl App {
       )
       .highlight_symbol(">> ");
 
+    // Launch filter list
+    let launch_items: Vec<ListItem> = self
+      .launch_ids()
+      .into_iter()
+      .map(|launch_id| {
+        let checked = if self.job_filter.launch_id.as_ref() == Some(&launch_id) {
+          "(x)"
+        } else {
+          "( )"
+        };
+        ListItem::new(format!("{} {}", checked, launch_id))
+      })
+      .collect();
+
+    let launch_list = List::new(launch_items)
+      .block(
+        Block::default()
+          .borders(Borders::ALL)
+          .title("Launch Filter")
+          .border_style(if matches!(section, FilterSection::Launch) {
+            Style::default().fg(Color::Yellow)
+          } else {
+            Style::default()
+          }),
+      )
+      .highlight_style(
+        Style::default()
+          .bg(Color::DarkGray)
+          .add_modifier(Modifier::BOLD),
+      )
+      .highlight_symbol(">> ");
+
     // Render based on active section
-    if matches!(section, FilterSection::Status) {
-      f.render_stateful_widget(status_list, chunks[0], &mut self.filter_status_list_state);
-      f.render_widget(config_list, chunks[1]);
-    } else {
-      f.render_widget(status_list, chunks[0]);
-      f.render_stateful_widget(config_list, chunks[1], &mut self.filter_config_list_state);
+    match section {
+      FilterSection::Status => {
+        f.render_stateful_widget(status_list, chunks[0], &mut self.filter_status_list_state);
+        f.render_widget(config_list, chunks[1]);
+        f.render_widget(launch_list, chunks[2]);
+      }
+      FilterSection::Config => {
+        f.render_widget(status_list, chunks[0]);
+        f.render_stateful_widget(config_list, chunks[1], &mut self.filter_config_list_state);
+        f.render_widget(launch_list, chunks[2]);
+      }
+      FilterSection::Launch => {
+        f.render_widget(status_list, chunks[0]);
+        f.render_widget(config_list, chunks[1]);
+        f.render_stateful_widget(launch_list, chunks[2], &mut self.filter_launch_list_state);
+      }
     }
 
     // Help text at the bottom
@@ -1430,6 +1690,55 @@ impl App {
   }
 }
 
+/// Split the jobs targeted by a "cancel all" or "delete all" action into the ones it acts on and
+/// the running ones it skips, so that no running process is left orphaned
+fn partition_bulk_action_jobs<'a>(jobs: &[&'a Job]) -> (Vec<&'a Job>, Vec<&'a Job>) {
+  jobs.iter().partition(|job| job.status != Status::Running)
+}
+
+/// Status line of an action on several jobs: how many succeeded and the first failure, if any
+fn bulk_action_outcome(verb: &str, done: usize, errors: &[String]) -> String {
+  match errors {
+    [] => format!("{} {} job(s)", verb, done),
+    [first, ..] => format!(
+      "{} {} job(s), {} failed ({})",
+      verb,
+      done,
+      errors.len(),
+      first
+    ),
+  }
+}
+
+/// The part of a bulk action confirmation about the skipped running jobs, empty if there are none
+fn skipped_running_clause(skipped: usize) -> String {
+  if skipped == 0 {
+    String::new()
+  } else {
+    format!(" {} running job(s) will be skipped.", skipped)
+  }
+}
+
+/// Format the resource usage of a job for the log viewer title
+fn format_resource_usage(usage: JobLog) -> Option<String> {
+  let JobLog::ResourceUsage {
+    max_rss_kb,
+    cpu_seconds,
+    elapsed_seconds,
+  } = usage
+  else {
+    return None;
+  };
+  let or_na =
+    |value: Option<u64>, unit: &str| value.map_or("N/A".to_string(), |v| format!("{}{}", v, unit));
+  Some(format!(
+    "MaxRSS: {} | CPU: {} | Elapsed: {}",
+    or_na(max_rss_kb, " KB"),
+    or_na(cpu_seconds, "s"),
+    or_na(elapsed_seconds, "s")
+  ))
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
   let popup_layout = Layout::default()
     .direction(Direction::Vertical)
@@ -1459,7 +1768,7 @@ pub fn launch_tui(sbatchman: &mut Sbatchman) -> io::Result<()> {
   let mut terminal = Terminal::new(backend)?;
 
   let mut app = App::new(sbatchman).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-  let res = app.run(&mut terminal);
+  let res = app.run(sbatchman, &mut terminal);
 
   // Restore terminal
   disable_raw_mode()?;
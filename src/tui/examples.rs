@@ -8,19 +8,19 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
       id: 1,
       cluster_name: "HPC-GPU-Cluster".to_string(),
       scheduler: crate::core::database::models::Scheduler::Slurm,
-      max_jobs: None,
+      ..Default::default()
     },
     Cluster {
       id: 2,
       cluster_name: "CPU-Cluster".to_string(),
       scheduler: crate::core::database::models::Scheduler::Pbs,
-      max_jobs: None,
+      ..Default::default()
     },
     Cluster {
       id: 3,
       cluster_name: "Local-Dev".to_string(),
       scheduler: crate::core::database::models::Scheduler::Local,
-      max_jobs: None,
+      ..Default::default()
     },
   ];
 
@@ -31,6 +31,8 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
       cluster_id: 1,
       flags: serde_json::from_str("null").unwrap(),
       env: serde_json::from_str("null").unwrap(),
+      description: Some("Full node with 8 A100 GPUs".to_string()),
+      ..Default::default()
     },
     Config {
       id: 2,
@@ -38,6 +40,7 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
       cluster_id: 1,
       flags: serde_json::from_str("null").unwrap(),
       env: serde_json::from_str("null").unwrap(),
+      ..Default::default()
     },
     Config {
       id: 3,
@@ -45,6 +48,7 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
       cluster_id: 2,
       flags: serde_json::from_str("null").unwrap(),
       env: serde_json::from_str("null").unwrap(),
+      ..Default::default()
     },
     Config {
       id: 4,
@@ -52,6 +56,7 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
       cluster_id: 2,
       flags: serde_json::from_str("null").unwrap(),
       env: serde_json::from_str("null").unwrap(),
+      ..Default::default()
     },
     Config {
       id: 5,
@@ -59,6 +64,7 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
       cluster_id: 3,
       flags: serde_json::from_str("null").unwrap(),
       env: serde_json::from_str("null").unwrap(),
+      ..Default::default()
     },
   ];
 
@@ -72,14 +78,13 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time),
             directory: "/scratch/user/experiments/llama3_medical/run_001".to_string(),
             command: "python train.py --model llama3-70b --dataset medical_qa --epochs 10".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Completed,
             job_id: Some("slurm_4891234".to_string()),
             end_time: Some(base_time + 43200),
             // FIXME exit_code: Some(0),
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 2,
@@ -88,14 +93,13 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 1000),
             directory: "/scratch/user/nlp/bert_sentiment/exp_042".to_string(),
             command: "python sentiment_analysis.py --model bert-large --data twitter_2024".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Completed,
             job_id: Some("slurm_4891235".to_string()),
             end_time: Some(base_time + 7200),
             // FIXME exit_code: Some(0),
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 3,
@@ -104,14 +108,13 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 5000),
             directory: "/scratch/user/vision/stable_diff/gen_batch_15".to_string(),
             command: "python generate.py --model sdxl --prompts art_dataset.json --samples 10000".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Completed,
             job_id: Some("slurm_4891240".to_string()),
             end_time: Some(base_time + 14400),
             // FIXME exit_code: Some(0),
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 4,
@@ -120,14 +123,14 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 8000),
             directory: "/scratch/user/cv/resnet/baseline_v2".to_string(),
             command: "python train_resnet.py --depth 152 --dataset imagenet --lr 0.1".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Completed,
             job_id: Some("slurm_4891245".to_string()),
             end_time: Some(base_time + 28800),
             // FIXME exit_code: Some(0),
             archived: Some(1),
+            ..Default::default()
         },
         Job {
             id: 5,
@@ -136,14 +139,13 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 10000),
             directory: "/scratch/user/quantum/simulations/water_molecule".to_string(),
             command: "mpirun -np 128 ./quantum_sim --molecule H2O --basis ccpvtz".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Completed,
             job_id: Some("pbs_987654".to_string()),
             end_time: Some(base_time + 86400),
             // FIXME exit_code: Some(0),
-            archived: None,
+            ..Default::default()
         },
         // Failed jobs
         Job {
@@ -153,14 +155,13 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 12000),
             directory: "/scratch/user/llm/gpt4_replica/trial_003".to_string(),
             command: "python train_gpt.py --params 1.76T --ctx_len 32768".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Failed,
             job_id: Some("slurm_4891250".to_string()),
             end_time: Some(base_time + 15000),
             // FIXME exit_code: Some(137), // OOM killed
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 7,
@@ -169,14 +170,13 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 15000),
             directory: "/scratch/user/biotech/md_sims/protein_folding_12".to_string(),
             command: "gromacs mdrun -s protein.tpr -deffnm output".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Failed,
             job_id: Some("pbs_987660".to_string()),
             end_time: Some(base_time + 16000),
             // FIXME exit_code: Some(1),
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 8,
@@ -185,14 +185,13 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 20000),
             directory: "/scratch/user/climate/cesm_runs/scenario_rcp85".to_string(),
             command: "./cesm.exe --scenario RCP8.5 --years 2024-2100".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Failed,
             job_id: Some("pbs_987665".to_string()),
             end_time: Some(base_time + 21000),
             // FIXME exit_code: Some(255),
-            archived: None,
+            ..Default::default()
         },
         // Timeout jobs
         Job {
@@ -202,14 +201,13 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 25000),
             directory: "/scratch/user/ml/hyperparam/search_space_v5".to_string(),
             command: "python optuna_search.py --trials 10000 --timeout 24h".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Timeout,
             job_id: Some("slurm_4891260".to_string()),
             end_time: Some(base_time + 111000),
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 10,
@@ -218,14 +216,14 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 30000),
             directory: "/scratch/user/finance/monte_carlo/risk_analysis_q4".to_string(),
             command: "python monte_carlo.py --simulations 1000000000 --instruments all".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Timeout,
             job_id: Some("pbs_987670".to_string()),
             end_time: Some(base_time + 117000),
             // FIXME exit_code: None,
             archived: Some(1),
+            ..Default::default()
         },
         // Running jobs
         Job {
@@ -235,14 +233,12 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 35000),
             directory: "/scratch/user/nmt/transformer/run_108".to_string(),
             command: "python train_nmt.py --src_lang en --tgt_langs fr,de,es,it,pt --model transformer-xl".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Running,
             job_id: Some("slurm_4891270".to_string()),
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 12,
@@ -251,14 +247,12 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 36000),
             directory: "/scratch/user/bioml/alphafold/complex_pred_045".to_string(),
             command: "python run_alphafold.py --fasta_paths complex.fasta --model_preset multimer".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Running,
             job_id: Some("slurm_4891271".to_string()),
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 13,
@@ -267,14 +261,12 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 37000),
             directory: "/scratch/user/rl/dqn/atari_breakout_v12".to_string(),
             command: "python train_dqn.py --env BreakoutNoFrameskip-v4 --frames 50000000".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Running,
             job_id: Some("slurm_4891272".to_string()),
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 14,
@@ -283,14 +275,12 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 38000),
             directory: "/scratch/user/genomics/assembly/sample_hs_001".to_string(),
             command: "canu -p assembly -d output genomeSize=3.2g -nanopore reads.fastq.gz".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Running,
             job_id: Some("pbs_987680".to_string()),
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         // Queued jobs
         Job {
@@ -300,14 +290,12 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 40000),
             directory: "/scratch/user/nas/search_space_v3/exp_022".to_string(),
             command: "python nas.py --search_space mobilenet --dataset cifar100 --budget gpu_hours=500".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Queued,
             job_id: Some("slurm_4891280".to_string()),
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 16,
@@ -316,14 +304,12 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 41000),
             directory: "/scratch/user/federated/medical_img/round_050".to_string(),
             command: "python fedavg.py --clients 100 --rounds 500 --model resnet50".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Queued,
             job_id: Some("slurm_4891281".to_string()),
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 17,
@@ -332,14 +318,12 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 42000),
             directory: "/scratch/user/gnn/chemistry/drug_discovery_batch_8".to_string(),
             command: "python train_gnn.py --dataset zinc --model mpnn --layers 8".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Queued,
             job_id: Some("slurm_4891282".to_string()),
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 18,
@@ -348,14 +332,12 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 43000),
             directory: "/scratch/user/timeseries/energy_forecast/model_v8".to_string(),
             command: "python forecast.py --model temporal_fusion_transformer --horizon 168 --data energy_consumption.csv".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Queued,
             job_id: Some("pbs_987690".to_string()),
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 19,
@@ -364,14 +346,12 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 44000),
             directory: "/scratch/user/asr/whisper_ft/languages_20".to_string(),
             command: "python finetune_whisper.py --model large-v3 --languages all --hours 10000".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Queued,
             job_id: Some("slurm_4891283".to_string()),
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 20,
@@ -380,14 +360,11 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 45000),
             directory: "/scratch/user/3d_vision/pointnet/urban_scenes".to_string(),
             command: "python train_pointnet.py --dataset semantic_kitti --backbone pointnet++".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::VirtualQueue,
-            job_id: None,
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         // Virtual Queue jobs
         Job {
@@ -397,14 +374,11 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 46000),
             directory: "/scratch/user/security/ids/network_traffic_v3".to_string(),
             command: "python train_autoencoder.py --data network_logs --window 24h".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::VirtualQueue,
-            job_id: None,
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 22,
@@ -413,47 +387,36 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 47000),
             directory: "/scratch/user/recsys/collaborative/movielens_100m".to_string(),
             command: "python train_ncf.py --dataset movielens-100m --embedding_dim 256".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::VirtualQueue,
-            job_id: None,
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         // Created but not submitted
         Job {
             id: 23,
             job_name: "text-generation-eval".to_string(),
             config_id: 5,
-            submit_time: None,
             directory: "/home/user/dev/eval/text_gen_metrics".to_string(),
             command: "python evaluate_all.py --models gpt2,gpt3,llama --metrics bleu,rouge,bertscore".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Created,
-            job_id: None,
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         Job {
             id: 24,
             job_name: "data-preprocessing-cleanup".to_string(),
             config_id: 5,
-            submit_time: None,
             directory: "/home/user/dev/data/cleaning_pipeline".to_string(),
             command: "python clean_dataset.py --input raw_data/ --output clean_data/ --workers 8".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::Created,
-            job_id: None,
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
         // Failed submission
         Job {
@@ -463,14 +426,11 @@ pub fn generate_sample_data() -> (Vec<Job>, Vec<Config>, Vec<Cluster>) {
             submit_time: Some(base_time + 50000),
             directory: "/scratch/user/distributed/large_scale/attempt_02".to_string(),
             command: "python -m torch.distributed.launch --nproc_per_node=8 --nnodes=128 train_distributed.py".to_string(),
-            preprocess: None,
-            postprocess: None,
             variables: serde_json::from_str("null").unwrap(),
+            labels: serde_json::json!({}),
             status: Status::FailedSubmission,
-            job_id: None,
-            end_time: None,
             // FIXME exit_code: None,
-            archived: None,
+            ..Default::default()
         },
     ];
 
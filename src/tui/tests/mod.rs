@@ -0,0 +1,92 @@
+use ratatui::style::Color;
+use serde_json::json;
+
+use crate::{
+  core::database::models::{Job, Status},
+  tui::{bulk_action_outcome, partition_bulk_action_jobs, skipped_running_clause},
+};
+
+#[test]
+fn test_status_colors() {
+  let cases = [
+    (Status::Created, Color::Gray),
+    (Status::Queued, Color::Yellow),
+    (Status::VirtualQueue, Color::Yellow),
+    (Status::Running, Color::Cyan),
+    (Status::Completed, Color::Green),
+    (Status::Failed, Color::Red),
+    (Status::FailedSubmission, Color::Red),
+    (Status::Timeout, Color::Magenta),
+    // Canceled jobs must not look like failed ones
+    (Status::Canceled, Color::DarkGray),
+  ];
+  assert_eq!(
+    Status::all_variants(),
+    cases
+      .iter()
+      .map(|(status, _)| status.clone())
+      .collect::<Vec<_>>()
+  );
+  for (status, color) in cases {
+    assert_eq!(status.color(), color, "{:?}", status);
+  }
+}
+
+fn job_with_status(id: i32, status: Status) -> Job {
+  Job {
+    id,
+    job_name: format!("job_{}", id),
+    config_id: 1,
+    submit_time: Some(1700000000),
+    directory: String::new(),
+    command: "echo".to_string(),
+    status,
+    variables: json!({}),
+    labels: json!({}),
+    ..Default::default()
+  }
+}
+
+#[test]
+fn test_bulk_actions_skip_running_jobs() {
+  let jobs = [
+    job_with_status(1, Status::Queued),
+    job_with_status(2, Status::Running),
+    job_with_status(3, Status::VirtualQueue),
+    job_with_status(4, Status::Running),
+    job_with_status(5, Status::Completed),
+  ];
+  let visible: Vec<&Job> = jobs.iter().collect();
+
+  let (actionable, skipped) = partition_bulk_action_jobs(&visible);
+  assert_eq!(
+    actionable.iter().map(|job| job.id).collect::<Vec<_>>(),
+    vec![1, 3, 5]
+  );
+  assert_eq!(
+    skipped.iter().map(|job| job.id).collect::<Vec<_>>(),
+    vec![2, 4]
+  );
+}
+
+#[test]
+fn test_skipped_running_clause() {
+  assert_eq!(skipped_running_clause(0), "");
+  assert_eq!(
+    skipped_running_clause(2),
+    " 2 running job(s) will be skipped."
+  );
+}
+
+#[test]
+fn test_bulk_action_outcome() {
+  assert_eq!(bulk_action_outcome("Deleted", 3, &[]), "Deleted 3 job(s)");
+  let errors = [
+    "job #4: not running".to_string(),
+    "job #5: scancel failed".to_string(),
+  ];
+  assert_eq!(
+    bulk_action_outcome("Canceled", 1, &errors),
+    "Canceled 1 job(s), 2 failed (job #4: not running)"
+  );
+}